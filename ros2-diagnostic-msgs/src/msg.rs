@@ -0,0 +1,49 @@
+use ros2_std_msgs::msg::Header;
+use serde::Deserialize;
+
+/// This message is used to send diagnostic information about the state of the robot.
+#[derive(Deserialize, PartialEq, Clone, Debug)]
+pub struct DiagnosticArray {
+    /// for timestamp
+    pub header: Header,
+
+    /// an array of components being reported on
+    pub status: Vec<DiagnosticStatus>,
+}
+
+/// This message holds the status of an individual component of the robot.
+#[derive(Deserialize, PartialEq, Clone, Debug)]
+pub struct DiagnosticStatus {
+    /// level of operation enumerated above
+    pub level: u8,
+
+    /// a description of the test/component reporting
+    pub name: String,
+
+    /// a description of the status
+    pub message: String,
+
+    /// a hardware unique string
+    pub hardware_id: String,
+
+    /// an array of values associated with the status
+    pub values: Vec<KeyValue>,
+}
+
+impl DiagnosticStatus {
+    // Possible levels of operations.
+    pub const OK: u8 = 0;
+    pub const WARN: u8 = 1;
+    pub const ERROR: u8 = 2;
+    pub const STALE: u8 = 3;
+}
+
+/// What to label this value when viewing.
+#[derive(Deserialize, PartialEq, Clone, Debug)]
+pub struct KeyValue {
+    /// What to label this value when viewing.
+    pub key: String,
+
+    /// A value to track over time.
+    pub value: String,
+}
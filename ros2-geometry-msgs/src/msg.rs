@@ -65,6 +65,27 @@ pub struct Pose {
     pub orientation: Quaternion,
 }
 
+#[derive(Deserialize, PartialEq, Clone, Debug)]
+pub struct PoseStamped {
+    pub header: Header,
+    pub pose: Pose,
+}
+
+#[derive(Deserialize, PartialEq, Clone, Debug)]
+pub struct PoseArray {
+    pub header: Header,
+    pub poses: Vec<Pose>,
+}
+
+/// A pose in free space with uncertainty. The covariance is a row-major representation of the
+/// 6x6 matrix, about the axes (x, y, z, rotation about X, rotation about Y, rotation about Z).
+#[derive(Deserialize, PartialEq, Clone, Debug)]
+pub struct PoseWithCovariance {
+    pub pose: Pose,
+    #[serde(with = "array36")]
+    pub covariance: [f64; 36],
+}
+
 #[derive(Deserialize, PartialEq, Clone, Debug)]
 pub struct Pose2D {
     pub x: f64,
@@ -97,9 +118,54 @@ pub struct TwistStamped {
     pub twist: Twist,
 }
 
+/// Velocity in free space with uncertainty. The covariance is a row-major representation of the
+/// 6x6 matrix, about the axes (x, y, z, rotation about X, rotation about Y, rotation about Z).
+#[derive(Deserialize, PartialEq, Clone, Debug)]
+pub struct TwistWithCovariance {
+    pub twist: Twist,
+    #[serde(with = "array36")]
+    pub covariance: [f64; 36],
+}
+
 #[derive(Deserialize, PartialEq, Clone, Debug)]
 pub struct Vector3 {
     pub x: f64,
     pub y: f64,
     pub z: f64,
 }
+
+/// Serde only supports fixed size arrays up to 32 elements, the 6x6 covariance matrices are
+/// deserialized as tuples here.
+mod array36 {
+    use serde::de::{Deserializer, Error, SeqAccess, Visitor};
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<[f64; 36], D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_tuple(36, ArrayVisitor)
+    }
+
+    struct ArrayVisitor;
+
+    impl<'de> Visitor<'de> for ArrayVisitor {
+        type Value = [f64; 36];
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            formatter.write_str("an array of 36 float64")
+        }
+
+        fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+        where
+            A: SeqAccess<'de>,
+        {
+            let mut values = [0.0; 36];
+            for (i, value) in values.iter_mut().enumerate() {
+                *value = seq
+                    .next_element()?
+                    .ok_or_else(|| A::Error::invalid_length(i, &self))?;
+            }
+            Ok(values)
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use ros2_builtin_interfaces::msg::Time;
+use ros2_geometry_msgs::msg::{Pose, PoseStamped, PoseWithCovariance, TwistWithCovariance};
+use ros2_std_msgs::msg::Header;
+use serde::Deserialize;
+
+/// This represents an estimate of a position and velocity in free space.
+/// The pose in this message should be specified in the coordinate frame given by header.frame_id
+/// The twist in this message should be specified in the coordinate frame given by the child_frame_id
+#[derive(Deserialize, PartialEq, Clone, Debug)]
+pub struct Odometry {
+    /// Includes the frame id of the pose parent.
+    pub header: Header,
+
+    /// Frame id the pose points to. The twist is in this coordinate frame.
+    pub child_frame_id: String,
+
+    /// Estimated pose that is typically relative to a fixed world frame.
+    pub pose: PoseWithCovariance,
+
+    /// Estimated linear and angular velocity relative to child_frame_id.
+    pub twist: TwistWithCovariance,
+}
+
+/// This hold basic information about the characteristics of the OccupancyGrid
+#[derive(Deserialize, PartialEq, Clone, Debug)]
+pub struct MapMetaData {
+    /// The time at which the map was loaded
+    pub map_load_time: Time,
+
+    /// The map resolution [m/cell]
+    pub resolution: f32,
+
+    /// Map width [cells]
+    pub width: u32,
+
+    /// Map height [cells]
+    pub height: u32,
+
+    /// The origin of the map [m, m, rad].  This is the real-world pose of the
+    /// bottom left corner of cell (0,0) in the map.
+    pub origin: Pose,
+}
+
+/// This represents a 2-D grid map
+#[derive(Deserialize, PartialEq, Clone, Debug)]
+pub struct OccupancyGrid {
+    pub header: Header,
+
+    /// MetaData for the map
+    pub info: MapMetaData,
+
+    /// The map data, in row-major order, starting with (0,0).
+    /// Cell (1, 0) will be listed second, representing the next cell in the x direction.
+    /// Cell (0, 1) will be at the index equal to info.width, followed by (1, 1).
+    /// The values inside are application dependent, but frequently,
+    /// 0 represents unoccupied, 1 represents definitely occupied, and
+    /// -1 represents unknown.
+    pub data: Vec<i8>,
+}
+
+/// An array of poses that represents a Path for a robot to follow.
+#[derive(Deserialize, PartialEq, Clone, Debug)]
+pub struct Path {
+    /// Indicates the frame_id of the path.
+    pub header: Header,
+
+    /// Array of poses to follow.
+    pub poses: Vec<PoseStamped>,
+}
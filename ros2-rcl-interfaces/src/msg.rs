@@ -0,0 +1,36 @@
+use ros2_builtin_interfaces::msg::Time;
+use serde::Deserialize;
+
+/// Messages of the ROS log, published on `/rosout`.
+#[derive(Deserialize, PartialEq, Clone, Debug)]
+pub struct Log {
+    /// Timestamp when this message was generated by the node.
+    pub stamp: Time,
+
+    /// Corresponding log level, see above definitions.
+    pub level: u8,
+
+    /// The name representing the logger this message came from.
+    pub name: String,
+
+    /// The full log message.
+    pub msg: String,
+
+    /// The file the message came from.
+    pub file: String,
+
+    /// The function the message came from.
+    pub function: String,
+
+    /// The line in the file the message came from.
+    pub line: u32,
+}
+
+impl Log {
+    // Logging levels.
+    pub const DEBUG: u8 = 10;
+    pub const INFO: u8 = 20;
+    pub const WARN: u8 = 30;
+    pub const ERROR: u8 = 40;
+    pub const FATAL: u8 = 50;
+}
@@ -0,0 +1,65 @@
+use ros2_std_msgs::msg::Header;
+use serde::Deserialize;
+
+/// Constants are chosen to match the enums in the linux kernel defined in
+/// include/linux/power_supply.h as of version 3.7. The one difference is for style reasons the
+/// constants are all uppercase not mixed case.
+///
+/// Unmeasured values are NaN.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BatteryState {
+    pub header: Header,
+
+    /// Voltage in Volts (Mandatory)
+    pub voltage: f32,
+
+    /// Temperature in Degrees Celsius (If unmeasured NaN)
+    pub temperature: f32,
+
+    /// Negative when discharging (A)  (If unmeasured NaN)
+    pub current: f32,
+
+    /// Current charge in Ah  (If unmeasured NaN)
+    pub charge: f32,
+
+    /// Capacity in Ah (last full capacity)  (If unmeasured NaN)
+    pub capacity: f32,
+
+    /// Capacity in Ah (design capacity)  (If unmeasured NaN)
+    pub design_capacity: f32,
+
+    /// Charge percentage on 0 to 1 range  (If unmeasured NaN)
+    pub percentage: f32,
+
+    /// The charging status as reported. Values defined above
+    pub power_supply_status: u8,
+
+    /// The battery health metric. Values defined above
+    pub power_supply_health: u8,
+
+    /// The battery chemistry. Values defined above
+    pub power_supply_technology: u8,
+
+    /// True if the battery is present
+    pub present: bool,
+
+    /// An array of individual cell voltages for each cell in the pack. If individual voltages
+    /// unknown but number of cells known set each to NaN
+    pub cell_voltage: Vec<f32>,
+
+    /// An array of individual cell temperatures for each cell in the pack. If individual
+    /// temperatures unknown but number of cells known set each to NaN
+    pub cell_temperature: Vec<f32>,
+
+    /// The location into which the battery is inserted. (slot number or plug)
+    pub location: String,
+
+    /// The best approximation of the battery serial number
+    pub serial_number: String,
+}
+
+impl BatteryState {
+    pub fn name() -> &'static str {
+        "sensor_msgs/BatteryState"
+    }
+}
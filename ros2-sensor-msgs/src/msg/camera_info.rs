@@ -0,0 +1,98 @@
+use ros2_std_msgs::msg::Header;
+use serde::Deserialize;
+
+/// This message is used to specify a region of interest within an image.
+///
+/// When used to specify the ROI setting of the camera when the image was
+/// taken, the height and width fields should either match the height and
+/// width fields for the associated image; or height = width = 0
+/// indicates that the full resolution image was captured.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize)]
+pub struct RegionOfInterest {
+    /// Leftmost pixel of the ROI
+    pub x_offset: u32,
+
+    /// Topmost pixel of the ROI
+    pub y_offset: u32,
+
+    /// Height of ROI
+    pub height: u32,
+
+    /// Width of ROI
+    pub width: u32,
+
+    /// True if a distinct rectified ROI should be calculated from the "raw"
+    /// ROI in this message. Typically this should be False if the full image
+    /// is captured (ROI not used), and True if a subwindow is captured (ROI
+    /// used).
+    pub do_rectify: bool,
+}
+
+/// This message defines meta information for a camera. It should be in a
+/// camera namespace on topic "camera_info" and accompanied by up to five
+/// image topics named:
+///
+///   image_raw - raw data from the camera driver, possibly Bayer encoded
+///   image            - monochrome, distorted
+///   image_color      - color, distorted
+///   image_rect       - monochrome, rectified
+///   image_rect_color - color, rectified
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct CameraInfo {
+    /// Time of image acquisition, camera coordinate frame ID
+    pub header: Header,
+
+    /// The image dimensions with which the camera was calibrated.
+    /// Normally this will be the full camera resolution in pixels.
+    pub height: u32,
+    pub width: u32,
+
+    /// The distortion model used. Supported models are listed in
+    /// sensor_msgs/distortion_models.hpp. For most cameras, "plumb_bob" - a
+    /// simple model of radial and tangential distortion - is sufficent.
+    pub distortion_model: String,
+
+    /// The distortion parameters, size depending on the distortion model.
+    /// For "plumb_bob", the 5 parameters are: (k1, k2, t1, t2, k3).
+    pub d: Vec<f64>,
+
+    /// Intrinsic camera matrix for the raw (distorted) images.
+    ///     [fx  0 cx]
+    /// K = [ 0 fy cy]
+    ///     [ 0  0  1]
+    pub k: [f64; 9],
+
+    /// Rectification matrix (stereo cameras only)
+    pub r: [f64; 9],
+
+    /// Projection/camera matrix
+    ///     [fx'  0  cx' Tx]
+    /// P = [ 0  fy' cy' Ty]
+    ///     [ 0   0   1   0]
+    pub p: [f64; 12],
+
+    /// Binning refers here to any camera setting which combines rectangular
+    /// neighborhoods of pixels into larger "super-pixels."
+    pub binning_x: u32,
+    pub binning_y: u32,
+
+    /// Region of interest (subwindow of full camera resolution), given in
+    /// full resolution (unbinned) image coordinates.
+    pub roi: RegionOfInterest,
+}
+
+impl CameraInfo {
+    /// Focal lengths (fx, fy) in pixels.
+    pub fn focal_length(&self) -> [f64; 2] {
+        [self.k[0], self.k[4]]
+    }
+
+    /// Principal point (cx, cy) in pixels.
+    pub fn principal_point(&self) -> [f64; 2] {
+        [self.k[2], self.k[5]]
+    }
+
+    pub fn name() -> &'static str {
+        "sensor_msgs/CameraInfo"
+    }
+}
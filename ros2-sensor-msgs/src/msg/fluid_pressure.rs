@@ -0,0 +1,25 @@
+use ros2_std_msgs::msg::Header;
+use serde::Deserialize;
+
+/// Single pressure reading. This message is appropriate for measuring the pressure inside of a
+/// fluid (air, water, etc). This also includes atmospheric or barometric pressure.
+///
+/// This message is not appropriate for force/pressure contact sensors.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct FluidPressure {
+    /// timestamp of the measurement
+    /// frame_id is the location of the pressure sensor
+    pub header: Header,
+
+    /// Absolute pressure reading in Pascals.
+    pub fluid_pressure: f64,
+
+    /// 0 is interpreted as variance unknown
+    pub variance: f64,
+}
+
+impl FluidPressure {
+    pub fn name() -> &'static str {
+        "sensor_msgs/FluidPressure"
+    }
+}
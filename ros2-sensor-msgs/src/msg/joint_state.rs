@@ -0,0 +1,37 @@
+use ros2_std_msgs::msg::Header;
+use serde::Deserialize;
+
+/// This is a message that holds data to describe the state of a set of torque controlled joints.
+///
+/// The state of each joint (revolute or prismatic) is defined by:
+///  * the position of the joint (rad or m),
+///  * the velocity of the joint (rad/s or m/s) and
+///  * the effort that is applied in the joint (Nm or N).
+///
+/// Each joint is uniquely identified by its name. The header specifies the time at which the joint
+/// states were recorded. All the joint states in one message have to be recorded at the same time.
+///
+/// This message consists of a multiple arrays, one for each part of the joint state. The goal is
+/// to make each of the fields optional. When e.g. your joints have no effort associated with
+/// them, you can leave the effort array empty.
+///
+/// All arrays in this message should have the same size, or be empty. This is the only way to
+/// uniquely associate the joint name with the correct states.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct JointState {
+    pub header: Header,
+
+    pub name: Vec<String>,
+
+    pub position: Vec<f64>,
+
+    pub velocity: Vec<f64>,
+
+    pub effort: Vec<f64>,
+}
+
+impl JointState {
+    pub fn name() -> &'static str {
+        "sensor_msgs/JointState"
+    }
+}
@@ -0,0 +1,21 @@
+use ros2_std_msgs::msg::Header;
+use serde::Deserialize;
+
+/// Reports the state of a joystick's axes and buttons.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Joy {
+    /// The timestamp is the time at which data is received from the joystick.
+    pub header: Header,
+
+    /// The axes measurements from a joystick.
+    pub axes: Vec<f32>,
+
+    /// The buttons measurements from a joystick.
+    pub buttons: Vec<i32>,
+}
+
+impl Joy {
+    pub fn name() -> &'static str {
+        "sensor_msgs/Joy"
+    }
+}
@@ -0,0 +1,29 @@
+use ros2_geometry_msgs::msg::Vector3;
+use ros2_std_msgs::msg::Header;
+use serde::Deserialize;
+
+/// Measurement of the Magnetic Field vector at a specific location.
+///
+/// If the covariance of the measurement is known, it should be filled in. If all you know is the
+/// variance of each measurement, e.g. from the datasheet, just put those along the diagonal. A
+/// covariance matrix of all zeros will be interpreted as "covariance unknown", and to use the data
+/// a covariance will have to be assumed or gotten from some other source.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct MagneticField {
+    /// timestamp is the time the field was measured
+    /// frame_id is the location and orientation of the field measurement
+    pub header: Header,
+
+    /// x, y, and z components of the field vector in Tesla. If your sensor does not output 3
+    /// axes, put NaNs in the components not reported.
+    pub magnetic_field: Vector3,
+
+    /// Row major about x, y, z axes. 0 is interpreted as variance unknown.
+    pub magnetic_field_covariance: [f64; 9],
+}
+
+impl MagneticField {
+    pub fn name() -> &'static str {
+        "sensor_msgs/MagneticField"
+    }
+}
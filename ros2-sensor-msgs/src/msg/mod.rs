@@ -1,10 +1,32 @@
+mod battery_state;
+mod camera_info;
 mod compressed_image;
+mod fluid_pressure;
 mod image;
+mod imu;
+mod joint_state;
+mod joy;
+mod laser_scan;
+mod magnetic_field;
+mod nav_sat_fix;
 mod point_cloud2;
 mod point_field;
+mod range;
+mod temperature;
 
 // Make these message types public
+pub use battery_state::BatteryState;
+pub use camera_info::{CameraInfo, RegionOfInterest};
 pub use compressed_image::CompressedImage;
+pub use fluid_pressure::FluidPressure;
 pub use image::Image;
+pub use imu::Imu;
+pub use joint_state::JointState;
+pub use joy::Joy;
+pub use laser_scan::LaserScan;
+pub use magnetic_field::MagneticField;
+pub use nav_sat_fix::{NavSatFix, NavSatStatus};
 pub use point_cloud2::{PointCloud2, PointCloud2Iterator};
-pub use point_field::PointField;
+pub use point_field::{Datatype, PointField};
+pub use range::Range;
+pub use temperature::Temperature;
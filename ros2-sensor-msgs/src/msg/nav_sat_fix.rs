@@ -0,0 +1,75 @@
+use ros2_std_msgs::msg::Header;
+use serde::Deserialize;
+
+/// Navigation Satellite fix status for any Global Navigation Satellite System.
+///
+/// Whether to output an augmented fix is determined by both the fix
+/// type and the last time differential corrections were received.  A
+/// fix is valid when status >= STATUS_FIX.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+pub struct NavSatStatus {
+    /// int8 STATUS_NO_FIX =  -1        # unable to fix position
+    /// int8 STATUS_FIX =      0        # unaugmented fix
+    /// int8 STATUS_SBAS_FIX = 1        # with satellite-based augmentation
+    /// int8 STATUS_GBAS_FIX = 2        # with ground-based augmentation
+    pub status: i8,
+
+    /// Bits defining which Global Navigation Satellite System signals were
+    /// used by the receiver.
+    pub service: u16,
+}
+
+impl NavSatStatus {
+    pub const STATUS_NO_FIX: i8 = -1;
+
+    /// Return true if the fix is valid.
+    pub fn has_fix(&self) -> bool {
+        self.status > Self::STATUS_NO_FIX
+    }
+}
+
+/// Navigation Satellite fix for any Global Navigation Satellite System
+///
+/// Specified using the WGS 84 reference ellipsoid
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct NavSatFix {
+    /// header.stamp specifies the ROS time for this measurement (the
+    ///        corresponding satellite time may be reported using the
+    ///        sensor_msgs/TimeReference message).
+    ///
+    /// header.frame_id is the frame of reference reported by the satellite
+    ///        receiver, usually the location of the antenna.  This is a
+    ///        Euclidean frame relative to the vehicle, not a reference
+    ///        ellipsoid.
+    pub header: Header,
+
+    /// Satellite fix status information.
+    pub status: NavSatStatus,
+
+    /// Latitude [degrees]. Positive is north of equator; negative is south.
+    pub latitude: f64,
+
+    /// Longitude [degrees]. Positive is east of prime meridian; negative is west.
+    pub longitude: f64,
+
+    /// Altitude [m]. Positive is above the WGS 84 ellipsoid
+    /// (quiet NaN if no altitude is available).
+    pub altitude: f64,
+
+    /// Position covariance [m^2] defined relative to a tangential plane
+    /// through the reported position. The components are East, North, and
+    /// Up (ENU), in row-major order.
+    pub position_covariance: [f64; 9],
+
+    /// If the covariance of the fix is known, fill it in completely. If the
+    /// GPS receiver provides the variance of each measurement, put them
+    /// along the diagonal. If only Dilution of Precision is available,
+    /// estimate an approximate covariance from that.
+    pub position_covariance_type: u8,
+}
+
+impl NavSatFix {
+    pub fn name() -> &'static str {
+        "sensor_msgs/NavSatFix"
+    }
+}
@@ -46,6 +46,11 @@ impl PointCloud2 {
         self.width as usize * self.height as usize
     }
 
+    /// Return true if the cloud contains no points
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
     /// Return names of the values in each field.
     pub fn field_names(&self) -> Vec<String> {
         self.fields.iter().map(|f| f.name.clone()).collect()
@@ -51,12 +51,27 @@ impl From<Datatype> for f32 {
             Datatype::UINT16(v) => v as f32,
             Datatype::INT32(v) => v as f32,
             Datatype::UINT32(v) => v as f32,
-            Datatype::FLOAT32(v) => v as f32,
+            Datatype::FLOAT32(v) => v,
             Datatype::FLOAT64(v) => v as f32,
         }
     }
 }
 
+impl From<Datatype> for f64 {
+    fn from(value: Datatype) -> Self {
+        match value {
+            Datatype::INT8(v) => v as f64,
+            Datatype::UINT8(v) => v as f64,
+            Datatype::INT16(v) => v as f64,
+            Datatype::UINT16(v) => v as f64,
+            Datatype::INT32(v) => v as f64,
+            Datatype::UINT32(v) => v as f64,
+            Datatype::FLOAT32(v) => v as f64,
+            Datatype::FLOAT64(v) => v,
+        }
+    }
+}
+
 impl PointField {
     pub fn decode_bytes(&self, data: &[u8]) -> Vec<Datatype> {
         let mut results: Vec<Datatype> = vec![];
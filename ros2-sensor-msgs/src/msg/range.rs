@@ -0,0 +1,48 @@
+use ros2_std_msgs::msg::Header;
+use serde::Deserialize;
+
+/// Single range reading from an active ranger that emits energy and reports one range reading
+/// that is valid along an arc at the distance measured.
+///
+/// This message is not appropriate for laser scanners. See the LaserScan message if you are
+/// working with a laser scanner.
+///
+/// This message also can represent a fixed-distance (binary) ranger. This sensor will have
+/// min_range===max_range===distance of detection. These sensors follow REP 117 and will output
+/// -Inf if the object is detected and +Inf if the object is outside of the detection range.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Range {
+    /// timestamp in the header is the time the ranger returned the distance reading
+    pub header: Header,
+
+    /// the type of radiation used by the sensor (sound, infrared, etc) [enum]
+    pub radiation_type: u8,
+
+    /// the size of the arc that the distance reading is valid for [rad]. the object causing the
+    /// range reading may have been anywhere within -field_of_view/2 and field_of_view/2 at the
+    /// measured range. 0 angle corresponds to the x-axis of the sensor.
+    pub field_of_view: f32,
+
+    /// minimum range value [m]
+    pub min_range: f32,
+
+    /// maximum range value [m]
+    pub max_range: f32,
+
+    /// range data [m] (Note: values < range_min or > range_max should be discarded)
+    pub range: f32,
+}
+
+impl Range {
+    pub const ULTRASOUND: u8 = 0;
+    pub const INFRARED: u8 = 1;
+
+    pub fn name() -> &'static str {
+        "sensor_msgs/Range"
+    }
+
+    /// Return true if the range is within the limits of the ranger.
+    pub fn is_valid(&self) -> bool {
+        self.range >= self.min_range && self.range <= self.max_range
+    }
+}
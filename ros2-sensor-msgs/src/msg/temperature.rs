@@ -0,0 +1,22 @@
+use ros2_std_msgs::msg::Header;
+use serde::Deserialize;
+
+/// Single temperature reading.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Temperature {
+    /// timestamp is the time the temperature was measured
+    /// frame_id is the location of the temperature reading
+    pub header: Header,
+
+    /// Measurement of the Temperature in Degrees Celsius.
+    pub temperature: f64,
+
+    /// 0 is interpreted as variance unknown.
+    pub variance: f64,
+}
+
+impl Temperature {
+    pub fn name() -> &'static str {
+        "sensor_msgs/Temperature"
+    }
+}
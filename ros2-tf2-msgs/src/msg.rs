@@ -0,0 +1,8 @@
+use ros2_geometry_msgs::msg::TransformStamped;
+use serde::Deserialize;
+
+/// A collection of transforms, published on `/tf` and `/tf_static`.
+#[derive(Deserialize, PartialEq, Clone, Debug)]
+pub struct TFMessage {
+    pub transforms: Vec<TransformStamped>,
+}
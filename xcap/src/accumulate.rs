@@ -0,0 +1,289 @@
+//! Accumulation of successive point clouds into a map in a world frame.
+use crate::{las, pcd, ply, trajectory::Trajectory};
+use rerun::external::glam;
+use ros2_sensor_msgs::msg::{PointCloud2, PointField};
+use ros2_std_msgs::msg::Header;
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    str::FromStr,
+    sync::Arc,
+};
+
+// Edge of the voxels in meters. Only points falling into empty voxels are shown, so that the map
+// in the viewer grows with the space covered instead of the clouds received.
+const VIEW_VOXEL: f32 = 0.1;
+
+/// File format of the map.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Format {
+    #[default]
+    Pcd,
+    Ply,
+    Las,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pcd" => Ok(Format::Pcd),
+            "ply" => Ok(Format::Ply),
+            "las" => Ok(Format::Las),
+            _ => Err(format!(
+                "Invalid map format: {}, expected one of pcd, ply, las",
+                s
+            )),
+        }
+    }
+}
+
+impl Format {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Pcd => "pcd",
+            Format::Ply => "ply",
+            Format::Las => "las",
+        }
+    }
+}
+
+/// How the clouds are accumulated and written.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    // Odometry topic or world frame giving the poses of the clouds
+    pub source: String,
+
+    // Format of the map file
+    pub format: Format,
+
+    // Edge of the voxels the map file is downsampled with in meters, every point is kept if zero
+    pub voxel: f32,
+}
+
+/// Where the poses of the clouds in the world frame come from.
+#[derive(Debug, Clone)]
+pub enum Source {
+    // Poses of the vehicle from an odometry topic, the world is the parent frame of the odometry
+    Odometry(Arc<Trajectory>),
+    // Latest transforms of TF into this frame
+    Frame(String),
+}
+
+impl Source {
+    /// Frame the clouds are accumulated in.
+    pub fn world_frame(&self) -> &str {
+        match self {
+            Source::Odometry(trajectory) => &trajectory.frame,
+            Source::Frame(frame) => frame,
+        }
+    }
+}
+
+/// Points of successive clouds in the world frame, shared by the parsers of all the clouds.
+pub struct Map {
+    // Where the poses of the clouds come from
+    source: Source,
+
+    // Edge of the voxels of the output, zero to keep every point
+    voxel: f32,
+
+    // Keep the points for the output?
+    keep: bool,
+
+    // Voxels holding a point shown already
+    shown: HashSet<[i32; 3]>,
+
+    // Sums of the points and intensities in each voxel, and their counts
+    voxels: HashMap<[i32; 3], ([f64; 4], u32)>,
+
+    // Every point with its intensity, if not downsampled
+    points: Vec<[f32; 4]>,
+
+    // Header of the latest cloud added, in the world frame
+    header: Option<Header>,
+
+    // Clouds added
+    clouds: u64,
+}
+
+impl Map {
+    pub fn new(source: Source, voxel: f32, keep: bool) -> Self {
+        Map {
+            source,
+            voxel,
+            keep,
+            shown: HashSet::new(),
+            voxels: HashMap::new(),
+            points: vec![],
+            header: None,
+            clouds: 0,
+        }
+    }
+
+    pub fn source(&self) -> &Source {
+        &self.source
+    }
+
+    pub fn clouds(&self) -> u64 {
+        self.clouds
+    }
+
+    /// Add the points of a cloud in the world frame, with their intensities. Return the points
+    /// falling into voxels empty before, which are the new part of the map to show.
+    pub fn add(
+        &mut self,
+        header: &Header,
+        points: impl IntoIterator<Item = (glam::Vec3, f32)>,
+    ) -> Vec<(glam::Vec3, f32)> {
+        self.header = Some(Header {
+            stamp: header.stamp,
+            frame_id: self.source.world_frame().to_owned(),
+        });
+        self.clouds += 1;
+        let mut new = vec![];
+        for (point, intensity) in points {
+            if !point.is_finite() {
+                continue;
+            }
+            if self.keep && self.voxel > 0.0 {
+                let (sum, count) = self.voxels.entry(voxel_of(point, self.voxel)).or_default();
+                for (s, v) in sum.iter_mut().zip([point.x, point.y, point.z, intensity]) {
+                    *s += v as f64;
+                }
+                *count += 1;
+            } else if self.keep {
+                self.points.push([point.x, point.y, point.z, intensity]);
+            }
+            if self.shown.insert(voxel_of(point, VIEW_VOXEL)) {
+                new.push((point, intensity));
+            }
+        }
+        new
+    }
+
+    /// Write the map into the file. Downsampled maps hold the centroid of the points in each
+    /// voxel, with their mean intensity. Nothing is written if no cloud is added. Return the
+    /// number of points written.
+    pub fn write(
+        &self,
+        path: &Path,
+        format: Format,
+        pcd_format: pcd::Format,
+    ) -> std::io::Result<usize> {
+        let Some(header) = &self.header else {
+            return Ok(0);
+        };
+        let points = if self.voxel > 0.0 {
+            // Sorted by voxel so that the same map gives the same file
+            let mut voxels: Vec<_> = self.voxels.iter().collect();
+            voxels.sort_unstable_by_key(|(voxel, _)| **voxel);
+            voxels
+                .into_iter()
+                .map(|(_, (sum, count))| sum.map(|s| (s / *count as f64) as f32))
+                .collect()
+        } else {
+            self.points.clone()
+        };
+        match format {
+            Format::Pcd => pcd::write(path, &to_cloud(header, &points), pcd_format)?,
+            Format::Ply => ply::write(path, &points)?,
+            Format::Las => las::write(path, &points)?,
+        }
+        Ok(points.len())
+    }
+}
+
+fn voxel_of(point: glam::Vec3, edge: f32) -> [i32; 3] {
+    (point / edge).floor().as_ivec3().to_array()
+}
+
+/// Points of `x`, `y`, `z` and `intensity` as one unordered cloud.
+fn to_cloud(header: &Header, points: &[[f32; 4]]) -> PointCloud2 {
+    let field = |name: &str, offset: u32| PointField {
+        name: name.to_owned(),
+        offset,
+        datatype: 7,
+        count: 1,
+    };
+    let width = points.len() as u32;
+    PointCloud2 {
+        header: header.clone(),
+        height: 1,
+        width,
+        fields: vec![
+            field("x", 0),
+            field("y", 4),
+            field("z", 8),
+            field("intensity", 12),
+        ],
+        is_bigendian: cfg!(target_endian = "big") as u8,
+        point_step: 16,
+        row_step: width * 16,
+        data: points
+            .iter()
+            .flatten()
+            .flat_map(|v| v.to_ne_bytes())
+            .collect(),
+        is_dense: 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+    use std::fs;
+
+    fn points(points: &[[f32; 4]]) -> Vec<(glam::Vec3, f32)> {
+        points
+            .iter()
+            .map(|[x, y, z, i]| (glam::Vec3::new(*x, *y, *z), *i))
+            .collect()
+    }
+
+    #[test]
+    fn write_downsampled_map() {
+        let mut map = Map::new(Source::Frame("map".to_owned()), 1.0, true);
+        let shown = map.add(
+            &test_util::header(),
+            points(&[
+                [0.2, 0.2, 0.2, 10.0],
+                [0.4, 0.6, 0.8, 20.0],
+                [1.5, 0.5, 0.5, 5.0],
+                [f32::NAN, 0.0, 0.0, 1.0],
+            ]),
+        );
+        assert_eq!(shown.len(), 3);
+
+        // Points in voxels of the view shown already are not shown again
+        let shown = map.add(&test_util::header(), points(&[[1.5, 0.5, 0.5, 7.0]]));
+        assert!(shown.is_empty());
+        assert_eq!(map.clouds(), 2);
+
+        // Centroids of the voxels, with their mean intensities
+        let path = test_util::dir("map").join("map.pcd");
+        assert_eq!(
+            map.write(&path, Format::Pcd, pcd::Format::Ascii).unwrap(),
+            2
+        );
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(
+            text.ends_with("DATA ascii\n0.3 0.4 0.5 15\n1.5 0.5 0.5 6\n"),
+            "{}",
+            text
+        );
+    }
+
+    #[test]
+    fn write_nothing_without_clouds() {
+        let map = Map::new(Source::Frame("map".to_owned()), 0.0, true);
+        let path = test_util::dir("map_empty").join("map.las");
+        assert_eq!(
+            map.write(&path, Format::Las, pcd::Format::Ascii).unwrap(),
+            0
+        );
+        assert!(!path.exists());
+    }
+}
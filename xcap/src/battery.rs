@@ -0,0 +1,119 @@
+use crate::extractor::Extractor;
+use mcap::Message;
+use rerun::RecordingStream;
+use ros2_sensor_msgs::msg::BatteryState;
+use std::{
+    fs,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::{atomic::AtomicBool, Arc},
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Payload error. {0}")]
+    Payload(#[from] crate::extractor::DecodeError),
+    #[error("CDR error. {0}")]
+    Cdr(#[from] cdr::Error),
+}
+
+/// Parser of battery states. Voltage, current and percentage are plotted over time, so battery
+/// incidents could be correlated with the other sensors.
+pub struct Parser {
+    // Visualizer with rerun
+    rec_stream: Option<RecordingStream>,
+
+    // CSV file for all the states, only available when dumping data
+    csv: Option<BufWriter<fs::File>>,
+
+    // Scratch buffer for decoded message payloads, reused across messages
+    buffer: Vec<u8>,
+}
+
+impl Parser {
+    pub fn new(output_path: &Path, rerun_stream: Option<RecordingStream>, dump_data: bool) -> Self {
+        // Create output file
+        let csv = if dump_data {
+            fs::create_dir_all(output_path).unwrap();
+            let mut file =
+                BufWriter::new(fs::File::create(output_path.join("battery.csv")).unwrap());
+            writeln!(
+                file,
+                "sec,nanosec,voltage,current,percentage,temperature,charge,capacity,\
+                design_capacity,power_supply_status,power_supply_health,present"
+            )
+            .unwrap();
+            Some(file)
+        } else {
+            None
+        };
+
+        Parser {
+            rec_stream: rerun_stream,
+            csv,
+            buffer: Vec::new(),
+        }
+    }
+}
+
+impl Extractor for Parser {
+    type ExtractorError = Box<dyn std::error::Error + Send + Sync>;
+
+    fn buffer(&mut self) -> &mut Vec<u8> {
+        &mut self.buffer
+    }
+
+    fn step(&mut self, message: &Message) -> Result<(), Self::ExtractorError> {
+        let payload = self.decode_into(message).map_err(Error::Payload)?;
+        let state = cdr::deserialize_from::<_, BatteryState, _>(payload, cdr::size::Infinite)
+            .map_err(Error::Cdr)?;
+
+        if let Some(rec) = &self.rec_stream {
+            rec.set_time_seconds(
+                "main",
+                state.header.stamp.sec as f64 + state.header.stamp.nanosec as f64 * 1e-9,
+            );
+            // Unmeasured values are NaN, and left out of the plots
+            for (name, value) in [
+                ("voltage", state.voltage),
+                ("current", state.current),
+                ("percentage", state.percentage),
+            ] {
+                if !value.is_nan() {
+                    rec.log(
+                        format!("battery/{}/{}", message.channel.topic, name),
+                        &rerun::Scalar::new(value as f64),
+                    )?;
+                }
+            }
+        }
+
+        // Dump data?
+        if let Some(csv) = self.csv.as_mut() {
+            writeln!(
+                csv,
+                "{},{},{},{},{},{},{},{},{},{},{},{}",
+                state.header.stamp.sec,
+                state.header.stamp.nanosec,
+                state.voltage,
+                state.current,
+                state.percentage,
+                state.temperature,
+                state.charge,
+                state.capacity,
+                state.design_capacity,
+                state.power_supply_status,
+                state.power_supply_health,
+                state.present
+            )?;
+        }
+        Ok(())
+    }
+
+    fn post_process(&mut self, _sigint: Arc<AtomicBool>) -> Result<(), Self::ExtractorError> {
+        if let Some(csv) = self.csv.as_mut() {
+            csv.flush()?;
+        }
+        Ok(())
+    }
+}
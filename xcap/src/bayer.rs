@@ -0,0 +1,90 @@
+//! Bilinear demosaicing of raw Bayer images.
+
+// Channel indices in RGB
+const R: u8 = 0;
+const G: u8 = 1;
+const B: u8 = 2;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Not a Bayer image: {0}")]
+    Encoding(String),
+    #[error("Image data is shorter than {0} rows of {1} bytes")]
+    Truncated(u32, u32),
+}
+
+/// Channels of the top left 2x2 block of the mosaic, row by row, and bytes per pixel.
+fn pattern(encoding: &str) -> Option<([u8; 4], usize)> {
+    let (pattern, depth) = encoding.strip_prefix("bayer_")?.split_at_checked(4)?;
+    let pattern = match pattern {
+        "rggb" => [R, G, G, B],
+        "bggr" => [B, G, G, R],
+        "gbrg" => [G, B, R, G],
+        "grbg" => [G, R, B, G],
+        _ => return None,
+    };
+    match depth {
+        "8" => Some((pattern, 1)),
+        "16" => Some((pattern, 2)),
+        _ => None,
+    }
+}
+
+/// Is this encoding a Bayer mosaic?
+pub fn is_bayer(encoding: &str) -> bool {
+    pattern(encoding).is_some()
+}
+
+/// Demosaic the image into 8 bit RGB. Each missing channel is the average of the neighbors
+/// having it. 16 bit images are scaled down to 8 bit.
+pub fn demosaic(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    step: u32,
+    encoding: &str,
+    is_bigendian: bool,
+) -> Result<Vec<u8>, Error> {
+    let (pattern, pixel_size) = pattern(encoding).ok_or(Error::Encoding(encoding.to_owned()))?;
+    let (w, h, step) = (width as usize, height as usize, step as usize);
+    if step < w * pixel_size || data.len() < step * h {
+        return Err(Error::Truncated(height, step as u32));
+    }
+
+    let value = |x: usize, y: usize| -> u32 {
+        let i = y * step + x * pixel_size;
+        match (pixel_size, is_bigendian) {
+            (1, _) => data[i] as u32,
+            (_, false) => u16::from_le_bytes([data[i], data[i + 1]]) as u32 >> 8,
+            (_, true) => u16::from_be_bytes([data[i], data[i + 1]]) as u32 >> 8,
+        }
+    };
+    let channel = |x: usize, y: usize| pattern[(y % 2) * 2 + x % 2];
+
+    let mut rgb = vec![0u8; w * h * 3];
+    for y in 0..h {
+        for x in 0..w {
+            let pixel = &mut rgb[(y * w + x) * 3..(y * w + x + 1) * 3];
+            let own = channel(x, y);
+            pixel[own as usize] = value(x, y) as u8;
+
+            // Neighbors within the image, borders have fewer of them
+            let mut sums = [0u32; 3];
+            let mut counts = [0u32; 3];
+            for ny in y.saturating_sub(1)..=(y + 1).min(h - 1) {
+                for nx in x.saturating_sub(1)..=(x + 1).min(w - 1) {
+                    let c = channel(nx, ny) as usize;
+                    sums[c] += value(nx, ny);
+                    counts[c] += 1;
+                }
+            }
+            for c in [R, G, B].into_iter().filter(|&c| c != own) {
+                let c = c as usize;
+                if let Some(mean) = sums[c].checked_div(counts[c]) {
+                    pixel[c] = mean as u8;
+                }
+            }
+        }
+    }
+    Ok(rgb)
+}
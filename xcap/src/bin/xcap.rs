@@ -1,11 +1,38 @@
 use clap::{Parser, Subcommand};
 use env_logger::Env;
+use indicatif::{HumanBytes, MultiProgress, ProgressBar, ProgressStyle};
 use log::{error, info, warn};
-use rand::Rng;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
 use std::sync::atomic::AtomicBool;
-use std::{env, fs, path::PathBuf, sync::Arc};
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
 use url::Url;
-use xcap::{process, storage::Agent, summary};
+use xcap::{
+    accumulate::{Format as MapFormat, Options as AccumulateOptions},
+    colormap::{parse_domain, Colormap},
+    composite::Options as CompositeOptions,
+    credentials,
+    decoder::Backend as VideoBackend,
+    diff::{self, Change},
+    digest::{Format as DigestFormat, Options as DigestOptions},
+    extrinsics::Extrinsics,
+    fetch_slice, filter,
+    image_file::{Format as ImageFormat, NameTemplate, Options as ImageOptions, Sampling},
+    merge,
+    pcd::Format as PcdFormat,
+    process,
+    progress::ProgressSink,
+    reindex, schema, split, stats,
+    storage::{self, Agent, Azure, Backend, Cache, Gcs, Http, RangeReader, Sftp},
+    summary, verify, CropBox, EgoModel, ErrorPolicy, ExtractOptions, FieldMap, Topic, TopicStats,
+};
 
 struct RuntimeError(String);
 
@@ -15,6 +42,73 @@ struct RuntimeError(String);
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+
+    /// Download remote objects again, instead of using the copies cached in `~/.cache/xcap` by their ETags.
+    #[arg(long, global = true, default_value_t = false)]
+    no_cache: bool,
+
+    /// Endpoint of `s3://` URLs, like `http://your_minio:9000`. Defaults to `S3_ENDPOINT`, `AWS_ENDPOINT_URL_S3` or `AWS_ENDPOINT_URL` if set, or AWS S3 of the region.
+    #[arg(long, global = true)]
+    s3_endpoint: Option<String>,
+
+    /// Region of S3 buckets. Defaults to `S3_REGION`, `AWS_REGION`, `AWS_DEFAULT_REGION` or the AWS profile.
+    #[arg(long, global = true)]
+    s3_region: Option<String>,
+
+    /// How `http(s)://` URLs are read: s3 as MinIO buckets like `http://host:port/bucket/path`, http as files served directly, or auto for s3 if S3 credentials are found and http otherwise.
+    #[arg(long, global = true, default_value = "auto")]
+    backend: HttpBackend,
+}
+
+/// Backend of `http(s)://` URLs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum HttpBackend {
+    Auto,
+    S3,
+    Http,
+}
+
+impl FromStr for HttpBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(HttpBackend::Auto),
+            "s3" => Ok(HttpBackend::S3),
+            "http" => Ok(HttpBackend::Http),
+            _ => Err(format!(
+                "Invalid backend: {}, expected one of auto, s3, http",
+                s
+            )),
+        }
+    }
+}
+
+/// Options of remote sources, shared by all the commands.
+struct RemoteOptions {
+    cache: Option<Cache>,
+    s3_endpoint: Option<String>,
+    s3_region: Option<String>,
+    backend: HttpBackend,
+}
+
+impl RemoteOptions {
+    /// Region of S3 buckets, the flag taking precedence.
+    fn s3_region(&self) -> Option<String> {
+        self.s3_region.clone().or_else(credentials::region)
+    }
+
+    /// Endpoint of `s3://` URLs, the flag taking precedence.
+    fn s3_endpoint(&self, region: &str) -> String {
+        self.s3_endpoint
+            .clone()
+            .or_else(|| {
+                ["S3_ENDPOINT", "AWS_ENDPOINT_URL_S3", "AWS_ENDPOINT_URL"]
+                    .iter()
+                    .find_map(|v| std::env::var(v).ok())
+            })
+            .unwrap_or(format!("https://s3.{}.amazonaws.com", region))
+    }
 }
 
 #[derive(Subcommand, Debug)]
@@ -33,6 +127,14 @@ enum Commands {
         #[arg(long)]
         topics: Option<String>,
 
+        /// Read only the chunks of the selected topics from remote MCAP objects, instead of downloading whole recordings. Needs `--topics` and an indexed file, S3 inputs only.
+        #[arg(long, default_value_t = false, requires = "topics")]
+        stream: bool,
+
+        /// Topics to be skipped, separated by comma. All supported topics except these are extracted if `--topics` is not set.
+        #[arg(long)]
+        exclude_topics: Option<String>,
+
         /// Scale the point cloud in spatial by this factor in preview. Default: 1.0
         #[arg(long)]
         point_cloud_scale: Option<f32>,
@@ -41,178 +143,1420 @@ enum Commands {
         #[arg(long)]
         intensity_scale: Option<f32>,
 
+        /// Scale images by this factor in preview, to save memory of the viewer. Extracted images are kept in full resolution. Example: 0.5. Default: 1.0
+        #[arg(long)]
+        preview_scale: Option<f32>,
+
+        /// Colormap of point cloud intensity: default, viridis, turbo, grayscale or custom:<color,color,...>. Example: "custom:#00F,#FFF,gold"
+        #[arg(long, default_value = "default")]
+        colormap: Colormap,
+
+        /// Intensity values mapped to the ends of the colormap, as `min,max`. Default: 0,0.6 for the default colormap, 0,1 for the others
+        #[arg(long, value_parser = parse_domain)]
+        colormap_domain: Option<(f32, f32)>,
+
+        /// Point fields used as coordinates and intensity, like "x=px,y=py,z=pz,intensity=reflectivity". Use "intensity=none" for clouds without intensity. Default: x, y, z and intensity
+        #[arg(long)]
+        pc_fields: Option<FieldMap>,
+
+        /// Drop points out of the box `xmin,xmax,ymin,ymax,zmin,zmax`, in meters. Example: "-50,50,-20,20,-3,5"
+        #[arg(long, allow_hyphen_values = true)]
+        pc_crop: Option<CropBox>,
+
+        /// Frame of the crop box. The frame of each cloud is used if not specified.
+        #[arg(long, requires = "pc_crop")]
+        pc_crop_frame: Option<String>,
+
+        /// Odometry topic to remove the motion distortion of point clouds with, using the measuring time of each point. Example: "/odom"
+        #[arg(long)]
+        deskew: Option<String>,
+
+        /// Accumulate point clouds into a map in a world frame, posed by an odometry topic or by TF to the frame. The clouds of all topics are merged into one map file. Example: "/odom" or "map"
+        #[arg(long)]
+        accumulate: Option<String>,
+
+        /// Format of the accumulated map: pcd, ply or las. The map is written as `map.<format>` in the output directory.
+        #[arg(long, default_value = "pcd", requires = "accumulate")]
+        map_format: MapFormat,
+
+        /// Edge of the voxels the accumulated map is downsampled with, in meters. Every point is kept if 0.
+        #[arg(long, default_value_t = 0.1, requires = "accumulate")]
+        map_voxel: f32,
+
+        /// 3D model of the ego vehicle shown in the viewer, like a `.glb` file. A placeholder box is shown if not specified.
+        #[arg(long)]
+        ego_model: Option<PathBuf>,
+
+        /// Do not show the ego vehicle in the viewer. Default: false
+        #[arg(long, default_value_t = false, conflicts_with = "ego_model")]
+        no_ego: bool,
+
+        /// Back-project depth images (16UC1 or 32FC1) into point clouds with the intrinsics of their CameraInfo topic, which should be selected too. Clouds are dumped as PCD files. Default: false
+        #[arg(long, default_value_t = false)]
+        depth_cloud: bool,
+
+        /// JSON file of static transforms of the topics in the rig frame, used to fuse sensors of recordings without TF. See README for the format.
+        #[arg(long)]
+        extrinsics: Option<PathBuf>,
+
+        /// Apply the extrinsics to the dumped point clouds, not only to the viewer. Default: false
+        #[arg(long, default_value_t = false, requires = "extrinsics")]
+        extrinsics_in_output: bool,
+
+        /// Decoder of H.264, HEVC and AV1 streams: native (OpenH264 and libde265, no AV1) or ffmpeg, which requires the `ffmpeg` command.
+        #[arg(long, default_value = "native")]
+        video_decoder: VideoBackend,
+
+        /// What to do with messages that fail to parse, like corrupt CDR or zstd payloads: abort, skip, or log and skip. Skipped messages are counted at the end.
+        #[arg(long, default_value = "abort")]
+        on_error: ErrorPolicy,
+
+        /// Format of decoded images and video frames: jpeg, png or webp. Images the format could not hold, like 16 bit images in JPEG, are saved as PNG.
+        #[arg(long, default_value = "jpeg")]
+        image_format: ImageFormat,
+
+        /// Quality of JPEG images, 1 to 100.
+        #[arg(long, default_value_t = 75, value_parser = clap::value_parser!(u8).range(1..=100))]
+        jpeg_quality: u8,
+
+        /// Decode JPEG and PNG CompressedImage messages and save them in --image-format, instead of saving their payloads as they are. Default: false
+        #[arg(long, default_value_t = false)]
+        transcode_images: bool,
+
+        /// Resize saved images and video frames by this factor, like 0.5. CompressedImage messages are decoded for this.
+        #[arg(long, value_parser = parse_scale)]
+        image_scale: Option<f32>,
+
+        /// Write an animated digest of each camera topic, `digest.gif` or `digest.webp` in the topic directory, with frames sampled at --digest-rate and downsized to --digest-height.
+        #[arg(long)]
+        digest: Option<DigestFormat>,
+
+        /// Frames per second of the recording taken into the digests. Example: "1Hz"
+        #[arg(long, default_value = "1Hz", value_parser = parse_rate)]
+        digest_rate: f64,
+
+        /// Height of the digest frames in pixels. Smaller frames are not enlarged.
+        #[arg(long, default_value_t = 480, value_parser = clap::value_parser!(u32).range(1..))]
+        digest_height: u32,
+
+        /// Image topics tiled in a grid into `composite.mp4` in the output directory, with the saved frames synchronized by the nearest stamp. Requires ffmpeg. Example: "/cam_front,/cam_left,/cam_right"
+        #[arg(long, value_delimiter = ',')]
+        composite: Option<Vec<String>>,
+
+        /// Frames per second of the composite video. Example: "10Hz"
+        #[arg(long, default_value = "10Hz", value_parser = parse_rate)]
+        composite_rate: f64,
+
+        /// Height of each tile of the composite video in pixels.
+        #[arg(long, default_value_t = 360, value_parser = clap::value_parser!(u32).range(2..))]
+        composite_height: u32,
+
+        /// Draw the topic, the header stamp and the frame number onto each saved image and video frame. CompressedImage messages are decoded for this. Default: false
+        #[arg(long, default_value_t = false)]
+        burn_timestamp: bool,
+
+        /// Save every Nth frame of each image topic only, like keyframes for labeling. Videos are muxed with all the frames.
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..), conflicts_with = "frame_rate")]
+        frame_stride: Option<u32>,
+
+        /// Save frames of each image topic at most this rate, by the stamps of the frames. Example: "2Hz"
+        #[arg(long, value_parser = parse_rate)]
+        frame_rate: Option<f64>,
+
+        /// Paths of frame files relative to the output directory, with placeholders {topic}, {stamp}, {stamp_sec}, {stamp_nsec}, {publish_time}, {seq} and {ext}. The extension is appended if missing. Example: "{topic}/{stamp_sec}_{stamp_nsec}_{seq}.jpg"
+        #[arg(long)]
+        name_template: Option<NameTemplate>,
+
+        /// Rename frames and point clouds as 000001.jpeg, 000002.jpeg and so on in message order of each topic. Their stamps are listed in index.csv.
+        #[arg(long, default_value_t = false, conflicts_with = "name_template")]
+        seq_names: bool,
+
+        /// Dump point clouds as PCD files in this format: ascii, binary or binary_compressed. Raw point data is dumped if not specified.
+        #[arg(long)]
+        pcd_format: Option<PcdFormat>,
+
+        /// Also export point clouds to Apache Parquet files, one row per point. Default: false
+        #[arg(long, default_value_t = false)]
+        parquet: bool,
+
         /// Enable preview. Default: false
         #[arg(long, default_value_t = false)]
         preview: bool,
 
-        /// Set the start time offset `HH:MM:SS` in UTC. Default: 00:00:00.
+        /// Set the start time `YEAR-MONTH-DAY HH:MM:SS` in UTC. Use `--start` for times relative to the first message, like `+00:05:30` or `+90s`.
         #[arg(long, default_value_t = String::from("1970-1-1 00:00:00"))]
         time_off: String,
 
-        /// Set the stop time `HH:MM:SS` in UTC. The decoding process will reatch to the end of the file if not specified.
-        #[arg(long)]
-        time_stop: Option<String>,
-    },
+        /// Set the stop time `HH:MM:SS` in UTC. The decoding process will reatch to the end of the file if not specified.
+        #[arg(long)]
+        time_stop: Option<String>,
+
+        /// Set the start time relative to the first message, as `+HH:MM:SS` or a duration like `+90s`. Conflicts with `--time-off`.
+        #[arg(long, value_parser = parse_offset, conflicts_with = "time_off")]
+        start: Option<u64>,
+
+        /// Extract this long from the start time. Example: "2m", "00:02:00". Conflicts with `--time-stop`.
+        #[arg(long, value_parser = parse_offset, conflicts_with = "time_stop")]
+        duration: Option<u64>,
+
+        /// Number of files processed concurrently for order independent topics like images and point clouds. Sampled, templated, stamped or digested images, clouds accumulated, exported to Parquet, deskewed or cropped in a TF frame, and clouds and scans shown in their TF frames are processed in order. Default: 1
+        #[arg(short, long, default_value_t = 1)]
+        jobs: usize,
+    },
+
+    /// Visualize ROS messages from MCAP files.
+    Show {
+        /// Input resource. Could be a local directory or a remote S3 URL.
+        #[arg(short, long)]
+        input: String,
+
+        /// Topics to be visualized, separated by comma. Example: "topic,another/topic,/yet/another/topic"
+        #[arg(long)]
+        topics: Option<String>,
+
+        /// Read only the chunks of the selected topics from remote MCAP objects, instead of downloading whole recordings. Needs `--topics` and an indexed file, S3 inputs only.
+        #[arg(long, default_value_t = false, requires = "topics")]
+        stream: bool,
+
+        /// Topics to be skipped, separated by comma. All supported topics except these are visualized if `--topics` is not set.
+        #[arg(long)]
+        exclude_topics: Option<String>,
+
+        /// Scale the point cloud by this factor. Default: 1.0
+        #[arg(long)]
+        point_cloud_scale: Option<f32>,
+
+        /// Scale the point cloud intensity by this factor in preview. Default: 1.0
+        #[arg(long)]
+        intensity_scale: Option<f32>,
+
+        /// Scale images by this factor in preview, to save memory of the viewer. Extracted images are kept in full resolution. Example: 0.5. Default: 1.0
+        #[arg(long)]
+        preview_scale: Option<f32>,
+
+        /// Colormap of point cloud intensity: default, viridis, turbo, grayscale or custom:<color,color,...>. Example: "custom:#00F,#FFF,gold"
+        #[arg(long, default_value = "default")]
+        colormap: Colormap,
+
+        /// Intensity values mapped to the ends of the colormap, as `min,max`. Default: 0,0.6 for the default colormap, 0,1 for the others
+        #[arg(long, value_parser = parse_domain)]
+        colormap_domain: Option<(f32, f32)>,
+
+        /// Point fields used as coordinates and intensity, like "x=px,y=py,z=pz,intensity=reflectivity". Use "intensity=none" for clouds without intensity. Default: x, y, z and intensity
+        #[arg(long)]
+        pc_fields: Option<FieldMap>,
+
+        /// Drop points out of the box `xmin,xmax,ymin,ymax,zmin,zmax`, in meters. Example: "-50,50,-20,20,-3,5"
+        #[arg(long, allow_hyphen_values = true)]
+        pc_crop: Option<CropBox>,
+
+        /// Frame of the crop box. The frame of each cloud is used if not specified.
+        #[arg(long, requires = "pc_crop")]
+        pc_crop_frame: Option<String>,
+
+        /// Odometry topic to remove the motion distortion of point clouds with, using the measuring time of each point. Example: "/odom"
+        #[arg(long)]
+        deskew: Option<String>,
+
+        /// Accumulate point clouds into a map in a world frame, posed by an odometry topic or by TF to the frame. Example: "/odom" or "map"
+        #[arg(long)]
+        accumulate: Option<String>,
+
+        /// 3D model of the ego vehicle shown in the viewer, like a `.glb` file. A placeholder box is shown if not specified.
+        #[arg(long)]
+        ego_model: Option<PathBuf>,
+
+        /// Do not show the ego vehicle in the viewer. Default: false
+        #[arg(long, default_value_t = false, conflicts_with = "ego_model")]
+        no_ego: bool,
+
+        /// Back-project depth images (16UC1 or 32FC1) into point clouds with the intrinsics of their CameraInfo topic, which should be selected too. Clouds are dumped as PCD files. Default: false
+        #[arg(long, default_value_t = false)]
+        depth_cloud: bool,
+
+        /// JSON file of static transforms of the topics in the rig frame, used to fuse sensors of recordings without TF. See README for the format.
+        #[arg(long)]
+        extrinsics: Option<PathBuf>,
+
+        /// Decoder of H.264, HEVC and AV1 streams: native (OpenH264 and libde265, no AV1) or ffmpeg, which requires the `ffmpeg` command.
+        #[arg(long, default_value = "native")]
+        video_decoder: VideoBackend,
+
+        /// What to do with messages that fail to parse, like corrupt CDR or zstd payloads: abort, skip, or log and skip. Skipped messages are counted at the end.
+        #[arg(long, default_value = "abort")]
+        on_error: ErrorPolicy,
+
+        /// Set the start time `YEAR-MONTH-DAY HH:MM:SS` in UTC. Use `--start` for times relative to the first message, like `+00:05:30` or `+90s`.
+        #[arg(long, default_value_t = String::from("1970-1-1 00:00:00"))]
+        time_off: String,
+
+        /// Set the stop time `YEAR-MONTH-DAY HH:MM:SS` in UTC. The decoding process will reatch to the end of the file if not specified.
+        #[arg(long)]
+        time_stop: Option<String>,
+
+        /// Set the start time relative to the first message, as `+HH:MM:SS` or a duration like `+90s`. Conflicts with `--time-off`.
+        #[arg(long, value_parser = parse_offset, conflicts_with = "time_off")]
+        start: Option<u64>,
+
+        /// Visualize this long from the start time. Example: "2m", "00:02:00". Conflicts with `--time-stop`.
+        #[arg(long, value_parser = parse_offset, conflicts_with = "time_stop")]
+        duration: Option<u64>,
+    },
+
+    /// Trim MCAP files.
+    Trim {
+        /// Input resource. Could be a local directory or a remote S3 URL.
+        #[arg(short, long)]
+        input: String,
+
+        /// Set the start time `YEAR-MONTH-DAY HH:MM:SS` in UTC. Use `--start` for times relative to the first message, like `+00:05:30` or `+90s`.
+        #[arg(long, default_value_t = String::from("1970-1-1 00:00:00"))]
+        time_off: String,
+
+        /// Set the stop time `YEAR-MONTH-DAY HH:MM:SS` in UTC. The decoding process will reatch to the end of the file if not specified.
+        #[arg(long)]
+        time_stop: Option<String>,
+
+        /// Set the start time relative to the first message, as `+HH:MM:SS` or a duration like `+90s`. Conflicts with `--time-off`.
+        #[arg(long, value_parser = parse_offset, conflicts_with = "time_off")]
+        start: Option<u64>,
+
+        /// Keep this long from the start time. Example: "2m", "00:02:00". Conflicts with `--time-stop`.
+        #[arg(long, value_parser = parse_offset, conflicts_with = "time_stop")]
+        duration: Option<u64>,
+
+        /// Upload the trimmed file into this remote directory, like a S3 URL of a directory.
+        #[arg(long)]
+        upload: Option<String>,
+    },
+
+    /// Merge MCAP files into one, messages interleaved by their log time.
+    Merge {
+        /// Input resources. Could be local directories or remote S3 URLs. Repeat for multiple inputs.
+        #[arg(short, long, required = true)]
+        input: Vec<String>,
+
+        /// Output MCAP file path.
+        #[arg(short, long, default_value = "merged.mcap")]
+        output: PathBuf,
+    },
+
+    /// Split MCAP files into segments by duration or size.
+    Split {
+        /// Input resource. Could be a local directory or a remote S3 URL.
+        #[arg(short, long)]
+        input: String,
+
+        /// Output directory path.
+        #[arg(short, long)]
+        output_dir: Option<PathBuf>,
+
+        /// Start a new segment every this long in log time. Example: "500ms", "60s", "5m", "1h"
+        #[arg(long, value_parser = parse_duration, required_unless_present = "max_size")]
+        every: Option<u64>,
+
+        /// Start a new segment once this much message data is written. Example: "500MB", "2GB"
+        #[arg(long, value_parser = parse_size)]
+        max_size: Option<u64>,
+    },
+
+    /// Rewrite MCAP files with rebuilt summaries, statistics and chunk indexes, like the ones of
+    /// crashed recorders.
+    Reindex {
+        /// Input resource. Could be a local directory or a remote S3 URL.
+        #[arg(short, long)]
+        input: String,
+
+        /// Output directory path. Files are written with their original names.
+        #[arg(short, long, default_value = "reindexed")]
+        output_dir: PathBuf,
+    },
+
+    /// Copy selected topics into a new MCAP file without decoding them.
+    Filter {
+        /// Input resource. Could be a local directory or a remote S3 URL.
+        #[arg(short, long)]
+        input: String,
+
+        /// Output MCAP file path.
+        #[arg(short, long, default_value = "filtered.mcap")]
+        output: PathBuf,
+
+        /// Topics to keep, separated by comma. All topics are kept if not specified.
+        #[arg(long)]
+        topics: Option<String>,
+
+        /// Topics to drop, separated by comma.
+        #[arg(long)]
+        exclude: Option<String>,
+    },
+
+    /// Report message rate, intervals, gaps, time range and size of every topic.
+    Stats {
+        /// Input resource. Could be a local directory or a remote S3 URL.
+        #[arg(short, long)]
+        input: String,
+
+        /// Print the topics as JSON to stdout, with their schemas, encodings, counts and time ranges.
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+
+    /// List the topics in the summaries of the files, without reading any message.
+    Topics {
+        /// Input resource. Could be a local directory or a remote S3 URL.
+        #[arg(short, long)]
+        input: String,
+
+        /// Print the topics as JSON to stdout, with their schemas, encodings and message counts.
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+
+    /// Print the schema of a topic as stored in the files, like its ros2msg definition or protobuf
+    /// file descriptor set.
+    Schema {
+        /// Input resource. Could be a local directory or a remote S3 URL.
+        #[arg(short, long)]
+        input: String,
+
+        /// Topic of the schema.
+        #[arg(long)]
+        topic: String,
+    },
+
+    /// Compare the topics of two recordings, reporting the added, removed and changed ones.
+    Diff {
+        /// The first recording. Could be a local directory or a remote S3 URL.
+        before: String,
+
+        /// The second recording, compared to the first one.
+        after: String,
+    },
+
+    /// Check the integrity of the files: CRCs, chunk indexes, truncation and corruption. Exits
+    /// with 1 if any file is bad.
+    Verify {
+        /// Input resource. Could be a local directory or a remote S3 URL.
+        #[arg(short, long)]
+        input: String,
+    },
+
+    /// List the recordings of a remote source, with their sizes and modification times.
+    Ls {
+        /// Remote source, like a S3 URL of a directory.
+        source: String,
+    },
+}
+
+/// Parse durations like `60s` into nanoseconds.
+fn parse_duration(s: &str) -> Result<u64, String> {
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (value, unit) = s.split_at(split);
+    let value: u64 = value
+        .parse()
+        .map_err(|_| format!("Invalid duration: {}", s))?;
+    let scale = match unit {
+        "ns" => 1,
+        "us" => 1_000,
+        "ms" => 1_000_000,
+        "s" | "" => 1_000_000_000,
+        "m" => 60_000_000_000,
+        "h" => 3_600_000_000_000,
+        _ => return Err(format!("Invalid duration unit: {}", unit)),
+    };
+    value
+        .checked_mul(scale)
+        .ok_or(format!("Duration out of range: {}", s))
+}
+
+/// Parse relative times like `+00:05:30`, `05:30` or `+90s` into nanoseconds.
+fn parse_offset(s: &str) -> Result<u64, String> {
+    let s = s.trim().trim_start_matches('+');
+    if !s.contains(':') {
+        return parse_duration(s);
+    }
+    let mut seconds = 0.0;
+    for part in s.split(':') {
+        let value: f64 = part
+            .parse()
+            .map_err(|_| format!("Invalid time offset: {}", s))?;
+        if !value.is_finite() || value.is_sign_negative() {
+            return Err(format!("Invalid time offset: {}", s));
+        }
+        seconds = seconds * 60.0 + value;
+    }
+    let nanoseconds = seconds * 1e9;
+    if nanoseconds >= u64::MAX as f64 {
+        return Err(format!("Time offset out of range: {}", s));
+    }
+    Ok(nanoseconds as u64)
+}
+
+/// Ego vehicle model to show from the command line arguments.
+fn ego(ego_model: &Option<PathBuf>, no_ego: bool) -> EgoModel {
+    match ego_model {
+        _ if no_ego => EgoModel::Hidden,
+        Some(path) => EgoModel::File(path.clone()),
+        None => EgoModel::Placeholder,
+    }
+}
+
+/// Crop box from the command line arguments.
+fn crop_box(pc_crop: &Option<CropBox>, pc_crop_frame: &Option<String>) -> Option<CropBox> {
+    pc_crop.clone().map(|crop| CropBox {
+        frame: pc_crop_frame.clone(),
+        ..crop
+    })
+}
+
+/// Options of the accumulated map from the command line arguments.
+fn map_options(
+    accumulate: &Option<String>,
+    format: MapFormat,
+    voxel: f32,
+) -> Option<AccumulateOptions> {
+    accumulate.clone().map(|source| AccumulateOptions {
+        source,
+        format,
+        voxel,
+    })
+}
+
+/// Frame sampling from the command line arguments.
+fn sampling(frame_stride: Option<u32>, frame_rate: Option<f64>) -> Sampling {
+    match (frame_stride, frame_rate) {
+        (Some(n), _) => Sampling::Stride(n),
+        (_, Some(hz)) => Sampling::Rate(hz),
+        _ => Sampling::All,
+    }
+}
+
+/// Parse rates like `2Hz` or `0.5` into frames per second.
+fn parse_rate(s: &str) -> Result<f64, String> {
+    let number = s.trim().trim_end_matches("Hz").trim_end_matches("hz");
+    match number.trim().parse::<f64>() {
+        Ok(hz) if hz > 0.0 && hz.is_finite() => Ok(hz),
+        _ => Err(format!(
+            "Invalid frame rate: {}, expected a positive number like 2Hz",
+            s
+        )),
+    }
+}
+
+/// Parse scales of images, positive and at most 1.
+fn parse_scale(s: &str) -> Result<f32, String> {
+    match s.trim().parse::<f32>() {
+        Ok(scale) if scale > 0.0 && scale <= 1.0 => Ok(scale),
+        _ => Err(format!(
+            "Invalid image scale: {}, expected a number in (0, 1] like 0.5",
+            s
+        )),
+    }
+}
+
+/// Parse sizes like `2GB` into bytes, in multiples of 1024.
+fn parse_size(s: &str) -> Result<u64, String> {
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (value, unit) = s.split_at(split);
+    let value: u64 = value.parse().map_err(|_| format!("Invalid size: {}", s))?;
+    let scale = match unit.to_uppercase().as_str() {
+        "B" | "" => 1,
+        "KB" | "K" => 1 << 10,
+        "MB" | "M" => 1 << 20,
+        "GB" | "G" => 1 << 30,
+        _ => return Err(format!("Invalid size unit: {}", unit)),
+    };
+    value
+        .checked_mul(scale)
+        .ok_or(format!("Size out of range: {}", s))
+}
+
+// Backend, bucket and directory of the objects of a remote source
+type Remote = Option<(Box<dyn Backend>, String, String)>;
+
+/// Open the backend of the remote source. Local sources are None.
+async fn open_remote(input_src: &str, options: &RemoteOptions) -> Result<Remote, RuntimeError> {
+    let remote: Remote = if input_src.starts_with("sftp://") {
+        // Like `sftp://user@host:port/path/to/dir`, the path is kept as it is
+        let location = input_src.trim_start_matches("sftp://");
+        let (authority, path) = location.split_once('/').unwrap_or((location, ""));
+        let (destination, port) = match authority.rsplit_once(':') {
+            Some((destination, port)) => (
+                destination,
+                Some(
+                    port.parse::<u16>()
+                        .map_err(|e| RuntimeError(format!("Invalid port. {}", e)))?,
+                ),
+            ),
+            None => (authority, None),
+        };
+        if destination.is_empty() {
+            return Err(RuntimeError("URL host is None.".to_string()));
+        }
+        let storage = Sftp::new(destination, port);
+        Some((
+            Box::new(storage),
+            destination.to_string(),
+            format!("/{}", path),
+        ))
+    } else if input_src.starts_with("gs://") || input_src.starts_with("az://") {
+        // Object names are kept as they are, without URL encoding
+        let (scheme, location) = input_src.split_once("://").unwrap();
+        let (bucket, prefix) = location.split_once('/').unwrap_or((location, ""));
+        if bucket.is_empty() {
+            return Err(RuntimeError("Failed to get bucket name.".to_string()));
+        }
+        let init_failed = |e| RuntimeError(format!("Storage init failed. {}", e));
+        let storage: Box<dyn Backend> = match scheme {
+            "gs" => Box::new(Gcs::new(bucket).map_err(init_failed)?),
+            _ => Box::new(Azure::new(bucket).map_err(init_failed)?),
+        };
+        let (bucket, prefix) = (bucket.to_string(), prefix.to_string());
+        Some((storage, bucket, prefix))
+    } else if input_src.starts_with("s3://") {
+        // Like `s3://bucket/prefix`, on AWS S3 of the region unless an endpoint is set
+        let location = input_src.trim_start_matches("s3://");
+        let (bucket, prefix) = location.split_once('/').unwrap_or((location, ""));
+        if bucket.is_empty() {
+            return Err(RuntimeError("Failed to get bucket name.".to_string()));
+        }
+        let region = options.s3_region().unwrap_or("us-east-1".to_string());
+        let endpoint = options.s3_endpoint(&region);
+        let credentials = credentials::resolve()
+            .await
+            .map_err(|e| RuntimeError(format!("Failed to get credentials. {}", e)))?;
+        let storage = Agent::new(&endpoint, &region, &credentials, bucket)
+            .map_err(|e| RuntimeError(format!("Storage init failed. {}", e)))?;
+        Some((
+            Box::new(storage),
+            bucket.to_string(),
+            prefix.trim_end_matches('/').to_string(),
+        ))
+    } else if input_src.starts_with("http") {
+        // MinIO buckets need credentials, files served directly do not
+        let credentials = match options.backend {
+            HttpBackend::Http => None,
+            HttpBackend::S3 => Some(
+                credentials::resolve()
+                    .await
+                    .map_err(|e| RuntimeError(format!("Failed to get credentials. {}", e)))?,
+            ),
+            HttpBackend::Auto => credentials::resolve().await.ok(),
+        };
+        let Some(credentials) = credentials else {
+            let valid_url =
+                Url::parse(input_src).map_err(|e| RuntimeError(format!("Invalid URL. {}", e)))?;
+            let host = valid_url
+                .host_str()
+                .ok_or(RuntimeError("URL host is None.".to_string()))?
+                .to_string();
+            let storage = Http::new(input_src)
+                .map_err(|e| RuntimeError(format!("Storage init failed. {}", e)))?;
+            return Ok(Some((Box::new(storage), host, String::new())));
+        };
+        let valid_url =
+            Url::parse(input_src).map_err(|e| RuntimeError(format!("Invalid URL. {}", e)))?;
+
+        let base_url = format!(
+            "{}://{}:{}",
+            valid_url.scheme(),
+            valid_url
+                .host_str()
+                .ok_or(RuntimeError("URL host is None.".to_string()))?,
+            valid_url
+                .port()
+                .ok_or(RuntimeError("URL port is None.".to_string()))?,
+        );
+        let bucket = valid_url
+            .path_segments()
+            .ok_or(RuntimeError("Invalid URL path.".to_string()))?
+            .next()
+            .ok_or(RuntimeError("Failed to get bucket name.".to_string()))?;
+        let obj_name = valid_url
+            .path_segments()
+            .unwrap()
+            .next_back()
+            .ok_or(RuntimeError("Failed to get object name.".to_string()))?;
+        let object_dir = valid_url
+            .path()
+            .trim_start_matches('/')
+            .trim_start_matches(bucket)
+            .trim_start_matches('/')
+            .trim_end_matches(obj_name)
+            .trim_end_matches('/');
+
+        let region = options.s3_region().ok_or(RuntimeError(
+            "Region not set. Set `--s3-region`, `S3_REGION` or `AWS_REGION`.".to_string(),
+        ))?;
+        let storage = Agent::new(&base_url, &region, &credentials, bucket)
+            .map_err(|e| RuntimeError(format!("Storage init failed. {}", e)))?;
+        Some((
+            Box::new(storage),
+            bucket.to_string(),
+            object_dir.to_string(),
+        ))
+    } else {
+        None
+    };
+    Ok(remote)
+}
+
+/// Prepare inputs. Download from remote server if necessary.
+async fn prepare_inputs(
+    source: &str,
+    download_path: &mut Option<PathBuf>,
+    sigint: &Arc<AtomicBool>,
+    stream_topics: Option<&[&str]>,
+    remote: &RemoteOptions,
+    progress: &dyn ProgressSink,
+) -> Result<Vec<PathBuf>, RuntimeError> {
+    // Safety first
+    let mut input_src = source.to_owned();
+    if input_src.is_empty() {
+        return Err(RuntimeError("Input source is empty.".to_string()));
+    }
+
+    // Download from remote server?
+    let opened = open_remote(&input_src, remote).await?;
+
+    if let Some((storage, bucket, object_dir)) = opened {
+        // The same source is downloaded into the same directory of the user, so that an
+        // interrupted download could be resumed by running again. Slices of topics are kept apart.
+        let mut hasher = Sha256::new();
+        hasher.update(source.as_bytes());
+        if let Some(topics) = stream_topics {
+            hasher.update(topics.join(",").as_bytes());
+        }
+        let digest: String = hasher.finalize()[..6]
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        let _down_path = storage::cache_home()
+            .ok_or(RuntimeError(
+                "Unknown home directory to download into, set HOME or XDG_CACHE_HOME.".to_string(),
+            ))?
+            .join("downloads")
+            .join(format!("{}-{}", bucket, digest));
+        std::fs::create_dir_all(&_down_path).map_err(|e| {
+            RuntimeError(format!(
+                "Failed to create download directory: {}, {}",
+                _down_path.display(),
+                e
+            ))
+        })?;
+        lock_download(&_down_path)?;
+
+        // Partial downloads are kept if failed or interrupted
+        info!("Downloading from bucket: {}", bucket);
+        let downloaded = match stream_topics {
+            Some(topics) => fetch_slices(
+                storage.as_ref(),
+                &object_dir,
+                topics,
+                &_down_path,
+                progress,
+                sigint,
+            )
+            .await
+            .map_err(|e| e.0),
+            None => storage
+                .download_dir(
+                    &object_dir,
+                    &_down_path,
+                    remote.cache.as_ref(),
+                    progress,
+                    sigint,
+                )
+                .await
+                .map_err(|e| e.to_string()),
+        };
+        downloaded.map_err(|e| {
+            RuntimeError(format!(
+                "Download failed. {}. Run again to resume from {}",
+                e,
+                _down_path.display()
+            ))
+        })?;
+        if sigint.load(std::sync::atomic::Ordering::Relaxed) {
+            return Err(RuntimeError(format!(
+                "Download interrupted. Run again to resume from {}",
+                _down_path.display()
+            )));
+        }
+        download_path.clone_from(&Some(_down_path.clone()));
+
+        input_src = _down_path
+            .to_str()
+            .ok_or(RuntimeError(format!(
+                "Get OS string failed. {}",
+                _down_path.display()
+            )))?
+            .to_string();
+    } else {
+        input_src = source.to_owned();
+    }
+
+    let input_dir = PathBuf::from(input_src);
+    if !input_dir.exists() {
+        return Err(RuntimeError(format!(
+            "Input directory not found: {}",
+            input_dir.display()
+        )));
+    }
+
+    // Find all MCAP files, and rosbag2 SQLite files
+    let mut files: Vec<PathBuf> = fs::read_dir(&input_dir)
+        .map_err(|e| RuntimeError(format!("Failed to read directory: {}", e)))?
+        .map(|f| f.unwrap().path())
+        .filter(|f| f.is_file() && f.extension().is_some_and(|f| f.eq("mcap") || f.eq("db3")))
+        .collect();
+    files.sort();
+
+    Ok(files)
+}
+
+/// Copy the topics of the remote MCAP objects in the directory into local files, reading their
+/// summaries and the chunks of the topics only. Other files are downloaded in whole.
+async fn fetch_slices(
+    storage: &dyn Backend,
+    dir: &str,
+    topics: &[&str],
+    local_path: &Path,
+    progress: &dyn ProgressSink,
+    sigint: &Arc<AtomicBool>,
+) -> Result<(), RuntimeError> {
+    let storage_error = |e: storage::Error| RuntimeError(e.to_string());
+    for object in storage.list_dir(dir).await.map_err(storage_error)? {
+        if sigint.load(std::sync::atomic::Ordering::Relaxed) {
+            break;
+        }
+        let obj_file = &storage::local_name(dir, &object.name);
+        let obj_file_path = local_path.join(obj_file);
+        if !obj_file.ends_with(".mcap") {
+            storage
+                .download_object(&object.name, &obj_file_path, progress, sigint)
+                .await
+                .map_err(storage_error)?;
+            continue;
+        }
+        info!("Reading topics from: {}", obj_file);
+        let mut reader = RangeReader::new(storage, &object.name)
+            .await
+            .map_err(storage_error)?;
+        let count = fetch_slice(&mut reader, topics, .., &obj_file_path)
+            .map_err(|e| RuntimeError(format!("{}. {}", obj_file, e)))?;
+        info!("Copied {} messages from {}", count, obj_file);
+    }
+    Ok(())
+}
+
+/// Merge all the inputs into one MCAP file.
+async fn run_merge(
+    inputs: &[String],
+    output: &Path,
+    sigint: &Arc<AtomicBool>,
+    remote: &RemoteOptions,
+) {
+    let progress = ProgressBars::new();
+    let mut download_paths: Vec<Option<PathBuf>> = vec![];
+    let mut files: Vec<PathBuf> = vec![];
+    for input in inputs {
+        let mut download_path = None;
+        let ret = prepare_inputs(input, &mut download_path, sigint, None, remote, &progress).await;
+        download_paths.push(download_path);
+        match ret {
+            Ok(f) => files.extend(f),
+            Err(e) => {
+                error!("{}", e.0);
+                download_paths.iter().for_each(cleanup);
+                return;
+            }
+        }
+    }
+    info!("Merging {} files into {}", files.len(), output.display());
+
+    match merge(&files, output, sigint.clone()) {
+        Ok(_) => {
+            info!("Done.");
+        }
+        Err(e) => {
+            error!("{}", e);
+            warn!("Sorry, job failed.");
+        }
+    }
+    download_paths.iter().for_each(cleanup);
+}
+
+/// Split the input into segments.
+async fn run_split(
+    input: &str,
+    output_dir: &Option<PathBuf>,
+    every: Option<u64>,
+    max_size: Option<u64>,
+    sigint: &Arc<AtomicBool>,
+    remote: &RemoteOptions,
+) {
+    let progress = ProgressBars::new();
+    let mut download_path = None;
+    let files =
+        match prepare_inputs(input, &mut download_path, sigint, None, remote, &progress).await {
+            Ok(f) => f,
+            Err(e) => {
+                error!("{}", e.0);
+                cleanup(&download_path);
+                return;
+            }
+        };
+    let output_dir = output_dir
+        .clone()
+        .unwrap_or(std::env::current_dir().unwrap());
+    info!("Output directory: {}", output_dir.display());
+
+    match split(&files, &output_dir, every, max_size, sigint.clone()) {
+        Ok(_) => {
+            info!("Done.");
+        }
+        Err(e) => {
+            error!("{}", e);
+            warn!("Sorry, job failed.");
+        }
+    }
+    cleanup(&download_path);
+}
+
+/// Rewrite every MCAP file of the input with a rebuilt summary into the output directory.
+async fn run_reindex(
+    input: &str,
+    output_dir: &Path,
+    sigint: &Arc<AtomicBool>,
+    remote: &RemoteOptions,
+) {
+    let progress = ProgressBars::new();
+    let mut download_path = None;
+    let files =
+        match prepare_inputs(input, &mut download_path, sigint, None, remote, &progress).await {
+            Ok(f) => f,
+            Err(e) => {
+                error!("{}", e.0);
+                cleanup(&download_path);
+                return;
+            }
+        };
+    if let Err(e) = fs::create_dir_all(output_dir) {
+        error!("Failed to create output directory: {}", e);
+        cleanup(&download_path);
+        return;
+    }
+    info!("Output directory: {}", output_dir.display());
+
+    for file in files
+        .iter()
+        .filter(|f| f.extension().is_some_and(|e| e == "mcap"))
+    {
+        let output = output_dir.join(file.file_name().unwrap());
+        // Never overwrite the file being read
+        if fs::canonicalize(&output).is_ok_and(|o| fs::canonicalize(file).is_ok_and(|f| f == o)) {
+            error!("Output is the input file itself: {}", file.display());
+            continue;
+        }
+        match reindex(file, &output, sigint.clone()) {
+            Ok(count) => info!("Reindexed {}, msgs: {}", output.display(), count),
+            Err(e) => {
+                error!("{}", e);
+                warn!("Sorry, job failed.");
+                break;
+            }
+        }
+    }
+    cleanup(&download_path);
+}
+
+/// Copy the selected topics of the input into a new MCAP file.
+async fn run_filter(
+    input: &str,
+    output: &Path,
+    topics: &Option<String>,
+    exclude: &Option<String>,
+    sigint: &Arc<AtomicBool>,
+    remote: &RemoteOptions,
+) {
+    let progress = ProgressBars::new();
+    let mut download_path = None;
+    let files =
+        match prepare_inputs(input, &mut download_path, sigint, None, remote, &progress).await {
+            Ok(f) => f,
+            Err(e) => {
+                error!("{}", e.0);
+                cleanup(&download_path);
+                return;
+            }
+        };
+    let split_topics = |topics: &Option<String>| -> Vec<String> {
+        topics
+            .as_ref()
+            .map(|t| t.trim().split(',').map(|t| t.to_string()).collect())
+            .unwrap_or_default()
+    };
+    info!("Writing topics into {}", output.display());
+
+    match filter(
+        &files,
+        output,
+        &split_topics(topics),
+        &split_topics(exclude),
+        sigint.clone(),
+    ) {
+        Ok(_) => {
+            info!("Done.");
+        }
+        Err(e) => {
+            error!("{}", e);
+            warn!("Sorry, job failed.");
+        }
+    }
+    cleanup(&download_path);
+}
+
+/// Report statistics of all the topics in the input.
+async fn run_stats(input: &str, json: bool, sigint: &Arc<AtomicBool>, remote: &RemoteOptions) {
+    let progress = ProgressBars::new();
+    let mut download_path = None;
+    let files =
+        match prepare_inputs(input, &mut download_path, sigint, None, remote, &progress).await {
+            Ok(f) => f,
+            Err(e) => {
+                error!("{}", e.0);
+                cleanup(&download_path);
+                return;
+            }
+        };
+
+    match stats(&files, sigint.clone()) {
+        Ok(topics) if json => {
+            // Schemas are taken from the summaries, if the files have them
+            let channels = summary(&files).unwrap_or_default();
+            let topics: Vec<TopicJson> = topics
+                .iter()
+                .map(|stats| TopicJson::new(stats, channels.iter().find(|t| t.name == stats.name)))
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&topics).unwrap());
+        }
+        Ok(topics) => {
+            info!("Found topics: {}", topics.len());
+            for topic in topics.iter() {
+                info!("- {}", topic);
+            }
+        }
+        Err(e) => {
+            error!("{}", e);
+            warn!("Sorry, job failed.");
+        }
+    }
+    cleanup(&download_path);
+}
+
+/// Print the topics in the summaries of the inputs.
+async fn run_topics(input: &str, json: bool, sigint: &Arc<AtomicBool>, remote: &RemoteOptions) {
+    let progress = ProgressBars::new();
+    let mut download_path = None;
+    let files =
+        match prepare_inputs(input, &mut download_path, sigint, None, remote, &progress).await {
+            Ok(f) => f,
+            Err(e) => {
+                error!("{}", e.0);
+                cleanup(&download_path);
+                return;
+            }
+        };
+
+    match summary(&files) {
+        Ok(topics) if json => {
+            let topics: Vec<ChannelJson> = topics.iter().map(ChannelJson::new).collect();
+            println!("{}", serde_json::to_string_pretty(&topics).unwrap());
+        }
+        Ok(topics) => {
+            for topic in topics.iter() {
+                println!("{}", topic);
+            }
+            info!("Found topics: {}", topics.len());
+        }
+        Err(e) => {
+            error!("{}", e);
+            warn!("Sorry, job failed.");
+        }
+    }
+    cleanup(&download_path);
+}
+
+/// Print the schema data of the topic to stdout. Binary schemas like protobuf descriptors are
+/// written as they are.
+async fn run_schema(input: &str, topic: &str, sigint: &Arc<AtomicBool>, remote: &RemoteOptions) {
+    let progress = ProgressBars::new();
+    let mut download_path = None;
+    let files =
+        match prepare_inputs(input, &mut download_path, sigint, None, remote, &progress).await {
+            Ok(f) => f,
+            Err(e) => {
+                error!("{}", e.0);
+                cleanup(&download_path);
+                return;
+            }
+        };
 
-    /// Visualize ROS messages from MCAP files.
-    Show {
-        /// Input resource. Could be a local directory or a remote S3 URL.
-        #[arg(short, long)]
-        input: String,
+    match schema(&files, topic) {
+        Ok(Some(schema)) => {
+            info!("Schema: {}, encoding: {}", schema.name, schema.encoding);
+            // Text schemas end with a new line, so that the prompt follows on its own line
+            let mut data = schema.data.into_owned();
+            if std::str::from_utf8(&data).is_ok() && !data.ends_with(b"\n") {
+                data.push(b'\n');
+            }
+            if let Err(e) = io::stdout().write_all(&data) {
+                error!("{}", e);
+            }
+        }
+        Ok(None) => error!("No schema found for topic {}", topic),
+        Err(e) => {
+            error!("{}", e);
+            warn!("Sorry, job failed.");
+        }
+    }
+    cleanup(&download_path);
+}
 
-        /// Topics to be visualized, separated by comma. Example: "topic,another/topic,/yet/another/topic"
-        #[arg(long)]
-        topics: Option<String>,
+/// Print the topics added, removed or changed from the first recording to the second.
+async fn run_diff(before: &str, after: &str, sigint: &Arc<AtomicBool>, remote: &RemoteOptions) {
+    let progress = ProgressBars::new();
+    let mut download_paths = [None, None];
+    let mut profiles = Vec::new();
+    for (source, download_path) in [before, after].iter().zip(download_paths.iter_mut()) {
+        let profile =
+            match prepare_inputs(source, download_path, sigint, None, remote, &progress).await {
+                Ok(files) => diff::profile(&files, sigint.clone()).map_err(|e| e.to_string()),
+                Err(e) => Err(e.0),
+            };
+        match profile {
+            Ok(p) => profiles.push(p),
+            Err(e) => {
+                error!("{}", e);
+                warn!("Sorry, job failed.");
+                download_paths.iter().for_each(cleanup);
+                return;
+            }
+        }
+    }
 
-        /// Scale the point cloud by this factor. Default: 1.0
-        #[arg(long)]
-        point_cloud_scale: Option<f32>,
+    let changes = diff::compare(&profiles[0], &profiles[1]);
+    let secs = |ns: u64| ns as f64 * 1e-9;
+    let short = |hash: &str| hash.get(..12).unwrap_or(hash).to_string();
+    for change in changes.iter() {
+        match change {
+            Change::Added(p) => println!("+ {}, {}, msgs: {}", p.name, p.schema, p.msg_count),
+            Change::Removed(p) => println!("- {}, {}, msgs: {}", p.name, p.schema, p.msg_count),
+            Change::Changed {
+                before,
+                after,
+                fields,
+            } => {
+                let details: Vec<String> = fields
+                    .iter()
+                    .map(|field| match *field {
+                        "schema" => format!(
+                            "schema: {} ({}) -> {} ({})",
+                            before.schema, before.encoding, after.schema, after.encoding
+                        ),
+                        "schema_data" => format!(
+                            "schema data: {} -> {}",
+                            short(&before.schema_hash),
+                            short(&after.schema_hash)
+                        ),
+                        "msg_count" => {
+                            format!("msgs: {} -> {}", before.msg_count, after.msg_count)
+                        }
+                        _ => format!(
+                            "coverage: {:.3} s -> {:.3} s",
+                            secs(before.coverage()),
+                            secs(after.coverage())
+                        ),
+                    })
+                    .collect();
+                println!("~ {}, {}", after.name, details.join(", "));
+            }
+        }
+    }
+    info!(
+        "Topics: {} -> {}, changes: {}",
+        profiles[0].len(),
+        profiles[1].len(),
+        changes.len()
+    );
+    download_paths.iter().for_each(cleanup);
+}
 
-        /// Scale the point cloud intensity by this factor in preview. Default: 1.0
-        #[arg(long)]
-        intensity_scale: Option<f32>,
+/// Verify every input file and report the problems found. Exits with 1 if any file is bad, so
+/// that bad uploads could be caught by scripts.
+async fn run_verify(input: &str, sigint: &Arc<AtomicBool>, remote: &RemoteOptions) {
+    let progress = ProgressBars::new();
+    let mut download_path = None;
+    let files =
+        match prepare_inputs(input, &mut download_path, sigint, None, remote, &progress).await {
+            Ok(f) => f,
+            Err(e) => {
+                error!("{}", e.0);
+                cleanup(&download_path);
+                return;
+            }
+        };
 
-        /// Set the start time offset `YEAR-MONTH-DAY HH:MM:SS` in UTC.
-        #[arg(long, default_value_t = String::from("1970-1-1 00:00:00"))]
-        time_off: String,
+    let mut bad = 0;
+    for file in files.iter() {
+        match verify::verify(file, sigint) {
+            Ok(report) if report.is_ok() => println!(
+                "OK {}, records: {}, chunks: {}, msgs: {}",
+                file.display(),
+                report.records,
+                report.chunks,
+                report.messages
+            ),
+            Ok(report) => {
+                bad += 1;
+                println!("BAD {}", file.display());
+                for issue in report.issues.iter() {
+                    println!("    {}", issue);
+                }
+            }
+            Err(e) => {
+                error!("{}", e);
+                warn!("Sorry, job failed.");
+                cleanup(&download_path);
+                return;
+            }
+        }
+    }
+    info!("Verified files: {}, bad: {}", files.len(), bad);
+    cleanup(&download_path);
+    if bad > 0 {
+        std::process::exit(1);
+    }
+}
 
-        /// Set the stop time `YEAR-MONTH-DAY HH:MM:SS` in UTC. The decoding process will reatch to the end of the file if not specified.
-        #[arg(long)]
-        time_stop: Option<String>,
-    },
+/// Topic in the JSON output of `topics`.
+#[derive(Serialize)]
+struct ChannelJson<'a> {
+    id: u16,
+    topic: &'a str,
+    schema: &'a str,
+    encoding: &'a str,
+    message_encoding: &'a str,
+    // None if the files have no statistics
+    message_count: Option<u64>,
+    // Could be processed by `extract` and `show`
+    supported: bool,
+}
 
-    /// Trim MCAP files.
-    Trim {
-        /// Input resource. Could be a local directory or a remote S3 URL.
-        #[arg(short, long)]
-        input: String,
+impl<'a> ChannelJson<'a> {
+    fn new(topic: &'a Topic) -> Self {
+        ChannelJson {
+            id: topic.id,
+            topic: &topic.name,
+            schema: &topic.format,
+            encoding: &topic.encoding,
+            message_encoding: &topic.message_encoding,
+            message_count: topic.msg_count,
+            supported: topic.is_supported(),
+        }
+    }
+}
 
-        /// Set the start time offset `YEAR-MONTH-DAY HH:MM:SS` in UTC.
-        #[arg(long, default_value_t = String::from("1970-1-1 00:00:00"))]
-        time_off: String,
+/// Topic in the JSON output of `stats`.
+#[derive(Serialize)]
+struct TopicJson<'a> {
+    topic: &'a str,
+    schema: Option<&'a str>,
+    encoding: Option<&'a str>,
+    message_encoding: Option<&'a str>,
+    message_count: u64,
+    bytes: u64,
+    // Log times of the first and the last message in nanoseconds
+    start_time: u64,
+    end_time: u64,
+    rate: f64,
+}
 
-        /// Set the stop time `YEAR-MONTH-DAY HH:MM:SS` in UTC. The decoding process will reatch to the end of the file if not specified.
-        #[arg(long)]
-        time_stop: Option<String>,
-    },
+impl<'a> TopicJson<'a> {
+    fn new(stats: &'a TopicStats, topic: Option<&'a Topic>) -> Self {
+        TopicJson {
+            topic: &stats.name,
+            schema: topic.map(|t| t.format.as_str()),
+            encoding: topic.map(|t| t.encoding.as_str()),
+            message_encoding: topic.map(|t| t.message_encoding.as_str()),
+            message_count: stats.msg_count,
+            bytes: stats.total_bytes,
+            start_time: stats.first,
+            end_time: stats.last,
+            rate: stats.rate(),
+        }
+    }
 }
 
-/// Prepare inputs. Download from remote server if necessary.
-async fn prepare_inputs(
-    source: &str,
-    download_path: &mut Option<PathBuf>,
-    sigint: &Arc<AtomicBool>,
-) -> Result<Vec<PathBuf>, RuntimeError> {
-    // Safety first
-    let mut input_src = source.to_owned();
-    if input_src.is_empty() {
-        return Err(RuntimeError("Input source is empty.".to_string()));
+/// Print the MCAP and rosbag2 objects of the remote source.
+async fn run_ls(source: &str, remote: &RemoteOptions) {
+    let (storage, _, dir) = match open_remote(source, remote).await {
+        Ok(Some(remote)) => remote,
+        Ok(None) => {
+            error!("Not a remote source: {}", source);
+            return;
+        }
+        Err(e) => {
+            error!("{}", e.0);
+            return;
+        }
+    };
+    let objects = match storage.list_dir(&dir).await {
+        Ok(objects) => objects,
+        Err(e) => {
+            error!("Listing failed. {}", e);
+            return;
+        }
+    };
+    let recordings: Vec<_> = objects
+        .iter()
+        .filter(|o| o.name.ends_with(".mcap") || o.name.ends_with(".db3"))
+        .collect();
+    for object in recordings.iter() {
+        let size = object
+            .size
+            .map_or("-".to_string(), |s| HumanBytes(s).to_string());
+        let modified = object.last_modified.map_or("-".to_string(), |t| {
+            t.format("%Y-%m-%d %H:%M:%S").to_string()
+        });
+        println!("{:>10}  {:<19}  {}", size, modified, object.name);
     }
+    info!("Found recordings: {}", recordings.len());
+}
 
-    // Download from remote server?
-    if input_src.starts_with("http") {
-        let valid_url =
-            Url::parse(&input_src).map_err(|e| RuntimeError(format!("Invalid URL. {}", e)))?;
+/// Progress bar of each topic in the terminal.
+struct ProgressBars {
+    bars: MultiProgress,
+    style: ProgressStyle,
+    topics: Mutex<HashMap<String, ProgressBar>>,
+    // Bars of the objects being downloaded, in bytes
+    download_style: ProgressStyle,
+    downloads: Mutex<HashMap<String, ProgressBar>>,
+}
 
-        let base_url = format!(
-            "{}://{}:{}",
-            valid_url.scheme(),
-            valid_url
-                .host_str()
-                .ok_or(RuntimeError(format!("URL host is None.")))?,
-            valid_url
-                .port()
-                .ok_or(RuntimeError(format!("URL port is None.")))?,
-        );
-        let bucket = valid_url
-            .path_segments()
-            .ok_or(RuntimeError(format!("Invalid URL path.")))?
-            .next()
-            .ok_or(RuntimeError(format!("Failed to get bucket name.")))?;
-        let obj_name = valid_url
-            .path_segments()
+impl ProgressBars {
+    fn new() -> Self {
+        ProgressBars {
+            bars: MultiProgress::new(),
+            style: ProgressStyle::with_template(
+                "[{elapsed_precise}] {bar:40.cyan/blue} {pos:>7}/{len:7} {msg}",
+            )
             .unwrap()
-            .last()
-            .ok_or(RuntimeError(format!("Failed to get object name.")))?;
-        let object_dir = valid_url
-            .path()
-            .trim_start_matches('/')
-            .trim_start_matches(bucket)
-            .trim_start_matches('/')
-            .trim_end_matches(obj_name)
-            .trim_end_matches('/');
+            .progress_chars("##-"),
+            topics: Mutex::new(HashMap::new()),
+            download_style: ProgressStyle::with_template(
+                "[{elapsed_precise}] {bar:40.green/blue} {bytes:>10}/{total_bytes:10} {bytes_per_sec:>12} ETA {eta} {msg}",
+            )
+            .unwrap()
+            .progress_chars("##-"),
+            downloads: Mutex::new(HashMap::new()),
+        }
+    }
+}
 
-        let region = env::var("S3_REGION")
-            .map_err(|_| RuntimeError("Environment variable `S3_REGION` not set.".to_string()))?;
-        let access_key = env::var("S3_ACCESS_KEY").map_err(|_| {
-            RuntimeError("Environment variable `S3_ACCESS_KEY` not set.".to_string())
-        })?;
-        let secret_key = env::var("S3_SECRET_KEY").map_err(|_| {
-            RuntimeError("Environment variable `S3_SECRET_KEY` not set.".to_string())
-        })?;
-        let storage = Agent::new(&base_url, &region, &access_key, &secret_key)
-            .map_err(|e| RuntimeError(format!("Storage init failed. {}", e)))?;
+impl ProgressSink for ProgressBars {
+    fn on_topic_start(&self, topic: &str, message_count: Option<u64>) {
+        let bar = self
+            .bars
+            .add(ProgressBar::new(message_count.unwrap_or(0)))
+            .with_style(self.style.clone())
+            .with_message(topic.to_string());
+        self.topics.lock().unwrap().insert(topic.to_string(), bar);
+    }
 
-        const CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
-        const STR_LEN: usize = 6;
-        let mut rng = rand::thread_rng();
-        let rand_str: String = (0..STR_LEN)
-            .map(|_| {
-                let idx = rng.gen_range(0..CHARSET.len());
-                CHARSET[idx] as char
-            })
-            .collect();
-        let _down_path = PathBuf::from(format!("/tmp/{}-{}", bucket, rand_str));
-        std::fs::create_dir_all(&_down_path).map_err(|e| {
-            RuntimeError(format!(
-                "Failed to create download directory: {}, {}",
-                _down_path.display(),
-                e
-            ))
-        })?;
-        download_path.clone_from(&Some(_down_path.clone()));
+    fn on_message(&self, topic: &str) {
+        if let Some(bar) = self.topics.lock().unwrap().get(topic) {
+            bar.inc(1);
+        }
+    }
 
-        info!("Downloading from bucket: {}", bucket);
-        storage
-            .download_dir(bucket, object_dir, &_down_path, sigint)
-            .await
-            .map_err(|e| RuntimeError(format!("Download failed. {}", e)))?;
+    fn on_download_start(&self, object: &str, size: Option<u64>) {
+        let bar = self
+            .bars
+            .add(ProgressBar::new(size.unwrap_or(0)))
+            .with_style(self.download_style.clone())
+            .with_message(object.to_string());
+        self.downloads
+            .lock()
+            .unwrap()
+            .insert(object.to_string(), bar);
+    }
 
-        input_src = _down_path
-            .to_str()
-            .ok_or(RuntimeError(format!(
-                "Get OS string failed. {}",
-                _down_path.display()
-            )))?
-            .to_string();
-    } else {
-        input_src = source.to_owned();
+    fn on_download(&self, object: &str, bytes: u64) {
+        if let Some(bar) = self.downloads.lock().unwrap().get(object) {
+            bar.inc(bytes);
+        }
     }
 
-    let input_dir = PathBuf::from(input_src);
-    if !input_dir.exists() {
-        return Err(RuntimeError(format!(
-            "Input directory not found: {}",
-            input_dir.display()
-        )));
+    fn on_download_done(&self, object: &str) {
+        if let Some(bar) = self.downloads.lock().unwrap().remove(object) {
+            bar.finish_and_clear();
+        }
     }
+}
 
-    // Find all MCAP files
-    let mut files: Vec<PathBuf> = fs::read_dir(&input_dir)
-        .map_err(|e| RuntimeError(format!("Failed to read directory: {}", e)))?
-        .map(|f| f.unwrap().path())
-        .filter(|f| f.is_file() && f.extension().is_some_and(|f| f.eq("mcap")))
-        .collect();
-    files.sort();
+/// Lock file of the download directory, holding the ID of the process using it.
+fn lock_path(dir: &Path) -> PathBuf {
+    let mut path = dir.as_os_str().to_owned();
+    path.push(".lock");
+    PathBuf::from(path)
+}
 
-    Ok(files)
+/// Lock the download directory for this process, so that it is not removed by another run using
+/// it meanwhile. Locks of processes no longer running are taken over.
+fn lock_download(dir: &Path) -> Result<(), RuntimeError> {
+    let lock = lock_path(dir);
+    let pid = std::process::id();
+    let locked = match fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&lock)
+    {
+        Ok(mut file) => write!(file, "{}", pid),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+            let holder = fs::read_to_string(&lock)
+                .ok()
+                .and_then(|holder| holder.trim().parse::<u32>().ok())
+                .filter(|&holder| {
+                    holder != pid && Path::new(&format!("/proc/{}", holder)).exists()
+                });
+            if let Some(holder) = holder {
+                return Err(RuntimeError(format!(
+                    "Download directory {} is in use by process {}.",
+                    dir.display(),
+                    holder
+                )));
+            }
+            fs::write(&lock, pid.to_string())
+        }
+        Err(e) => Err(e),
+    };
+    locked.map_err(|e| {
+        RuntimeError(format!(
+            "Failed to lock download directory: {}, {}",
+            dir.display(),
+            e
+        ))
+    })
 }
 
 fn cleanup(local_path: &Option<PathBuf>) {
     if let Some(path) = local_path {
-        match std::fs::remove_dir_all(path) {
+        match std::fs::remove_dir_all(path).and_then(|_| std::fs::remove_file(lock_path(path))) {
             Ok(_) => {
-                info!("Temp directory cleaned.");
+                info!("Download directory cleaned.");
             }
             Err(e) => {
                 error!("Failed to remove directory: {}. {}", path.display(), e);
@@ -233,7 +1577,7 @@ fn make_rerun_stream() -> (
         let strm = builder.spawn().expect("Rerun should be spawned");
         (strm, None)
     };
-    return (stream, storage);
+    (stream, storage)
 }
 
 #[tokio::main]
@@ -256,66 +1600,287 @@ async fn main() {
 
     // Parse user args
     let cli = Cli::parse();
+    let remote = RemoteOptions {
+        cache: if cli.no_cache { None } else { Cache::open() },
+        s3_endpoint: cli.s3_endpoint.clone(),
+        s3_region: cli.s3_region.clone(),
+        backend: cli.backend,
+    };
     let (
         input,
         output_dir,
         topics,
+        exclude_topics,
         visualize,
-        dump_data,
-        point_cloud_scale,
-        intensity_scale,
         time_off,
         time_stop,
+        start,
+        duration,
+        extrinsics,
+        mut options,
     ) = match &cli.command {
         Commands::Extract {
             input,
             output_dir,
             topics,
+            exclude_topics,
             preview,
             point_cloud_scale,
             intensity_scale,
+            preview_scale,
+            colormap,
+            colormap_domain,
+            pc_fields,
+            pc_crop,
+            pc_crop_frame,
+            deskew,
+            accumulate,
+            map_format,
+            map_voxel,
+            video_decoder,
+            on_error,
+            image_format,
+            jpeg_quality,
+            transcode_images,
+            image_scale,
+            digest,
+            digest_rate,
+            digest_height,
+            composite,
+            composite_rate,
+            composite_height,
+            burn_timestamp,
+            frame_stride,
+            frame_rate,
+            name_template,
+            seq_names,
+            pcd_format,
+            parquet,
             time_off,
             time_stop,
+            start,
+            duration,
+            jobs,
+            ego_model,
+            no_ego,
+            depth_cloud,
+            extrinsics,
+            extrinsics_in_output,
+            ..
         } => (
             input,
             output_dir,
             topics,
+            exclude_topics,
             preview,
-            true,
-            *point_cloud_scale,
-            *intensity_scale,
             time_off,
             time_stop,
+            *start,
+            *duration,
+            extrinsics,
+            ExtractOptions {
+                point_cloud_scale: *point_cloud_scale,
+                intensity_scale: *intensity_scale,
+                preview_scale: *preview_scale,
+                image_options: ImageOptions {
+                    format: *image_format,
+                    jpeg_quality: *jpeg_quality,
+                    sampling: sampling(*frame_stride, *frame_rate),
+                    name_template: name_template.clone(),
+                    seq_names: *seq_names,
+                    transcode: *transcode_images,
+                    scale: *image_scale,
+                    digest: digest.map(|format| DigestOptions {
+                        format,
+                        rate: *digest_rate,
+                        height: *digest_height,
+                    }),
+                    composite: composite.as_ref().map(|topics| CompositeOptions {
+                        topics: topics.clone(),
+                        rate: *composite_rate,
+                        height: *composite_height,
+                    }),
+                    burn_timestamp: *burn_timestamp,
+                },
+                video_decoder: *video_decoder,
+                colormap: colormap.clone(),
+                colormap_domain: *colormap_domain,
+                field_map: pc_fields.clone().unwrap_or_default(),
+                crop: crop_box(pc_crop, pc_crop_frame),
+                extrinsics_in_output: *extrinsics_in_output,
+                depth_cloud: *depth_cloud,
+                pcd_format: *pcd_format,
+                parquet: *parquet,
+                deskew: deskew.clone(),
+                accumulate: map_options(accumulate, *map_format, *map_voxel),
+                jobs: *jobs,
+                ego_model: ego(ego_model, *no_ego),
+                on_error: *on_error,
+                ..ExtractOptions::default()
+            },
         ),
         Commands::Show {
             input,
             topics,
+            exclude_topics,
             point_cloud_scale,
             intensity_scale,
+            preview_scale,
+            colormap,
+            colormap_domain,
+            pc_fields,
+            pc_crop,
+            pc_crop_frame,
+            deskew,
+            accumulate,
             time_off,
             time_stop,
+            start,
+            duration,
+            ego_model,
+            no_ego,
+            depth_cloud,
+            extrinsics,
+            video_decoder,
+            on_error,
+            ..
         } => (
             input,
             &None,
             topics,
+            exclude_topics,
             &true,
-            false,
-            *point_cloud_scale,
-            *intensity_scale,
             time_off,
             time_stop,
+            *start,
+            *duration,
+            extrinsics,
+            ExtractOptions {
+                dump_data: false,
+                point_cloud_scale: *point_cloud_scale,
+                intensity_scale: *intensity_scale,
+                preview_scale: *preview_scale,
+                video_decoder: *video_decoder,
+                colormap: colormap.clone(),
+                colormap_domain: *colormap_domain,
+                field_map: pc_fields.clone().unwrap_or_default(),
+                crop: crop_box(pc_crop, pc_crop_frame),
+                depth_cloud: *depth_cloud,
+                deskew: deskew.clone(),
+                accumulate: map_options(accumulate, MapFormat::default(), 0.0),
+                ego_model: ego(ego_model, *no_ego),
+                on_error: *on_error,
+                ..ExtractOptions::default()
+            },
         ),
         Commands::Trim {
             input,
             time_off,
             time_stop,
+            start,
+            duration,
+            ..
         } => (
-            input, &None, &None, &true, false, None, None, time_off, time_stop,
+            input,
+            &None,
+            &None,
+            &None,
+            &true,
+            time_off,
+            time_stop,
+            *start,
+            *duration,
+            &None,
+            ExtractOptions {
+                dump_data: false,
+                trim_only: true,
+                ego_model: EgoModel::Hidden,
+                ..ExtractOptions::default()
+            },
+        ),
+        Commands::Merge { input, output } => {
+            return run_merge(input, output, &sigint, &remote).await
+        }
+        Commands::Split {
+            input,
+            output_dir,
+            every,
+            max_size,
+        } => return run_split(input, output_dir, *every, *max_size, &sigint, &remote).await,
+        Commands::Reindex { input, output_dir } => {
+            return run_reindex(input, output_dir, &sigint, &remote).await
+        }
+        Commands::Filter {
+            input,
+            output,
+            topics,
+            exclude,
+        } => return run_filter(input, output, topics, exclude, &sigint, &remote).await,
+        Commands::Stats { input, json } => return run_stats(input, *json, &sigint, &remote).await,
+        Commands::Topics { input, json } => {
+            return run_topics(input, *json, &sigint, &remote).await
+        }
+        Commands::Schema { input, topic } => {
+            return run_schema(input, topic, &sigint, &remote).await
+        }
+        Commands::Diff { before, after } => return run_diff(before, after, &sigint, &remote).await,
+        Commands::Verify { input } => return run_verify(input, &sigint, &remote).await,
+        Commands::Ls { source } => return run_ls(source, &remote).await,
+    };
+
+    // Topics read from remote objects without downloading them in whole
+    let stream_topics: Option<Vec<&str>> = match &cli.command {
+        Commands::Extract {
+            stream: true,
+            topics: Some(topics),
+            ..
+        }
+        | Commands::Show {
+            stream: true,
+            topics: Some(topics),
+            ..
+        } => Some(
+            topics
+                .trim()
+                .split(',')
+                .chain(options.deskew.as_deref())
+                .chain(options.accumulate.iter().map(|a| a.source.as_str()))
+                .collect(),
         ),
+        _ => None,
+    };
+
+    // Destination of the trimmed file, opened first to fail before the work
+    let upload = match &cli.command {
+        Commands::Trim {
+            upload: Some(destination),
+            ..
+        } => match open_remote(destination, &remote).await {
+            Ok(Some((storage, _, dir))) => Some((storage, dir)),
+            Ok(None) => {
+                error!("Not a remote destination: {}", destination);
+                return;
+            }
+            Err(e) => {
+                error!("{}", e.0);
+                return;
+            }
+        },
+        _ => None,
     };
 
     // Prepare inputs
-    let files = match prepare_inputs(&input, &mut download_path, &sigint).await {
+    let progress = ProgressBars::new();
+    let files = match prepare_inputs(
+        input,
+        &mut download_path,
+        &sigint,
+        stream_topics.as_deref(),
+        &remote,
+        &progress,
+    )
+    .await
+    {
         Ok(f) => f,
         Err(e) => {
             error!("{}", e.0);
@@ -355,7 +1920,7 @@ async fn main() {
     let output_dir = output_dir
         .clone()
         .unwrap_or(std::env::current_dir().unwrap());
-    if dump_data {
+    if options.dump_data {
         info!("Output directory: {}", output_dir.display());
     }
 
@@ -368,7 +1933,8 @@ async fn main() {
     };
 
     // Start time and stop time
-    let start_time = match chrono::NaiveDateTime::parse_from_str(&time_off, "%Y-%m-%d %H:%M:%S") {
+    let mut start_time = match chrono::NaiveDateTime::parse_from_str(time_off, "%Y-%m-%d %H:%M:%S")
+    {
         Ok(t) => t.and_utc().timestamp_nanos_opt().unwrap(),
         Err(e) => {
             error!("Parse start time failed, {}", e);
@@ -376,7 +1942,7 @@ async fn main() {
             return;
         }
     };
-    let stop_time = if time_stop.is_none() {
+    let mut stop_time = if time_stop.is_none() {
         i64::MAX
     } else {
         match chrono::NaiveDateTime::parse_from_str(
@@ -392,29 +1958,55 @@ async fn main() {
         }
     };
 
-    // Trim only mode?
-    let trim_only = match &cli.command {
-        Commands::Trim { .. } => true,
-        _ => false,
-    };
+    // Relative start time and duration are measured from the first message
+    if start.is_some() || duration.is_some() {
+        let first = match xcap::start_time(&files) {
+            Ok(Some(t)) => t as i64,
+            Ok(None) => {
+                error!("No message found in the input files.");
+                cleanup(&download_path);
+                return;
+            }
+            Err(e) => {
+                error!("Read start time failed, {}", e);
+                cleanup(&download_path);
+                return;
+            }
+        };
+        if let Some(start) = start {
+            start_time = first + start as i64;
+        }
+        if let Some(duration) = duration {
+            stop_time = start_time.max(first) + duration as i64;
+        }
+    }
 
     // Check target topics to make sure they make sense for extraction and
     // visualization. Trim does not need this.
-    let mut target_topics: Vec<String> = vec![];
-
-    if !trim_only {
-        let Some(topic_str) = topics else {
-            error!("No topic specified. Use `--topics` to set topics.");
-            cleanup(&download_path);
-            return;
+    if !options.trim_only {
+        let mut target_topics: Vec<String> = match (topics, exclude_topics) {
+            (Some(topic_str), _) => topic_str
+                .trim()
+                .split(',')
+                .map(|t| t.to_string())
+                .collect::<Vec<_>>(),
+            (None, Some(_)) => topics_in_mcap
+                .iter()
+                .filter(|t| t.is_supported())
+                .map(|t| t.name.clone())
+                .collect::<Vec<_>>(),
+            (None, None) => {
+                error!("No topic specified. Use `--topics` to set topics.");
+                cleanup(&download_path);
+                return;
+            }
         };
-        target_topics = topic_str
-            .trim()
-            .split(',')
-            .map(|t| t.to_string())
-            .collect::<Vec<_>>();
+        if let Some(exclude_str) = exclude_topics {
+            let excluded = exclude_str.trim().split(',').collect::<Vec<_>>();
+            target_topics.retain(|t| !excluded.contains(&t.as_str()));
+        }
         if target_topics.is_empty() {
-            error!("No topic specified. Use `--topics` to set topics.");
+            error!("No topic left to process. Check `--topics` and `--exclude-topics`.");
             cleanup(&download_path);
             return;
         }
@@ -425,23 +2017,33 @@ async fn main() {
                 return;
             };
         }
+        options.topic_names = target_topics;
+    }
+
+    // Load the calibration
+    if let Some(path) = extrinsics {
+        options.extrinsics = match Extrinsics::load(path) {
+            Ok(extrinsics) => extrinsics,
+            Err(e) => {
+                error!("Failed to load extrinsics from {}: {}", path.display(), e);
+                cleanup(&download_path);
+                return;
+            }
+        };
     }
 
     // Process
     info!("Processing...");
+    options.trim_start = start_time;
+    options.trim_end = stop_time;
     let ret = process(
         &files,
         &output_dir,
-        &target_topics,
+        topics_in_mcap,
+        &options,
         sigint,
         rerun_stream,
-        dump_data,
-        point_cloud_scale,
-        intensity_scale,
-        topics_in_mcap,
-        start_time,
-        stop_time,
-        trim_only,
+        &progress,
     );
 
     // Cleanup
@@ -458,6 +2060,18 @@ async fn main() {
     // Take aways
     match ret {
         Ok(_) => {
+            if let Some((storage, dir)) = &upload {
+                let object = match dir.trim_end_matches('/') {
+                    "" => "trim.mcap".to_string(),
+                    dir => format!("{}/trim.mcap", dir),
+                };
+                info!("Uploading to {}: {}", storage.location(), object);
+                if let Err(e) = storage.upload(Path::new("trim.mcap"), &object).await {
+                    error!("Upload failed. {}", e);
+                    warn!("Sorry, job failed.");
+                    return;
+                }
+            }
             info!("Done.");
         }
         Err(e) => {
@@ -466,3 +2080,57 @@ async fn main() {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_durations() {
+        assert_eq!(parse_duration("250ns"), Ok(250));
+        assert_eq!(parse_duration("3us"), Ok(3_000));
+        assert_eq!(parse_duration("20ms"), Ok(20_000_000));
+        assert_eq!(parse_duration("90"), Ok(90_000_000_000));
+        assert_eq!(parse_duration("90s"), Ok(90_000_000_000));
+        assert_eq!(parse_duration("5m"), Ok(300_000_000_000));
+        assert_eq!(parse_duration("2h"), Ok(7_200_000_000_000));
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("1.5s").is_err());
+        assert!(parse_duration("5d").is_err());
+        assert_eq!(
+            parse_duration("9999999h"),
+            Err("Duration out of range: 9999999h".to_owned())
+        );
+    }
+
+    #[test]
+    fn parse_sizes() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("512B"), Ok(512));
+        assert_eq!(parse_size("4k"), Ok(4 << 10));
+        assert_eq!(parse_size("100MB"), Ok(100 << 20));
+        assert_eq!(parse_size("2GB"), Ok(2 << 30));
+        assert!(parse_size("2TB").is_err());
+        assert!(parse_size("-2GB").is_err());
+        assert_eq!(
+            parse_size("99999999999GB"),
+            Err("Size out of range: 99999999999GB".to_owned())
+        );
+    }
+
+    #[test]
+    fn parse_offsets() {
+        assert_eq!(parse_offset("+00:05:30"), Ok(330_000_000_000));
+        assert_eq!(parse_offset("05:30"), Ok(330_000_000_000));
+        assert_eq!(parse_offset("0:1.5"), Ok(1_500_000_000));
+        assert_eq!(parse_offset("+90s"), Ok(90_000_000_000));
+        assert_eq!(parse_offset(" 90 "), Ok(90_000_000_000));
+        for invalid in ["-5:00", "00:-1", "nan:1", "inf:1", "inf", "a:b"] {
+            assert!(parse_offset(invalid).is_err(), "{}", invalid);
+        }
+        assert_eq!(
+            parse_offset("1e20:0"),
+            Err("Time offset out of range: 1e20:0".to_owned())
+        );
+    }
+}
@@ -0,0 +1,157 @@
+//! Viewer layout for the topics: one 2D view per camera, a 3D view for point clouds and
+//! transforms, time series views for scalar topics, and text log views for diagnostics,
+//! strings and the ROS log.
+use crate::Topic;
+use rerun::{
+    external::{
+        re_log_types::BlueprintActivationCommand,
+        re_types::blueprint::{
+            archetypes::{ContainerBlueprint, ViewBlueprint, ViewContents, ViewportBlueprint},
+            components::ContainerKind,
+        },
+    },
+    RecordingStream, RecordingStreamBuilder, RecordingStreamResult,
+};
+use std::collections::BTreeMap;
+
+/// Blueprint under construction, logged into its own stream.
+struct Builder {
+    stream: RecordingStream,
+}
+
+impl Builder {
+    /// Log a view of the entities under `origin` and return its blueprint path.
+    fn view(&self, class: &str, name: &str, origin: &str) -> RecordingStreamResult<String> {
+        let path = format!("view/{}", uuid::Uuid::new_v4());
+        self.stream.log(
+            path.as_str(),
+            &ViewBlueprint::new(class)
+                .with_display_name(name)
+                .with_space_origin(origin),
+        )?;
+        self.stream.log(
+            format!("{}/ViewContents", path),
+            &ViewContents::new(["+ $origin/**"]),
+        )?;
+        Ok(path)
+    }
+
+    /// Log a container of the views or containers and return its ID and blueprint path.
+    fn container(
+        &self,
+        kind: ContainerKind,
+        contents: Vec<String>,
+    ) -> RecordingStreamResult<(uuid::Uuid, String)> {
+        let id = uuid::Uuid::new_v4();
+        let path = format!("container/{}", id);
+        self.stream.log(
+            path.as_str(),
+            &ContainerBlueprint::new(kind).with_contents(contents),
+        )?;
+        Ok((id, path))
+    }
+}
+
+/// Entity path of the parts, empty parts from leading or doubled slashes dropped.
+fn entity_path(parts: &[&str]) -> String {
+    let parts: Vec<&str> = parts
+        .iter()
+        .flat_map(|p| p.split('/'))
+        .filter(|p| !p.is_empty())
+        .collect();
+    format!("/{}", parts.join("/"))
+}
+
+/// Build the layout for the topics and make it the active blueprint of the recording. Images are
+/// shown by the entity paths they were logged at.
+pub fn send(
+    rec: &RecordingStream,
+    topics: &[&Topic],
+    images: &BTreeMap<String, String>,
+) -> RecordingStreamResult<()> {
+    let app_id = rec
+        .store_info()
+        .map(|info| info.application_id.to_string())
+        .unwrap_or("XCAP".to_owned());
+    let (stream, storage) = RecordingStreamBuilder::new(app_id).blueprint().memory()?;
+    stream.set_time_sequence("blueprint", 0);
+    let builder = Builder { stream };
+
+    let mut columns = vec![];
+
+    // Cameras
+    let cameras = images
+        .iter()
+        .map(|(topic, path)| builder.view("Spatial2D", topic, &entity_path(&[path])))
+        .collect::<RecordingStreamResult<Vec<_>>>()?;
+    if !cameras.is_empty() {
+        columns.push(builder.container(ContainerKind::Grid, cameras)?.1);
+    }
+
+    // Point clouds, laser scans, odometry and transforms
+    columns.push(builder.view("Spatial3D", "3D", "/")?);
+
+    // Scalars and maps
+    let mut plots = vec![];
+    for topic in topics {
+        let (class, origin) = match topic.format.as_str() {
+            "sensor_msgs/msg/Imu" => ("TimeSeries", entity_path(&["imu", &topic.name])),
+            "sensor_msgs/msg/JointState" => ("TimeSeries", entity_path(&["joints", &topic.name])),
+            "sensor_msgs/msg/BatteryState" => {
+                ("TimeSeries", entity_path(&["battery", &topic.name]))
+            }
+            "sensor_msgs/msg/Range" => ("TimeSeries", entity_path(&["range", &topic.name])),
+            "sensor_msgs/msg/MagneticField" => {
+                ("TimeSeries", entity_path(&["magnetic_field", &topic.name]))
+            }
+            "sensor_msgs/msg/Temperature" => {
+                ("TimeSeries", entity_path(&["temperature", &topic.name]))
+            }
+            "sensor_msgs/msg/FluidPressure" => {
+                ("TimeSeries", entity_path(&["fluid_pressure", &topic.name]))
+            }
+            "sensor_msgs/msg/Joy" => ("TimeSeries", entity_path(&["joy", &topic.name])),
+            "nav_msgs/msg/Odometry" => ("TimeSeries", entity_path(&["velocity", &topic.name])),
+            "sensor_msgs/msg/NavSatFix" => ("Map", entity_path(&["gps", &topic.name])),
+            "diagnostic_msgs/msg/DiagnosticArray" => {
+                ("TextLog", entity_path(&["diagnostics", &topic.name]))
+            }
+            "std_msgs/msg/String" | "rcl_interfaces/msg/Log" => {
+                ("TextLog", entity_path(&[&topic.name]))
+            }
+            "std_msgs/msg/Float32"
+            | "std_msgs/msg/Float64"
+            | "std_msgs/msg/Int32"
+            | "std_msgs/msg/Bool" => ("TimeSeries", entity_path(&[&topic.name])),
+            _ if topic.is_supported()
+                && !crate::SUPPORTED_FORMATS.contains(&topic.format.as_str()) =>
+            {
+                ("TimeSeries", entity_path(&[&topic.name]))
+            }
+            _ => continue,
+        };
+        plots.push(builder.view(class, &topic.name, &origin)?);
+    }
+    if !plots.is_empty() {
+        columns.push(builder.container(ContainerKind::Vertical, plots)?.1);
+    }
+
+    let (root, _) = builder.container(ContainerKind::Horizontal, columns)?;
+    builder.stream.log(
+        "viewport",
+        &ViewportBlueprint::new()
+            .with_root_container(root)
+            .with_auto_layout(false)
+            .with_auto_views(false),
+    )?;
+
+    let Some(info) = builder.stream.store_info() else {
+        return Ok(());
+    };
+    builder.stream.flush_blocking();
+    rec.send_blueprint(
+        storage.take(),
+        BlueprintActivationCommand::make_active(info.store_id),
+    );
+    Ok(())
+}
@@ -0,0 +1,162 @@
+use crate::depth::Intrinsics;
+use crate::extractor::Extractor;
+use crate::tf::FrameTree;
+use mcap::Message;
+use rerun::{external::glam, RecordingStream};
+use ros2_sensor_msgs::msg::CameraInfo;
+use serde::Serialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{atomic::AtomicBool, Arc, Mutex},
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Payload error. {0}")]
+    Payload(#[from] crate::extractor::DecodeError),
+    #[error("CDR error. {0}")]
+    Cdr(#[from] cdr::Error),
+    #[error("JSON error. {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// Camera calibration dumped to disk.
+#[derive(Serialize)]
+struct Calibration {
+    frame_id: String,
+    width: u32,
+    height: u32,
+    distortion_model: String,
+    d: Vec<f64>,
+    k: [f64; 9],
+    r: [f64; 9],
+    p: [f64; 12],
+}
+
+pub struct Parser {
+    // Output directory
+    output_dir: PathBuf,
+
+    // Visualizer with rerun
+    rec_stream: Option<RecordingStream>,
+
+    // Should dump data to disk
+    dump_data: bool,
+
+    // Scratch buffer for decoded message payloads, reused across messages
+    buffer: Vec<u8>,
+
+    // Transform tree, the camera is registered here for image parsers
+    frame_tree: Arc<Mutex<FrameTree>>,
+
+    // The latest camera info
+    latest: Option<CameraInfo>,
+
+    // Transform of the camera in the rig frame, from the calibration file
+    extrinsic: Option<glam::Affine3A>,
+}
+
+impl Parser {
+    pub fn new(
+        output_path: &Path,
+        rerun_stream: Option<RecordingStream>,
+        dump_data: bool,
+        frame_tree: Arc<Mutex<FrameTree>>,
+        extrinsic: Option<glam::Affine3A>,
+    ) -> Self {
+        // Create output dir
+        if dump_data {
+            fs::create_dir_all(output_path).unwrap();
+        }
+
+        Parser {
+            output_dir: output_path.into(),
+            rec_stream: rerun_stream,
+            dump_data,
+            buffer: Vec::new(),
+            frame_tree,
+            latest: None,
+            extrinsic,
+        }
+    }
+}
+
+impl Extractor for Parser {
+    type ExtractorError = Box<dyn std::error::Error + Send + Sync>;
+
+    fn buffer(&mut self) -> &mut Vec<u8> {
+        &mut self.buffer
+    }
+
+    fn step(&mut self, message: &Message) -> Result<(), Self::ExtractorError> {
+        let payload = self.decode_into(message).map_err(Error::Payload)?;
+        let info = cdr::deserialize_from::<_, CameraInfo, _>(payload, cdr::size::Infinite)
+            .map_err(Error::Cdr)?;
+
+        // Depth images of this camera are back-projected with the intrinsics
+        let ([fx, fy], [cx, cy]) = (info.focal_length(), info.principal_point());
+        self.frame_tree.lock().unwrap().set_intrinsics(
+            &info.header.frame_id,
+            Intrinsics {
+                fx: fx as f32,
+                fy: fy as f32,
+                cx: cx as f32,
+                cy: cy as f32,
+            },
+        );
+
+        if let Some(rec) = &self.rec_stream {
+            let entity_path = self
+                .frame_tree
+                .lock()
+                .unwrap()
+                .add_camera(&info.header.frame_id);
+            rec.set_time_seconds(
+                "main",
+                info.header.stamp.sec as f64 + info.header.stamp.nanosec as f64 * 1e-9,
+            );
+            rec.log(
+                entity_path.as_str(),
+                &rerun::Pinhole::from_focal_length_and_resolution(
+                    [fx as f32, fy as f32],
+                    [info.width as f32, info.height as f32],
+                )
+                .with_principal_point([cx as f32, cy as f32])
+                .with_camera_xyz(rerun::components::ViewCoordinates::RDF),
+            )?;
+            if let Some(extrinsic) = self.extrinsic {
+                let (_, rotation, translation) = extrinsic.to_scale_rotation_translation();
+                rec.log(
+                    entity_path,
+                    &rerun::Transform3D::from_translation_rotation(
+                        translation.to_array(),
+                        rerun::Quaternion::from_xyzw(rotation.to_array()),
+                    ),
+                )?;
+            }
+        }
+
+        self.latest = Some(info);
+        Ok(())
+    }
+
+    fn post_process(&mut self, _sigint: Arc<AtomicBool>) -> Result<(), Self::ExtractorError> {
+        // Intrinsics rarely change during a recording, the latest one is dumped.
+        if let Some(info) = self.latest.as_ref().filter(|_| self.dump_data) {
+            let calibration = Calibration {
+                frame_id: info.header.frame_id.clone(),
+                width: info.width,
+                height: info.height,
+                distortion_model: info.distortion_model.clone(),
+                d: info.d.clone(),
+                k: info.k,
+                r: info.r,
+                p: info.p,
+            };
+            let file = fs::File::create(self.output_dir.join("camera_info.json"))?;
+            serde_json::to_writer_pretty(file, &calibration).map_err(Error::Json)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,115 @@
+//! Colormaps for point cloud intensity.
+use colorgrad::{BlendMode, Gradient as _, GradientBuilder, LinearGradient};
+use std::str::FromStr;
+
+/// Colors of the map, from the low end to the high end.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum Colormap {
+    /// Blue, white and gold.
+    #[default]
+    Default,
+    Viridis,
+    Turbo,
+    Grayscale,
+    /// Evenly spaced colors in HTML notation, like `#00F`, `#FFFFFF` or `gold`.
+    Custom(Vec<String>),
+}
+
+impl FromStr for Colormap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "default" => Ok(Colormap::Default),
+            "viridis" => Ok(Colormap::Viridis),
+            "turbo" => Ok(Colormap::Turbo),
+            "grayscale" => Ok(Colormap::Grayscale),
+            _ => {
+                let Some(colors) = s.strip_prefix("custom:") else {
+                    return Err(format!(
+                        "Invalid colormap: {}, expected one of default, viridis, turbo, grayscale, custom:<color,color,...>",
+                        s
+                    ));
+                };
+                let colormap =
+                    Colormap::Custom(colors.split(',').map(|c| c.trim().to_owned()).collect());
+                colormap.gradient(None).map_err(|e| e.to_string())?;
+                Ok(colormap)
+            }
+        }
+    }
+}
+
+impl Colormap {
+    /// Values mapped to the ends of the colormap if no domain is given.
+    fn default_domain(&self) -> (f32, f32) {
+        match self {
+            Colormap::Default => (0.0, 0.6),
+            _ => (0.0, 1.0),
+        }
+    }
+
+    /// Build the gradient spanning the domain. Values out of the domain get the end colors.
+    pub fn gradient(
+        &self,
+        domain: Option<(f32, f32)>,
+    ) -> Result<Gradient, colorgrad::GradientBuilderError> {
+        let inner: Box<dyn colorgrad::Gradient> = match self {
+            Colormap::Default => linear(&["#00F", "#FFF", "gold"])?.boxed(),
+            Colormap::Viridis => colorgrad::preset::viridis().boxed(),
+            Colormap::Turbo => colorgrad::preset::turbo().boxed(),
+            Colormap::Grayscale => linear(&["#000", "#FFF"])?.boxed(),
+            Colormap::Custom(colors) => linear(colors)?.boxed(),
+        };
+        let (min, max) = domain.unwrap_or(self.default_domain());
+        Ok(Gradient { inner, min, max })
+    }
+}
+
+fn linear<S: AsRef<str> + ToString>(
+    colors: &[S],
+) -> Result<LinearGradient, colorgrad::GradientBuilderError> {
+    GradientBuilder::new()
+        .html_colors(colors)
+        .mode(BlendMode::LinearRgb)
+        .build::<LinearGradient>()
+}
+
+/// Colormap spanning a domain of values.
+pub struct Gradient {
+    inner: Box<dyn colorgrad::Gradient>,
+    min: f32,
+    max: f32,
+}
+
+impl Gradient {
+    /// Color of the value in RGBA.
+    pub fn at(&self, value: f32) -> [u8; 4] {
+        let t = if self.max > self.min {
+            (value - self.min) / (self.max - self.min)
+        } else {
+            0.0
+        };
+        self.inner.at(t.clamp(0.0, 1.0)).to_rgba8()
+    }
+}
+
+/// Parse domains like `0,255` into the min and max values.
+pub fn parse_domain(s: &str) -> Result<(f32, f32), String> {
+    let (min, max) = s
+        .split_once(',')
+        .ok_or(format!("Invalid colormap domain: {}, expected min,max", s))?;
+    let parse = |v: &str| {
+        v.trim()
+            .parse::<f32>()
+            .map_err(|_| format!("Invalid colormap domain: {}", s))
+    };
+    let (min, max) = (parse(min)?, parse(max)?);
+    if min >= max {
+        return Err(format!(
+            "Colormap domain min should be less than max: {}",
+            s
+        ));
+    }
+    Ok((min, max))
+}
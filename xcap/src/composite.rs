@@ -0,0 +1,185 @@
+//! Tiled video of several camera topics, composed from the extracted frames. Frames are aligned
+//! by the nearest stamp and encoded by the `ffmpeg` command.
+use crate::frame_index::{Entry, FrameIndex};
+use image::{imageops::FilterType, RgbImage};
+use log::warn;
+use std::{
+    io::{self, Write},
+    path::Path,
+    process::{Command, Stdio},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+// Frames farther than this from the tick are left out of the tile, in nanoseconds
+const MAX_OFFSET: u64 = 1_000_000_000;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Failed to run ffmpeg, is it installed? {0}")]
+    Spawn(io::Error),
+    #[error("IO error. {0}")]
+    Io(#[from] io::Error),
+    #[error("Image error. {0}")]
+    Image(#[from] image::ImageError),
+    #[error("ffmpeg exited with {0}")]
+    Exit(std::process::ExitStatus),
+    #[error("Interrupted.")]
+    Interrupted,
+}
+
+/// Options of the composite video.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    // Topics of the tiles, in the order of the grid from left to right, top to bottom
+    pub topics: Vec<String>,
+    // Frames per second of the video
+    pub rate: f64,
+    // Height of each tile in pixels
+    pub height: u32,
+}
+
+// Tile of a topic, with the frame shown last
+struct Tile<'a> {
+    // Frames sorted by stamp
+    entries: Vec<&'a Entry>,
+    shown: Option<usize>,
+    image: Option<RgbImage>,
+}
+
+/// Write the composite video of the topics in the index to `path`. Return the number of frames,
+/// 0 if none of the topics has frames.
+pub fn write(
+    path: &Path,
+    index: &FrameIndex,
+    options: &Options,
+    sigint: &AtomicBool,
+) -> Result<u64, Error> {
+    let mut tiles: Vec<Tile> = Vec::new();
+    for topic in &options.topics {
+        match index.frames(topic) {
+            Some(entries) if !entries.is_empty() => {
+                let mut entries: Vec<&Entry> = entries.iter().collect();
+                entries.sort_by_key(|e| e.stamp);
+                tiles.push(Tile {
+                    entries,
+                    shown: None,
+                    image: None,
+                });
+            }
+            _ => warn!("No frames of {} for the composite video.", topic),
+        }
+    }
+    if tiles.is_empty() {
+        return Ok(0);
+    }
+
+    // Tiles take the aspect ratio of the first frame of the first topic
+    let first = image::open(&tiles[0].entries[0].file)?;
+    let tile_height = even(options.height);
+    let tile_width = even(
+        (first.width() as u64 * tile_height as u64 / first.height().max(1) as u64).max(2) as u32,
+    );
+    let columns = (tiles.len() as f64).sqrt().ceil() as u32;
+    let rows = (tiles.len() as u32).div_ceil(columns);
+    let (width, height) = (tile_width * columns, tile_height * rows);
+
+    let mut child = Command::new("ffmpeg")
+        .args(["-hide_banner", "-loglevel", "error", "-y"])
+        .args(["-f", "rawvideo", "-pix_fmt", "rgb24"])
+        .args(["-s", &format!("{}x{}", width, height)])
+        .args(["-r", &options.rate.to_string()])
+        .args(["-i", "pipe:0", "-c:v", "libx264", "-pix_fmt", "yuv420p"])
+        .arg(path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .map_err(Error::Spawn)?;
+    let mut stdin = child.stdin.take().unwrap();
+
+    let start = tiles.iter().map(|t| t.entries[0].stamp).min().unwrap();
+    let end = tiles
+        .iter()
+        .map(|t| t.entries[t.entries.len() - 1].stamp)
+        .max()
+        .unwrap();
+    let step = 1e9 / options.rate;
+    let mut canvas = RgbImage::new(width, height);
+    let mut count = 0;
+    loop {
+        let tick = start + (count as f64 * step) as u64;
+        if tick > end {
+            break;
+        }
+        if sigint.load(Ordering::Relaxed) {
+            drop(stdin);
+            let _ = child.wait();
+            return Err(Error::Interrupted);
+        }
+        for (i, tile) in tiles.iter_mut().enumerate() {
+            let nearest = nearest(&tile.entries, tick);
+            if nearest != tile.shown {
+                tile.image = match nearest {
+                    Some(n) => Some(fit(
+                        &image::open(&tile.entries[n].file)?.to_rgb8(),
+                        tile_width,
+                        tile_height,
+                    )),
+                    None => None,
+                };
+                tile.shown = nearest;
+            }
+            let (x, y) = (i as u32 % columns, i as u32 / columns);
+            let blank = RgbImage::new(tile_width, tile_height);
+            image::imageops::replace(
+                &mut canvas,
+                tile.image.as_ref().unwrap_or(&blank),
+                (x * tile_width) as i64,
+                (y * tile_height) as i64,
+            );
+        }
+        stdin.write_all(canvas.as_raw())?;
+        count += 1;
+    }
+    drop(stdin);
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(Error::Exit(status));
+    }
+    Ok(count)
+}
+
+/// Index of the frame nearest to the stamp, None if it is too far away.
+fn nearest(entries: &[&Entry], stamp: u64) -> Option<usize> {
+    let after = entries.partition_point(|e| e.stamp < stamp);
+    [after.checked_sub(1), Some(after)]
+        .into_iter()
+        .flatten()
+        .filter(|&i| i < entries.len())
+        .min_by_key(|&i| entries[i].stamp.abs_diff(stamp))
+        .filter(|&i| entries[i].stamp.abs_diff(stamp) <= MAX_OFFSET)
+}
+
+/// Resize the image to fit in the tile, centered on a black background.
+fn fit(image: &RgbImage, width: u32, height: u32) -> RgbImage {
+    let scale = (width as f64 / image.width() as f64).min(height as f64 / image.height() as f64);
+    let resized = image::imageops::resize(
+        image,
+        ((image.width() as f64 * scale).round() as u32).clamp(1, width),
+        ((image.height() as f64 * scale).round() as u32).clamp(1, height),
+        FilterType::Triangle,
+    );
+    let mut tile = RgbImage::new(width, height);
+    image::imageops::replace(
+        &mut tile,
+        &resized,
+        ((width - resized.width()) / 2) as i64,
+        ((height - resized.height()) / 2) as i64,
+    );
+    tile
+}
+
+/// Round down to an even number of at least 2, as the chroma subsampling of H.264 requires.
+fn even(size: u32) -> u32 {
+    (size & !1).max(2)
+}
@@ -1,28 +1,37 @@
+use crate::decoder::{self, Backend, Codec, VideoDecoder};
+use crate::digest::Digest;
 use crate::extractor::Extractor;
-use log::error;
+use crate::frame_index::{Entry, FrameIndex};
+use crate::image::preview;
+use crate::image_file::{self, Frame, Sampler};
+use crate::tf::FrameTree;
+use crate::video::H264Muxer;
+use image::ImageFormat;
 use mcap::Message;
 use rerun::RecordingStream;
 use ros2_sensor_msgs::msg::CompressedImage;
 use std::{
     fs,
     path::{Path, PathBuf},
-    sync::{atomic::AtomicBool, Arc},
+    sync::{atomic::AtomicBool, Arc, Mutex},
 };
 
-const ZSTD_MAGIC_NUMBER: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
-
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("Interrupted.")]
     Interrupted,
     #[error("Init image from buf failed.")]
     ImageBuf,
-    #[error("ZSTD error. {0}")]
-    Zstd(#[from] std::io::Error),
+    #[error("Payload error. {0}")]
+    Payload(#[from] crate::extractor::DecodeError),
     #[error("CDR error. {0}")]
-    CDR(#[from] cdr::Error),
+    Cdr(#[from] cdr::Error),
     #[error("Image error. {0}")]
     Image(#[from] image::ImageError),
+    #[error("Video error. {0}")]
+    Video(#[from] crate::video::Error),
+    #[error("Decoder error. {0}")]
+    Decoder(#[from] decoder::Error),
 }
 
 pub struct Parser {
@@ -34,10 +43,58 @@ pub struct Parser {
 
     // Should dump data to disk
     dump_data: bool,
+
+    // Scratch buffer for decoded message payloads, reused across messages
+    buffer: Vec<u8>,
+
+    // Muxer for H.264 streams, created on the first H.264 frame
+    muxer: Option<H264Muxer>,
+
+    // H.264 streams are shown in the viewer as videos rather than decoded frames
+    video_asset: bool,
+
+    // Decoder for video streams, created on the first video frame with the backend
+    decoder: Option<Box<dyn VideoDecoder>>,
+    backend: Backend,
+
+    // Topic name and frame, used for the entity path of decoded frames
+    topic: String,
+    frame_id: String,
+
+    // Transform tree, images are logged under their cameras once the cameras are known
+    frame_tree: Arc<Mutex<FrameTree>>,
+
+    // Scale of images in preview, images are decoded for resizing if set
+    preview_scale: Option<f32>,
+
+    // Format and quality of decoded video frames
+    image_options: image_file::Options,
+
+    // Frames to be saved
+    sampler: Sampler,
+
+    // Animated digest of the topic
+    digest: Option<Digest>,
+
+    // Index of the saved frames, shared with parsers of other files
+    frame_index: Arc<Mutex<FrameIndex>>,
+
+    // Input file of the messages
+    source: String,
 }
 
 impl Parser {
-    pub fn new(output_path: &Path, rerun_stream: Option<RecordingStream>, dump_data: bool) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        output_path: &Path,
+        rerun_stream: Option<RecordingStream>,
+        dump_data: bool,
+        frame_tree: Arc<Mutex<FrameTree>>,
+        preview_scale: Option<f32>,
+        image_options: image_file::Options,
+        frame_index: Arc<Mutex<FrameIndex>>,
+        video_decoder: Backend,
+    ) -> Self {
         // Create output dir
         if dump_data {
             fs::create_dir_all(output_path).unwrap();
@@ -47,54 +104,295 @@ impl Parser {
             output_dir: output_path.into(),
             rec_stream: rerun_stream,
             dump_data,
+            buffer: Vec::new(),
+            muxer: None,
+            video_asset: false,
+            decoder: None,
+            backend: video_decoder,
+            topic: String::new(),
+            frame_id: String::new(),
+            frame_tree,
+            preview_scale,
+            sampler: Sampler::new(image_options.sampling),
+            digest: image_options
+                .digest
+                .filter(|_| dump_data)
+                .map(|options| Digest::new(output_path, options)),
+            image_options,
+            frame_index,
+            source: String::new(),
+        }
+    }
+
+    /// Add the saved frame to the index.
+    fn index(&self, frame: &Frame, file: PathBuf) {
+        self.frame_index.lock().unwrap().add(
+            frame.topic,
+            &self.output_dir,
+            Entry {
+                file,
+                stamp: frame.stamp,
+                publish_time: frame.publish_time,
+                seq: frame.seq,
+                source: self.source.clone(),
+            },
+        );
+    }
+
+    /// Path of the muxed H.264 video, a temporary file if the video is only shown in the viewer.
+    fn video_path(&self) -> PathBuf {
+        match self.dump_data {
+            true => self.output_dir.join("video.mp4"),
+            false => std::env::temp_dir().join(format!(
+                "xcap-{}-{}.mp4",
+                std::process::id(),
+                self.topic.trim_start_matches('/').replace('/', "_")
+            )),
+        }
+    }
+
+    /// Log the muxed H.264 video to the viewer, with a reference to its frame at each stamp.
+    fn log_video(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let (Some(rec), Some(muxer)) = (&self.rec_stream, &self.muxer) else {
+            return Ok(());
+        };
+        let Some(&first) = muxer.stamps().first() else {
+            return Ok(());
+        };
+        let entity_path = self
+            .frame_tree
+            .lock()
+            .unwrap()
+            .image_entity_path(&self.frame_id, &self.topic);
+        rec.log_static(
+            entity_path.as_str(),
+            &rerun::AssetVideo::from_file_path(muxer.path())?,
+        )?;
+        for stamp in muxer.stamps() {
+            rec.set_time_seconds("main", *stamp as f64 * 1e-9);
+            rec.log(
+                entity_path.as_str(),
+                &rerun::VideoFrameReference::new(
+                    rerun::components::VideoTimestamp::from_nanoseconds((stamp - first) as i64),
+                ),
+            )?;
+        }
+        if !self.dump_data {
+            fs::remove_file(muxer.path())?;
         }
+        Ok(())
+    }
+
+    /// Visualize and dump decoded frames.
+    fn on_frames(
+        &mut self,
+        frames: Vec<decoder::Frame>,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        for frame in frames {
+            let (sec, nanosec) = (frame.pts / 1_000_000_000, frame.pts % 1_000_000_000);
+            if let Some(rec) = self.rec_stream.as_ref().filter(|_| !self.video_asset) {
+                rec.set_time_seconds("main", frame.pts as f64 * 1e-9);
+                let image =
+                    image::RgbImage::from_raw(frame.width, frame.height, frame.data.clone())
+                        .ok_or(Error::ImageBuf)?;
+                rec.log(
+                    self.frame_tree
+                        .lock()
+                        .unwrap()
+                        .image_entity_path(&self.frame_id, &self.topic),
+                    &rerun::Image::from_image(preview(image.into(), self.preview_scale))?,
+                )?;
+            }
+            if let Some(digest) = self.digest.as_mut() {
+                if digest.wants(frame.pts as u64) {
+                    let image =
+                        image::RgbImage::from_raw(frame.width, frame.height, frame.data.clone())
+                            .ok_or(Error::ImageBuf)?;
+                    digest.push(&image.into()).map_err(Error::Image)?;
+                }
+            }
+            let Some(seq) = self
+                .sampler
+                .keep(frame.pts as u64)
+                .filter(|_| self.dump_data)
+            else {
+                continue;
+            };
+            // Decoded frames carry the stamps of their messages only
+            let info = Frame {
+                topic: &self.topic,
+                stamp: frame.pts as u64,
+                publish_time: frame.pts as u64,
+                seq,
+            };
+            let image = image::RgbImage::from_raw(frame.width, frame.height, frame.data)
+                .ok_or(Error::ImageBuf)?;
+            let path = self
+                .image_options
+                .save(
+                    &image.into(),
+                    &self.output_dir,
+                    &format!("{}-{}", sec, nanosec),
+                    &info,
+                )
+                .map_err(Error::Image)?;
+            self.index(&info, path);
+        }
+        Ok(())
+    }
+}
+
+/// Extension of a still image file, told by the image data as format fields vary between
+/// publishers, like "jpeg" or "rgb8; jpeg compressed bgr8".
+fn extension(format: &str, data: &[u8]) -> String {
+    match image::guess_format(data) {
+        Ok(ImageFormat::Jpeg) => "jpeg".to_owned(),
+        Ok(format) => format.extensions_str()[0].to_owned(),
+        Err(_) => format
+            .chars()
+            .filter(char::is_ascii_alphanumeric)
+            .collect::<String>()
+            .to_lowercase(),
     }
 }
 
 impl Extractor for Parser {
-    type ExtractorError = Box<dyn std::error::Error>;
+    type ExtractorError = Box<dyn std::error::Error + Send + Sync>;
+
+    fn buffer(&mut self) -> &mut Vec<u8> {
+        &mut self.buffer
+    }
 
     fn step(&mut self, message: &Message) -> Result<(), Self::ExtractorError> {
-        let buf = message.data.as_ref();
-        let serialized = if &message.data[..4] == ZSTD_MAGIC_NUMBER {
-            zstd::stream::decode_all(buf).map_err(|e| Error::Zstd(e))?
-        } else {
-            message.data.to_vec()
-        };
-        let deserialized = cdr::deserialize_from::<_, CompressedImage, _>(
-            serialized.as_slice(),
-            cdr::size::Infinite,
-        )
-        .map_err(|e| Error::CDR(e))?;
+        let payload = self.decode_into(message).map_err(Error::Payload)?;
+        let deserialized =
+            cdr::deserialize_from::<_, CompressedImage, _>(payload, cdr::size::Infinite)
+                .map_err(Error::Cdr)?;
+        self.topic.clone_from(&message.channel.topic);
+        self.frame_id.clone_from(&deserialized.header.frame_id);
+
+        // Video frames are decoded here for both preview and extraction. H.264 streams are shown
+        // as videos, and decoded for extraction only.
+        let codec = Codec::from_format(&deserialized.format);
+        self.video_asset |= codec == Some(Codec::H264) && self.rec_stream.is_some();
+        if let Some(codec) = codec.filter(|_| self.dump_data || !self.video_asset) {
+            if self.decoder.is_none() {
+                self.decoder = Some(decoder::create(codec, self.backend).map_err(Error::Decoder)?);
+            }
+            let stamp = deserialized.header.stamp.sec as i64 * 1_000_000_000
+                + deserialized.header.stamp.nanosec as i64;
+            let frames = self
+                .decoder
+                .as_mut()
+                .unwrap()
+                .decode(&deserialized.data, stamp)
+                .map_err(Error::Decoder)?;
+            self.on_frames(frames)?;
+        }
 
         // Visualize?
-        if let Some(rec) = &self.rec_stream {
+        if let Some(rec) = self.rec_stream.as_ref().filter(|_| codec.is_none()) {
             rec.set_time_seconds(
                 "main",
                 deserialized.header.stamp.sec as f64
                     + deserialized.header.stamp.nanosec as f64 * 1e-9,
             );
-            rec.log(
-                format!("image/{}", message.channel.topic.clone()),
-                &rerun::EncodedImage::from_file_contents(deserialized.data.clone()),
-            )?;
+            let entity_path = self
+                .frame_tree
+                .lock()
+                .unwrap()
+                .image_entity_path(&self.frame_id, &self.topic);
+            if self.preview_scale.is_some() {
+                let image = image::load_from_memory(&deserialized.data).map_err(Error::Image)?;
+                rec.log(
+                    entity_path,
+                    &rerun::Image::from_image(preview(image, self.preview_scale))?,
+                )?;
+            } else {
+                rec.log(
+                    entity_path,
+                    &rerun::EncodedImage::from_file_contents(deserialized.data.clone()),
+                )?;
+            }
+        }
+
+        // Mux H.264 frames into a playable video as well, which is shown in the viewer
+        if codec == Some(Codec::H264) && (self.dump_data || self.video_asset) {
+            let stamp = deserialized.header.stamp.sec as u64 * 1_000_000_000
+                + deserialized.header.stamp.nanosec as u64;
+            if self.muxer.is_none() {
+                self.muxer = Some(H264Muxer::new(&self.video_path()));
+            }
+            self.muxer
+                .as_mut()
+                .unwrap()
+                .push(stamp, &deserialized.data)
+                .map_err(Error::Video)?;
         }
 
-        // Dump data?
-        if self.dump_data {
-            let path = self.output_dir.join(format!(
-                "{}-{}.{}",
-                deserialized.header.stamp.sec,
-                deserialized.header.stamp.nanosec,
-                deserialized.format
-            ));
-            std::fs::write(path, deserialized.data)?;
+        // Frames are sampled, while the video keeps all of them
+        let stamp = deserialized.header.stamp.sec as u64 * 1_000_000_000
+            + deserialized.header.stamp.nanosec as u64;
+        let seq = match self.dump_data && codec.is_none() {
+            true => self.sampler.keep(stamp),
+            false => None,
+        };
+        if let Some(digest) = self.digest.as_mut().filter(|_| codec.is_none()) {
+            if digest.wants(stamp) {
+                let image = image::load_from_memory(&deserialized.data).map_err(Error::Image)?;
+                digest.push(&image).map_err(Error::Image)?;
+            }
+        }
+        if let Some(seq) = seq {
+            let frame = Frame {
+                topic: &self.topic,
+                stamp,
+                publish_time: message.publish_time,
+                seq,
+            };
+            let stem = format!(
+                "{}-{}",
+                deserialized.header.stamp.sec, deserialized.header.stamp.nanosec
+            );
+            let path = if self.image_options.decode_compressed() {
+                let image = image::load_from_memory(&deserialized.data).map_err(Error::Image)?;
+                self.image_options
+                    .save(&image, &self.output_dir, &stem, &frame)
+                    .map_err(Error::Image)?
+            } else {
+                let path = self.image_options.path(
+                    &self.output_dir,
+                    &stem,
+                    &extension(&deserialized.format, &deserialized.data),
+                    &frame,
+                )?;
+                std::fs::write(&path, deserialized.data)?;
+                path
+            };
+            self.index(&frame, path);
         }
 
         Ok(())
     }
 
+    fn set_source(&mut self, source: &Path) {
+        self.source = source.display().to_string();
+    }
+
     fn post_process(&mut self, _sigint: Arc<AtomicBool>) -> Result<(), Self::ExtractorError> {
+        if let Some(muxer) = self.muxer.as_mut() {
+            muxer.finish().map_err(Error::Video)?;
+        }
+        if self.video_asset {
+            self.log_video()?;
+        }
+        if let Some(decoder) = self.decoder.as_mut() {
+            let frames = decoder.flush().map_err(Error::Decoder)?;
+            self.on_frames(frames)?;
+        }
+        if let Some(digest) = self.digest.as_mut() {
+            digest.finish().map_err(Error::Image)?;
+        }
         Ok(())
     }
 }
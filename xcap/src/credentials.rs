@@ -0,0 +1,401 @@
+//! Credentials of S3 buckets, resolved like the AWS tools do: environment variables, web identity
+//! tokens of IRSA, profiles in `~/.aws`, then the roles of ECS containers and EC2 instances.
+use log::debug;
+use serde::Deserialize;
+use std::{collections::HashMap, env, fs, path::PathBuf, time::Duration};
+use thiserror::Error;
+
+// Instance metadata service of EC2, and the credentials endpoint of ECS
+const IMDS_URL: &str = "http://169.254.169.254/latest";
+const ECS_URL: &str = "http://169.254.170.2";
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("Reqwest error: {0}")]
+    RequestError(#[from] reqwest::Error),
+    #[error("Disk IO error: {0}")]
+    DiskError(#[from] std::io::Error),
+    #[error("JSON error: {0}")]
+    JsonError(#[from] serde_json::Error),
+    #[error("Invalid data: {0}")]
+    InvalidData(String),
+    #[error("No credentials found in the environment, AWS profiles, or instance metadata")]
+    NotFound,
+}
+
+/// Keys of a S3 bucket. Temporary keys of roles come with a session token.
+#[derive(Debug, Clone)]
+pub struct Credentials {
+    pub access_key: String,
+    pub secret_key: String,
+    pub session_token: Option<String>,
+}
+
+// Credentials returned by the ECS and EC2 endpoints
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct RoleCredentials {
+    access_key_id: String,
+    secret_access_key: String,
+    token: Option<String>,
+}
+
+impl From<RoleCredentials> for Credentials {
+    fn from(c: RoleCredentials) -> Self {
+        Credentials {
+            access_key: c.access_key_id,
+            secret_key: c.secret_access_key,
+            session_token: c.token,
+        }
+    }
+}
+
+/// Region of the bucket, from `S3_REGION`, `AWS_REGION`, `AWS_DEFAULT_REGION`, or the profile.
+pub fn region() -> Option<String> {
+    ["S3_REGION", "AWS_REGION", "AWS_DEFAULT_REGION"]
+        .iter()
+        .find_map(|v| env::var(v).ok())
+        .or_else(|| profile_value("region"))
+}
+
+/// Resolve the credentials, from the first source providing them.
+pub async fn resolve() -> Result<Credentials, Error> {
+    if let Some(credentials) = from_env("S3_ACCESS_KEY", "S3_SECRET_KEY", "S3_SESSION_TOKEN")
+        .or_else(|| {
+            from_env(
+                "AWS_ACCESS_KEY_ID",
+                "AWS_SECRET_ACCESS_KEY",
+                "AWS_SESSION_TOKEN",
+            )
+        })
+    {
+        debug!("Credentials from environment variables");
+        return Ok(credentials);
+    }
+    if let Some(credentials) = from_web_identity().await? {
+        debug!("Credentials from web identity token");
+        return Ok(credentials);
+    }
+    if let Some(credentials) = from_profile() {
+        debug!("Credentials from AWS profile");
+        return Ok(credentials);
+    }
+    if let Some(credentials) = from_container().await? {
+        debug!("Credentials from container role");
+        return Ok(credentials);
+    }
+    if env::var("AWS_EC2_METADATA_DISABLED").is_ok_and(|v| v.eq_ignore_ascii_case("true")) {
+        return Err(Error::NotFound);
+    }
+    match from_instance().await {
+        Ok(credentials) => {
+            debug!("Credentials from instance role");
+            Ok(credentials)
+        }
+        Err(e) => {
+            debug!("No instance role. {}", e);
+            Err(Error::NotFound)
+        }
+    }
+}
+
+fn from_env(access: &str, secret: &str, token: &str) -> Option<Credentials> {
+    Some(Credentials {
+        access_key: env::var(access).ok()?,
+        secret_key: env::var(secret).ok()?,
+        session_token: env::var(token).ok(),
+    })
+}
+
+/// Assume the role with the web identity token, like IRSA of EKS.
+async fn from_web_identity() -> Result<Option<Credentials>, Error> {
+    let (Ok(token_file), Ok(role_arn)) = (
+        env::var("AWS_WEB_IDENTITY_TOKEN_FILE"),
+        env::var("AWS_ROLE_ARN"),
+    ) else {
+        return Ok(None);
+    };
+    let token = fs::read_to_string(token_file)?;
+    let session_name = env::var("AWS_ROLE_SESSION_NAME").unwrap_or("xcap".to_string());
+    let endpoint = match region() {
+        Some(region) => format!("https://sts.{}.amazonaws.com/", region),
+        None => "https://sts.amazonaws.com/".to_string(),
+    };
+    // STS is on the internet, unlike the metadata endpoints
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()?;
+    let response = client
+        .get(endpoint)
+        .query(&[
+            ("Action", "AssumeRoleWithWebIdentity"),
+            ("Version", "2011-06-15"),
+            ("RoleArn", role_arn.as_str()),
+            ("RoleSessionName", session_name.as_str()),
+            ("WebIdentityToken", token.trim()),
+        ])
+        .send()
+        .await?
+        .error_for_status()?;
+    let body = response.bytes().await?;
+    let root =
+        xmltree::Element::parse(body.as_ref()).map_err(|e| Error::InvalidData(e.to_string()))?;
+    let credentials = root
+        .get_child("AssumeRoleWithWebIdentityResult")
+        .and_then(|r| r.get_child("Credentials"))
+        .ok_or(Error::InvalidData(
+            "No credentials in STS response".to_string(),
+        ))?;
+    let text = |name: &str| {
+        credentials
+            .get_child(name)
+            .and_then(|e| e.get_text())
+            .map(|t| t.into_owned())
+            .ok_or(Error::InvalidData(format!("No {} in STS response", name)))
+    };
+    Ok(Some(Credentials {
+        access_key: text("AccessKeyId")?,
+        secret_key: text("SecretAccessKey")?,
+        session_token: Some(text("SessionToken")?),
+    }))
+}
+
+/// Static keys of the profile in `~/.aws/credentials`, or in `~/.aws/config`.
+fn from_profile() -> Option<Credentials> {
+    Some(Credentials {
+        access_key: profile_value("aws_access_key_id")?,
+        secret_key: profile_value("aws_secret_access_key")?,
+        session_token: profile_value("aws_session_token"),
+    })
+}
+
+/// Value of the key in the profile of `AWS_PROFILE`, or the default one. The credentials file
+/// takes precedence over the config file.
+fn profile_value(key: &str) -> Option<String> {
+    let profile = env::var("AWS_PROFILE").unwrap_or("default".to_string());
+    let aws_dir = || env::var_os("HOME").map(|home| PathBuf::from(home).join(".aws"));
+    let credentials_file = env::var_os("AWS_SHARED_CREDENTIALS_FILE")
+        .map(PathBuf::from)
+        .or_else(|| aws_dir().map(|d| d.join("credentials")));
+    let config_file = env::var_os("AWS_CONFIG_FILE")
+        .map(PathBuf::from)
+        .or_else(|| aws_dir().map(|d| d.join("config")));
+    // Profiles other than the default are prefixed in the config file
+    let config_section = match profile.as_str() {
+        "default" => profile.clone(),
+        name => format!("profile {}", name),
+    };
+    [
+        (credentials_file, profile.clone()),
+        (config_file, config_section),
+    ]
+    .into_iter()
+    .find_map(|(file, section)| {
+        let text = fs::read_to_string(file?).ok()?;
+        parse_ini(&text).get(&section)?.get(key).cloned()
+    })
+}
+
+/// Sections of an INI file, with their keys and values.
+fn parse_ini(text: &str) -> HashMap<String, HashMap<String, String>> {
+    let mut sections: HashMap<String, HashMap<String, String>> = HashMap::new();
+    let mut current = None;
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with(['#', ';']) {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            current = Some(name.trim().to_string());
+            continue;
+        }
+        if let (Some(section), Some((key, value))) = (&current, line.split_once('=')) {
+            sections
+                .entry(section.clone())
+                .or_default()
+                .insert(key.trim().to_string(), value.trim().to_string());
+        }
+    }
+    sections
+}
+
+/// Credentials of the task role of an ECS container.
+async fn from_container() -> Result<Option<Credentials>, Error> {
+    let url = match (
+        env::var("AWS_CONTAINER_CREDENTIALS_RELATIVE_URI"),
+        env::var("AWS_CONTAINER_CREDENTIALS_FULL_URI"),
+    ) {
+        (Ok(uri), _) => format!("{}{}", ECS_URL, uri),
+        (_, Ok(uri)) => uri,
+        _ => return Ok(None),
+    };
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(1))
+        .build()?;
+    let mut request = client.get(url);
+    if let Ok(token) = env::var("AWS_CONTAINER_AUTHORIZATION_TOKEN") {
+        request = request.header("Authorization", token);
+    }
+    let body = request.send().await?.error_for_status()?.bytes().await?;
+    let credentials: RoleCredentials = serde_json::from_slice(&body)?;
+    Ok(Some(credentials.into()))
+}
+
+/// Credentials of the role of the EC2 instance, with a session token of IMDSv2.
+async fn from_instance() -> Result<Credentials, Error> {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(1))
+        .build()?;
+    let token = client
+        .put(format!("{}/api/token", IMDS_URL))
+        .header("X-aws-ec2-metadata-token-ttl-seconds", "21600")
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    let get = |path: String| {
+        client
+            .get(format!(
+                "{}/meta-data/iam/security-credentials/{}",
+                IMDS_URL, path
+            ))
+            .header("X-aws-ec2-metadata-token", &token)
+            .send()
+    };
+    let roles = get(String::new()).await?.error_for_status()?.text().await?;
+    let role = roles
+        .lines()
+        .next()
+        .ok_or(Error::InvalidData("No instance role".to_string()))?;
+    let body = get(role.to_string())
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    let credentials: RoleCredentials = serde_json::from_slice(&body)?;
+    Ok(credentials.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+    use std::sync::Mutex;
+
+    // Tests changing the environment run one at a time
+    static ENV: Mutex<()> = Mutex::new(());
+
+    /// Clear the variables the credentials and the region are resolved from.
+    fn clear_env() {
+        for var in [
+            "S3_ACCESS_KEY",
+            "S3_SECRET_KEY",
+            "S3_SESSION_TOKEN",
+            "S3_REGION",
+            "AWS_ACCESS_KEY_ID",
+            "AWS_SECRET_ACCESS_KEY",
+            "AWS_SESSION_TOKEN",
+            "AWS_REGION",
+            "AWS_DEFAULT_REGION",
+            "AWS_PROFILE",
+            "AWS_SHARED_CREDENTIALS_FILE",
+            "AWS_CONFIG_FILE",
+            "AWS_WEB_IDENTITY_TOKEN_FILE",
+            "AWS_ROLE_ARN",
+        ] {
+            env::remove_var(var);
+        }
+    }
+
+    #[test]
+    fn parse_ini_sections() {
+        let sections = parse_ini(
+            "# Comment\n\
+            key = before any section\n\
+            [default]\n\
+            aws_access_key_id = AKID\n\
+            ; Another comment\n\
+            \n\
+            [profile dev]\n  \
+              region=eu-west-1  \n\
+            endpoint = http://localhost:9000/?a=b\n",
+        );
+        assert_eq!(sections.len(), 2);
+        assert_eq!(sections["default"].len(), 1);
+        assert_eq!(sections["default"]["aws_access_key_id"], "AKID");
+        assert_eq!(sections["profile dev"]["region"], "eu-west-1");
+        // Values keep everything after the first `=`
+        assert_eq!(
+            sections["profile dev"]["endpoint"],
+            "http://localhost:9000/?a=b"
+        );
+    }
+
+    #[test]
+    fn read_the_credentials_file_before_the_config_file() {
+        let _env = ENV.lock().unwrap();
+        clear_env();
+        let dir = test_util::dir("aws");
+        fs::write(
+            dir.join("credentials"),
+            "[dev]\naws_access_key_id = CREDENTIALS\naws_secret_access_key = secret\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("config"),
+            "[default]\nregion = us-east-2\n\
+            [dev]\nregion = ignored\n\
+            [profile dev]\naws_access_key_id = CONFIG\nregion = eu-west-1\n",
+        )
+        .unwrap();
+        env::set_var("AWS_SHARED_CREDENTIALS_FILE", dir.join("credentials"));
+        env::set_var("AWS_CONFIG_FILE", dir.join("config"));
+        assert_eq!(region().as_deref(), Some("us-east-2"));
+        assert!(from_profile().is_none());
+
+        env::set_var("AWS_PROFILE", "dev");
+        let credentials = from_profile().unwrap();
+        assert_eq!(credentials.access_key, "CREDENTIALS");
+        assert_eq!(credentials.secret_key, "secret");
+        assert_eq!(credentials.session_token, None);
+        assert_eq!(region().as_deref(), Some("eu-west-1"));
+
+        // Variables take precedence over the profiles
+        env::set_var("AWS_DEFAULT_REGION", "ap-south-1");
+        assert_eq!(region().as_deref(), Some("ap-south-1"));
+        env::set_var("AWS_REGION", "ap-east-1");
+        assert_eq!(region().as_deref(), Some("ap-east-1"));
+        env::set_var("S3_REGION", "local");
+        assert_eq!(region().as_deref(), Some("local"));
+        clear_env();
+    }
+
+    #[test]
+    fn read_s3_variables_before_aws_variables() {
+        let _env = ENV.lock().unwrap();
+        clear_env();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let resolved = || runtime.block_on(resolve()).unwrap();
+        env::set_var("AWS_ACCESS_KEY_ID", "AWS");
+        env::set_var("AWS_SECRET_ACCESS_KEY", "aws-secret");
+        env::set_var("AWS_SESSION_TOKEN", "aws-token");
+        let credentials = resolved();
+        assert_eq!(credentials.access_key, "AWS");
+        assert_eq!(credentials.session_token.as_deref(), Some("aws-token"));
+
+        // The token of the AWS variables is not mixed in
+        env::set_var("S3_ACCESS_KEY", "S3");
+        env::set_var("S3_SECRET_KEY", "s3-secret");
+        let credentials = resolved();
+        assert_eq!(credentials.access_key, "S3");
+        assert_eq!(credentials.secret_key, "s3-secret");
+        assert_eq!(credentials.session_token, None);
+
+        // Both keys are required
+        env::remove_var("S3_SECRET_KEY");
+        assert_eq!(resolved().access_key, "AWS");
+        clear_env();
+    }
+}
@@ -0,0 +1,216 @@
+//! Video decoders of compressed image streams. Parsers work with the `VideoDecoder` trait, so
+//! that codecs and backends could be added without touching the extraction logic.
+use crate::{ffmpeg, hevc};
+use openh264::formats::YUVSource;
+use std::{cmp::Reverse, collections::BinaryHeap, str::FromStr};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("HEVC error. {0}")]
+    Hevc(#[from] hevc::Error),
+    #[error("H.264 error. {0}")]
+    H264(#[from] openh264::Error),
+    #[error("FFmpeg error. {0}")]
+    Ffmpeg(#[from] ffmpeg::Error),
+    #[error("No native decoder of {0:?}, try `--video-decoder ffmpeg`.")]
+    Unsupported(Codec),
+}
+
+/// A decoded frame in RGB24 format.
+pub struct Frame {
+    pub width: u32,
+    pub height: u32,
+
+    // Presentation timestamp passed in with the data, in nanoseconds
+    pub pts: i64,
+
+    pub data: Vec<u8>,
+}
+
+/// Decoder of an Annex-B video stream.
+pub trait VideoDecoder {
+    /// Feed a chunk of the stream and return all frames decoded so far.
+    fn decode(&mut self, data: &[u8], pts: i64) -> Result<Vec<Frame>, Error>;
+
+    /// Signal the end of stream and return the remaining frames.
+    fn flush(&mut self) -> Result<Vec<Frame>, Error>;
+}
+
+/// Video codecs, told by the format field of compressed images.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    H264,
+    Hevc,
+    Av1,
+}
+
+impl Codec {
+    /// Codec of the format, None for still images like JPEG or PNG.
+    pub fn from_format(format: &str) -> Option<Self> {
+        let format = format.to_lowercase();
+        if format.contains("h264") {
+            Some(Codec::H264)
+        } else if format.contains("h265") || format.contains("hevc") {
+            Some(Codec::Hevc)
+        } else if format.contains("av1") {
+            Some(Codec::Av1)
+        } else {
+            None
+        }
+    }
+}
+
+/// Libraries doing the decoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backend {
+    /// OpenH264 for H.264, libde265 for HEVC.
+    #[default]
+    Native,
+    /// The `ffmpeg` command, for every codec it supports.
+    Ffmpeg,
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "native" => Ok(Backend::Native),
+            "ffmpeg" => Ok(Backend::Ffmpeg),
+            _ => Err(format!(
+                "Invalid video decoder: {}, expected one of native, ffmpeg",
+                s
+            )),
+        }
+    }
+}
+
+impl Backend {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Backend::Native => "native",
+            Backend::Ffmpeg => "ffmpeg",
+        }
+    }
+}
+
+/// Create a decoder of the codec with the backend.
+pub fn create(codec: Codec, backend: Backend) -> Result<Box<dyn VideoDecoder>, Error> {
+    Ok(match (codec, backend) {
+        (Codec::H264, Backend::Native) => Box::new(OpenH264::new()?),
+        (Codec::Hevc, Backend::Native) => Box::new(hevc::Decoder::new()?),
+        (Codec::Av1, Backend::Native) => return Err(Error::Unsupported(codec)),
+        (_, Backend::Ffmpeg) => Box::new(ffmpeg::Decoder::new(codec)?),
+    })
+}
+
+/// Timestamps of the frames pushed but not yet decoded. Frames come out in presentation order,
+/// so each of them takes the earliest timestamp pending.
+#[derive(Debug, Default)]
+pub(crate) struct PtsQueue(BinaryHeap<Reverse<i64>>);
+
+impl PtsQueue {
+    pub fn push(&mut self, pts: i64) {
+        self.0.push(Reverse(pts));
+    }
+
+    /// Timestamp of the next decoded frame, or the given one if nothing is pending.
+    pub fn pop_or(&mut self, pts: i64) -> i64 {
+        self.0.pop().map_or(pts, |Reverse(pts)| pts)
+    }
+}
+
+/// Does the chunk carry a coded picture, not only parameter sets or other metadata?
+pub(crate) fn has_picture(codec: Codec, data: &[u8]) -> bool {
+    let is_vcl = match codec {
+        Codec::H264 => |header: u8| (1..=5).contains(&(header & 0x1f)),
+        Codec::Hevc => |header: u8| (header >> 1) & 0x3f < 32,
+        Codec::Av1 => return has_av1_frame(data),
+    };
+    let mut i = 0;
+    while i + 3 < data.len() {
+        if data[i..i + 3] == [0, 0, 1] && is_vcl(data[i + 3]) {
+            return true;
+        }
+        i += 1;
+    }
+    false
+}
+
+/// Does the AV1 chunk, in the low overhead bitstream format, carry a frame header, a frame or a
+/// tile group OBU?
+fn has_av1_frame(data: &[u8]) -> bool {
+    let mut rest = data;
+    while let Some((&header, tail)) = rest.split_first() {
+        if matches!((header >> 3) & 0x0f, 3 | 4 | 6) {
+            return true;
+        }
+        // Skip the extension byte, then the payload by its size field
+        let tail = if header & 0x04 != 0 {
+            tail.get(1..).unwrap_or_default()
+        } else {
+            tail
+        };
+        if header & 0x02 == 0 {
+            return false;
+        }
+        let (mut size, mut read) = (0usize, 0);
+        for (i, byte) in tail.iter().take(8).enumerate() {
+            size |= ((byte & 0x7f) as usize) << (7 * i);
+            read = i + 1;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+        rest = tail.get(read + size..).unwrap_or_default();
+    }
+    false
+}
+
+/// H.264 decoder backed by OpenH264. Frames are decoded without delay, so each one takes the
+/// timestamp of the data it is decoded from.
+struct OpenH264 {
+    decoder: openh264::decoder::Decoder,
+    last_pts: i64,
+}
+
+impl OpenH264 {
+    fn new() -> Result<Self, Error> {
+        Ok(OpenH264 {
+            decoder: openh264::decoder::Decoder::new()?,
+            last_pts: 0,
+        })
+    }
+}
+
+impl VideoDecoder for OpenH264 {
+    fn decode(&mut self, data: &[u8], pts: i64) -> Result<Vec<Frame>, Error> {
+        self.last_pts = pts;
+        Ok(self
+            .decoder
+            .decode(data)?
+            .map(|yuv| to_frame(&yuv, pts))
+            .into_iter()
+            .collect())
+    }
+
+    fn flush(&mut self) -> Result<Vec<Frame>, Error> {
+        let remaining = self.decoder.flush_remaining()?;
+        Ok(remaining
+            .iter()
+            .map(|yuv| to_frame(yuv, self.last_pts))
+            .collect())
+    }
+}
+
+fn to_frame(yuv: &openh264::decoder::DecodedYUV, pts: i64) -> Frame {
+    let (width, height) = yuv.dimensions();
+    let mut data = vec![0; width * height * 3];
+    yuv.write_rgb8(&mut data);
+    Frame {
+        width: width as u32,
+        height: height as u32,
+        pts,
+        data,
+    }
+}
@@ -0,0 +1,94 @@
+//! Back-projection of depth images into point clouds with the pinhole camera model.
+use ros2_sensor_msgs::msg::{Image, PointCloud2, PointField};
+
+// Depth of 16 bit images is in millimeters
+const MILLIMETER: f32 = 0.001;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Not a depth image: {0}, expected 16UC1 or 32FC1")]
+    Encoding(String),
+    #[error("Image data is shorter than {0} rows of {1} bytes")]
+    Truncated(u32, u32),
+}
+
+/// Is this encoding a depth image?
+pub fn is_depth(encoding: &str) -> bool {
+    matches!(encoding, "16UC1" | "32FC1")
+}
+
+/// Pinhole camera intrinsics, in pixels.
+#[derive(Debug, Clone, Copy)]
+pub struct Intrinsics {
+    pub fx: f32,
+    pub fy: f32,
+    pub cx: f32,
+    pub cy: f32,
+}
+
+/// Back-project the depth image into an unorganized cloud with float x, y and z fields, in the
+/// optical frame of the camera. Pixels without a valid depth are dropped.
+pub fn to_cloud(image: &Image, intrinsics: &Intrinsics) -> Result<PointCloud2, Error> {
+    let pixel_size = match image.encoding.as_str() {
+        "16UC1" => 2,
+        "32FC1" => 4,
+        _ => return Err(Error::Encoding(image.encoding.clone())),
+    };
+    if (image.step as usize) < image.width as usize * pixel_size
+        || image.data.len() < image.step as usize * image.height as usize
+    {
+        return Err(Error::Truncated(image.height, image.step));
+    }
+
+    let depth_at = |bytes: &[u8]| -> f32 {
+        match (pixel_size, image.is_bigendian != 0) {
+            (2, false) => u16::from_le_bytes([bytes[0], bytes[1]]) as f32 * MILLIMETER,
+            (2, true) => u16::from_be_bytes([bytes[0], bytes[1]]) as f32 * MILLIMETER,
+            (_, false) => f32::from_le_bytes(bytes.try_into().unwrap()),
+            (_, true) => f32::from_be_bytes(bytes.try_into().unwrap()),
+        }
+    };
+
+    let mut data = Vec::new();
+    for (v, row) in image
+        .data
+        .chunks_exact(image.step as usize)
+        .take(image.height as usize)
+        .enumerate()
+    {
+        for (u, bytes) in row
+            .chunks_exact(pixel_size)
+            .take(image.width as usize)
+            .enumerate()
+        {
+            let z = depth_at(bytes);
+            if !z.is_finite() || z <= 0.0 {
+                continue;
+            }
+            let x = (u as f32 - intrinsics.cx) * z / intrinsics.fx;
+            let y = (v as f32 - intrinsics.cy) * z / intrinsics.fy;
+            for value in [x, y, z] {
+                data.extend_from_slice(&value.to_ne_bytes());
+            }
+        }
+    }
+
+    let field = |name: &str, offset: u32| PointField {
+        name: name.to_owned(),
+        offset,
+        datatype: 7,
+        count: 1,
+    };
+    let width = (data.len() / 12) as u32;
+    Ok(PointCloud2 {
+        header: image.header.clone(),
+        height: 1,
+        width,
+        fields: vec![field("x", 0), field("y", 4), field("z", 8)],
+        is_bigendian: cfg!(target_endian = "big") as u8,
+        point_step: 12,
+        row_step: width * 12,
+        data,
+        is_dense: 1,
+    })
+}
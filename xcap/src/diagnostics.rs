@@ -0,0 +1,170 @@
+use crate::extractor::Extractor;
+use crate::frame_index::csv_field;
+use mcap::Message;
+use rerun::{RecordingStream, TextLogLevel};
+use ros2_diagnostic_msgs::msg::{DiagnosticArray, DiagnosticStatus};
+use std::{
+    collections::HashMap,
+    fs,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::{atomic::AtomicBool, Arc},
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Payload error. {0}")]
+    Payload(#[from] crate::extractor::DecodeError),
+    #[error("CDR error. {0}")]
+    Cdr(#[from] cdr::Error),
+    #[error("JSON error. {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// Parser of diagnostics. The status of every component is written to a timeline, and changes of
+/// the levels are shown as text logs, so faults could be looked into without replaying the bag.
+pub struct Parser {
+    // Visualizer with rerun
+    rec_stream: Option<RecordingStream>,
+
+    // CSV file for the status of all the components, only available when dumping data
+    csv: Option<BufWriter<fs::File>>,
+
+    // JSONL file for the status of all the components with their values, only available when
+    // dumping data
+    jsonl: Option<BufWriter<fs::File>>,
+
+    // Scratch buffer for decoded message payloads, reused across messages
+    buffer: Vec<u8>,
+
+    // Last level of each component, by name
+    levels: HashMap<String, u8>,
+}
+
+impl Parser {
+    pub fn new(output_path: &Path, rerun_stream: Option<RecordingStream>, dump_data: bool) -> Self {
+        // Create output files
+        let (csv, jsonl) = if dump_data {
+            fs::create_dir_all(output_path).unwrap();
+            let mut csv =
+                BufWriter::new(fs::File::create(output_path.join("diagnostics.csv")).unwrap());
+            writeln!(csv, "sec,nanosec,name,hardware_id,level,message").unwrap();
+            let jsonl =
+                BufWriter::new(fs::File::create(output_path.join("diagnostics.jsonl")).unwrap());
+            (Some(csv), Some(jsonl))
+        } else {
+            (None, None)
+        };
+
+        Parser {
+            rec_stream: rerun_stream,
+            csv,
+            jsonl,
+            buffer: Vec::new(),
+            levels: HashMap::new(),
+        }
+    }
+}
+
+/// Name of the level of operation.
+fn level_name(level: u8) -> &'static str {
+    match level {
+        DiagnosticStatus::OK => "OK",
+        DiagnosticStatus::WARN => "WARN",
+        DiagnosticStatus::ERROR => "ERROR",
+        DiagnosticStatus::STALE => "STALE",
+        _ => "UNKNOWN",
+    }
+}
+
+/// Level of the text log for the level of operation.
+fn log_level(level: u8) -> &'static str {
+    match level {
+        DiagnosticStatus::OK => TextLogLevel::INFO,
+        DiagnosticStatus::WARN => TextLogLevel::WARN,
+        DiagnosticStatus::ERROR => TextLogLevel::ERROR,
+        _ => "STALE",
+    }
+}
+
+impl Extractor for Parser {
+    type ExtractorError = Box<dyn std::error::Error + Send + Sync>;
+
+    fn buffer(&mut self) -> &mut Vec<u8> {
+        &mut self.buffer
+    }
+
+    fn step(&mut self, message: &Message) -> Result<(), Self::ExtractorError> {
+        let payload = self.decode_into(message).map_err(Error::Payload)?;
+        let array = cdr::deserialize_from::<_, DiagnosticArray, _>(payload, cdr::size::Infinite)
+            .map_err(Error::Cdr)?;
+        let stamp = &array.header.stamp;
+
+        if let Some(rec) = &self.rec_stream {
+            rec.set_time_seconds("main", stamp.sec as f64 + stamp.nanosec as f64 * 1e-9);
+        }
+        for status in &array.status {
+            // Only the changes of the levels are logged, the first report of a component included
+            let changed =
+                self.levels.insert(status.name.clone(), status.level) != Some(status.level);
+            if let Some(rec) = self.rec_stream.as_ref().filter(|_| changed) {
+                rec.log(
+                    format!("diagnostics/{}", message.channel.topic),
+                    &rerun::TextLog::new(format!(
+                        "{}: {} {}",
+                        status.name,
+                        level_name(status.level),
+                        status.message
+                    ))
+                    .with_level(log_level(status.level)),
+                )?;
+            }
+
+            // Dump data?
+            if let Some(csv) = self.csv.as_mut() {
+                writeln!(
+                    csv,
+                    "{},{},{},{},{},{}",
+                    stamp.sec,
+                    stamp.nanosec,
+                    csv_field(&status.name),
+                    csv_field(&status.hardware_id),
+                    level_name(status.level),
+                    csv_field(&status.message)
+                )?;
+            }
+            if let Some(jsonl) = self.jsonl.as_mut() {
+                let values: serde_json::Map<String, serde_json::Value> = status
+                    .values
+                    .iter()
+                    .map(|kv| (kv.key.clone(), kv.value.clone().into()))
+                    .collect();
+                serde_json::to_writer(
+                    &mut *jsonl,
+                    &serde_json::json!({
+                        "sec": stamp.sec,
+                        "nanosec": stamp.nanosec,
+                        "name": status.name,
+                        "hardware_id": status.hardware_id,
+                        "level": level_name(status.level),
+                        "message": status.message,
+                        "values": values,
+                    }),
+                )
+                .map_err(Error::Json)?;
+                writeln!(jsonl)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn post_process(&mut self, _sigint: Arc<AtomicBool>) -> Result<(), Self::ExtractorError> {
+        if let Some(csv) = self.csv.as_mut() {
+            csv.flush()?;
+        }
+        if let Some(jsonl) = self.jsonl.as_mut() {
+            jsonl.flush()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,123 @@
+//! Differences between the topics of two recordings, to validate changes of recorder configs.
+use crate::{Error, Input};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeMap,
+    path::PathBuf,
+    sync::{atomic::AtomicBool, Arc},
+};
+
+// Time coverages differing less than this are taken as the same, in nanoseconds
+const COVERAGE_TOLERANCE: u64 = 1_000_000_000;
+
+/// Topic of a recording, as compared by `diff`.
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+    pub name: String,
+    pub schema: String,
+    pub encoding: String,
+    // SHA-256 of the schema data in hex, empty if the topic has no schema
+    pub schema_hash: String,
+    pub msg_count: u64,
+    // Log times of the first and the last message in nanoseconds
+    pub first: u64,
+    pub last: u64,
+}
+
+impl Profile {
+    /// Time covered by the messages in nanoseconds
+    pub fn coverage(&self) -> u64 {
+        self.last - self.first
+    }
+}
+
+/// A topic added, removed or changed in the second recording.
+#[derive(Debug)]
+pub enum Change {
+    Added(Profile),
+    Removed(Profile),
+    Changed {
+        before: Profile,
+        after: Profile,
+        // Fields differing, like `schema` and `msg_count`
+        fields: Vec<&'static str>,
+    },
+}
+
+/// Profiles of all the topics with messages, sorted by name, by reading through every message.
+pub fn profile(files: &[PathBuf], sigint: Arc<AtomicBool>) -> Result<Vec<Profile>, Error> {
+    let mut topics: BTreeMap<String, Profile> = BTreeMap::new();
+    for file in files {
+        let input = Input::open(file)?;
+        for message in input.all_messages()? {
+            if sigint.load(std::sync::atomic::Ordering::Relaxed) {
+                return Err(Error::Interrupted);
+            }
+
+            let msg = message?;
+            let topic = topics.entry(msg.channel.topic.clone()).or_insert_with(|| {
+                let schema = msg.channel.schema.as_deref();
+                Profile {
+                    name: msg.channel.topic.clone(),
+                    schema: schema.map(|s| s.name.clone()).unwrap_or_default(),
+                    encoding: schema.map(|s| s.encoding.clone()).unwrap_or_default(),
+                    schema_hash: schema.map(|s| hash(&s.data)).unwrap_or_default(),
+                    first: msg.log_time,
+                    last: msg.log_time,
+                    ..Default::default()
+                }
+            });
+            topic.msg_count += 1;
+            topic.first = topic.first.min(msg.log_time);
+            topic.last = topic.last.max(msg.log_time);
+        }
+    }
+    Ok(topics.into_values().collect())
+}
+
+/// Changes of the topics from the first recording to the second, sorted by topic name.
+pub fn compare(before: &[Profile], after: &[Profile]) -> Vec<Change> {
+    let before: BTreeMap<&str, &Profile> = before.iter().map(|p| (p.name.as_str(), p)).collect();
+    let after: BTreeMap<&str, &Profile> = after.iter().map(|p| (p.name.as_str(), p)).collect();
+    let mut names: Vec<&str> = before.keys().chain(after.keys()).copied().collect();
+    names.sort();
+    names.dedup();
+
+    let mut changes = Vec::new();
+    for name in names {
+        match (before.get(name), after.get(name)) {
+            (Some(b), None) => changes.push(Change::Removed((*b).clone())),
+            (None, Some(a)) => changes.push(Change::Added((*a).clone())),
+            (Some(b), Some(a)) => {
+                let mut fields = Vec::new();
+                if b.schema != a.schema || b.encoding != a.encoding {
+                    fields.push("schema");
+                } else if b.schema_hash != a.schema_hash {
+                    fields.push("schema_data");
+                }
+                if b.msg_count != a.msg_count {
+                    fields.push("msg_count");
+                }
+                if b.coverage().abs_diff(a.coverage()) >= COVERAGE_TOLERANCE {
+                    fields.push("coverage");
+                }
+                if !fields.is_empty() {
+                    changes.push(Change::Changed {
+                        before: (*b).clone(),
+                        after: (*a).clone(),
+                        fields,
+                    });
+                }
+            }
+            (None, None) => {}
+        }
+    }
+    changes
+}
+
+fn hash(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
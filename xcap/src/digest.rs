@@ -0,0 +1,213 @@
+//! Animated digests of camera topics: frames sampled at a low rate and downsized, played in a
+//! short GIF or WebP for a quick look at the recording.
+use crate::image_file::{Sampler, Sampling};
+use image::{
+    codecs::{
+        gif::{GifEncoder, Repeat},
+        webp::WebPEncoder,
+    },
+    imageops::FilterType,
+    Delay, DynamicImage, ExtendedColorType, ImageResult,
+};
+use std::{
+    fs,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+// Display time of each frame in milliseconds, so digests play faster than the recording
+const FRAME_DELAY_MS: u32 = 100;
+
+// Animation flag of the VP8X chunk
+const WEBP_ANIMATION: u8 = 0x02;
+
+/// Format of the digests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Gif,
+    Webp,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gif" => Ok(Format::Gif),
+            "webp" => Ok(Format::Webp),
+            _ => Err(format!(
+                "Invalid digest format: {}, expected one of gif, webp",
+                s
+            )),
+        }
+    }
+}
+
+impl Format {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Gif => "gif",
+            Format::Webp => "webp",
+        }
+    }
+}
+
+/// Options of the digests.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Options {
+    pub format: Format,
+    // Frames per second of the recording to be taken
+    pub rate: f64,
+    // Height of the frames in pixels, smaller frames are not enlarged
+    pub height: u32,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            format: Format::Gif,
+            rate: 1.0,
+            height: 480,
+        }
+    }
+}
+
+/// Digest of a topic, written as `digest.gif` or `digest.webp` in the topic directory.
+pub struct Digest {
+    path: PathBuf,
+    options: Options,
+    sampler: Sampler,
+    // Size of the frames, taken from the first one
+    size: Option<(u32, u32)>,
+    // GIF frames are encoded to the file as they come
+    gif: Option<GifEncoder<BufWriter<fs::File>>>,
+    // WebP frames are encoded in memory, and the file is written at the end
+    webp: Vec<Vec<u8>>,
+}
+
+impl Digest {
+    pub fn new(dir: &Path, options: Options) -> Self {
+        Digest {
+            path: dir.join(format!("digest.{}", options.format.extension())),
+            options,
+            sampler: Sampler::new(Sampling::Rate(options.rate)),
+            size: None,
+            gif: None,
+            webp: Vec::new(),
+        }
+    }
+
+    /// Should the frame stamped at `stamp` nanoseconds be added? Frames are decoded only if so.
+    pub fn wants(&mut self, stamp: u64) -> bool {
+        self.sampler.keep(stamp).is_some()
+    }
+
+    /// Add the frame, resized to the height of the digest.
+    pub fn push(&mut self, image: &DynamicImage) -> ImageResult<()> {
+        let (width, height) = *self.size.get_or_insert_with(|| {
+            let height = image.height().min(self.options.height).max(1);
+            let width =
+                (image.width() as u64 * height as u64 / image.height().max(1) as u64).max(1) as u32;
+            (width, height)
+        });
+        let image = match (image.width(), image.height()) == (width, height) {
+            true => image.clone(),
+            false => image.resize_exact(width, height, FilterType::Triangle),
+        };
+        match self.options.format {
+            Format::Gif => {
+                if self.gif.is_none() {
+                    let file = BufWriter::new(fs::File::create(&self.path)?);
+                    let mut encoder = GifEncoder::new_with_speed(file, 10);
+                    encoder.set_repeat(Repeat::Infinite)?;
+                    self.gif = Some(encoder);
+                }
+                self.gif
+                    .as_mut()
+                    .unwrap()
+                    .encode_frame(image::Frame::from_parts(
+                        image.to_rgba8(),
+                        0,
+                        0,
+                        Delay::from_numer_denom_ms(FRAME_DELAY_MS, 1),
+                    ))
+            }
+            Format::Webp => {
+                self.webp.push(webp_frame(&image)?);
+                Ok(())
+            }
+        }
+    }
+
+    /// Finish the file of the digest, if any frame was added.
+    pub fn finish(&mut self) -> ImageResult<()> {
+        if let Some(encoder) = self.gif.take() {
+            // The trailer is written on drop
+            drop(encoder);
+        }
+        if let (Some((width, height)), false) = (self.size, self.webp.is_empty()) {
+            let mut file = BufWriter::new(fs::File::create(&self.path)?);
+            file.write_all(&animated_webp(&self.webp, width, height))?;
+            file.flush()?;
+            self.webp.clear();
+        }
+        Ok(())
+    }
+}
+
+/// Image chunks of the frame encoded as a lossless WebP, without the RIFF header.
+fn webp_frame(image: &DynamicImage) -> ImageResult<Vec<u8>> {
+    let mut file = Vec::new();
+    WebPEncoder::new_lossless(&mut file).encode(
+        image.to_rgb8().as_raw(),
+        image.width(),
+        image.height(),
+        ExtendedColorType::Rgb8,
+    )?;
+    // Chunks following the header of 12 bytes, padded to even sizes
+    let mut chunks = Vec::new();
+    let mut pos = 12;
+    while pos + 8 <= file.len() {
+        let size = u32::from_le_bytes(file[pos + 4..pos + 8].try_into().unwrap()) as usize;
+        let end = (pos + 8 + size + size % 2).min(file.len());
+        if &file[pos..pos + 4] != b"VP8X" {
+            chunks.extend_from_slice(&file[pos..end]);
+        }
+        pos = end;
+    }
+    Ok(chunks)
+}
+
+/// Animated WebP file of the frames, each shown for the frame delay and looped forever.
+fn animated_webp(frames: &[Vec<u8>], width: u32, height: u32) -> Vec<u8> {
+    let u24 = |v: u32| {
+        let [a, b, c, _] = v.to_le_bytes();
+        [a, b, c]
+    };
+    let mut body = b"WEBP".to_vec();
+    body.extend_from_slice(b"VP8X");
+    body.extend_from_slice(&10u32.to_le_bytes());
+    body.extend_from_slice(&[WEBP_ANIMATION, 0, 0, 0]);
+    body.extend_from_slice(&u24(width - 1));
+    body.extend_from_slice(&u24(height - 1));
+    // Background color and loop count, 0 for looping forever
+    body.extend_from_slice(b"ANIM");
+    body.extend_from_slice(&6u32.to_le_bytes());
+    body.extend_from_slice(&[0; 6]);
+    for frame in frames {
+        body.extend_from_slice(b"ANMF");
+        body.extend_from_slice(&(16 + frame.len() as u32).to_le_bytes());
+        // Offsets, size, duration, and no blending with the previous frame
+        body.extend_from_slice(&[0; 6]);
+        body.extend_from_slice(&u24(width - 1));
+        body.extend_from_slice(&u24(height - 1));
+        body.extend_from_slice(&u24(FRAME_DELAY_MS));
+        body.push(0x02);
+        body.extend_from_slice(frame);
+    }
+    let mut file = b"RIFF".to_vec();
+    file.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    file.extend_from_slice(&body);
+    file
+}
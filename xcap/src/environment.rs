@@ -0,0 +1,141 @@
+use crate::extractor::Extractor;
+use mcap::Message;
+use rerun::RecordingStream;
+use ros2_sensor_msgs::msg::{FluidPressure, Temperature};
+use ros2_std_msgs::msg::Header;
+use std::{
+    fs,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::{atomic::AtomicBool, Arc},
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Payload error. {0}")]
+    Payload(#[from] crate::extractor::DecodeError),
+    #[error("CDR error. {0}")]
+    Cdr(#[from] cdr::Error),
+}
+
+/// Quantities measured by environmental sensors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Quantity {
+    Temperature,
+    FluidPressure,
+}
+
+impl Quantity {
+    /// Name of the quantity, used for the entity paths and the CSV files.
+    fn as_str(&self) -> &'static str {
+        match self {
+            Quantity::Temperature => "temperature",
+            Quantity::FluidPressure => "fluid_pressure",
+        }
+    }
+}
+
+/// Parser of environmental sensors reporting a single value, like temperatures and pressures. The
+/// value is plotted over time.
+pub struct Parser {
+    // Quantity of the readings
+    quantity: Quantity,
+
+    // Visualizer with rerun
+    rec_stream: Option<RecordingStream>,
+
+    // CSV file for all the readings, only available when dumping data
+    csv: Option<BufWriter<fs::File>>,
+
+    // Scratch buffer for decoded message payloads, reused across messages
+    buffer: Vec<u8>,
+}
+
+impl Parser {
+    pub fn new(
+        output_path: &Path,
+        rerun_stream: Option<RecordingStream>,
+        dump_data: bool,
+        quantity: Quantity,
+    ) -> Self {
+        // Create output file
+        let csv = if dump_data {
+            fs::create_dir_all(output_path).unwrap();
+            let mut file = BufWriter::new(
+                fs::File::create(output_path.join(format!("{}.csv", quantity.as_str()))).unwrap(),
+            );
+            writeln!(file, "sec,nanosec,{},variance", quantity.as_str()).unwrap();
+            Some(file)
+        } else {
+            None
+        };
+
+        Parser {
+            quantity,
+            rec_stream: rerun_stream,
+            csv,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Header, value and variance of the reading.
+    fn decode(&self) -> Result<(Header, f64, f64), cdr::Error> {
+        match self.quantity {
+            Quantity::Temperature => {
+                let msg = cdr::deserialize_from::<_, Temperature, _>(
+                    self.buffer.as_slice(),
+                    cdr::size::Infinite,
+                )?;
+                Ok((msg.header, msg.temperature, msg.variance))
+            }
+            Quantity::FluidPressure => {
+                let msg = cdr::deserialize_from::<_, FluidPressure, _>(
+                    self.buffer.as_slice(),
+                    cdr::size::Infinite,
+                )?;
+                Ok((msg.header, msg.fluid_pressure, msg.variance))
+            }
+        }
+    }
+}
+
+impl Extractor for Parser {
+    type ExtractorError = Box<dyn std::error::Error + Send + Sync>;
+
+    fn buffer(&mut self) -> &mut Vec<u8> {
+        &mut self.buffer
+    }
+
+    fn step(&mut self, message: &Message) -> Result<(), Self::ExtractorError> {
+        self.decode_into(message).map_err(Error::Payload)?;
+        let (header, value, variance) = self.decode().map_err(Error::Cdr)?;
+
+        if let Some(rec) = &self.rec_stream {
+            rec.set_time_seconds(
+                "main",
+                header.stamp.sec as f64 + header.stamp.nanosec as f64 * 1e-9,
+            );
+            rec.log(
+                format!("{}/{}", self.quantity.as_str(), message.channel.topic),
+                &rerun::Scalar::new(value),
+            )?;
+        }
+
+        // Dump data?
+        if let Some(csv) = self.csv.as_mut() {
+            writeln!(
+                csv,
+                "{},{},{},{}",
+                header.stamp.sec, header.stamp.nanosec, value, variance
+            )?;
+        }
+        Ok(())
+    }
+
+    fn post_process(&mut self, _sigint: Arc<AtomicBool>) -> Result<(), Self::ExtractorError> {
+        if let Some(csv) = self.csv.as_mut() {
+            csv.flush()?;
+        }
+        Ok(())
+    }
+}
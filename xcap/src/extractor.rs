@@ -1,6 +1,33 @@
+use crate::ros2msg;
 use mcap::Message;
+use std::cell::RefCell;
+use std::io;
+use std::path::Path;
 use std::sync::{atomic::AtomicBool, Arc};
 
+const ZSTD_MAGIC_NUMBER: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+const LZ4_MAGIC_NUMBER: [u8; 4] = [0x04, 0x22, 0x4d, 0x18];
+
+thread_local! {
+    // Decompressed XCDR2 payloads, kept across messages while they are transcoded
+    static SCRATCH: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Failure to decode the payload of a message into plain CDR.
+#[derive(thiserror::Error, Debug)]
+pub enum DecodeError {
+    #[error("ZSTD error. {0}")]
+    Zstd(io::Error),
+    #[error("LZ4 error. {0}")]
+    Lz4(io::Error),
+    #[error("No ros2msg schema to decode XCDR2 topic {0}")]
+    Schema(String),
+    #[error("XCDR2 error. {0}")]
+    Xcdr2(#[from] ros2msg::Error),
+    #[error("JSON error. {0}")]
+    Json(#[from] serde_json::Error),
+}
+
 pub trait Extractor {
     type ExtractorError;
 
@@ -9,4 +36,63 @@ pub trait Extractor {
 
     /// Function to be called after all messages have been processed.
     fn post_process(&mut self, sigint: Arc<AtomicBool>) -> Result<(), Self::ExtractorError>;
+
+    /// Function to be called before the messages of each input file.
+    fn set_source(&mut self, _source: &Path) {}
+
+    /// Scratch buffer of the parser for decoded message payloads, reused across messages.
+    fn buffer(&mut self) -> &mut Vec<u8>;
+
+    /// Decode the message payload into the scratch buffer of the parser, decompressing zstd or
+    /// LZ4 frames if necessary, and return it. The buffer is cleared first and keeps its
+    /// capacity, so no new buffer is allocated for every message.
+    fn decode_into(&mut self, message: &Message) -> Result<&[u8], DecodeError> {
+        let buf = self.buffer();
+        decode_payload(message, buf)?;
+        Ok(buf)
+    }
+
+    /// Decode the JSON encoded message payload, decompressing it first if necessary.
+    fn decode_json(&mut self, message: &Message) -> Result<serde_json::Value, DecodeError> {
+        Ok(serde_json::from_slice(self.decode_into(message)?)?)
+    }
+}
+
+/// Decode the message payload into `buf`, see `Extractor::decode_into`. XCDR2 payloads are
+/// transcoded into XCDR1 with the schema of the channel, as the CDR deserializer of the message
+/// structs only knows XCDR1.
+pub(crate) fn decode_payload(message: &Message, buf: &mut Vec<u8>) -> Result<(), DecodeError> {
+    buf.clear();
+    let data = message.data.as_ref();
+    let compressed = if data.starts_with(&ZSTD_MAGIC_NUMBER) {
+        zstd::stream::copy_decode(data, &mut *buf).map_err(DecodeError::Zstd)?;
+        true
+    } else if data.starts_with(&LZ4_MAGIC_NUMBER) {
+        io::copy(&mut lz4_flex::frame::FrameDecoder::new(data), buf).map_err(DecodeError::Lz4)?;
+        true
+    } else {
+        false
+    };
+    let payload = if compressed { buf.as_slice() } else { data };
+    if message.channel.message_encoding != "cdr" || !ros2msg::is_xcdr2(payload) {
+        if !compressed {
+            buf.extend_from_slice(data);
+        }
+        return Ok(());
+    }
+
+    let schema = message
+        .channel
+        .schema
+        .as_ref()
+        .filter(|s| s.encoding == "ros2msg")
+        .ok_or(DecodeError::Schema(message.channel.topic.clone()))?;
+    if !compressed {
+        return Ok(ros2msg::to_xcdr1(schema, data, buf)?);
+    }
+    // The decompressed payload is moved aside, and transcoded back into the buffer
+    SCRATCH.with_borrow_mut(|scratch| {
+        std::mem::swap(scratch, buf);
+        Ok(ros2msg::to_xcdr1(schema, scratch, buf)?)
+    })
 }
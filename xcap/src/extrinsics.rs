@@ -0,0 +1,67 @@
+//! Static extrinsic transforms of sensors, loaded from a calibration file. Used to fuse sensors of
+//! a rig into one frame in recordings without TF.
+use rerun::external::glam;
+use serde::Deserialize;
+use std::{collections::HashMap, fs, path::Path};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("IO error. {0}")]
+    Io(#[from] std::io::Error),
+    #[error("JSON error. {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Invalid transform of {0}, the last row should be 0 0 0 1")]
+    NotRigid(String),
+}
+
+/// Transform of a sensor in the rig frame, as a 4x4 row major matrix or a translation and a
+/// quaternion in x, y, z, w order.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Entry {
+    Matrix {
+        matrix: [[f32; 4]; 4],
+    },
+    Bare([[f32; 4]; 4]),
+    Pose {
+        translation: [f32; 3],
+        rotation: [f32; 4],
+    },
+}
+
+/// Transforms of the topics in the rig frame.
+#[derive(Debug, Default, Clone)]
+pub struct Extrinsics(HashMap<String, glam::Affine3A>);
+
+impl Extrinsics {
+    /// Load the JSON file mapping topic names to transforms. Example:
+    /// `{"/lidar_left": {"translation": [0, 0.5, 1.8], "rotation": [0, 0, 0, 1]}}`
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let entries: HashMap<String, Entry> = serde_json::from_slice(&fs::read(path)?)?;
+        let mut transforms = HashMap::new();
+        for (topic, entry) in entries {
+            let transform = match entry {
+                Entry::Matrix { matrix: m } | Entry::Bare(m) => {
+                    if m[3] != [0.0, 0.0, 0.0, 1.0] {
+                        return Err(Error::NotRigid(topic));
+                    }
+                    glam::Affine3A::from_mat4(glam::Mat4::from_cols_array_2d(&m).transpose())
+                }
+                Entry::Pose {
+                    translation: t,
+                    rotation: [x, y, z, w],
+                } => glam::Affine3A::from_rotation_translation(
+                    glam::Quat::from_xyzw(x, y, z, w).normalize(),
+                    glam::Vec3::from(t),
+                ),
+            };
+            transforms.insert(topic, transform);
+        }
+        Ok(Extrinsics(transforms))
+    }
+
+    /// Transform of the topic, if calibrated.
+    pub fn get(&self, topic: &str) -> Option<glam::Affine3A> {
+        self.0.get(topic).copied()
+    }
+}
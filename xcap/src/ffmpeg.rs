@@ -0,0 +1,193 @@
+//! Video decoder running the `ffmpeg` command. The stream is piped in and frames come out as PPM
+//! images, which carry their own dimensions.
+use crate::decoder::{self, Codec, Frame, PtsQueue, VideoDecoder};
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::mpsc::{self, Receiver},
+    thread::JoinHandle,
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Failed to run ffmpeg, is it installed? {0}")]
+    Spawn(io::Error),
+    #[error("IO error. {0}")]
+    Io(#[from] io::Error),
+    #[error("Invalid PPM frame from ffmpeg. {0}")]
+    Ppm(String),
+    #[error("ffmpeg exited with {0}")]
+    Exit(std::process::ExitStatus),
+}
+
+// Decoded image: width, height and RGB24 data
+type Image = (u32, u32, Vec<u8>);
+
+/// Decoder piping the stream through an `ffmpeg` process.
+pub struct Decoder {
+    child: Child,
+    stdin: Option<ChildStdin>,
+
+    // Frames read from ffmpeg by the reader thread
+    frames: Receiver<Result<Image, Error>>,
+    reader: Option<JoinHandle<()>>,
+
+    codec: Codec,
+    pending: PtsQueue,
+    last_pts: i64,
+}
+
+impl Decoder {
+    pub fn new(codec: Codec) -> Result<Self, Error> {
+        let format = match codec {
+            Codec::H264 => "h264",
+            Codec::Hevc => "hevc",
+            // Low overhead bitstream format, as AV1 encoders publish
+            Codec::Av1 => "obu",
+        };
+        let mut child = Command::new("ffmpeg")
+            .args(["-hide_banner", "-loglevel", "error", "-f", format])
+            .args([
+                "-i",
+                "pipe:0",
+                "-f",
+                "image2pipe",
+                "-vcodec",
+                "ppm",
+                "pipe:1",
+            ])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(Error::Spawn)?;
+        let stdin = child.stdin.take();
+        let mut stdout = BufReader::new(child.stdout.take().unwrap());
+
+        // Read frames in the background, so that writing the stream never blocks on a full pipe.
+        let (sender, frames) = mpsc::channel();
+        let reader = std::thread::spawn(move || loop {
+            match read_ppm(&mut stdout) {
+                Ok(Some(image)) => {
+                    if sender.send(Ok(image)).is_err() {
+                        break;
+                    }
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    let _ = sender.send(Err(e));
+                    break;
+                }
+            }
+        });
+
+        Ok(Decoder {
+            child,
+            stdin,
+            frames,
+            reader: Some(reader),
+            codec,
+            pending: PtsQueue::default(),
+            last_pts: 0,
+        })
+    }
+
+    fn next_frame(&mut self, (width, height, data): Image) -> Frame {
+        Frame {
+            width,
+            height,
+            pts: self.pending.pop_or(self.last_pts),
+            data,
+        }
+    }
+}
+
+impl VideoDecoder for Decoder {
+    fn decode(&mut self, data: &[u8], pts: i64) -> Result<Vec<Frame>, decoder::Error> {
+        if decoder::has_picture(self.codec, data) {
+            self.pending.push(pts);
+        }
+        self.last_pts = pts;
+        if let Some(stdin) = self.stdin.as_mut() {
+            stdin.write_all(data).map_err(Error::Io)?;
+        }
+        let images: Vec<_> = self.frames.try_iter().collect();
+        let mut frames = Vec::with_capacity(images.len());
+        for image in images {
+            frames.push(self.next_frame(image?));
+        }
+        Ok(frames)
+    }
+
+    fn flush(&mut self) -> Result<Vec<Frame>, decoder::Error> {
+        // Closing the input lets ffmpeg drain and exit
+        self.stdin = None;
+        if let Some(reader) = self.reader.take() {
+            let _ = reader.join();
+        }
+        let images: Vec<_> = self.frames.try_iter().collect();
+        let mut frames = Vec::with_capacity(images.len());
+        for image in images {
+            frames.push(self.next_frame(image?));
+        }
+        let status = self.child.wait().map_err(Error::Io)?;
+        if !status.success() {
+            return Err(Error::Exit(status).into());
+        }
+        Ok(frames)
+    }
+}
+
+impl Drop for Decoder {
+    fn drop(&mut self) {
+        if self.reader.is_some() {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
+
+/// Read a binary PPM image, None at the end of the stream.
+fn read_ppm(reader: &mut impl BufRead) -> Result<Option<Image>, Error> {
+    if reader.fill_buf()?.is_empty() {
+        return Ok(None);
+    }
+    let mut header = Vec::with_capacity(4);
+    while header.len() < 4 {
+        let token = read_token(reader)?;
+        if token.is_empty() {
+            return Err(Error::Ppm("truncated header".to_owned()));
+        }
+        header.push(token);
+    }
+    if header[0] != "P6" {
+        return Err(Error::Ppm(format!("unexpected magic {}", header[0])));
+    }
+    let parse = |s: &str| {
+        s.parse::<u32>()
+            .map_err(|_| Error::Ppm(format!("invalid number {}", s)))
+    };
+    let (width, height, max) = (parse(&header[1])?, parse(&header[2])?, parse(&header[3])?);
+    if max > 255 {
+        return Err(Error::Ppm(format!("unsupported max value {}", max)));
+    }
+    let mut data = vec![0; width as usize * height as usize * 3];
+    reader.read_exact(&mut data)?;
+    Ok(Some((width, height, data)))
+}
+
+/// Read a header token and the single whitespace after it.
+fn read_token(reader: &mut impl BufRead) -> Result<String, Error> {
+    let mut token = String::new();
+    let mut byte = [0u8];
+    loop {
+        if reader.read(&mut byte)? == 0 {
+            return Ok(token);
+        }
+        match byte[0] {
+            b if b.is_ascii_whitespace() && token.is_empty() => continue,
+            b if b.is_ascii_whitespace() => return Ok(token),
+            b => token.push(b as char),
+        }
+    }
+}
@@ -0,0 +1,179 @@
+//! Index of the extracted frames of each topic, so that frames could be aligned without reading
+//! the recording again.
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+/// A saved frame.
+#[derive(Debug)]
+pub struct Entry {
+    pub file: PathBuf,
+    // Stamp of the frame in nanoseconds
+    pub stamp: u64,
+    pub publish_time: u64,
+    pub seq: u64,
+    // Recording the frame is extracted from
+    pub source: String,
+}
+
+/// Saved frames by topic, shared by the parsers of all files.
+#[derive(Debug, Default)]
+pub struct FrameIndex {
+    // Output directory, files are listed relative to it
+    root: PathBuf,
+
+    // Topic to its output directory and frames
+    topics: BTreeMap<String, (PathBuf, Vec<Entry>)>,
+
+    // Rename the frames as `000001.jpeg`, `000002.jpeg` and so on in message order
+    seq_names: bool,
+}
+
+impl FrameIndex {
+    pub fn shared(root: &Path, seq_names: bool) -> Arc<Mutex<Self>> {
+        Arc::new(Mutex::new(FrameIndex {
+            root: root.into(),
+            topics: BTreeMap::new(),
+            seq_names,
+        }))
+    }
+
+    /// Add a frame of the topic, whose index is written into `dir`.
+    pub fn add(&mut self, topic: &str, dir: &Path, entry: Entry) {
+        self.topics
+            .entry(topic.to_owned())
+            .or_insert_with(|| (dir.into(), Vec::new()))
+            .1
+            .push(entry);
+    }
+
+    /// Frames of the topic saved so far.
+    pub fn frames(&self, topic: &str) -> Option<&[Entry]> {
+        self.topics
+            .get(topic)
+            .map(|(_, entries)| entries.as_slice())
+    }
+
+    /// Write `index.csv` of every topic, with frames sorted by stamp. Frames are numbered in
+    /// message order first, as the parsers of concurrently processed files count their own
+    /// frames. With sequential names, frames are renamed in the same order, so the index maps the
+    /// names to the stamps.
+    pub fn write(&mut self) -> io::Result<()> {
+        for (dir, entries) in self.topics.values_mut() {
+            entries.sort_by_key(|e| (e.publish_time, e.stamp));
+            for (i, entry) in entries.iter_mut().enumerate() {
+                entry.seq = i as u64;
+                if self.seq_names {
+                    let mut name = format!("{:06}", i + 1);
+                    if let Some(extension) = entry.file.extension() {
+                        name = format!("{}.{}", name, extension.to_string_lossy());
+                    }
+                    let file = entry.file.with_file_name(name);
+                    fs::rename(&entry.file, &file)?;
+                    entry.file = file;
+                }
+            }
+            if !self.seq_names {
+                entries.sort_by_key(|e| (e.stamp, e.publish_time));
+            }
+            fs::create_dir_all(&*dir)?;
+            let mut file = BufWriter::new(fs::File::create(dir.join("index.csv"))?);
+            writeln!(file, "file,stamp,publish_time,seq,source")?;
+            for entry in entries.iter() {
+                let path = entry.file.strip_prefix(&self.root).unwrap_or(&entry.file);
+                writeln!(
+                    file,
+                    "{},{},{},{},{}",
+                    csv_field(&path.to_string_lossy()),
+                    entry.stamp,
+                    entry.publish_time,
+                    entry.seq,
+                    csv_field(&entry.source)
+                )?;
+            }
+            file.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// Quote the field if it has commas, quotes or line breaks.
+pub(crate) fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+
+    /// Index of frames saved out of order, like by concurrent jobs, as `(stamp, publish time)`.
+    fn index(root: &Path, seq_names: bool) -> Arc<Mutex<FrameIndex>> {
+        let index = FrameIndex::shared(root, seq_names);
+        let dir = root.join("camera");
+        fs::create_dir_all(&dir).unwrap();
+        for (stamp, publish_time) in [(30, 300), (10, 100), (25, 200)] {
+            let file = dir.join(format!("{}.jpeg", publish_time));
+            fs::write(&file, stamp.to_string()).unwrap();
+            index.lock().unwrap().add(
+                "/camera",
+                &dir,
+                Entry {
+                    file,
+                    stamp,
+                    publish_time,
+                    seq: 0,
+                    source: "a,b.mcap".to_owned(),
+                },
+            );
+        }
+        index
+    }
+
+    #[test]
+    fn write_sorted_by_stamp() {
+        let root = test_util::dir("frame_index");
+        index(&root, false).lock().unwrap().write().unwrap();
+        let csv = fs::read_to_string(root.join("camera/index.csv")).unwrap();
+        assert_eq!(
+            csv,
+            "file,stamp,publish_time,seq,source\n\
+            camera/100.jpeg,10,100,0,\"a,b.mcap\"\n\
+            camera/200.jpeg,25,200,1,\"a,b.mcap\"\n\
+            camera/300.jpeg,30,300,2,\"a,b.mcap\"\n"
+        );
+    }
+
+    #[test]
+    fn write_with_seq_names() {
+        let root = test_util::dir("frame_index_seq");
+        let index = index(&root, true);
+        index.lock().unwrap().write().unwrap();
+        let csv = fs::read_to_string(root.join("camera/index.csv")).unwrap();
+        assert_eq!(
+            csv,
+            "file,stamp,publish_time,seq,source\n\
+            camera/000001.jpeg,10,100,0,\"a,b.mcap\"\n\
+            camera/000002.jpeg,25,200,1,\"a,b.mcap\"\n\
+            camera/000003.jpeg,30,300,2,\"a,b.mcap\"\n"
+        );
+
+        // Files are renamed in message order, and the index follows them
+        for (name, stamp) in [("000001", "10"), ("000002", "25"), ("000003", "30")] {
+            let file = root.join("camera").join(format!("{}.jpeg", name));
+            assert_eq!(fs::read_to_string(file).unwrap(), stamp);
+        }
+        assert!(!root.join("camera/100.jpeg").exists());
+        let index = index.lock().unwrap();
+        let frames = index.frames("/camera").unwrap();
+        assert_eq!(frames[2].file, root.join("camera/000003.jpeg"));
+    }
+}
@@ -0,0 +1,249 @@
+use crate::extractor::Extractor;
+use crate::ros2msg;
+use mcap::Message;
+use rerun::RecordingStream;
+use std::{
+    fs,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{atomic::AtomicBool, Arc},
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Payload error. {0}")]
+    Payload(#[from] crate::extractor::DecodeError),
+    #[error("Message decoding error. {0}")]
+    Decode(#[from] ros2msg::Error),
+    #[error("Schema of topic {0} is not available in ros2msg encoding")]
+    Schema(String),
+    #[error("JSON error. {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("IO error. {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Fallback parser for topics without a dedicated parser. Messages are decoded with the schema in
+/// the MCAP file, or as JSON for JSON encoded channels. Primitive fields are flattened into CSV
+/// rows, whole messages are dumped as JSON Lines, and numeric fields are plotted.
+pub struct Parser {
+    // Output directory
+    output_dir: PathBuf,
+
+    // Visualizer with rerun
+    rec_stream: Option<RecordingStream>,
+
+    // Should dump data to disk
+    dump_data: bool,
+
+    // Scratch buffer for decoded message payloads, reused across messages
+    buffer: Vec<u8>,
+
+    // Schema of the topic, parsed from the first message
+    schema: Option<ros2msg::Schema>,
+
+    // CSV file for all the messages, created with the first message
+    csv: Option<BufWriter<fs::File>>,
+
+    // JSON Lines file for all the messages, created with the first message
+    json: Option<BufWriter<fs::File>>,
+}
+
+impl Parser {
+    pub fn new(output_path: &Path, rerun_stream: Option<RecordingStream>, dump_data: bool) -> Self {
+        // Create output dir
+        if dump_data {
+            fs::create_dir_all(output_path).unwrap();
+        }
+
+        Parser {
+            output_dir: output_path.into(),
+            rec_stream: rerun_stream,
+            dump_data,
+            buffer: Vec::new(),
+            schema: None,
+            csv: None,
+            json: None,
+        }
+    }
+
+    fn dump(&mut self, message: &Message, value: &ros2msg::Value) -> Result<(), Error> {
+        let cells = value.flatten();
+
+        // Columns are the same for all messages, take them from the first one
+        if self.csv.is_none() {
+            let mut file = BufWriter::new(fs::File::create(self.output_dir.join("messages.csv"))?);
+            let header: Vec<String> = cells.iter().map(|(name, _)| csv_cell(name)).collect();
+            writeln!(file, "publish_time,{}", header.join(","))?;
+            self.csv = Some(file);
+            self.json = Some(BufWriter::new(fs::File::create(
+                self.output_dir.join("messages.jsonl"),
+            )?));
+        }
+        let row: Vec<String> = cells.iter().map(|(_, cell)| csv_cell(cell)).collect();
+        writeln!(
+            self.csv.as_mut().unwrap(),
+            "{},{}",
+            message.publish_time,
+            row.join(",")
+        )?;
+
+        let json = self.json.as_mut().unwrap();
+        serde_json::to_writer(
+            &mut *json,
+            &serde_json::json!({
+                "publish_time": message.publish_time,
+                "message": value.to_json(),
+            }),
+        )?;
+        writeln!(json)?;
+        Ok(())
+    }
+}
+
+impl Extractor for Parser {
+    type ExtractorError = Box<dyn std::error::Error + Send + Sync>;
+
+    fn buffer(&mut self) -> &mut Vec<u8> {
+        &mut self.buffer
+    }
+
+    fn step(&mut self, message: &Message) -> Result<(), Self::ExtractorError> {
+        if !self.dump_data && self.rec_stream.is_none() {
+            return Ok(());
+        }
+
+        let value = if message.channel.message_encoding == "json" {
+            ros2msg::Value::from_json(&self.decode_json(message)?)
+        } else {
+            // Parse the schema once
+            if self.schema.is_none() {
+                let schema = message
+                    .channel
+                    .schema
+                    .as_ref()
+                    .filter(|s| s.encoding == "ros2msg")
+                    .ok_or(Error::Schema(message.channel.topic.clone()))?;
+                let text = String::from_utf8_lossy(&schema.data);
+                self.schema =
+                    Some(ros2msg::Schema::parse(&schema.name, &text).map_err(Error::Decode)?);
+            }
+
+            self.decode_into(message).map_err(Error::Payload)?;
+            self.schema
+                .as_ref()
+                .unwrap()
+                .decode(&self.buffer)
+                .map_err(Error::Decode)?
+        };
+
+        // Plot all the numeric fields, on the header stamp if there is one
+        if let Some(rec) = &self.rec_stream {
+            let stamp = match (
+                value.get("header.stamp.sec").and_then(|v| v.as_f64()),
+                value.get("header.stamp.nanosec").and_then(|v| v.as_f64()),
+            ) {
+                (Some(sec), Some(nanosec)) => sec + nanosec * 1e-9,
+                _ => message.publish_time as f64 * 1e-9,
+            };
+            rec.set_time_seconds("main", stamp);
+            for (column, _) in value.flatten() {
+                let Some(number) = value.get(&column).and_then(|v| v.as_f64()) else {
+                    continue;
+                };
+                rec.log(
+                    format!("{}/{}", message.channel.topic, column.replace('.', "/")),
+                    &rerun::Scalar::new(number),
+                )?;
+            }
+        }
+
+        if self.dump_data {
+            self.dump(message, &value)?;
+        }
+        Ok(())
+    }
+
+    fn post_process(&mut self, _sigint: Arc<AtomicBool>) -> Result<(), Self::ExtractorError> {
+        if let Some(csv) = self.csv.as_mut() {
+            csv.flush()?;
+        }
+        if let Some(json) = self.json.as_mut() {
+            json.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// Quote the cell if it contains separators, quotes or line breaks.
+fn csv_cell(cell: &str) -> String {
+    if cell.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+    use std::{borrow::Cow, collections::BTreeMap};
+
+    #[test]
+    fn dump_csv_and_json_lines() {
+        let dir = test_util::dir("generic");
+        let channel = Arc::new(mcap::Channel {
+            topic: "/drive".to_owned(),
+            schema: Some(Arc::new(mcap::Schema {
+                name: "test_msgs/msg/Drive".to_owned(),
+                encoding: "ros2msg".to_owned(),
+                data: Cow::Borrowed(
+                    b"uint8 IDLE=0\nuint8 mode\nstring<=8 label\nfloat32[<=4] gains\n",
+                ),
+            })),
+            message_encoding: "cdr".to_owned(),
+            metadata: BTreeMap::new(),
+        });
+        let mut parser = Parser::new(&dir, None, true);
+        for (publish_time, fields) in [
+            (10, (1u8, "wheel", vec![0.5f32, -2.0])),
+            (20, (0, "a,b", vec![])),
+        ] {
+            let data = cdr::serialize::<_, _, cdr::CdrLe>(&fields, cdr::Infinite).unwrap();
+            let message = Message {
+                channel: channel.clone(),
+                sequence: 0,
+                log_time: publish_time,
+                publish_time,
+                data: Cow::Owned(data),
+            };
+            parser.step(&message).unwrap();
+        }
+        drop(parser);
+
+        let csv = fs::read_to_string(dir.join("messages.csv")).unwrap();
+        assert_eq!(
+            csv,
+            "publish_time,mode,label,gains\n10,1,wheel,0.5 -2\n20,0,\"a,b\",\n"
+        );
+        let lines: Vec<serde_json::Value> = fs::read_to_string(dir.join("messages.jsonl"))
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                serde_json::json!({
+                    "publish_time": 10,
+                    "message": {"mode": 1, "label": "wheel", "gains": [0.5, -2.0]},
+                }),
+                serde_json::json!({
+                    "publish_time": 20,
+                    "message": {"mode": 0, "label": "a,b", "gains": []},
+                }),
+            ]
+        );
+    }
+}
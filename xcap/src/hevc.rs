@@ -0,0 +1,236 @@
+use crate::decoder::{self, Frame, VideoDecoder};
+use libloading::Library;
+use std::ffi::{c_char, c_int, c_void, CStr};
+
+// Candidates of the libde265 shared library, loaded at runtime so that it is only required when
+// HEVC topics are actually processed.
+const LIBRARY_NAMES: [&str; 2] = ["libde265.so.0", "libde265.so"];
+
+// libde265 error code when more input data is needed.
+const DE265_ERROR_WAITING_FOR_INPUT_DATA: c_int = 13;
+
+type NewDecoder = unsafe extern "C" fn() -> *mut c_void;
+type FreeDecoder = unsafe extern "C" fn(*mut c_void) -> c_int;
+type PushData = unsafe extern "C" fn(*mut c_void, *const c_void, c_int, i64, *mut c_void) -> c_int;
+type FlushData = unsafe extern "C" fn(*mut c_void) -> c_int;
+type Decode = unsafe extern "C" fn(*mut c_void, *mut c_int) -> c_int;
+type GetNextPicture = unsafe extern "C" fn(*mut c_void) -> *const c_void;
+type GetImageSize = unsafe extern "C" fn(*const c_void, c_int) -> c_int;
+type GetImagePlane = unsafe extern "C" fn(*const c_void, c_int, *mut c_int) -> *const u8;
+type GetChromaFormat = unsafe extern "C" fn(*const c_void) -> c_int;
+type GetImagePts = unsafe extern "C" fn(*const c_void) -> i64;
+type GetErrorText = unsafe extern "C" fn(c_int) -> *const c_char;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Failed to load libde265. {0}")]
+    Library(#[from] libloading::Error),
+    #[error("Failed to create decoder.")]
+    Init,
+    #[error("Decoding failed. {0}")]
+    Decode(String),
+}
+
+/// HEVC decoder backed by libde265.
+pub struct Decoder {
+    ctx: *mut c_void,
+    free_decoder: FreeDecoder,
+    push_data: PushData,
+    flush_data: FlushData,
+    decode: Decode,
+    get_next_picture: GetNextPicture,
+    get_image_width: GetImageSize,
+    get_image_height: GetImageSize,
+    get_bits_per_pixel: GetImageSize,
+    get_image_plane: GetImagePlane,
+    get_chroma_format: GetChromaFormat,
+    get_image_pts: GetImagePts,
+    get_error_text: GetErrorText,
+
+    // Keep the library loaded as long as the function pointers above are in use.
+    _lib: Library,
+}
+
+impl Decoder {
+    pub fn new() -> Result<Self, Error> {
+        let lib = load_library()?;
+        unsafe {
+            let new_decoder = *lib.get::<NewDecoder>(b"de265_new_decoder\0")?;
+            let free_decoder = *lib.get::<FreeDecoder>(b"de265_free_decoder\0")?;
+            let push_data = *lib.get::<PushData>(b"de265_push_data\0")?;
+            let flush_data = *lib.get::<FlushData>(b"de265_flush_data\0")?;
+            let decode = *lib.get::<Decode>(b"de265_decode\0")?;
+            let get_next_picture = *lib.get::<GetNextPicture>(b"de265_get_next_picture\0")?;
+            let get_image_width = *lib.get::<GetImageSize>(b"de265_get_image_width\0")?;
+            let get_image_height = *lib.get::<GetImageSize>(b"de265_get_image_height\0")?;
+            let get_bits_per_pixel = *lib.get::<GetImageSize>(b"de265_get_bits_per_pixel\0")?;
+            let get_image_plane = *lib.get::<GetImagePlane>(b"de265_get_image_plane\0")?;
+            let get_chroma_format = *lib.get::<GetChromaFormat>(b"de265_get_chroma_format\0")?;
+            let get_image_pts = *lib.get::<GetImagePts>(b"de265_get_image_PTS\0")?;
+            let get_error_text = *lib.get::<GetErrorText>(b"de265_get_error_text\0")?;
+
+            let ctx = new_decoder();
+            if ctx.is_null() {
+                return Err(Error::Init);
+            }
+            Ok(Decoder {
+                ctx,
+                free_decoder,
+                push_data,
+                flush_data,
+                decode,
+                get_next_picture,
+                get_image_width,
+                get_image_height,
+                get_bits_per_pixel,
+                get_image_plane,
+                get_chroma_format,
+                get_image_pts,
+                get_error_text,
+                _lib: lib,
+            })
+        }
+    }
+
+    fn drain(&mut self) -> Result<Vec<Frame>, Error> {
+        let mut frames = Vec::new();
+        loop {
+            let mut more: c_int = 0;
+            let err = unsafe { (self.decode)(self.ctx, &mut more) };
+            if err != DE265_ERROR_WAITING_FOR_INPUT_DATA {
+                self.check(err)?;
+            }
+            loop {
+                let image = unsafe { (self.get_next_picture)(self.ctx) };
+                if image.is_null() {
+                    break;
+                }
+                frames.push(self.to_frame(image));
+            }
+            if err == DE265_ERROR_WAITING_FOR_INPUT_DATA || more == 0 {
+                break;
+            }
+        }
+        Ok(frames)
+    }
+
+    fn check(&self, err: c_int) -> Result<(), Error> {
+        if err == 0 {
+            return Ok(());
+        }
+        let text = unsafe { CStr::from_ptr((self.get_error_text)(err)) };
+        Err(Error::Decode(text.to_string_lossy().into_owned()))
+    }
+
+    /// Convert a decoded YUV picture to RGB.
+    fn to_frame(&self, image: *const c_void) -> Frame {
+        unsafe {
+            let width = (self.get_image_width)(image, 0) as usize;
+            let height = (self.get_image_height)(image, 0) as usize;
+            let monochrome = (self.get_chroma_format)(image) == 0;
+            let mut planes = Vec::with_capacity(3);
+            for channel in 0..if monochrome { 1 } else { 3 } {
+                let mut stride: c_int = 0;
+                let ptr = (self.get_image_plane)(image, channel, &mut stride);
+                let plane_height = (self.get_image_height)(image, channel) as usize;
+                planes.push(Plane {
+                    data: std::slice::from_raw_parts(ptr, stride as usize * plane_height),
+                    stride: stride as usize,
+                    width: (self.get_image_width)(image, channel) as usize,
+                    height: plane_height,
+                    bits: (self.get_bits_per_pixel)(image, channel) as u32,
+                });
+            }
+
+            let mut data = Vec::with_capacity(width * height * 3);
+            for y in 0..height {
+                for x in 0..width {
+                    let luma = planes[0].sample(x, y, width, height);
+                    let (u, v) = if monochrome {
+                        (128.0, 128.0)
+                    } else {
+                        (
+                            planes[1].sample(x, y, width, height),
+                            planes[2].sample(x, y, width, height),
+                        )
+                    };
+                    let r = luma + 1.402 * (v - 128.0);
+                    let g = luma - 0.344136 * (u - 128.0) - 0.714136 * (v - 128.0);
+                    let b = luma + 1.772 * (u - 128.0);
+                    data.extend([r, g, b].map(|c| c.clamp(0.0, 255.0) as u8));
+                }
+            }
+
+            Frame {
+                width: width as u32,
+                height: height as u32,
+                pts: (self.get_image_pts)(image),
+                data,
+            }
+        }
+    }
+}
+
+impl VideoDecoder for Decoder {
+    fn decode(&mut self, data: &[u8], pts: i64) -> Result<Vec<Frame>, decoder::Error> {
+        let err = unsafe {
+            (self.push_data)(
+                self.ctx,
+                data.as_ptr() as *const c_void,
+                data.len() as c_int,
+                pts,
+                std::ptr::null_mut(),
+            )
+        };
+        self.check(err)?;
+        Ok(self.drain()?)
+    }
+
+    fn flush(&mut self) -> Result<Vec<Frame>, decoder::Error> {
+        let err = unsafe { (self.flush_data)(self.ctx) };
+        self.check(err)?;
+        Ok(self.drain()?)
+    }
+}
+
+impl Drop for Decoder {
+    fn drop(&mut self) {
+        unsafe {
+            (self.free_decoder)(self.ctx);
+        }
+    }
+}
+
+struct Plane<'a> {
+    data: &'a [u8],
+    stride: usize,
+    width: usize,
+    height: usize,
+    bits: u32,
+}
+
+impl Plane<'_> {
+    /// Sample the plane at luma coordinates, scaled to 8 bits.
+    fn sample(&self, x: usize, y: usize, luma_width: usize, luma_height: usize) -> f32 {
+        let px = x * self.width / luma_width;
+        let py = y * self.height / luma_height;
+        if self.bits <= 8 {
+            self.data[py * self.stride + px] as f32
+        } else {
+            let offset = py * self.stride + px * 2;
+            let value = u16::from_le_bytes([self.data[offset], self.data[offset + 1]]);
+            (value >> (self.bits - 8)) as f32
+        }
+    }
+}
+
+fn load_library() -> Result<Library, libloading::Error> {
+    let mut last_error = None;
+    for name in LIBRARY_NAMES {
+        match unsafe { Library::new(name) } {
+            Ok(lib) => return Ok(lib),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(last_error.unwrap())
+}
@@ -1,22 +1,41 @@
+use crate::bayer;
+use crate::depth;
+use crate::digest::Digest;
 use crate::extractor::Extractor;
+use crate::frame_index::{Entry, FrameIndex};
+use crate::image_file::{self, Frame, Sampler};
+use crate::pcd;
+use crate::tf::FrameTree;
+use crate::yuv;
+use log::warn;
 use mcap::Message;
-use rerun::RecordingStream;
+use rerun::{external::glam, RecordingStream};
 use ros2_sensor_msgs::msg::Image;
 use std::{
     fs,
     io::Write,
     path::{Path, PathBuf},
-    sync::{atomic::AtomicBool, Arc},
+    sync::{atomic::AtomicBool, Arc, Mutex},
 };
 
-const ZSTD_MAGIC_NUMBER: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
-
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
-    #[error("ZSTD error. {0}")]
-    Zstd(#[from] std::io::Error),
+    #[error("Payload error. {0}")]
+    Payload(#[from] crate::extractor::DecodeError),
     #[error("CDR error. {0}")]
-    CDR(#[from] cdr::Error),
+    Cdr(#[from] cdr::Error),
+    #[error("Depth error. {0}")]
+    Depth(#[from] depth::Error),
+    #[error("Bayer error. {0}")]
+    Bayer(#[from] bayer::Error),
+    #[error("YUV error. {0}")]
+    Yuv(#[from] yuv::Error),
+    #[error("Init image from buf failed.")]
+    ImageBuf,
+    #[error("Image error. {0}")]
+    Image(#[from] image::ImageError),
+    #[error("Image data is shorter than {0} rows of {1} bytes")]
+    Truncated(u32, u32),
 }
 
 pub struct Parser {
@@ -28,61 +47,331 @@ pub struct Parser {
 
     // Should dump data to disk
     dump_data: bool,
+
+    // Scratch buffer for decoded message payloads, reused across messages
+    buffer: Vec<u8>,
+
+    // Transform tree, for the camera intrinsics and the frame of depth clouds
+    frame_tree: Arc<Mutex<FrameTree>>,
+
+    // Back-project depth images into point clouds
+    depth_cloud: bool,
+
+    // PCD format of the depth clouds
+    pcd_format: pcd::Format,
+
+    // Warned about the missing camera info already?
+    warned: bool,
+
+    // Scale of images in preview
+    preview_scale: Option<f32>,
+
+    // Format and quality of the image files
+    image_options: image_file::Options,
+
+    // Frames to be saved
+    sampler: Sampler,
+
+    // Animated digest of the topic
+    digest: Option<Digest>,
+
+    // Index of the saved frames, shared with parsers of other files
+    frame_index: Arc<Mutex<FrameIndex>>,
+
+    // Input file of the messages
+    source: String,
 }
 
 impl Parser {
-    pub fn new(output_path: &Path, rerun_stream: Option<RecordingStream>, dump_data: bool) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        output_path: &Path,
+        rerun_stream: Option<RecordingStream>,
+        dump_data: bool,
+        frame_tree: Arc<Mutex<FrameTree>>,
+        depth_cloud: bool,
+        pcd_format: Option<pcd::Format>,
+        preview_scale: Option<f32>,
+        image_options: image_file::Options,
+        frame_index: Arc<Mutex<FrameIndex>>,
+    ) -> Self {
         // Create output dir
         if dump_data {
             fs::create_dir_all(output_path).unwrap();
+            if depth_cloud {
+                fs::create_dir_all(output_path.join("cloud")).unwrap();
+            }
         }
 
         Parser {
             output_dir: output_path.into(),
             rec_stream: rerun_stream,
             dump_data,
+            buffer: Vec::new(),
+            frame_tree,
+            depth_cloud,
+            pcd_format: pcd_format.unwrap_or(pcd::Format::Binary),
+            warned: false,
+            preview_scale,
+            sampler: Sampler::new(image_options.sampling),
+            digest: image_options
+                .digest
+                .filter(|_| dump_data)
+                .map(|options| Digest::new(output_path, options)),
+            image_options,
+            frame_index,
+            source: String::new(),
+        }
+    }
+
+    /// Add the saved frame to the index.
+    fn index(&self, frame: &Frame, file: PathBuf) {
+        self.frame_index.lock().unwrap().add(
+            frame.topic,
+            &self.output_dir,
+            Entry {
+                file,
+                stamp: frame.stamp,
+                publish_time: frame.publish_time,
+                seq: frame.seq,
+                source: self.source.clone(),
+            },
+        );
+    }
+
+    /// Back-project the depth image, then visualize and dump the cloud.
+    fn depth_to_cloud(
+        &mut self,
+        image: &Image,
+        message: &Message,
+        dump_data: bool,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let (intrinsics, frame_path) = {
+            let tree = self.frame_tree.lock().unwrap();
+            (
+                tree.intrinsics(&image.header.frame_id),
+                tree.entity_path(&image.header.frame_id),
+            )
+        };
+        let Some(intrinsics) = intrinsics else {
+            if !self.warned {
+                warn!(
+                    "No camera info of frame {}, depth images of {} are not converted.",
+                    image.header.frame_id, message.channel.topic
+                );
+                self.warned = true;
+            }
+            return Ok(());
+        };
+        let cloud = depth::to_cloud(image, &intrinsics).map_err(Error::Depth)?;
+
+        if let Some(rec) = &self.rec_stream {
+            let points: Vec<[f32; 3]> = cloud
+                .data
+                .chunks_exact(12)
+                .map(|p| {
+                    let value = |i: usize| f32::from_ne_bytes(p[i..i + 4].try_into().unwrap());
+                    [value(0), value(4), value(8)]
+                })
+                .collect();
+            // Clouds are in the optical frame, rotated into the vehicle axes without TF
+            let entity_path = match frame_path {
+                Some(path) => format!("{}/depth/{}", path, message.channel.topic),
+                None => {
+                    let path = format!("depth/{}", message.channel.topic);
+                    rec.log_static(
+                        path.as_str(),
+                        &rerun::Transform3D::from_mat3x3(glam::Mat3::from_cols(
+                            glam::Vec3::NEG_Y,
+                            glam::Vec3::NEG_Z,
+                            glam::Vec3::X,
+                        )),
+                    )?;
+                    path
+                }
+            };
+            rec.log(entity_path, &rerun::Points3D::new(points))?;
+        }
+
+        if dump_data {
+            pcd::write(
+                &self
+                    .output_dir
+                    .join("cloud")
+                    .join(format!("{}.pcd", message.publish_time)),
+                &cloud,
+                self.pcd_format,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Decode the image if the encoding is known. Raw Bayer, packed YUV and BGR images are converted
+/// to RGB, BGRA images to RGBA.
+fn decode(image_msg: &Image) -> Result<Option<image::DynamicImage>, Error> {
+    let (width, height) = (image_msg.width, image_msg.height);
+    let decoded = match image_msg.encoding.as_str() {
+        encoding if bayer::is_bayer(encoding) => {
+            let rgb = bayer::demosaic(
+                &image_msg.data,
+                width,
+                height,
+                image_msg.step,
+                encoding,
+                image_msg.is_bigendian != 0,
+            )?;
+            image::RgbImage::from_raw(width, height, rgb).map(image::DynamicImage::from)
+        }
+        encoding if yuv::is_packed(encoding) => {
+            let rgb = yuv::to_rgb(&image_msg.data, width, height, image_msg.step, encoding)?;
+            image::RgbImage::from_raw(width, height, rgb).map(image::DynamicImage::from)
+        }
+        "rgb8" => image::RgbImage::from_raw(width, height, rows(image_msg, 3)?)
+            .map(image::DynamicImage::from),
+        "bgr8" => {
+            let mut rgb = rows(image_msg, 3)?;
+            rgb.chunks_exact_mut(3).for_each(|p| p.swap(0, 2));
+            image::RgbImage::from_raw(width, height, rgb).map(image::DynamicImage::from)
+        }
+        "rgba8" => image::RgbaImage::from_raw(width, height, rows(image_msg, 4)?)
+            .map(image::DynamicImage::from),
+        "bgra8" => {
+            let mut rgba = rows(image_msg, 4)?;
+            rgba.chunks_exact_mut(4).for_each(|p| p.swap(0, 2));
+            image::RgbaImage::from_raw(width, height, rgba).map(image::DynamicImage::from)
+        }
+        "mono8" | "8UC1" => image::GrayImage::from_raw(width, height, rows(image_msg, 1)?)
+            .map(image::DynamicImage::from),
+        "mono16" => {
+            let luma = rows(image_msg, 2)?
+                .chunks_exact(2)
+                .map(|b| match image_msg.is_bigendian {
+                    0 => u16::from_le_bytes([b[0], b[1]]),
+                    _ => u16::from_be_bytes([b[0], b[1]]),
+                })
+                .collect();
+            image::ImageBuffer::<image::Luma<u16>, _>::from_raw(width, height, luma)
+                .map(image::DynamicImage::from)
+        }
+        _ => return Ok(None),
+    };
+    decoded.map(Some).ok_or(Error::ImageBuf)
+}
+
+/// Resize the image by the scale for preview, to save memory and bandwidth of the viewer.
+pub(crate) fn preview(image: image::DynamicImage, scale: Option<f32>) -> image::DynamicImage {
+    match scale {
+        Some(scale) if scale != 1.0 => {
+            let resize = |size: u32| ((size as f32 * scale).round() as u32).max(1);
+            image.resize_exact(
+                resize(image.width()),
+                resize(image.height()),
+                image::imageops::FilterType::Triangle,
+            )
         }
+        _ => image,
     }
 }
 
+/// Pixel bytes of all the rows without the padding at the end of each row.
+fn rows(image_msg: &Image, pixel_size: usize) -> Result<Vec<u8>, Error> {
+    let row_size = image_msg.width as usize * pixel_size;
+    let step = image_msg.step as usize;
+    if step < row_size || image_msg.data.len() < step * image_msg.height as usize {
+        return Err(Error::Truncated(image_msg.height, image_msg.step));
+    }
+    Ok(image_msg
+        .data
+        .chunks_exact(step)
+        .take(image_msg.height as usize)
+        .flat_map(|row| &row[..row_size])
+        .copied()
+        .collect())
+}
+
 impl Extractor for Parser {
-    type ExtractorError = Box<dyn std::error::Error>;
+    type ExtractorError = Box<dyn std::error::Error + Send + Sync>;
+
+    fn buffer(&mut self) -> &mut Vec<u8> {
+        &mut self.buffer
+    }
 
     fn step(&mut self, message: &Message) -> Result<(), Self::ExtractorError> {
-        let buf = message.data.as_ref();
-        let serialized = if &message.data[..4] == ZSTD_MAGIC_NUMBER {
-            zstd::stream::decode_all(buf).map_err(|e| Error::Zstd(e))?
-        } else {
-            message.data.to_vec()
-        };
-        let image_msg =
-            cdr::deserialize_from::<_, Image, _>(serialized.as_slice(), cdr::size::Infinite)
-                .map_err(|e| Error::CDR(e))?;
+        let payload = self.decode_into(message).map_err(Error::Payload)?;
+        let image_msg = cdr::deserialize_from::<_, Image, _>(payload, cdr::size::Infinite)
+            .map_err(Error::Cdr)?;
+
+        // Frames are sampled for extraction only
+        let stamp = image_msg.header.stamp.sec as u64 * 1_000_000_000
+            + image_msg.header.stamp.nanosec as u64;
+        let seq = self.sampler.keep(stamp).filter(|_| self.dump_data);
+        let dump_data = seq.is_some();
+        let digest = self.digest.as_mut().is_some_and(|d| d.wants(stamp));
+        if !dump_data && !digest && self.rec_stream.is_none() {
+            return Ok(());
+        }
+
+        // Images of known encodings are decoded for both preview and extraction
+        let decoded = decode(&image_msg)?;
+        if let (Some(digest), Some(decoded)) = (self.digest.as_mut().filter(|_| digest), &decoded) {
+            digest.push(decoded)?;
+        }
 
         if let Some(rec) = &self.rec_stream {
             rec.set_time_seconds(
                 "main",
                 image_msg.header.stamp.sec as f64 + image_msg.header.stamp.nanosec as f64 * 1e-9,
             );
-            // rec.log(
-            //     format!("image/{}", message.channel.topic.clone()),
-            //     &rerun::Image::new(image_msg.data),
-            // )?;
+            if let Some(decoded) = &decoded {
+                rec.log(
+                    self.frame_tree
+                        .lock()
+                        .unwrap()
+                        .image_entity_path(&image_msg.header.frame_id, &message.channel.topic),
+                    &rerun::Image::from_image(preview(decoded.clone(), self.preview_scale))?,
+                )?;
+            }
         }
 
-        // Create output file
-        if self.dump_data {
-            let mut file = fs::File::create(
-                &self
-                    .output_dir
-                    .join(format!("{}.bin", message.publish_time)),
-            )?;
-            file.write_all(&image_msg.data)?;
+        if self.depth_cloud && depth::is_depth(&image_msg.encoding) {
+            self.depth_to_cloud(&image_msg, message, dump_data)?;
         }
+
+        // Create output file
+        let Some(seq) = seq else {
+            return Ok(());
+        };
+        let frame = Frame {
+            topic: &message.channel.topic,
+            stamp,
+            publish_time: message.publish_time,
+            seq,
+        };
+        let stem = message.publish_time.to_string();
+        let path = if let Some(decoded) = decoded {
+            self.image_options
+                .save(&decoded, &self.output_dir, &stem, &frame)
+                .map_err(Error::Image)?
+        } else {
+            let path = self
+                .image_options
+                .path(&self.output_dir, &stem, "bin", &frame)?;
+            fs::File::create(&path)?.write_all(&image_msg.data)?;
+            path
+        };
+        self.index(&frame, path);
         Ok(())
     }
 
+    fn set_source(&mut self, source: &Path) {
+        self.source = source.display().to_string();
+    }
+
     fn post_process(&mut self, _sigint: Arc<AtomicBool>) -> Result<(), Self::ExtractorError> {
+        if let Some(digest) = self.digest.as_mut() {
+            digest.finish()?;
+        }
         Ok(())
     }
 }
@@ -0,0 +1,352 @@
+//! Files of decoded images and video frames.
+use crate::{composite, digest, overlay};
+use image::{
+    codecs::jpeg::JpegEncoder, imageops::FilterType, ColorType, DynamicImage, ImageFormat,
+};
+use std::{
+    borrow::Cow,
+    fs,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+// Placeholders of the name template
+const PLACEHOLDERS: [&str; 7] = [
+    "topic",
+    "stamp",
+    "stamp_sec",
+    "stamp_nsec",
+    "publish_time",
+    "seq",
+    "ext",
+];
+
+/// Format of the image files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    #[default]
+    Jpeg,
+    Png,
+    Webp,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "jpeg" | "jpg" => Ok(Format::Jpeg),
+            "png" => Ok(Format::Png),
+            "webp" => Ok(Format::Webp),
+            _ => Err(format!(
+                "Invalid image format: {}, expected one of jpeg, png, webp",
+                s
+            )),
+        }
+    }
+}
+
+impl Format {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Jpeg => "jpeg",
+            Format::Png => "png",
+            Format::Webp => "webp",
+        }
+    }
+}
+
+/// Frames of each topic to be saved.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Sampling {
+    #[default]
+    All,
+    /// Every Nth frame, starting from the first one.
+    Stride(u32),
+    /// Frames at most this many per second, by the stamps of the frames.
+    Rate(f64),
+}
+
+/// Picks the frames to be saved of a topic.
+#[derive(Debug)]
+pub struct Sampler {
+    sampling: Sampling,
+    // Frames seen so far
+    count: u64,
+    // Frames kept so far
+    kept: u64,
+    // Stamp of the last frame kept, in nanoseconds
+    last: Option<u64>,
+}
+
+impl Sampler {
+    pub fn new(sampling: Sampling) -> Self {
+        Sampler {
+            sampling,
+            count: 0,
+            kept: 0,
+            last: None,
+        }
+    }
+
+    /// Should the frame stamped at `stamp` nanoseconds be saved? Return the sequence number of
+    /// the frame among the saved ones if so.
+    pub fn keep(&mut self, stamp: u64) -> Option<u64> {
+        let keep = match self.sampling {
+            Sampling::All => true,
+            Sampling::Stride(n) => self.count.is_multiple_of(n.max(1) as u64),
+            Sampling::Rate(hz) => self
+                .last
+                .is_none_or(|last| stamp.saturating_sub(last) as f64 >= 1e9 / hz),
+        };
+        self.count += 1;
+        if !keep {
+            return None;
+        }
+        self.last = Some(stamp);
+        self.kept += 1;
+        Some(self.kept - 1)
+    }
+}
+
+/// Frame to be saved, for the name template.
+pub struct Frame<'a> {
+    pub topic: &'a str,
+    // Stamp of the frame in nanoseconds
+    pub stamp: u64,
+    pub publish_time: u64,
+    // Sequence number among the saved frames of the topic
+    pub seq: u64,
+}
+
+/// Template of frame file paths, like `{topic}/{stamp_sec}_{stamp_nsec}_{seq}.jpg`, relative
+/// to the output directory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NameTemplate {
+    template: String,
+    // Output directory the paths are relative to
+    root: PathBuf,
+}
+
+impl FromStr for NameTemplate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rest = s;
+        while let Some(start) = rest.find('{') {
+            let end = rest[start..]
+                .find('}')
+                .ok_or(format!("Unclosed placeholder in name template: {}", s))?;
+            let name = &rest[start + 1..start + end];
+            if !PLACEHOLDERS.contains(&name) {
+                return Err(format!(
+                    "Invalid placeholder in name template: {{{}}}, expected one of {}",
+                    name,
+                    PLACEHOLDERS.map(|p| format!("{{{}}}", p)).join(", ")
+                ));
+            }
+            rest = &rest[start + end + 1..];
+        }
+        if s.trim().is_empty() || s.starts_with('/') {
+            return Err(format!(
+                "Invalid name template: {}, expected a relative path",
+                s
+            ));
+        }
+        Ok(NameTemplate {
+            template: s.to_owned(),
+            root: PathBuf::new(),
+        })
+    }
+}
+
+impl NameTemplate {
+    pub fn as_str(&self) -> &str {
+        &self.template
+    }
+
+    /// Resolve the paths in the output directory.
+    pub(crate) fn under(self, root: &Path) -> Self {
+        NameTemplate {
+            root: root.into(),
+            ..self
+        }
+    }
+
+    /// Path of the frame. The extension is appended if the template has none.
+    fn render(&self, frame: &Frame, extension: &str) -> PathBuf {
+        let mut name = self.template.clone();
+        for (placeholder, value) in [
+            ("topic", frame.topic.trim_start_matches('/').to_owned()),
+            ("stamp", frame.stamp.to_string()),
+            ("stamp_sec", (frame.stamp / 1_000_000_000).to_string()),
+            ("stamp_nsec", (frame.stamp % 1_000_000_000).to_string()),
+            ("publish_time", frame.publish_time.to_string()),
+            ("seq", frame.seq.to_string()),
+            ("ext", extension.to_owned()),
+        ] {
+            name = name.replace(&format!("{{{}}}", placeholder), &value);
+        }
+        let path = self.root.join(name);
+        match path.extension() {
+            Some(_) => path,
+            None => path.with_extension(extension),
+        }
+    }
+}
+
+/// Format, quality, sampling and names of the image files.
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub format: Format,
+    // Quality of JPEG files, 1 to 100
+    pub jpeg_quality: u8,
+    pub sampling: Sampling,
+    // Paths of the frames, `{stem}.{extension}` in the topic directory if not set
+    pub name_template: Option<NameTemplate>,
+    // Rename frames and point clouds as `000001.jpeg` and so on in message order at the end
+    pub seq_names: bool,
+    // Decode still compressed images and save them in the format, instead of their payloads
+    pub transcode: bool,
+    // Scale of the saved images, like 0.5, the original size if not set
+    pub scale: Option<f32>,
+    // Animated digest of each camera topic, none if not set
+    pub digest: Option<digest::Options>,
+    // Tiled video of camera topics in the output directory, none if not set
+    pub composite: Option<composite::Options>,
+    // Draw the topic, stamp and sequence number onto the saved images
+    pub burn_timestamp: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            format: Format::Jpeg,
+            jpeg_quality: 75,
+            sampling: Sampling::All,
+            name_template: None,
+            seq_names: false,
+            transcode: false,
+            scale: None,
+            digest: None,
+            composite: None,
+            burn_timestamp: false,
+        }
+    }
+}
+
+impl Options {
+    /// Path of the frame file, by the name template or as `{stem}.{extension}` in the directory.
+    /// Missing directories are created.
+    pub fn path(
+        &self,
+        dir: &Path,
+        stem: &str,
+        extension: &str,
+        frame: &Frame,
+    ) -> std::io::Result<PathBuf> {
+        let Some(template) = &self.name_template else {
+            return Ok(dir.join(format!("{}.{}", stem, extension)));
+        };
+        let path = template.render(frame, extension);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        Ok(path)
+    }
+
+    /// Whether still compressed images should be decoded, to be converted, resized or stamped.
+    pub fn decode_compressed(&self) -> bool {
+        self.transcode || self.scale.is_some() || self.burn_timestamp
+    }
+
+    /// Save the image as a frame file, see `path`, and return the path. Images the format could
+    /// not hold, like 16 bit images in JPEG, are saved as PNG files. Images are resized first if
+    /// a scale is set, then stamped if asked to.
+    pub fn save(
+        &self,
+        image: &DynamicImage,
+        dir: &Path,
+        stem: &str,
+        frame: &Frame,
+    ) -> image::ImageResult<PathBuf> {
+        let mut image = Cow::Borrowed(image);
+        if let Some(scale) = self.scale.filter(|s| *s != 1.0) {
+            let resize = |size: u32| ((size as f32 * scale).round() as u32).max(1);
+            image = Cow::Owned(image.resize_exact(
+                resize(image.width()),
+                resize(image.height()),
+                FilterType::Triangle,
+            ));
+        }
+        if self.burn_timestamp {
+            overlay::burn(image.to_mut(), frame);
+        }
+        let image = image.as_ref();
+        let format = match (self.format, image.color()) {
+            (Format::Jpeg, ColorType::L8 | ColorType::Rgb8) => Format::Jpeg,
+            (Format::Webp, ColorType::L8 | ColorType::La8 | ColorType::Rgb8 | ColorType::Rgba8) => {
+                Format::Webp
+            }
+            _ => Format::Png,
+        };
+        let path = self.path(dir, stem, format.extension(), frame)?;
+        let mut file = BufWriter::new(fs::File::create(&path)?);
+        match format {
+            Format::Jpeg => {
+                JpegEncoder::new_with_quality(&mut file, self.jpeg_quality).encode_image(image)?
+            }
+            Format::Png => image.write_to(&mut file, ImageFormat::Png)?,
+            Format::Webp => image.write_to(&mut file, ImageFormat::WebP)?,
+        }
+        file.flush()?;
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sequence numbers of the kept frames, None for dropped ones.
+    fn keep(sampling: Sampling, stamps: impl IntoIterator<Item = u64>) -> Vec<Option<u64>> {
+        let mut sampler = Sampler::new(sampling);
+        stamps.into_iter().map(|s| sampler.keep(s)).collect()
+    }
+
+    #[test]
+    fn keep_all_frames() {
+        assert_eq!(keep(Sampling::All, [5, 5, 1]), [Some(0), Some(1), Some(2)]);
+    }
+
+    #[test]
+    fn keep_every_nth_frame() {
+        assert_eq!(
+            keep(Sampling::Stride(3), 0..7),
+            [Some(0), None, None, Some(1), None, None, Some(2)]
+        );
+        assert_eq!(keep(Sampling::Stride(1), 0..2), [Some(0), Some(1)]);
+    }
+
+    #[test]
+    fn keep_frames_at_rate() {
+        // Frames of 10Hz sampled at 2Hz
+        let stamps = (0..12).map(|i| 1_000_000_000 + i * 100_000_000);
+        let kept: Vec<usize> = keep(Sampling::Rate(2.0), stamps)
+            .iter()
+            .enumerate()
+            .filter_map(|(i, seq)| seq.map(|_| i))
+            .collect();
+        assert_eq!(kept, [0, 5, 10]);
+
+        // Stamps going back are dropped until the rate allows
+        assert_eq!(
+            keep(
+                Sampling::Rate(1.0),
+                [5_000_000_000, 1_000_000_000, 6_000_000_000]
+            ),
+            [Some(0), None, Some(1)]
+        );
+    }
+}
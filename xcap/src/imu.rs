@@ -0,0 +1,163 @@
+use crate::extractor::Extractor;
+use mcap::Message;
+use rerun::RecordingStream;
+use ros2_sensor_msgs::msg::Imu;
+use std::{
+    fs,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::{atomic::AtomicBool, Arc},
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Payload error. {0}")]
+    Payload(#[from] crate::extractor::DecodeError),
+    #[error("CDR error. {0}")]
+    Cdr(#[from] cdr::Error),
+}
+
+pub struct Parser {
+    // Visualizer with rerun
+    rec_stream: Option<RecordingStream>,
+
+    // CSV file for all the samples, only available when dumping data
+    csv: Option<BufWriter<fs::File>>,
+
+    // Scratch buffer for decoded message payloads, reused across messages
+    buffer: Vec<u8>,
+
+    // Axes of the orientation logged already?
+    axes_logged: bool,
+}
+
+impl Parser {
+    pub fn new(output_path: &Path, rerun_stream: Option<RecordingStream>, dump_data: bool) -> Self {
+        // Create output file
+        let csv = if dump_data {
+            fs::create_dir_all(output_path).unwrap();
+            let mut file = BufWriter::new(fs::File::create(output_path.join("imu.csv")).unwrap());
+            writeln!(
+                file,
+                "sec,nanosec,orientation_x,orientation_y,orientation_z,orientation_w,\
+                angular_velocity_x,angular_velocity_y,angular_velocity_z,\
+                linear_acceleration_x,linear_acceleration_y,linear_acceleration_z"
+            )
+            .unwrap();
+            Some(file)
+        } else {
+            None
+        };
+
+        Parser {
+            rec_stream: rerun_stream,
+            csv,
+            buffer: Vec::new(),
+            axes_logged: false,
+        }
+    }
+}
+
+/// Whether the IMU reports its orientation. Per REP 145, the first element of the covariance is -1
+/// if not, and some drivers leave the quaternion zeroed instead.
+fn has_orientation(imu: &Imu) -> bool {
+    let q = &imu.orientation;
+    imu.orientation_covariance[0] != -1.0 && q.x * q.x + q.y * q.y + q.z * q.z + q.w * q.w > 0.0
+}
+
+impl Extractor for Parser {
+    type ExtractorError = Box<dyn std::error::Error + Send + Sync>;
+
+    fn buffer(&mut self) -> &mut Vec<u8> {
+        &mut self.buffer
+    }
+
+    fn step(&mut self, message: &Message) -> Result<(), Self::ExtractorError> {
+        let payload = self.decode_into(message).map_err(Error::Payload)?;
+        let imu =
+            cdr::deserialize_from::<_, Imu, _>(payload, cdr::size::Infinite).map_err(Error::Cdr)?;
+
+        if let Some(rec) = &self.rec_stream {
+            rec.set_time_seconds(
+                "main",
+                imu.header.stamp.sec as f64 + imu.header.stamp.nanosec as f64 * 1e-9,
+            );
+            let topic = message.channel.topic.as_str();
+            let (w, a) = (&imu.angular_velocity, &imu.linear_acceleration);
+            for (name, value) in [
+                ("angular_velocity/x", w.x),
+                ("angular_velocity/y", w.y),
+                ("angular_velocity/z", w.z),
+                ("linear_acceleration/x", a.x),
+                ("linear_acceleration/y", a.y),
+                ("linear_acceleration/z", a.z),
+            ] {
+                rec.log(
+                    format!("imu/{}/{}", topic, name),
+                    &rerun::Scalar::new(value),
+                )?;
+            }
+
+            // Attitude as rotating axes in the 3D view: X red, Y green, Z blue
+            if has_orientation(&imu) {
+                let entity_path = format!("imu/{}/orientation", topic);
+                if !self.axes_logged {
+                    rec.log_static(
+                        entity_path.as_str(),
+                        &rerun::Arrows3D::from_vectors([
+                            [1.0, 0.0, 0.0],
+                            [0.0, 1.0, 0.0],
+                            [0.0, 0.0, 1.0],
+                        ])
+                        .with_colors([
+                            [255, 0, 0],
+                            [0, 255, 0],
+                            [0, 0, 255],
+                        ]),
+                    )?;
+                    self.axes_logged = true;
+                }
+                let q = &imu.orientation;
+                rec.log(
+                    entity_path.as_str(),
+                    &rerun::Transform3D::from_rotation(rerun::Quaternion::from_xyzw([
+                        q.x as f32, q.y as f32, q.z as f32, q.w as f32,
+                    ])),
+                )?;
+            }
+        }
+
+        // Dump data?
+        if let Some(csv) = self.csv.as_mut() {
+            let (q, w, a) = (
+                &imu.orientation,
+                &imu.angular_velocity,
+                &imu.linear_acceleration,
+            );
+            writeln!(
+                csv,
+                "{},{},{},{},{},{},{},{},{},{},{},{}",
+                imu.header.stamp.sec,
+                imu.header.stamp.nanosec,
+                q.x,
+                q.y,
+                q.z,
+                q.w,
+                w.x,
+                w.y,
+                w.z,
+                a.x,
+                a.y,
+                a.z
+            )?;
+        }
+        Ok(())
+    }
+
+    fn post_process(&mut self, _sigint: Arc<AtomicBool>) -> Result<(), Self::ExtractorError> {
+        if let Some(csv) = self.csv.as_mut() {
+            csv.flush()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,119 @@
+use crate::extractor::Extractor;
+use crate::frame_index::csv_field;
+use mcap::Message;
+use rerun::RecordingStream;
+use ros2_sensor_msgs::msg::JointState;
+use std::{
+    fs,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::{atomic::AtomicBool, Arc},
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Payload error. {0}")]
+    Payload(#[from] crate::extractor::DecodeError),
+    #[error("CDR error. {0}")]
+    Cdr(#[from] cdr::Error),
+}
+
+/// Parser of joint states, like the ones of robot arms. The position, velocity and effort of each
+/// joint are plotted over time.
+pub struct Parser {
+    // Visualizer with rerun
+    rec_stream: Option<RecordingStream>,
+
+    // CSV file for the states of all the joints, only available when dumping data
+    csv: Option<BufWriter<fs::File>>,
+
+    // Scratch buffer for decoded message payloads, reused across messages
+    buffer: Vec<u8>,
+}
+
+impl Parser {
+    pub fn new(output_path: &Path, rerun_stream: Option<RecordingStream>, dump_data: bool) -> Self {
+        // Create output file
+        let csv = if dump_data {
+            fs::create_dir_all(output_path).unwrap();
+            let mut file =
+                BufWriter::new(fs::File::create(output_path.join("joint_states.csv")).unwrap());
+            writeln!(file, "sec,nanosec,name,position,velocity,effort").unwrap();
+            Some(file)
+        } else {
+            None
+        };
+
+        Parser {
+            rec_stream: rerun_stream,
+            csv,
+            buffer: Vec::new(),
+        }
+    }
+}
+
+impl Extractor for Parser {
+    type ExtractorError = Box<dyn std::error::Error + Send + Sync>;
+
+    fn buffer(&mut self) -> &mut Vec<u8> {
+        &mut self.buffer
+    }
+
+    fn step(&mut self, message: &Message) -> Result<(), Self::ExtractorError> {
+        let payload = self.decode_into(message).map_err(Error::Payload)?;
+        let state = cdr::deserialize_from::<_, JointState, _>(payload, cdr::size::Infinite)
+            .map_err(Error::Cdr)?;
+
+        // Any of the arrays could be empty if the joints do not report it
+        let value = |values: &[f64], i: usize| values.get(i).copied();
+
+        if let Some(rec) = &self.rec_stream {
+            rec.set_time_seconds(
+                "main",
+                state.header.stamp.sec as f64 + state.header.stamp.nanosec as f64 * 1e-9,
+            );
+            for (i, name) in state.name.iter().enumerate() {
+                for (field, values) in [
+                    ("position", &state.position),
+                    ("velocity", &state.velocity),
+                    ("effort", &state.effort),
+                ] {
+                    if let Some(v) = value(values, i) {
+                        rec.log(
+                            format!("joints/{}/{}/{}", message.channel.topic, field, name),
+                            &rerun::Scalar::new(v),
+                        )?;
+                    }
+                }
+            }
+        }
+
+        // Dump data?
+        if let Some(csv) = self.csv.as_mut() {
+            let field = |values: &[f64], i: usize| match value(values, i) {
+                Some(v) => v.to_string(),
+                None => String::new(),
+            };
+            for (i, name) in state.name.iter().enumerate() {
+                writeln!(
+                    csv,
+                    "{},{},{},{},{},{}",
+                    state.header.stamp.sec,
+                    state.header.stamp.nanosec,
+                    csv_field(name),
+                    field(&state.position, i),
+                    field(&state.velocity, i),
+                    field(&state.effort, i)
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    fn post_process(&mut self, _sigint: Arc<AtomicBool>) -> Result<(), Self::ExtractorError> {
+        if let Some(csv) = self.csv.as_mut() {
+            csv.flush()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,108 @@
+use crate::extractor::Extractor;
+use mcap::Message;
+use rerun::RecordingStream;
+use ros2_sensor_msgs::msg::Joy;
+use std::{
+    fs,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::{atomic::AtomicBool, Arc},
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Payload error. {0}")]
+    Payload(#[from] crate::extractor::DecodeError),
+    #[error("CDR error. {0}")]
+    Cdr(#[from] cdr::Error),
+}
+
+/// Parser of joysticks. The axes and buttons are plotted over time, so the inputs of the operator
+/// could be correlated with the behavior of the vehicle.
+pub struct Parser {
+    // Visualizer with rerun
+    rec_stream: Option<RecordingStream>,
+
+    // CSV file for the states of all the inputs, only available when dumping data
+    csv: Option<BufWriter<fs::File>>,
+
+    // Scratch buffer for decoded message payloads, reused across messages
+    buffer: Vec<u8>,
+}
+
+impl Parser {
+    pub fn new(output_path: &Path, rerun_stream: Option<RecordingStream>, dump_data: bool) -> Self {
+        // Create output file
+        let csv = if dump_data {
+            fs::create_dir_all(output_path).unwrap();
+            let mut file = BufWriter::new(fs::File::create(output_path.join("joy.csv")).unwrap());
+            writeln!(file, "sec,nanosec,input,index,value").unwrap();
+            Some(file)
+        } else {
+            None
+        };
+
+        Parser {
+            rec_stream: rerun_stream,
+            csv,
+            buffer: Vec::new(),
+        }
+    }
+}
+
+impl Extractor for Parser {
+    type ExtractorError = Box<dyn std::error::Error + Send + Sync>;
+
+    fn buffer(&mut self) -> &mut Vec<u8> {
+        &mut self.buffer
+    }
+
+    fn step(&mut self, message: &Message) -> Result<(), Self::ExtractorError> {
+        let payload = self.decode_into(message).map_err(Error::Payload)?;
+        let joy =
+            cdr::deserialize_from::<_, Joy, _>(payload, cdr::size::Infinite).map_err(Error::Cdr)?;
+        let inputs = joy
+            .axes
+            .iter()
+            .enumerate()
+            .map(|(i, &v)| ("axis", i, v as f64))
+            .chain(
+                joy.buttons
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &v)| ("button", i, v as f64)),
+            );
+
+        if let Some(rec) = &self.rec_stream {
+            rec.set_time_seconds(
+                "main",
+                joy.header.stamp.sec as f64 + joy.header.stamp.nanosec as f64 * 1e-9,
+            );
+            for (input, index, value) in inputs.clone() {
+                rec.log(
+                    format!("joy/{}/{}/{}", message.channel.topic, input, index),
+                    &rerun::Scalar::new(value),
+                )?;
+            }
+        }
+
+        // Dump data?
+        if let Some(csv) = self.csv.as_mut() {
+            for (input, index, value) in inputs {
+                writeln!(
+                    csv,
+                    "{},{},{},{},{}",
+                    joy.header.stamp.sec, joy.header.stamp.nanosec, input, index, value
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    fn post_process(&mut self, _sigint: Arc<AtomicBool>) -> Result<(), Self::ExtractorError> {
+        if let Some(csv) = self.csv.as_mut() {
+            csv.flush()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,156 @@
+//! Writer for the LAS 1.2 file format of the ASPRS, with point data record format 0.
+//! Reference: https://www.asprs.org/wp-content/uploads/2010/12/asprs_las_format_v12.pdf
+use std::{
+    fs,
+    io::{BufWriter, Error, ErrorKind, Write},
+    path::Path,
+};
+
+// Size of the public header block, and of a point data record of format 0
+const HEADER_SIZE: u16 = 227;
+const RECORD_SIZE: u16 = 20;
+
+// Coordinates are stored as integers of millimeters
+const SCALE: f64 = 0.001;
+
+// Single return of every point: return number 1 of 1
+const RETURN_FLAGS: u8 = 0b0000_1001;
+
+/// Write the points of `x`, `y`, `z` and `intensity` into a LAS file. Intensities are clamped
+/// into the range of 16 bit integers.
+pub fn write(path: &Path, points: &[[f32; 4]]) -> std::io::Result<()> {
+    let count = u32::try_from(points.len())
+        .map_err(|_| Error::new(ErrorKind::InvalidInput, "Too many points for a LAS file"))?;
+    let mut min = [f64::MAX; 3];
+    let mut max = [f64::MIN; 3];
+    for point in points {
+        for axis in 0..3 {
+            min[axis] = min[axis].min(point[axis] as f64);
+            max[axis] = max[axis].max(point[axis] as f64);
+        }
+    }
+    if points.is_empty() {
+        (min, max) = ([0.0; 3], [0.0; 3]);
+    }
+    // Offsets keep the integers small for maps far from the origin
+    let offset = min.map(f64::floor);
+
+    let mut file = BufWriter::new(fs::File::create(path)?);
+
+    // Public header block
+    file.write_all(b"LASF")?;
+    file.write_all(&0u16.to_le_bytes())?; // File source ID
+    file.write_all(&0u16.to_le_bytes())?; // Global encoding
+    file.write_all(&[0; 16])?; // Project ID
+    file.write_all(&[1, 2])?; // Version
+    file.write_all(&padded(b"xcap"))?; // System identifier
+    file.write_all(&padded(b"xcap"))?; // Generating software
+    file.write_all(&0u16.to_le_bytes())?; // Creation day of year
+    file.write_all(&0u16.to_le_bytes())?; // Creation year
+    file.write_all(&HEADER_SIZE.to_le_bytes())?;
+    file.write_all(&(HEADER_SIZE as u32).to_le_bytes())?; // Offset to point data
+    file.write_all(&0u32.to_le_bytes())?; // Number of variable length records
+    file.write_all(&[0])?; // Point data format
+    file.write_all(&RECORD_SIZE.to_le_bytes())?;
+    file.write_all(&count.to_le_bytes())?;
+    for returns in [count, 0, 0, 0, 0] {
+        file.write_all(&returns.to_le_bytes())?;
+    }
+    for value in [SCALE; 3].into_iter().chain(offset) {
+        file.write_all(&value.to_le_bytes())?;
+    }
+    for axis in 0..3 {
+        file.write_all(&max[axis].to_le_bytes())?;
+        file.write_all(&min[axis].to_le_bytes())?;
+    }
+
+    // Point data records
+    for point in points {
+        for axis in 0..3 {
+            let value = ((point[axis] as f64 - offset[axis]) / SCALE).round() as i32;
+            file.write_all(&value.to_le_bytes())?;
+        }
+        let intensity = point[3].round().clamp(0.0, u16::MAX as f32) as u16;
+        file.write_all(&intensity.to_le_bytes())?;
+        file.write_all(&[RETURN_FLAGS])?;
+        file.write_all(&[0, 0, 0])?; // Classification, scan angle rank, user data
+        file.write_all(&0u16.to_le_bytes())?; // Point source ID
+    }
+    file.flush()
+}
+
+/// Text in a field of 32 bytes, padded with zeros.
+fn padded(text: &[u8]) -> [u8; 32] {
+    let mut field = [0; 32];
+    field[..text.len()].copy_from_slice(text);
+    field
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+    use std::fs;
+
+    fn f64_at(data: &[u8], offset: usize) -> f64 {
+        f64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+    }
+
+    fn i32_at(data: &[u8], offset: usize) -> i32 {
+        i32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn write_points() {
+        let path = test_util::dir("las").join("map.las");
+        let points = [
+            [100.25, -20.5, 3.0, 12.0],
+            [101.5, -19.0, 2.5, 70000.0],
+            [100.0, -21.75, 4.0, -5.0],
+        ];
+        write(&path, &points).unwrap();
+        let data = fs::read(&path).unwrap();
+        assert_eq!(data.len(), HEADER_SIZE as usize + 3 * RECORD_SIZE as usize);
+        assert_eq!(&data[..4], b"LASF");
+        assert_eq!(data[24..26], [1, 2]);
+        assert_eq!(u32::from_le_bytes(data[107..111].try_into().unwrap()), 3);
+
+        // Scales, offsets, then the maximum and minimum of each axis
+        let scales = [131, 139, 147].map(|o| f64_at(&data, o));
+        let offsets = [155, 163, 171].map(|o| f64_at(&data, o));
+        assert_eq!(scales, [SCALE; 3]);
+        assert_eq!(offsets, [100.0, -22.0, 2.0]);
+        assert_eq!(f64_at(&data, 179), 101.5);
+        assert_eq!(f64_at(&data, 187), 100.0);
+        assert_eq!(f64_at(&data, 211), 4.0);
+        assert_eq!(f64_at(&data, 219), 2.5);
+
+        for (i, point) in points.iter().enumerate() {
+            let record = &data[HEADER_SIZE as usize + i * RECORD_SIZE as usize..];
+            for axis in 0..3 {
+                let value = i32_at(record, axis * 4) as f64 * SCALE + offsets[axis];
+                assert!((value - point[axis] as f64).abs() < 1e-6);
+            }
+            assert_eq!(record[14], RETURN_FLAGS);
+        }
+
+        // Intensities are clamped into 16 bits
+        let intensity = |i: usize| {
+            let offset = HEADER_SIZE as usize + i * RECORD_SIZE as usize + 12;
+            u16::from_le_bytes(data[offset..offset + 2].try_into().unwrap())
+        };
+        assert_eq!(
+            [intensity(0), intensity(1), intensity(2)],
+            [12, u16::MAX, 0]
+        );
+    }
+
+    #[test]
+    fn write_no_points() {
+        let path = test_util::dir("las_empty").join("map.las");
+        write(&path, &[]).unwrap();
+        let data = fs::read(&path).unwrap();
+        assert_eq!(data.len(), HEADER_SIZE as usize);
+        assert_eq!(f64_at(&data, 155), 0.0);
+    }
+}
@@ -0,0 +1,133 @@
+use crate::extractor::Extractor;
+use crate::tf::FrameTree;
+use mcap::Message;
+use rerun::RecordingStream;
+use ros2_sensor_msgs::msg::LaserScan;
+use std::{
+    fs,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{atomic::AtomicBool, Arc, Mutex},
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Payload error. {0}")]
+    Payload(#[from] crate::extractor::DecodeError),
+    #[error("CDR error. {0}")]
+    Cdr(#[from] cdr::Error),
+}
+
+pub struct Parser {
+    // Output directory
+    output_dir: PathBuf,
+
+    // Visualizer with rerun
+    rec_stream: Option<RecordingStream>,
+
+    // Should dump data to disk
+    dump_data: bool,
+
+    // Scratch buffer for decoded message payloads, reused across messages
+    buffer: Vec<u8>,
+
+    // Transform tree, scans are logged in their own frames once the frames are known
+    frame_tree: Arc<Mutex<FrameTree>>,
+}
+
+impl Parser {
+    pub fn new(
+        output_path: &Path,
+        rerun_stream: Option<RecordingStream>,
+        dump_data: bool,
+        frame_tree: Arc<Mutex<FrameTree>>,
+    ) -> Self {
+        // Create output dir
+        if dump_data {
+            fs::create_dir_all(output_path).unwrap();
+        }
+
+        Parser {
+            output_dir: output_path.into(),
+            rec_stream: rerun_stream,
+            dump_data,
+            buffer: Vec::new(),
+            frame_tree,
+        }
+    }
+}
+
+impl Extractor for Parser {
+    type ExtractorError = Box<dyn std::error::Error + Send + Sync>;
+
+    fn buffer(&mut self) -> &mut Vec<u8> {
+        &mut self.buffer
+    }
+
+    fn step(&mut self, message: &Message) -> Result<(), Self::ExtractorError> {
+        let payload = self.decode_into(message).map_err(Error::Payload)?;
+        let scan = cdr::deserialize_from::<_, LaserScan, _>(payload, cdr::size::Infinite)
+            .map_err(Error::Cdr)?;
+
+        if let Some(rec) = &self.rec_stream {
+            let points = scan
+                .ranges
+                .iter()
+                .enumerate()
+                .filter(|(_, r)| scan.is_valid(**r))
+                .map(|(i, r)| {
+                    let angle = scan.angle(i);
+                    [r * angle.cos(), r * angle.sin(), 0.0]
+                });
+
+            rec.set_time_seconds(
+                "main",
+                scan.header.stamp.sec as f64 + scan.header.stamp.nanosec as f64 * 1e-9,
+            );
+            let entity_path = match self
+                .frame_tree
+                .lock()
+                .unwrap()
+                .entity_path(&scan.header.frame_id)
+            {
+                Some(frame_path) => format!("{}/scan/{}", frame_path, message.channel.topic),
+                None => format!("scan/{}", message.channel.topic),
+            };
+            rec.log(
+                entity_path,
+                &rerun::Points3D::new(points).with_radii([0.02]),
+            )?;
+        }
+
+        // Create output file
+        if self.dump_data {
+            let mut file = BufWriter::new(fs::File::create(
+                self.output_dir
+                    .join(format!("{}.csv", message.publish_time)),
+            )?);
+            writeln!(file, "angle,range,intensity,x,y")?;
+            for (i, range) in scan.ranges.iter().enumerate() {
+                if !scan.is_valid(*range) {
+                    continue;
+                }
+                let angle = scan.angle(i);
+                let intensity = scan.intensities.get(i).copied().unwrap_or(0.0);
+                writeln!(
+                    file,
+                    "{},{},{},{},{}",
+                    angle,
+                    range,
+                    intensity,
+                    range * angle.cos(),
+                    range * angle.sin()
+                )?;
+            }
+            file.flush()?;
+        }
+        Ok(())
+    }
+
+    fn post_process(&mut self, _sigint: Arc<AtomicBool>) -> Result<(), Self::ExtractorError> {
+        Ok(())
+    }
+}
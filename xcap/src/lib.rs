@@ -1,18 +1,78 @@
-use extractor::Extractor;
-use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
-use log::{error, info, warn};
-use std::sync::{atomic::AtomicBool, Arc};
+pub use extractor::{DecodeError, Extractor};
+use log::{info, warn};
+use rayon::prelude::*;
+use std::sync::{atomic::AtomicBool, Arc, Mutex};
 use std::{
-    collections::HashMap,
+    borrow::Cow,
+    cmp::Reverse,
+    collections::{BTreeMap, BinaryHeap, HashMap, HashSet},
     fs, io,
     path::{Path, PathBuf},
 };
 
+pub mod accumulate;
+mod battery;
+mod bayer;
+mod blueprint;
+mod camera_info;
+pub mod colormap;
+pub mod composite;
 mod compressed_image;
+pub mod credentials;
+pub mod decoder;
+mod depth;
+mod diagnostics;
+pub mod diff;
+pub mod digest;
+mod environment;
 mod extractor;
+pub mod extrinsics;
+mod ffmpeg;
+mod frame_index;
+mod generic;
+mod hevc;
 mod image;
+pub mod image_file;
+mod imu;
+mod joint_state;
+mod joy;
+mod las;
+mod laser_scan;
+mod magnetic_field;
+mod manifest;
+mod meta;
+mod navsat;
+mod occupancy_grid;
+mod odometry;
+mod overlay;
+mod path;
+pub mod pcd;
+mod ply;
 mod pointcloud;
+mod pose;
+mod primitive;
+mod range;
+mod raw;
+pub use pointcloud::{CropBox, FieldMap};
+mod pointcloud_parquet;
+pub mod progress;
+mod registry;
+pub use registry::{register_extractor, ExtractorFactory};
+mod remote;
+pub use remote::fetch_slice;
+mod ros2msg;
+mod rosbag2;
+mod rosout;
+mod session;
+pub use session::{DecodedMessage, McapSession};
 pub mod storage;
+#[cfg(test)]
+mod test_util;
+mod tf;
+mod trajectory;
+pub mod verify;
+mod video;
+mod yuv;
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -26,24 +86,289 @@ pub enum Error {
     McapError(#[from] mcap::McapError),
     #[error("IO error. {0}")]
     IOError(#[from] io::Error),
+    #[error("rosbag2 SQLite error. {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("Rerun error. {0}")]
+    Rerun(#[from] rerun::RecordingStreamError),
     #[error("Interrupted")]
     Interrupted,
     #[error("H.264 error. {0}")]
     H264Error(#[from] compressed_image::Error),
-    #[error("Failed to parse message. {0}")]
-    ParserError(String),
+    #[error("{0}")]
+    ParserError(#[from] ParserError),
+    #[error("Composite video error. {0}")]
+    Composite(#[from] composite::Error),
+    #[error("{0}")]
+    Trajectory(#[from] trajectory::Error),
+    #[error("Failed to start jobs. {0}")]
+    ThreadPool(#[from] rayon::ThreadPoolBuildError),
     #[error("unknown error")]
     Unknown,
 }
 
+/// Failure of the parser of a topic, with the message and the file it failed on.
+/// The cause could be told by `kind`, or downcast to the underlying error, like
+/// `std::io::Error` of a full disk.
+#[derive(thiserror::Error, Debug)]
+pub struct ParserError {
+    pub topic: String,
+    // Log time of the message in nanoseconds, None if the parser failed before or after messages
+    pub log_time: Option<u64>,
+    // Input file of the message, None if the parser failed on no particular file
+    pub file: Option<PathBuf>,
+    #[source]
+    pub cause: Box<dyn std::error::Error + Send + Sync>,
+}
+
+impl ParserError {
+    fn new(topic: &str, cause: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> Self {
+        ParserError {
+            topic: topic.to_owned(),
+            log_time: None,
+            file: None,
+            cause: cause.into(),
+        }
+    }
+
+    fn at(self, log_time: u64, file: &Path) -> Self {
+        ParserError {
+            log_time: Some(log_time),
+            file: Some(file.into()),
+            ..self
+        }
+    }
+
+    /// Kind of the failure, by the first known error in the chain of causes.
+    pub fn kind(&self) -> ParserErrorKind {
+        let mut error: Option<&(dyn std::error::Error + 'static)> = Some(self.cause.as_ref());
+        while let Some(e) = error {
+            if e.is::<DecodeError>() {
+                return ParserErrorKind::Payload;
+            }
+            if e.is::<cdr::Error>() || e.is::<serde_json::Error>() || e.is::<ros2msg::Error>() {
+                return ParserErrorKind::Deserialize;
+            }
+            if e.is::<io::Error>() {
+                return ParserErrorKind::Io;
+            }
+            error = e.source();
+        }
+        ParserErrorKind::Other
+    }
+}
+
+/// What the parser of a topic failed on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParserErrorKind {
+    /// The payload could not be decompressed, or transcoded from XCDR2.
+    Payload,
+    /// The message does not match its type, like truncated CDR.
+    Deserialize,
+    /// Files could not be read or written, like on a full disk.
+    Io,
+    /// Anything else, like images in unsupported encodings.
+    Other,
+}
+
+impl std::fmt::Display for ParserError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Failed to parse {}", self.topic)?;
+        if let Some(log_time) = self.log_time {
+            write!(f, " at {}", log_time)?;
+        }
+        if let Some(file) = &self.file {
+            write!(f, " in {}", file.display())?;
+        }
+        write!(f, ". {}", self.cause)
+    }
+}
+
+/// 3D model of the ego vehicle in visualization.
+#[derive(Clone, Debug, Default)]
+pub enum EgoModel {
+    /// A box of a typical car.
+    #[default]
+    Placeholder,
+    /// glTF binary (`.glb`) or other mesh file loaded at runtime.
+    File(PathBuf),
+    /// Do not show the ego vehicle.
+    Hidden,
+}
+
+/// What to do with messages that parsers fail on, like corrupt CDR or zstd payloads.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// Stop the job.
+    #[default]
+    Abort,
+    /// Skip the message, only counted in the summary.
+    Skip,
+    /// Skip the message and log the error.
+    Log,
+}
+
+impl std::str::FromStr for ErrorPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "abort" => Ok(ErrorPolicy::Abort),
+            "skip" => Ok(ErrorPolicy::Skip),
+            "log" => Ok(ErrorPolicy::Log),
+            _ => Err(format!(
+                "Invalid error policy: {}, expected one of skip, abort, log",
+                s
+            )),
+        }
+    }
+}
+
+/// Options of the extraction, everything but the inputs and where the results go.
+#[derive(Clone, Debug)]
+pub struct ExtractOptions {
+    // Names of the topics to extract
+    pub topic_names: Vec<String>,
+
+    // Dump the messages into the output directory, otherwise they are only visualized
+    pub dump_data: bool,
+
+    // Size of the points in the viewer, and the scale of the intensities for coloring
+    pub point_cloud_scale: Option<f32>,
+    pub intensity_scale: Option<f32>,
+
+    // Scale of the images in the viewer
+    pub preview_scale: Option<f32>,
+
+    // How images and video frames are saved
+    pub image_options: image_file::Options,
+    pub video_decoder: decoder::Backend,
+
+    // Coloring of the point clouds, over the domain of the intensities if set
+    pub colormap: colormap::Colormap,
+    pub colormap_domain: Option<(f32, f32)>,
+
+    // Point fields, and the box of points kept
+    pub field_map: FieldMap,
+    pub crop: Option<CropBox>,
+
+    // Static transforms of the topics, applied to the dumped clouds too if `extrinsics_in_output`
+    pub extrinsics: extrinsics::Extrinsics,
+    pub extrinsics_in_output: bool,
+
+    // Back-project depth images into point clouds
+    pub depth_cloud: bool,
+
+    // Dump point clouds as PCD files in the format instead of raw payloads, and into Parquet
+    pub pcd_format: Option<pcd::Format>,
+    pub parquet: bool,
+
+    // Odometry topic the clouds are deskewed with
+    pub deskew: Option<String>,
+
+    // Accumulate the clouds into a map
+    pub accumulate: Option<accumulate::Options>,
+
+    // Messages published within the range are processed, in nanoseconds
+    pub trim_start: i64,
+    pub trim_end: i64,
+
+    // Only write the messages within the range into `trim.mcap`
+    pub trim_only: bool,
+
+    // Number of files processed concurrently for order independent topics
+    pub jobs: usize,
+
+    pub ego_model: EgoModel,
+    pub on_error: ErrorPolicy,
+}
+
+impl Default for ExtractOptions {
+    fn default() -> Self {
+        ExtractOptions {
+            topic_names: vec![],
+            dump_data: true,
+            point_cloud_scale: None,
+            intensity_scale: None,
+            preview_scale: None,
+            image_options: image_file::Options::default(),
+            video_decoder: decoder::Backend::default(),
+            colormap: colormap::Colormap::default(),
+            colormap_domain: None,
+            field_map: FieldMap::default(),
+            crop: None,
+            extrinsics: extrinsics::Extrinsics::default(),
+            extrinsics_in_output: false,
+            depth_cloud: false,
+            pcd_format: None,
+            parquet: false,
+            deskew: None,
+            accumulate: None,
+            trim_start: 0,
+            trim_end: i64::MAX,
+            trim_only: false,
+            jobs: 1,
+            ego_model: EgoModel::default(),
+            on_error: ErrorPolicy::default(),
+        }
+    }
+}
+
 pub struct Topic {
     pub id: u16,
     pub name: String,
     pub format: String,
+    pub encoding: String,
+    pub message_encoding: String,
     pub description: String,
     pub msg_count: Option<u64>,
 }
 
+// Formats with a dedicated parser
+const SUPPORTED_FORMATS: [&str; 27] = [
+    "sensor_msgs/msg/Image",
+    "sensor_msgs/msg/CompressedImage",
+    "sensor_msgs/msg/CameraInfo",
+    "sensor_msgs/msg/Imu",
+    "sensor_msgs/msg/JointState",
+    "sensor_msgs/msg/BatteryState",
+    "sensor_msgs/msg/Range",
+    "sensor_msgs/msg/MagneticField",
+    "sensor_msgs/msg/Temperature",
+    "sensor_msgs/msg/FluidPressure",
+    "sensor_msgs/msg/Joy",
+    "sensor_msgs/msg/LaserScan",
+    "sensor_msgs/msg/NavSatFix",
+    "nav_msgs/msg/Odometry",
+    "nav_msgs/msg/OccupancyGrid",
+    "nav_msgs/msg/Path",
+    "geometry_msgs/msg/PoseStamped",
+    "geometry_msgs/msg/PoseArray",
+    "diagnostic_msgs/msg/DiagnosticArray",
+    "std_msgs/msg/Float32",
+    "std_msgs/msg/Float64",
+    "std_msgs/msg/Int32",
+    "std_msgs/msg/Bool",
+    "std_msgs/msg/String",
+    "rcl_interfaces/msg/Log",
+    "sensor_msgs/msg/PointCloud2",
+    "tf2_msgs/msg/TFMessage",
+];
+
+/// Format of topics without schemas
+pub const UNKNOWN_SCHEMA: &str = "unknown";
+
+impl Topic {
+    /// Could the topic be processed, by a registered, dedicated or the generic parser? Topics
+    /// without schemas are dumped as raw bytes.
+    pub fn is_supported(&self) -> bool {
+        registry::get(&self.format).is_some()
+            || SUPPORTED_FORMATS.contains(&self.format.as_str())
+            || self.format == UNKNOWN_SCHEMA
+            || self.encoding == "ros2msg"
+            || self.message_encoding == "json"
+    }
+}
+
 impl std::fmt::Display for Topic {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -51,10 +376,9 @@ impl std::fmt::Display for Topic {
             "{}, {}, msgs: {}, {}, {}",
             self.id,
             self.name,
-            if self.msg_count.is_some() {
-                self.msg_count.unwrap().to_string()
-            } else {
-                "Unknown".to_owned()
+            match self.msg_count {
+                Some(count) => count.to_string(),
+                None => "Unknown".to_owned(),
             },
             self.format,
             self.description
@@ -62,55 +386,148 @@ impl std::fmt::Display for Topic {
     }
 }
 
-pub fn summary(files: &Vec<PathBuf>) -> Result<Vec<Topic>, Error> {
+/// Channel of the summary, keeping what is needed only, as channels borrow from the file.
+fn summary_channel(channel: &mcap::Channel) -> Arc<mcap::Channel<'static>> {
+    Arc::new(mcap::Channel {
+        topic: channel.topic.clone(),
+        schema: channel.schema.as_ref().map(|s| {
+            Arc::new(mcap::Schema {
+                name: s.name.clone(),
+                encoding: s.encoding.clone(),
+                data: std::borrow::Cow::Owned(Vec::new()),
+            })
+        }),
+        message_encoding: channel.message_encoding.clone(),
+        metadata: Default::default(),
+    })
+}
+
+/// Channels of the MCAP file with their message counts, by scanning the data section. Files of
+/// crashed recorders are read until the first truncated or corrupted record.
+#[allow(clippy::type_complexity)]
+fn scan_channels(
+    mmap: &[u8],
+) -> Result<Vec<(u16, Arc<mcap::Channel<'static>>, Option<u64>)>, Error> {
+    let records = mcap::read::ChunkFlattener::new_with_options(
+        mmap,
+        mcap::read::Options::IgnoreEndMagic.into(),
+    )?;
+    let mut schemas: HashMap<u16, Arc<mcap::Schema>> = HashMap::new();
+    let mut channels: BTreeMap<u16, (Arc<mcap::Channel>, u64)> = BTreeMap::new();
+    for record in records {
+        match record {
+            Ok(mcap::records::Record::Schema { header, data }) => {
+                let schema = mcap::Schema {
+                    name: header.name,
+                    encoding: header.encoding,
+                    data,
+                };
+                schemas.insert(header.id, Arc::new(schema));
+            }
+            Ok(mcap::records::Record::Channel(c)) => {
+                let channel = mcap::Channel {
+                    topic: c.topic,
+                    schema: schemas.get(&c.schema_id).cloned(),
+                    message_encoding: c.message_encoding,
+                    metadata: c.metadata,
+                };
+                channels.insert(c.id, (Arc::new(channel), 0));
+            }
+            Ok(mcap::records::Record::Message { header, .. }) => {
+                if let Some((_, count)) = channels.get_mut(&header.channel_id) {
+                    *count += 1;
+                }
+            }
+            Ok(mcap::records::Record::DataEnd(_)) => break,
+            Ok(_) => {}
+            Err(e) => {
+                warn!("Stopped counting at a truncated or corrupted record. {}", e);
+                break;
+            }
+        }
+    }
+    Ok(channels
+        .into_iter()
+        .map(|(id, (channel, count))| (id, summary_channel(&channel), Some(count)))
+        .collect())
+}
+
+pub fn summary(files: &[PathBuf]) -> Result<Vec<Topic>, Error> {
     // Collect all topics
     let mut topics: HashMap<u16, Topic> = HashMap::new();
 
     // Enumerate all files
     for file in files {
-        // Read summary
-        let fd = fs::File::open(file)?;
-        let mmap = unsafe { memmap2::Mmap::map(&fd)? };
-        let summary = match mcap::read::Summary::read(&mmap) {
-            Ok(summary) => summary.unwrap(),
-            Err(e) => {
-                warn!("Failed to read summary from {}: {}", file.display(), e);
-                continue;
+        // Channels and their message counts
+        let channels: Vec<(u16, Arc<mcap::Channel>, Option<u64>)> = if rosbag2::is_db3(file) {
+            rosbag2::Reader::open(file)?
+                .topics()
+                .map(|t| (t.id as u16, t.channel.clone(), Some(t.msg_count)))
+                .collect()
+        } else {
+            // Read summary
+            let fd = fs::File::open(file)?;
+            let mmap = unsafe { memmap2::Mmap::map(&fd)? };
+            match mcap::read::Summary::read(&mmap) {
+                Ok(Some(summary)) if summary.stats.is_some() => {
+                    let stats = summary.stats.unwrap();
+                    summary
+                        .channels
+                        .into_iter()
+                        .map(|(id, channel)| {
+                            let count = stats.channel_message_counts.get(&id).copied();
+                            (id, summary_channel(&channel), count)
+                        })
+                        .collect()
+                }
+                Ok(_) => {
+                    info!("No statistics in {}, counting messages.", file.display());
+                    scan_channels(&mmap)?
+                }
+                Err(e) => {
+                    warn!(
+                        "Failed to read summary from {}: {}. Counting messages.",
+                        file.display(),
+                        e
+                    );
+                    scan_channels(&mmap)?
+                }
             }
         };
 
-        // Statistics
-        let stats = summary
-            .stats
-            .ok_or(Error::NoStatistics(file.display().to_string()))?;
-
         // Topics
-        for chn in summary.channels {
+        for (id, channel, count) in channels {
+            // Schemaless channels, like JSON encoded ones, are allowed.
+            let (format, encoding) = channel.schema.as_ref().map_or_else(
+                || (UNKNOWN_SCHEMA.to_string(), String::new()),
+                |s| (s.name.clone(), s.encoding.clone()),
+            );
+            let description = match channel.schema {
+                Some(_) => format!("Encoding: {}", encoding),
+                None => format!("No schema, message encoding: {}", channel.message_encoding),
+            };
             topics
-                .entry(chn.0)
+                .entry(id)
                 .and_modify(|t| {
-                    t.id = chn.0;
-                    t.name.clone_from(&chn.1.topic);
-                    t.format.clone_from(&chn.1.schema.as_ref().unwrap().name);
-                    t.description =
-                        format!("Encoding: {}", chn.1.schema.as_ref().unwrap().encoding);
-                    t.msg_count = if t.msg_count.is_some()
-                        && stats.channel_message_counts.contains_key(&chn.0)
-                    {
-                        Some(
-                            t.msg_count.unwrap()
-                                + stats.channel_message_counts.get(&chn.0).unwrap(),
-                        )
-                    } else {
-                        None
+                    t.id = id;
+                    t.name.clone_from(&channel.topic);
+                    t.format.clone_from(&format);
+                    t.encoding.clone_from(&encoding);
+                    t.message_encoding.clone_from(&channel.message_encoding);
+                    t.description.clone_from(&description);
+                    t.msg_count = match (t.msg_count, count) {
+                        (Some(count), Some(more)) => Some(count + more),
+                        _ => None,
                     };
                 })
                 .or_insert(Topic {
-                    id: chn.0,
-                    name: chn.1.topic.clone(),
-                    format: chn.1.schema.as_ref().unwrap().name.clone(),
-                    description: format!("Encoding: {}", chn.1.schema.as_ref().unwrap().encoding),
-                    msg_count: stats.channel_message_counts.get(&chn.0).copied(),
+                    id,
+                    name: channel.topic.clone(),
+                    format: format.clone(),
+                    encoding: encoding.clone(),
+                    message_encoding: channel.message_encoding.clone(),
+                    description,
+                    msg_count: count,
                 });
         }
     }
@@ -119,100 +536,349 @@ pub fn summary(files: &Vec<PathBuf>) -> Result<Vec<Topic>, Error> {
     Ok(topics)
 }
 
-pub fn process(
-    files: &Vec<PathBuf>,
-    output_dir: &Path,
-    topic_names: &Vec<String>,
-    sigint: Arc<AtomicBool>,
+/// Schema of the topic with its data, like the ros2msg text or the protobuf file descriptor set,
+/// from the first file having the topic. None if no file has the topic, or it has no schema.
+pub fn schema(files: &[PathBuf], topic: &str) -> Result<Option<mcap::Schema<'static>>, Error> {
+    for file in files {
+        let schema = match Input::open(file)? {
+            Input::Rosbag2(reader) => reader
+                .topics()
+                .find(|t| t.channel.topic == topic)
+                .map(|t| t.channel.schema.as_deref().map(owned_schema)),
+            Input::Mcap(mmap) => mcap_schema(&mmap, topic)?,
+        };
+        if let Some(schema) = schema {
+            return Ok(schema);
+        }
+    }
+    Ok(None)
+}
+
+fn owned_schema(schema: &mcap::Schema) -> mcap::Schema<'static> {
+    mcap::Schema {
+        name: schema.name.clone(),
+        encoding: schema.encoding.clone(),
+        data: std::borrow::Cow::Owned(schema.data.to_vec()),
+    }
+}
+
+/// Schema of the channel of the topic in the MCAP file, from the summary, or from the records of
+/// the data section if there is no summary. None if there is no such channel.
+fn mcap_schema(mmap: &[u8], topic: &str) -> Result<Option<Option<mcap::Schema<'static>>>, Error> {
+    if let Ok(Some(summary)) = mcap::read::Summary::read(mmap) {
+        return Ok(summary
+            .channels
+            .values()
+            .find(|c| c.topic == topic)
+            .map(|c| c.schema.as_deref().map(owned_schema)));
+    }
+    let mut schemas = HashMap::new();
+    for record in mcap::read::LinearReader::new(mmap)? {
+        match record? {
+            mcap::records::Record::Schema { header, data } => {
+                schemas.insert(header.id, (header.name, header.encoding, data));
+            }
+            mcap::records::Record::Channel(channel) if channel.topic == topic => {
+                let schema = schemas
+                    .remove(&channel.schema_id)
+                    .map(|(name, encoding, data)| mcap::Schema {
+                        name,
+                        encoding,
+                        data: std::borrow::Cow::Owned(data.into_owned()),
+                    });
+                return Ok(Some(schema));
+            }
+            _ => {}
+        }
+    }
+    Ok(None)
+}
+
+/// Log time of the first message in all the files, None if there is no message at all.
+pub fn start_time(files: &[PathBuf]) -> Result<Option<u64>, Error> {
+    let mut start = None;
+    for file in files {
+        if let Some(t) = Input::open(file)?.start_time()? {
+            start = Some(start.map_or(t, |s: u64| s.min(t)));
+        }
+    }
+    Ok(start)
+}
+
+/// Parser of a topic.
+pub type BoxedExtractor =
+    Box<dyn Extractor<ExtractorError = Box<dyn std::error::Error + Send + Sync>>>;
+
+/// Everything needed to create a parser for a topic.
+struct ParserContext {
+    output_dir: PathBuf,
     vis_stream: Option<rerun::RecordingStream>,
     dump_data: bool,
     point_cloud_scale: Option<f32>,
     intensity_scale: Option<f32>,
-    topics: Vec<Topic>,
-    trim_start: i64,
-    trim_end: i64,
-    trim_only: bool,
-) -> Result<(), Error> {
-    // Visualization setup, Ego content from disk file
-    let ego = include_bytes!("/home/robin/Documents/3d-models/ego.glb").to_vec();
-    if let Some(rec) = &vis_stream {
-        rec.log_static("/", &rerun::ViewCoordinates::FLU).unwrap();
-        rec.log_static(
-            "ego",
-            &rerun::Asset3D::from_file_contents(ego, Some(rerun::MediaType::glb())),
-        )
-        .unwrap();
-        rec.log_static(
-            "ego",
-            &rerun::Transform3D::from_translation_rotation_scale(
-                rerun::Vec3D::from([-0.35, 0.0, -0.8]),
-                rerun::Quaternion::from_xyzw([0.5, 0.5, 0.5, 0.5]),
-                rerun::Scale3D::from(0.3),
-            ),
-        )
-        .unwrap();
-    }
-
-    // Setup a progress bar as this could be a time consuming process.
-    let bars = MultiProgress::new();
-    let sty = ProgressStyle::with_template(
-        "[{elapsed_precise}] {bar:40.cyan/blue} {pos:>7}/{len:7} {msg}",
-    )
-    .unwrap()
-    .progress_chars("##-");
-    let mut bar_handles: HashMap<&str, ProgressBar> = HashMap::new();
-
-    // Create a parser group for all different topics.
-    let mut parsers: HashMap<
-        &str,
-        Box<dyn Extractor<ExtractorError = Box<dyn std::error::Error>>>,
-    > = HashMap::new();
-    for topic_name in topic_names {
-        // Locate corresponding topic
-        let topic = topics
-            .iter()
-            .find(|x| x.name == *topic_name)
-            .ok_or(Error::InvalidTopic(format!(
-                "Topic not found: {}",
-                topic_name
-            )))?;
+    preview_scale: Option<f32>,
+    image_options: image_file::Options,
+    video_decoder: decoder::Backend,
+    colormap: colormap::Colormap,
+    colormap_domain: Option<(f32, f32)>,
+    field_map: FieldMap,
+    crop: Option<CropBox>,
+    extrinsics: extrinsics::Extrinsics,
+    extrinsics_in_output: bool,
+    depth_cloud: bool,
+    pcd_format: Option<pcd::Format>,
+    parquet: bool,
+    deskew: Option<Arc<trajectory::Trajectory>>,
+    map: Option<Arc<Mutex<accumulate::Map>>>,
+    frame_tree: Arc<Mutex<tf::FrameTree>>,
+    frame_index: Arc<Mutex<frame_index::FrameIndex>>,
+    on_error: ErrorPolicy,
+    // Messages skipped by the error policy, by topic
+    skipped: Mutex<BTreeMap<String, u64>>,
+    // Messages processed, by topic
+    processed: Mutex<BTreeMap<String, meta::Processed>>,
+}
 
-        // Using topic name as output directory path
-        let output_dir = output_dir.join(PathBuf::from(topic_name.trim_start_matches('/')));
+impl ParserContext {
+    /// Apply the error policy to the failed message, the error is returned if the job should stop.
+    fn on_error(&self, error: ParserError) -> Result<(), Error> {
+        match self.on_error {
+            ErrorPolicy::Abort => return Err(error.into()),
+            ErrorPolicy::Skip => {}
+            ErrorPolicy::Log => warn!("Message skipped. {}", error),
+        }
+        *self.skipped.lock().unwrap().entry(error.topic).or_default() += 1;
+        Ok(())
+    }
+
+    /// Count the message processed successfully.
+    fn processed(&self, topic: &str, log_time: u64) {
+        self.processed
+            .lock()
+            .unwrap()
+            .entry(topic.to_owned())
+            .or_default()
+            .add(log_time);
+    }
 
-        // Create parser by topic format
+    /// Whether the parsers of the topic could process several files concurrently. Messages of
+    /// these formats are independent of each other, and every message is dumped into its own
+    /// file. Images are sampled, named and collected into digests in message order, clouds are
+    /// accumulated with the transforms of their time, and exported into one Parquet file per
+    /// topic, which a parser per file would break. TF only keeps the latest transforms, so
+    /// clouds cropped in a TF frame or deskewed, and sensors shown in their TF frames, stay in
+    /// order too.
+    fn is_concurrent(&self, topic: &Topic) -> bool {
+        // Sensors shown in the viewer are placed in their TF frames, unless the extrinsics are set
+        let placed_by_tf = self.vis_stream.is_some() && self.extrinsics.get(&topic.name).is_none();
         match topic.format.as_str() {
             "sensor_msgs/msg/Image" => {
-                parsers.insert(
-                    topic.name.as_str(),
-                    Box::new(image::Parser::new(
-                        &output_dir,
-                        vis_stream.clone(),
-                        dump_data,
-                    )),
-                );
+                let options = &self.image_options;
+                options.sampling == image_file::Sampling::All
+                    && options.name_template.is_none()
+                    && options.digest.is_none()
+                    && !options.burn_timestamp
             }
-            "sensor_msgs/msg/CompressedImage" => {
-                parsers.insert(
-                    topic.name.as_str(),
-                    Box::new(compressed_image::Parser::new(
-                        &output_dir,
-                        vis_stream.clone(),
-                        dump_data,
-                    )),
+            "sensor_msgs/msg/PointCloud2" => {
+                self.map.is_none()
+                    && !self.parquet
+                    && self.deskew.is_none()
+                    && self.crop.as_ref().and_then(|c| c.frame.as_ref()).is_none()
+                    && !placed_by_tf
+            }
+            "sensor_msgs/msg/LaserScan" => !placed_by_tf,
+            _ => false,
+        }
+    }
+
+    /// Create parser by topic format.
+    fn create(&self, topic: &Topic) -> Result<BoxedExtractor, Error> {
+        // Using topic name as output directory path
+        let output_dir = self
+            .output_dir
+            .join(PathBuf::from(topic.name.trim_start_matches('/')));
+        let vis_stream = self.vis_stream.clone();
+        let dump_data = self.dump_data;
+        let frame_tree = self.frame_tree.clone();
+
+        if let Some(factory) = registry::get(&topic.format) {
+            return Ok(factory(&output_dir, vis_stream, dump_data));
+        }
+
+        let parser: BoxedExtractor = match topic.format.as_str() {
+            _ if topic.message_encoding == "json" => {
+                Box::new(generic::Parser::new(&output_dir, vis_stream, dump_data))
+            }
+            "sensor_msgs/msg/Image" => Box::new(image::Parser::new(
+                &output_dir,
+                vis_stream,
+                dump_data,
+                frame_tree,
+                self.depth_cloud,
+                self.pcd_format,
+                self.preview_scale,
+                self.image_options.clone(),
+                self.frame_index.clone(),
+            )),
+            "sensor_msgs/msg/CompressedImage" => Box::new(compressed_image::Parser::new(
+                &output_dir,
+                vis_stream,
+                dump_data,
+                frame_tree,
+                self.preview_scale,
+                self.image_options.clone(),
+                self.frame_index.clone(),
+                self.video_decoder,
+            )),
+            "sensor_msgs/msg/CameraInfo" => Box::new(camera_info::Parser::new(
+                &output_dir,
+                vis_stream,
+                dump_data,
+                frame_tree,
+                self.extrinsics.get(&topic.name),
+            )),
+            "sensor_msgs/msg/Imu" => Box::new(imu::Parser::new(&output_dir, vis_stream, dump_data)),
+            "sensor_msgs/msg/JointState" => {
+                Box::new(joint_state::Parser::new(&output_dir, vis_stream, dump_data))
+            }
+            "sensor_msgs/msg/BatteryState" => {
+                Box::new(battery::Parser::new(&output_dir, vis_stream, dump_data))
+            }
+            "sensor_msgs/msg/Range" => Box::new(range::Parser::new(
+                &output_dir,
+                vis_stream,
+                dump_data,
+                frame_tree,
+            )),
+            "sensor_msgs/msg/MagneticField" => Box::new(magnetic_field::Parser::new(
+                &output_dir,
+                vis_stream,
+                dump_data,
+            )),
+            "sensor_msgs/msg/Temperature" => Box::new(environment::Parser::new(
+                &output_dir,
+                vis_stream,
+                dump_data,
+                environment::Quantity::Temperature,
+            )),
+            "sensor_msgs/msg/FluidPressure" => Box::new(environment::Parser::new(
+                &output_dir,
+                vis_stream,
+                dump_data,
+                environment::Quantity::FluidPressure,
+            )),
+            "sensor_msgs/msg/Joy" => Box::new(joy::Parser::new(&output_dir, vis_stream, dump_data)),
+            "sensor_msgs/msg/LaserScan" => Box::new(laser_scan::Parser::new(
+                &output_dir,
+                vis_stream,
+                dump_data,
+                frame_tree,
+            )),
+            "sensor_msgs/msg/NavSatFix" => {
+                Box::new(navsat::Parser::new(&output_dir, vis_stream, dump_data))
+            }
+            "nav_msgs/msg/Odometry" => Box::new(odometry::Parser::new(
+                &output_dir,
+                vis_stream,
+                dump_data,
+                frame_tree,
+            )),
+            "nav_msgs/msg/OccupancyGrid" => Box::new(occupancy_grid::Parser::new(
+                &output_dir,
+                vis_stream,
+                dump_data,
+                frame_tree,
+            )),
+            "nav_msgs/msg/Path" => Box::new(path::Parser::new(
+                &output_dir,
+                vis_stream,
+                dump_data,
+                frame_tree,
+            )),
+            "geometry_msgs/msg/PoseStamped" => Box::new(pose::Parser::new(
+                &output_dir,
+                vis_stream,
+                dump_data,
+                frame_tree,
+                false,
+            )),
+            "geometry_msgs/msg/PoseArray" => Box::new(pose::Parser::new(
+                &output_dir,
+                vis_stream,
+                dump_data,
+                frame_tree,
+                true,
+            )),
+            "diagnostic_msgs/msg/DiagnosticArray" => {
+                Box::new(diagnostics::Parser::new(&output_dir, vis_stream, dump_data))
+            }
+            "std_msgs/msg/Float32" => Box::new(primitive::Parser::new(
+                &output_dir,
+                vis_stream,
+                dump_data,
+                primitive::Kind::Float32,
+            )),
+            "std_msgs/msg/Float64" => Box::new(primitive::Parser::new(
+                &output_dir,
+                vis_stream,
+                dump_data,
+                primitive::Kind::Float64,
+            )),
+            "std_msgs/msg/Int32" => Box::new(primitive::Parser::new(
+                &output_dir,
+                vis_stream,
+                dump_data,
+                primitive::Kind::Int32,
+            )),
+            "std_msgs/msg/Bool" => Box::new(primitive::Parser::new(
+                &output_dir,
+                vis_stream,
+                dump_data,
+                primitive::Kind::Bool,
+            )),
+            "std_msgs/msg/String" => Box::new(primitive::Parser::new(
+                &output_dir,
+                vis_stream,
+                dump_data,
+                primitive::Kind::String,
+            )),
+            "rcl_interfaces/msg/Log" => {
+                Box::new(rosout::Parser::new(&output_dir, vis_stream, dump_data))
+            }
+            "sensor_msgs/msg/PointCloud2" => Box::new(pointcloud::Parser::new(
+                &output_dir,
+                vis_stream,
+                dump_data,
+                self.point_cloud_scale,
+                self.intensity_scale,
+                self.colormap
+                    .gradient(self.colormap_domain)
+                    .map_err(|e| ParserError::new(&topic.name, e.to_string()))?,
+                self.field_map.clone(),
+                self.crop.clone(),
+                self.extrinsics.get(&topic.name),
+                self.extrinsics_in_output,
+                self.pcd_format,
+                self.parquet,
+                frame_tree,
+                self.deskew.clone(),
+                self.map.clone(),
+                self.frame_index.clone(),
+            )),
+            "tf2_msgs/msg/TFMessage" => Box::new(tf::Parser::new(vis_stream, frame_tree)),
+            UNKNOWN_SCHEMA => {
+                warn!(
+                    "{} has no schema, payloads are dumped as they are.",
+                    topic.name
                 );
+                Box::new(raw::Parser::new(&output_dir, dump_data))
             }
-            "sensor_msgs/msg/PointCloud2" => {
-                parsers.insert(
-                    topic.name.as_str(),
-                    Box::new(pointcloud::Parser::new(
-                        &output_dir,
-                        vis_stream.clone(),
-                        dump_data,
-                        point_cloud_scale,
-                        intensity_scale,
-                    )),
+            _ if topic.encoding == "ros2msg" => {
+                info!(
+                    "No dedicated parser for {}, {} is decoded with its schema.",
+                    topic.format, topic.name
                 );
+                Box::new(generic::Parser::new(&output_dir, vis_stream, dump_data))
             }
             _ => {
                 return Err(Error::InvalidTopic(format!(
@@ -220,35 +886,403 @@ pub fn process(
                     topic.format
                 )));
             }
+        };
+        Ok(parser)
+    }
+}
+
+type MessageIter<'a> = Box<dyn Iterator<Item = mcap::McapResult<mcap::Message<'a>>> + 'a>;
+
+/// Input file, MCAP or rosbag2 SQLite storage.
+enum Input {
+    Mcap(memmap2::Mmap),
+    Rosbag2(rosbag2::Reader),
+}
+
+impl Input {
+    fn open(file: &Path) -> Result<Self, Error> {
+        if rosbag2::is_db3(file) {
+            return Ok(Input::Rosbag2(rosbag2::Reader::open(file)?));
         }
+        let fd = fs::File::open(file)?;
+        Ok(Input::Mcap(unsafe { memmap2::Mmap::map(&fd)? }))
+    }
 
-        // Init progress bars
-        bar_handles.insert(
-            topic_name,
-            bars.add(ProgressBar::new(topic.msg_count.unwrap_or(0))),
-        );
-        for h in bar_handles.values_mut() {
-            h.set_style(sty.clone());
+    /// Summary section of MCAP files, used to skip chunks.
+    fn summary(&self) -> Option<mcap::read::Summary<'_>> {
+        match self {
+            Input::Mcap(mmap) => mcap::read::Summary::read(mmap).ok().flatten(),
+            Input::Rosbag2(_) => None,
         }
     }
 
-    // Trim only mode?
-    let mut trim_out = if trim_only {
-        Some(mcap::Writer::new(std::io::BufWriter::new(
-            fs::File::create("trim.mcap")?,
-        ))?)
-    } else {
-        None
+    /// Summary section owning its channels and schemas, so that it could be kept along with the
+    /// input.
+    fn owned_summary(&self) -> Option<mcap::read::Summary<'static>> {
+        let summary = self.summary()?;
+        let owned = |schema: &mcap::Schema<'_>| {
+            Arc::new(mcap::Schema {
+                name: schema.name.clone(),
+                encoding: schema.encoding.clone(),
+                data: Cow::Owned(schema.data.to_vec()),
+            })
+        };
+        let channels = summary
+            .channels
+            .iter()
+            .map(|(id, channel)| {
+                let channel = mcap::Channel {
+                    topic: channel.topic.clone(),
+                    schema: channel.schema.as_deref().map(owned),
+                    message_encoding: channel.message_encoding.clone(),
+                    metadata: channel.metadata.clone(),
+                };
+                (*id, Arc::new(channel))
+            })
+            .collect();
+        let schemas = summary
+            .schemas
+            .iter()
+            .map(|(id, schema)| (*id, owned(schema)))
+            .collect();
+        Some(mcap::read::Summary {
+            stats: summary.stats,
+            channels,
+            schemas,
+            chunk_indexes: summary.chunk_indexes,
+            attachment_indexes: summary.attachment_indexes,
+            metadata_indexes: summary.metadata_indexes,
+        })
+    }
+
+    /// Messages of the selected topics.
+    fn messages<'a>(
+        &'a self,
+        summary: Option<&'a mcap::read::Summary<'a>>,
+        topics: &[&str],
+    ) -> Result<MessageIter<'a>, Error> {
+        match self {
+            Input::Mcap(mmap) => read_messages(mmap, summary, topics),
+            Input::Rosbag2(reader) => {
+                Ok(Box::new(reader.messages(Some(topics)).map(|message| {
+                    message.map(|message| message as mcap::Message<'a>)
+                })))
+            }
+        }
+    }
+
+    /// Log time of the first message. Taken from the statistics if available, otherwise all the
+    /// messages are scanned.
+    fn start_time(&self) -> Result<Option<u64>, Error> {
+        match self {
+            Input::Mcap(_) => {
+                if let Some(stats) = self.summary().and_then(|s| s.stats) {
+                    return Ok((stats.message_count > 0).then_some(stats.message_start_time));
+                }
+                let mut start = None;
+                for message in self.all_messages()? {
+                    let log_time = message?.log_time;
+                    start = Some(start.map_or(log_time, |t: u64| t.min(log_time)));
+                }
+                Ok(start)
+            }
+            Input::Rosbag2(reader) => Ok(reader.start_time()?),
+        }
+    }
+
+    /// All the messages, owning their data.
+    fn all_messages(
+        &self,
+    ) -> Result<Box<dyn Iterator<Item = mcap::McapResult<mcap::Message<'static>>> + '_>, Error>
+    {
+        match self {
+            Input::Mcap(mmap) => Ok(Box::new(mcap::MessageStream::new(mmap)?)),
+            Input::Rosbag2(reader) => Ok(Box::new(reader.messages(None))),
+        }
+    }
+}
+
+/// Messages of the selected topics in the file. With the chunk indexes in the summary section,
+/// chunks without any of the selected channels are skipped without being decompressed. Fall back
+/// to reading the whole file if it is not indexed, or the summary is broken like in files still
+/// being recorded.
+fn read_messages<'a>(
+    mcap: &'a [u8],
+    summary: Option<&'a mcap::read::Summary<'a>>,
+    topics: &[&str],
+) -> Result<MessageIter<'a>, Error> {
+    let Some(summary) = summary.filter(|s| !s.chunk_indexes.is_empty()) else {
+        // Files without summaries could be truncated, read as many messages as possible
+        let messages = mcap::MessageStream::new_with_options(
+            mcap,
+            mcap::read::Options::IgnoreEndMagic.into(),
+        )?;
+        // Messages from the stream own their data, shorten the lifetime to match.
+        return Ok(Box::new(messages.map(|message| {
+            message.map(|message| message as mcap::Message<'a>)
+        })));
     };
 
-    // Enumerate all files
-    for file in files.iter() {
+    let channels: HashSet<u16> = summary
+        .channels
+        .iter()
+        .filter(|(_, channel)| topics.contains(&channel.topic.as_str()))
+        .map(|(id, _)| *id)
+        .collect();
+
+    // Chunks without message indexes could not be told apart, keep them.
+    let mut chunks: Vec<_> = summary
+        .chunk_indexes
+        .iter()
+        .filter(|chunk| {
+            chunk.message_index_offsets.is_empty()
+                || chunk
+                    .message_index_offsets
+                    .keys()
+                    .any(|id| channels.contains(id))
+        })
+        .collect();
+    chunks.sort_by_key(|chunk| chunk.chunk_start_offset);
+
+    // Chunks are decompressed one by one while iterating.
+    Ok(Box::new(chunks.into_iter().flat_map(
+        move |chunk| match summary.stream_chunk(mcap, chunk) {
+            Ok(messages) => Box::new(messages) as MessageIter<'a>,
+            Err(e) => Box::new(std::iter::once(Err(e))),
+        },
+    )))
+}
+
+/// Process one file with a dedicated set of parsers. Used for order independent topics only.
+fn process_file_concurrently(
+    file: &Path,
+    topics: &[&Topic],
+    context: &ParserContext,
+    progress: &dyn progress::ProgressSink,
+    sigint: &AtomicBool,
+    trim_start: i64,
+    trim_end: i64,
+) -> Result<(), Error> {
+    let mut parsers: HashMap<&str, BoxedExtractor> = HashMap::new();
+    for topic in topics {
+        parsers.insert(topic.name.as_str(), context.create(topic)?);
+    }
+
+    let input = Input::open(file)?;
+    progress.on_file_start(file);
+    let summary = input.summary();
+    let topic_names: Vec<&str> = parsers.keys().copied().collect();
+    for parser in parsers.values_mut() {
+        parser.set_source(file);
+    }
+    for message in input.messages(summary.as_ref(), &topic_names)? {
+        // Check for interrupt
+        if sigint.load(std::sync::atomic::Ordering::Relaxed) {
+            return Err(Error::Interrupted);
+        }
+
+        let msg = message?;
+
+        // Trim start/end
+        if msg.publish_time < trim_start as u64 {
+            continue;
+        }
+        if msg.publish_time > trim_end as u64 {
+            break;
+        }
+
+        // Parse message
+        let topic_name = msg.channel.topic.as_str();
+        let Some(parser) = parsers.get_mut(topic_name) else {
+            continue;
+        };
+        match parser.step(&msg) {
+            Ok(()) => context.processed(topic_name, msg.log_time),
+            Err(e) => context.on_error(ParserError::new(topic_name, e).at(msg.log_time, file))?,
+        }
+        progress.on_message(topic_name);
+    }
+
+    let sigint = Arc::new(AtomicBool::new(
+        sigint.load(std::sync::atomic::Ordering::Relaxed),
+    ));
+    for (name, parser) in parsers.iter_mut() {
+        parser
+            .post_process(sigint.clone())
+            .map_err(|e| ParserError {
+                file: Some(file.into()),
+                ..ParserError::new(name, e)
+            })?;
+    }
+    Ok(())
+}
+
+/// Show the ego vehicle at the origin.
+fn log_ego(rec: &rerun::RecordingStream, ego_model: &EgoModel) -> Result<(), Error> {
+    match ego_model {
+        EgoModel::Placeholder => {
+            rec.log_static(
+                "ego",
+                &rerun::Boxes3D::from_centers_and_half_sizes(
+                    [(0.0, 0.0, -0.8)],
+                    [(2.3, 0.9, 0.75)],
+                )
+                .with_colors([rerun::Color::from_rgb(128, 128, 128)]),
+            )
+            .unwrap();
+        }
+        EgoModel::File(path) => {
+            rec.log_static("ego", &rerun::Asset3D::from_file(path)?)
+                .unwrap();
+            rec.log_static(
+                "ego",
+                &rerun::Transform3D::from_translation_rotation_scale(
+                    rerun::Vec3D::from([-0.35, 0.0, -0.8]),
+                    rerun::Quaternion::from_xyzw([0.5, 0.5, 0.5, 0.5]),
+                    rerun::Scale3D::from(0.3),
+                ),
+            )
+            .unwrap();
+        }
+        EgoModel::Hidden => {}
+    }
+    Ok(())
+}
+
+/// Extract the selected topics of the files into the output directory, and show them in the
+/// viewer if a stream is given. `topics` are all the topics of the files, see `summary`.
+pub fn process(
+    files: &[PathBuf],
+    output_dir: &Path,
+    topics: Vec<Topic>,
+    options: &ExtractOptions,
+    sigint: Arc<AtomicBool>,
+    vis_stream: Option<rerun::RecordingStream>,
+    progress: &dyn progress::ProgressSink,
+) -> Result<(), Error> {
+    let image_options = &options.image_options;
+    let deskew = options.deskew.as_deref();
+    let accumulate = options.accumulate.as_ref();
+    let (dump_data, pcd_format, trim_start, trim_end) = (
+        options.dump_data,
+        options.pcd_format,
+        options.trim_start,
+        options.trim_end,
+    );
+
+    // Visualization setup
+    if let Some(rec) = &vis_stream {
+        rec.log_static("/", &rerun::ViewCoordinates::FLU).unwrap();
+        log_ego(rec, &options.ego_model)?;
+    }
+
+    let deskew_trajectory = match deskew {
+        Some(topic) => Some(Arc::new(trajectory::Trajectory::load(files, topic)?)),
+        None => None,
+    };
+
+    // Map of the accumulated clouds. Odometry topics give the poses of the clouds, frames are
+    // looked up in TF otherwise.
+    let map = match accumulate {
+        Some(options) => {
+            let source = &options.source;
+            let is_odometry = topics
+                .iter()
+                .any(|t| t.name == *source && t.format == "nav_msgs/msg/Odometry");
+            let source = match &deskew_trajectory {
+                Some(trajectory) if is_odometry && deskew == Some(source) => {
+                    accumulate::Source::Odometry(trajectory.clone())
+                }
+                _ if is_odometry => accumulate::Source::Odometry(Arc::new(
+                    trajectory::Trajectory::load(files, source)?,
+                )),
+                _ => accumulate::Source::Frame(source.clone()),
+            };
+            Some(Arc::new(Mutex::new(accumulate::Map::new(
+                source,
+                options.voxel,
+                dump_data,
+            ))))
+        }
+        None => None,
+    };
+
+    // Everything parsers need, transform tree is shared by all of them.
+    let context = ParserContext {
+        output_dir: output_dir.into(),
+        vis_stream: vis_stream.clone(),
+        dump_data,
+        point_cloud_scale: options.point_cloud_scale,
+        intensity_scale: options.intensity_scale,
+        preview_scale: options.preview_scale,
+        image_options: image_file::Options {
+            name_template: image_options
+                .name_template
+                .clone()
+                .map(|t| t.under(output_dir)),
+            ..image_options.clone()
+        },
+        video_decoder: options.video_decoder,
+        colormap: options.colormap.clone(),
+        colormap_domain: options.colormap_domain,
+        field_map: options.field_map.clone(),
+        crop: options.crop.clone(),
+        extrinsics: options.extrinsics.clone(),
+        extrinsics_in_output: options.extrinsics_in_output,
+        depth_cloud: options.depth_cloud,
+        pcd_format,
+        parquet: options.parquet,
+        deskew: deskew_trajectory.clone(),
+        map: map.clone(),
+        frame_tree: tf::FrameTree::shared(),
+        frame_index: frame_index::FrameIndex::shared(output_dir, image_options.seq_names),
+        on_error: options.on_error,
+        skipped: Mutex::new(BTreeMap::new()),
+        processed: Mutex::new(BTreeMap::new()),
+    };
+
+    // Create a parser group for all different topics. Order independent topics are processed
+    // file by file concurrently if multiple jobs are allowed.
+    let mut parsers: HashMap<&str, BoxedExtractor> = HashMap::new();
+    let mut concurrent_topics: Vec<&Topic> = vec![];
+    for topic_name in &options.topic_names {
+        // Locate corresponding topic
+        let topic = topics
+            .iter()
+            .find(|x| x.name == *topic_name)
+            .ok_or(Error::InvalidTopic(format!(
+                "Topic not found: {}",
+                topic_name
+            )))?;
+
+        if options.jobs > 1 && context.is_concurrent(topic) {
+            concurrent_topics.push(topic);
+        } else {
+            parsers.insert(topic.name.as_str(), context.create(topic)?);
+        }
+
+        progress.on_topic_start(topic_name, topic.msg_count);
+    }
+
+    // Trim only mode?
+    if options.trim_only {
+        return trim(files, &sigint, trim_start, trim_end);
+    }
+
+    // Enumerate all files. Topics which depend on message order go first, so that the transform
+    // tree is ready for the others.
+    let sequential_topics: Vec<&str> = parsers.keys().copied().collect();
+    for file in files.iter().filter(|_| !sequential_topics.is_empty()) {
         // Read in files
-        let fd = fs::File::open(file)?;
-        let mmap = unsafe { memmap2::Mmap::map(&fd)? };
+        let input = Input::open(file)?;
+        progress.on_file_start(file);
+        let summary = input.summary();
+        for parser in parsers.values_mut() {
+            parser.set_source(file);
+        }
 
-        // Enumerate all messages
-        for message in mcap::MessageStream::new(&mmap)? {
+        // Enumerate all messages of the selected topics
+        for message in input.messages(summary.as_ref(), &sequential_topics)? {
             // Check for interrupt
             if sigint.load(std::sync::atomic::Ordering::Relaxed) {
                 return Err(Error::Interrupted);
@@ -267,19 +1301,39 @@ pub fn process(
 
             // Parse message
             let topic_name = msg.channel.topic.as_str();
-            if trim_only {
-                trim_out.as_mut().unwrap().write(&msg)?;
-            } else {
-                let Some(parser) = parsers.get_mut(topic_name) else {
-                    continue;
-                };
-                parser
-                    .step(&msg)
-                    .map_err(|e| Error::ParserError(e.to_string()))?;
-                let bar = bar_handles.get(topic_name).unwrap();
-                bar.set_message(topic_name.to_string());
-                bar.inc(1);
+            let Some(parser) = parsers.get_mut(topic_name) else {
+                continue;
+            };
+            match parser.step(&msg) {
+                Ok(()) => context.processed(topic_name, msg.log_time),
+                Err(e) => {
+                    context.on_error(ParserError::new(topic_name, e).at(msg.log_time, file))?
+                }
             }
+            progress.on_message(topic_name);
+        }
+    }
+
+    // Order independent topics, one file per job
+    if !concurrent_topics.is_empty() {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(options.jobs)
+            .build()?;
+        pool.install(|| {
+            files.par_iter().try_for_each(|file| {
+                process_file_concurrently(
+                    file,
+                    &concurrent_topics,
+                    &context,
+                    progress,
+                    &sigint,
+                    trim_start,
+                    trim_end,
+                )
+            })
+        })?;
+        for topic in &concurrent_topics {
+            progress.on_topic_done(&topic.name);
         }
     }
 
@@ -289,11 +1343,734 @@ pub fn process(
         info!("- {}", name);
         parser
             .post_process(sigint.clone())
-            .map_err(|e| Error::ParserError(e.to_string()))?;
+            .map_err(|e| ParserError::new(name, e))?;
+        progress.on_topic_done(name);
+    }
+
+    for (topic, count) in context.skipped.lock().unwrap().iter() {
+        warn!("Skipped {} messages of {} on errors.", count, topic);
+    }
+
+    // Index of the saved frames, and metadata of every topic
+    if dump_data {
+        context.frame_index.lock().unwrap().write()?;
+        if let (Some(map), Some(options)) = (&map, accumulate) {
+            let map = map.lock().unwrap();
+            let path = output_dir.join(format!("map.{}", options.format.extension()));
+            let points = map.write(
+                &path,
+                options.format,
+                pcd_format.unwrap_or(pcd::Format::Binary),
+            )?;
+            if points > 0 {
+                info!(
+                    "Map of {} clouds, {} points: {}",
+                    map.clouds(),
+                    points,
+                    path.display()
+                );
+            }
+        }
+        if let Some(options) = &image_options.composite {
+            let path = output_dir.join("composite.mp4");
+            let frames = composite::write(
+                &path,
+                &context.frame_index.lock().unwrap(),
+                options,
+                &sigint,
+            )?;
+            if frames > 0 {
+                info!("Composite video of {} frames: {}", frames, path.display());
+            }
+        }
+        let parameters = meta::Parameters {
+            image_format: image_options.format.extension().to_owned(),
+            jpeg_quality: image_options.jpeg_quality,
+            frame_stride: match image_options.sampling {
+                image_file::Sampling::Stride(n) => Some(n),
+                _ => None,
+            },
+            frame_rate: match image_options.sampling {
+                image_file::Sampling::Rate(hz) => Some(hz),
+                _ => None,
+            },
+            name_template: image_options
+                .name_template
+                .as_ref()
+                .map(|t| t.as_str().to_owned()),
+            seq_names: image_options.seq_names,
+            transcode_images: image_options.transcode,
+            image_scale: image_options.scale,
+            digest: image_options
+                .digest
+                .map(|d| d.format.extension().to_owned()),
+            composite: image_options
+                .composite
+                .as_ref()
+                .map(|c| c.topics.clone())
+                .unwrap_or_default(),
+            burn_timestamp: image_options.burn_timestamp,
+            video_decoder: options.video_decoder.as_str().to_owned(),
+            pcd_format: pcd_format.map(|f| f.as_str().to_owned()),
+            parquet: options.parquet,
+            extrinsics_in_output: options.extrinsics_in_output,
+            depth_cloud: options.depth_cloud,
+            deskew: deskew.map(str::to_owned),
+            accumulate: accumulate.map(|a| a.source.clone()),
+            map_format: accumulate.map(|a| a.format.extension().to_owned()),
+            map_voxel: accumulate.map(|a| a.voxel),
+            trim_start,
+            trim_end,
+        };
+        let processed = context.processed.lock().unwrap();
+        let selected: Vec<&Topic> = topics
+            .iter()
+            .filter(|t| options.topic_names.contains(&t.name))
+            .collect();
+        for topic in &selected {
+            meta::write(
+                &output_dir.join(topic.name.trim_start_matches('/')),
+                topic,
+                processed
+                    .get(&topic.name)
+                    .unwrap_or(&meta::Processed::default()),
+                &parameters,
+            )?;
+        }
+        manifest::write(
+            output_dir,
+            files,
+            &selected,
+            &processed,
+            &context.skipped.lock().unwrap(),
+            &parameters,
+        )?;
+    }
+
+    // Lay out the viewer once the entity paths of all topics are known
+    if let Some(rec) = &vis_stream {
+        let selected: Vec<&Topic> = topics
+            .iter()
+            .filter(|t| options.topic_names.contains(&t.name))
+            .collect();
+        blueprint::send(rec, &selected, context.frame_tree.lock().unwrap().images())?;
+    }
+
+    Ok(())
+}
+
+/// Write all the messages within the time range into `trim.mcap`. Attachments, metadata and
+/// channels without messages in the range are kept too.
+fn trim(
+    files: &[PathBuf],
+    sigint: &AtomicBool,
+    trim_start: i64,
+    trim_end: i64,
+) -> Result<(), Error> {
+    let mut trim_out = mcap::Writer::new(std::io::BufWriter::new(fs::File::create("trim.mcap")?))?;
+
+    // Sliced files may share the same attachments and metadata, copy them once only.
+    let mut copied: HashSet<(String, u64)> = HashSet::new();
+
+    for file in files {
+        // Read in files
+        let input = Input::open(file)?;
+        copy_records(&input, &mut trim_out, &mut copied)?;
+
+        // Enumerate all messages
+        for message in input.all_messages()? {
+            // Check for interrupt
+            if sigint.load(std::sync::atomic::Ordering::Relaxed) {
+                return Err(Error::Interrupted);
+            }
+
+            let msg = message?;
+
+            // Trim start/end
+            if msg.publish_time < trim_start as u64 {
+                continue;
+            }
+            if msg.publish_time > trim_end as u64 {
+                info!("Trimming end reached.");
+                break;
+            }
+            trim_out.write(&msg)?;
+        }
     }
-    if trim_only {
-        trim_out.unwrap().finish()?;
+    trim_out.finish()?;
+    Ok(())
+}
+
+/// Copy channels, schemas, attachments and metadata of the input into `out`. Attachments and
+/// metadata already in `copied`, keyed by name and log time, are skipped.
+fn copy_records<W: std::io::Write + std::io::Seek>(
+    input: &Input,
+    out: &mut mcap::Writer<'static, W>,
+    copied: &mut HashSet<(String, u64)>,
+) -> Result<(), Error> {
+    let mmap = match input {
+        Input::Mcap(mmap) => mmap,
+        Input::Rosbag2(reader) => {
+            for topic in reader.topics() {
+                out.add_channel(&topic.channel)?;
+            }
+            return Ok(());
+        }
+    };
+    let Some(summary) = input.summary() else {
+        warn!("No summary found, attachments and metadata are not copied.");
+        return Ok(());
+    };
+
+    for channel in summary.channels.values() {
+        // Channels borrow from the file, the writer needs owned ones.
+        out.add_channel(&mcap::Channel {
+            topic: channel.topic.clone(),
+            schema: channel.schema.as_ref().map(|s| {
+                Arc::new(mcap::Schema {
+                    name: s.name.clone(),
+                    encoding: s.encoding.clone(),
+                    data: std::borrow::Cow::Owned(s.data.to_vec()),
+                })
+            }),
+            message_encoding: channel.message_encoding.clone(),
+            metadata: channel.metadata.clone(),
+        })?;
+    }
+    for index in summary.attachment_indexes.iter() {
+        if copied.insert((index.name.clone(), index.log_time)) {
+            out.attach(&mcap::read::attachment(mmap, index)?)?;
+        }
+    }
+    // Metadata records have no log time
+    for index in summary.metadata_indexes.iter() {
+        if copied.insert((index.name.clone(), u64::MAX)) {
+            out.write_metadata(&mcap::read::metadata(mmap, index)?)?;
+        }
     }
+    Ok(())
+}
+
+/// Merge all the files into one MCAP file, messages interleaved by their log time. Channels and
+/// schemas shared by the files are written only once.
+pub fn merge(files: &[PathBuf], output: &Path, sigint: Arc<AtomicBool>) -> Result<(), Error> {
+    let inputs = files
+        .iter()
+        .map(|f| Input::open(f))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut streams = inputs
+        .iter()
+        .map(|i| i.all_messages())
+        .collect::<Result<Vec<_>, _>>()?;
 
+    // The next message of each file, and a min-heap of their log time
+    let mut heads: Vec<Option<mcap::Message>> = Vec::with_capacity(streams.len());
+    let mut heap = BinaryHeap::new();
+    for (idx, stream) in streams.iter_mut().enumerate() {
+        let head = stream.next().transpose()?;
+        if let Some(msg) = &head {
+            heap.push(Reverse((msg.log_time, idx)));
+        }
+        heads.push(head);
+    }
+
+    let mut out = mcap::Writer::new(std::io::BufWriter::new(fs::File::create(output)?))?;
+    while let Some(Reverse((_, idx))) = heap.pop() {
+        // Check for interrupt
+        if sigint.load(std::sync::atomic::Ordering::Relaxed) {
+            return Err(Error::Interrupted);
+        }
+
+        let msg = heads[idx].take().unwrap();
+        out.write(&msg)?;
+
+        heads[idx] = streams[idx].next().transpose()?;
+        if let Some(next) = &heads[idx] {
+            heap.push(Reverse((next.log_time, idx)));
+        }
+    }
+    out.finish()?;
     Ok(())
 }
+
+/// Split the files into a sequence of MCAP files in `output_dir`. A new segment is started every
+/// `every` nanoseconds of log time, or once `max_size` bytes of message data are written.
+pub fn split(
+    files: &[PathBuf],
+    output_dir: &Path,
+    every: Option<u64>,
+    max_size: Option<u64>,
+    sigint: Arc<AtomicBool>,
+) -> Result<(), Error> {
+    fs::create_dir_all(output_dir)?;
+    let mut segment: Option<mcap::Writer<std::io::BufWriter<fs::File>>> = None;
+    let mut segment_count = 0;
+    let (mut segment_start, mut segment_size) = (0, 0);
+
+    // All the channels seen so far, they are written into every new segment.
+    let mut channels: Vec<Arc<mcap::Channel>> = Vec::new();
+
+    for file in files {
+        let input = Input::open(file)?;
+        for message in input.all_messages()? {
+            // Check for interrupt
+            if sigint.load(std::sync::atomic::Ordering::Relaxed) {
+                return Err(Error::Interrupted);
+            }
+
+            let msg = message?;
+            if !channels.contains(&msg.channel) {
+                channels.push(msg.channel.clone());
+                if let Some(out) = segment.as_mut() {
+                    out.add_channel(&msg.channel)?;
+                }
+            }
+
+            // Time for a new segment?
+            let full = every.is_some_and(|e| msg.log_time >= segment_start + e)
+                || max_size.is_some_and(|s| segment_size + msg.data.len() as u64 > s);
+            if segment.is_none() || (full && segment_size > 0) {
+                if let Some(mut out) = segment.take() {
+                    out.finish()?;
+                }
+                let path = output_dir.join(format!("segment_{:04}.mcap", segment_count));
+                info!("Writing {}", path.display());
+                let mut out = mcap::Writer::new(std::io::BufWriter::new(fs::File::create(path)?))?;
+                for channel in channels.iter() {
+                    out.add_channel(channel)?;
+                }
+                segment = Some(out);
+                segment_count += 1;
+                segment_start = msg.log_time;
+                segment_size = 0;
+            }
+
+            segment.as_mut().unwrap().write(&msg)?;
+            segment_size += msg.data.len() as u64;
+        }
+    }
+    if let Some(mut out) = segment {
+        out.finish()?;
+    }
+    Ok(())
+}
+
+/// Copy messages of the selected topics into a new MCAP file without decoding them. All the
+/// topics are selected if `topics` is empty, then topics in `exclude` are dropped.
+pub fn filter(
+    files: &[PathBuf],
+    output: &Path,
+    topics: &[String],
+    exclude: &[String],
+    sigint: Arc<AtomicBool>,
+) -> Result<(), Error> {
+    let selected: Vec<String> = summary(files)?
+        .into_iter()
+        .map(|t| t.name)
+        .filter(|name| (topics.is_empty() || topics.contains(name)) && !exclude.contains(name))
+        .collect();
+    if selected.is_empty() {
+        return Err(Error::InvalidTopic("No topic selected.".to_owned()));
+    }
+    for name in selected.iter() {
+        info!("- {}", name);
+    }
+    let selected: Vec<&str> = selected.iter().map(|s| s.as_str()).collect();
+
+    // Inputs outlive the writer, as messages borrow from them.
+    let inputs = files
+        .iter()
+        .map(|f| Input::open(f))
+        .collect::<Result<Vec<_>, _>>()?;
+    let summaries: Vec<_> = inputs.iter().map(|i| i.summary()).collect();
+
+    let mut out = mcap::Writer::new(std::io::BufWriter::new(fs::File::create(output)?))?;
+    for (input, summary) in inputs.iter().zip(summaries.iter()) {
+        for message in input.messages(summary.as_ref(), &selected)? {
+            // Check for interrupt
+            if sigint.load(std::sync::atomic::Ordering::Relaxed) {
+                return Err(Error::Interrupted);
+            }
+
+            let msg = message?;
+            if selected.contains(&msg.channel.topic.as_str()) {
+                out.write(&msg)?;
+            }
+        }
+    }
+    out.finish()?;
+    Ok(())
+}
+
+/// Rewrite the MCAP file into `output` with a rebuilt summary, statistics and chunk indexes, like
+/// files of crashed recorders missing their summary sections. Records are read until the end of
+/// the data section, or the first truncated or corrupted one. Returns the number of messages
+/// recovered.
+pub fn reindex(file: &Path, output: &Path, sigint: Arc<AtomicBool>) -> Result<u64, Error> {
+    let fd = fs::File::open(file)?;
+    let mmap = unsafe { memmap2::Mmap::map(&fd)? };
+    let records = mcap::read::ChunkFlattener::new_with_options(
+        &mmap,
+        mcap::read::Options::IgnoreEndMagic.into(),
+    )?;
+
+    let mut out = mcap::Writer::new(std::io::BufWriter::new(fs::File::create(output)?))?;
+    let mut schemas: HashMap<u16, Arc<mcap::Schema>> = HashMap::new();
+    let mut channels: HashMap<u16, Arc<mcap::Channel>> = HashMap::new();
+    let mut count = 0;
+    for record in records {
+        // Check for interrupt
+        if sigint.load(std::sync::atomic::Ordering::Relaxed) {
+            return Err(Error::Interrupted);
+        }
+
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                warn!("Stopped at a truncated or corrupted record. {}", e);
+                break;
+            }
+        };
+        match record {
+            mcap::records::Record::Schema { header, data } => {
+                let schema = mcap::Schema {
+                    name: header.name,
+                    encoding: header.encoding,
+                    data,
+                };
+                schemas.insert(header.id, Arc::new(schema));
+            }
+            mcap::records::Record::Channel(c) => {
+                let channel = mcap::Channel {
+                    topic: c.topic,
+                    schema: schemas.get(&c.schema_id).cloned(),
+                    message_encoding: c.message_encoding,
+                    metadata: c.metadata,
+                };
+                channels.insert(c.id, Arc::new(channel));
+            }
+            mcap::records::Record::Message { header, data } => {
+                let Some(channel) = channels.get(&header.channel_id) else {
+                    warn!("Message of unknown channel {} dropped.", header.channel_id);
+                    continue;
+                };
+                out.write(&mcap::Message {
+                    channel: channel.clone(),
+                    sequence: header.sequence,
+                    log_time: header.log_time,
+                    publish_time: header.publish_time,
+                    data,
+                })?;
+                count += 1;
+            }
+            mcap::records::Record::Attachment { header, data } => {
+                out.attach(&mcap::Attachment {
+                    log_time: header.log_time,
+                    create_time: header.create_time,
+                    name: header.name,
+                    media_type: header.media_type,
+                    data,
+                })?;
+            }
+            mcap::records::Record::Metadata(metadata) => out.write_metadata(&metadata)?,
+            // Summary records are rebuilt by the writer
+            mcap::records::Record::DataEnd(_) => break,
+            _ => {}
+        }
+    }
+    out.finish()?;
+    Ok(count)
+}
+
+// Number of the largest gaps reported for each topic
+const LARGEST_GAPS: usize = 3;
+
+/// Statistics of one topic. Times are log times in nanoseconds.
+#[derive(Debug, Default)]
+pub struct TopicStats {
+    pub name: String,
+    pub msg_count: u64,
+    pub total_bytes: u64,
+    pub first: u64,
+    pub last: u64,
+    pub min_interval: u64,
+    pub max_interval: u64,
+
+    // Largest gaps between messages, as (start time, length), the largest first
+    pub largest_gaps: Vec<(u64, u64)>,
+}
+
+impl TopicStats {
+    /// Message rate in Hz
+    pub fn rate(&self) -> f64 {
+        if self.msg_count < 2 || self.last == self.first {
+            return 0.0;
+        }
+        (self.msg_count - 1) as f64 / ((self.last - self.first) as f64 * 1e-9)
+    }
+
+    /// Mean interval between messages in nanoseconds
+    pub fn mean_interval(&self) -> f64 {
+        if self.msg_count < 2 {
+            return 0.0;
+        }
+        (self.last - self.first) as f64 / (self.msg_count - 1) as f64
+    }
+
+    fn add(&mut self, log_time: u64, bytes: u64) {
+        if self.msg_count == 0 {
+            self.first = log_time;
+            self.min_interval = u64::MAX;
+        } else {
+            let interval = log_time.abs_diff(self.last);
+            self.min_interval = self.min_interval.min(interval);
+            self.max_interval = self.max_interval.max(interval);
+            if self.largest_gaps.len() < LARGEST_GAPS
+                || interval > self.largest_gaps.last().unwrap().1
+            {
+                self.largest_gaps.push((self.last, interval));
+                self.largest_gaps.sort_by_key(|gap| Reverse(gap.1));
+                self.largest_gaps.truncate(LARGEST_GAPS);
+            }
+        }
+        self.msg_count += 1;
+        self.total_bytes += bytes;
+        self.last = self.last.max(log_time);
+    }
+}
+
+impl std::fmt::Display for TopicStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let ms = |ns: f64| ns * 1e-6;
+        write!(
+            f,
+            "{}, msgs: {}, bytes: {}, rate: {:.2} Hz, first: {}, last: {}, interval min/mean/max: {:.3}/{:.3}/{:.3} ms",
+            self.name,
+            self.msg_count,
+            self.total_bytes,
+            self.rate(),
+            self.first,
+            self.last,
+            ms(if self.msg_count < 2 { 0.0 } else { self.min_interval as f64 }),
+            ms(self.mean_interval()),
+            ms(self.max_interval as f64),
+        )?;
+        let gaps: Vec<String> = self
+            .largest_gaps
+            .iter()
+            .map(|(start, len)| format!("{:.3} ms at {}", ms(*len as f64), start))
+            .collect();
+        if !gaps.is_empty() {
+            write!(f, ", largest gaps: {}", gaps.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Collect statistics of all the topics by reading through every message.
+pub fn stats(files: &[PathBuf], sigint: Arc<AtomicBool>) -> Result<Vec<TopicStats>, Error> {
+    let mut topics: HashMap<String, TopicStats> = HashMap::new();
+    for file in files {
+        let input = Input::open(file)?;
+        for message in input.all_messages()? {
+            // Check for interrupt
+            if sigint.load(std::sync::atomic::Ordering::Relaxed) {
+                return Err(Error::Interrupted);
+            }
+
+            let msg = message?;
+            topics
+                .entry(msg.channel.topic.clone())
+                .or_insert_with(|| TopicStats {
+                    name: msg.channel.topic.clone(),
+                    ..Default::default()
+                })
+                .add(msg.log_time, msg.data.len() as u64);
+        }
+    }
+    let mut topics: Vec<TopicStats> = topics.into_values().collect();
+    topics.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(topics)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Write messages of `(topic, log time in seconds)` into a MCAP file, in small chunks.
+    fn write_mcap(path: &Path, messages: &[(&str, u64)]) {
+        let mut out = mcap::WriteOptions::new()
+            .chunk_size(Some(64))
+            .create(std::io::BufWriter::new(fs::File::create(path).unwrap()))
+            .unwrap();
+        let schema = Arc::new(mcap::Schema {
+            name: "std_msgs/msg/String".to_owned(),
+            encoding: "ros2msg".to_owned(),
+            data: Cow::Borrowed(b"string data"),
+        });
+        let mut channels: HashMap<&str, Arc<mcap::Channel>> = HashMap::new();
+        for (sequence, (topic, seconds)) in messages.iter().enumerate() {
+            let channel = channels.entry(topic).or_insert_with(|| {
+                Arc::new(mcap::Channel {
+                    topic: topic.to_string(),
+                    schema: Some(schema.clone()),
+                    message_encoding: "cdr".to_owned(),
+                    metadata: BTreeMap::new(),
+                })
+            });
+            out.write(&mcap::Message {
+                channel: channel.clone(),
+                sequence: sequence as u32,
+                log_time: seconds * 1_000_000_000,
+                publish_time: seconds * 1_000_000_000,
+                data: Cow::Owned(vec![0, 1, 0, 0, 1, 0, 0, 0, 0]),
+            })
+            .unwrap();
+        }
+        out.finish().unwrap();
+    }
+
+    /// Messages of the file as `(topic, log time in seconds)`.
+    fn read_mcap(path: &Path) -> Vec<(String, u64)> {
+        Input::open(path)
+            .unwrap()
+            .all_messages()
+            .unwrap()
+            .map(|m| m.unwrap())
+            .map(|m| (m.channel.topic.clone(), m.log_time / 1_000_000_000))
+            .collect()
+    }
+
+    fn owned(messages: &[(&str, u64)]) -> Vec<(String, u64)> {
+        messages.iter().map(|(t, s)| (t.to_string(), *s)).collect()
+    }
+
+    fn sigint() -> Arc<AtomicBool> {
+        Arc::new(AtomicBool::new(false))
+    }
+
+    #[test]
+    fn merge_by_log_time() {
+        let dir = test_util::dir("merge");
+        let (a, b, output) = (dir.join("a.mcap"), dir.join("b.mcap"), dir.join("out.mcap"));
+        write_mcap(&a, &[("/imu", 1), ("/imu", 4), ("/imu", 5)]);
+        write_mcap(&b, &[("/gps", 2), ("/gps", 3), ("/gps", 6)]);
+        merge(&[a, b], &output, sigint()).unwrap();
+        assert_eq!(
+            read_mcap(&output),
+            owned(&[
+                ("/imu", 1),
+                ("/gps", 2),
+                ("/gps", 3),
+                ("/imu", 4),
+                ("/imu", 5),
+                ("/gps", 6)
+            ])
+        );
+        let topics = summary(&[output]).unwrap();
+        assert_eq!(topics.len(), 2);
+    }
+
+    #[test]
+    fn split_by_time() {
+        let dir = test_util::dir("split");
+        let input = dir.join("in.mcap");
+        let messages: Vec<(&str, u64)> = (0..7)
+            .map(|i| (if i % 2 == 0 { "/imu" } else { "/gps" }, i))
+            .collect();
+        write_mcap(&input, &messages);
+        let output_dir = dir.join("segments");
+        split(&[input], &output_dir, Some(3_000_000_000), None, sigint()).unwrap();
+
+        let mut segments: Vec<PathBuf> = fs::read_dir(&output_dir)
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .collect();
+        segments.sort();
+        let names: Vec<_> = segments
+            .iter()
+            .map(|p| p.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "segment_0000.mcap",
+                "segment_0001.mcap",
+                "segment_0002.mcap"
+            ]
+        );
+        let times: Vec<Vec<u64>> = segments
+            .iter()
+            .map(|p| read_mcap(p).into_iter().map(|(_, s)| s).collect())
+            .collect();
+        assert_eq!(times, [vec![0, 1, 2], vec![3, 4, 5], vec![6]]);
+
+        // Every segment has all the channels seen so far
+        assert_eq!(summary(&segments[2..]).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn split_by_size() {
+        let dir = test_util::dir("split_size");
+        let input = dir.join("in.mcap");
+        write_mcap(&input, &[("/imu", 0), ("/imu", 1), ("/imu", 2)]);
+        let output_dir = dir.join("segments");
+        split(&[input], &output_dir, None, Some(18), sigint()).unwrap();
+        assert_eq!(fs::read_dir(&output_dir).unwrap().count(), 2);
+        assert_eq!(
+            read_mcap(&output_dir.join("segment_0001.mcap")),
+            owned(&[("/imu", 2)])
+        );
+    }
+
+    #[test]
+    fn reindex_truncated_file() {
+        let dir = test_util::dir("reindex");
+        let input = dir.join("in.mcap");
+        let messages: Vec<(&str, u64)> = (0..20).map(|i| ("/imu", i)).collect();
+        write_mcap(&input, &messages);
+
+        // Summary and footer of the complete file are dropped
+        let data = fs::read(&input).unwrap();
+        let footer = data.len() - 8 - 29;
+        let summary_start = u64::from_le_bytes(data[footer + 9..footer + 17].try_into().unwrap());
+        let output = dir.join("out.mcap");
+        let truncated = dir.join("truncated.mcap");
+        fs::write(&truncated, &data[..summary_start as usize]).unwrap();
+        assert_eq!(reindex(&truncated, &output, sigint()).unwrap(), 20);
+        assert_eq!(read_mcap(&output), owned(&messages));
+        let stats = Input::open(&output)
+            .unwrap()
+            .summary()
+            .unwrap()
+            .stats
+            .unwrap();
+        assert_eq!(stats.message_count, 20);
+
+        // Messages of the chunks before the cut are recovered
+        fs::write(&truncated, &data[..data.len() / 2]).unwrap();
+        let count = reindex(&truncated, &output, sigint()).unwrap();
+        assert!(count > 0 && count < 20, "{}", count);
+        assert_eq!(read_mcap(&output), owned(&messages[..count as usize]));
+    }
+
+    #[test]
+    fn tell_parser_error_kinds() {
+        let io = io::Error::new(io::ErrorKind::StorageFull, "full");
+        assert_eq!(ParserError::new("/a", io).kind(), ParserErrorKind::Io);
+        let payload = imu::Error::Payload(DecodeError::Schema("/a".to_owned()));
+        assert_eq!(
+            ParserError::new("/a", payload).kind(),
+            ParserErrorKind::Payload
+        );
+        let cdr = cdr::deserialize::<u32>(&[0, 1, 0, 0]).unwrap_err();
+        assert_eq!(
+            ParserError::new("/a", imu::Error::Cdr(cdr)).kind(),
+            ParserErrorKind::Deserialize
+        );
+        assert_eq!(
+            ParserError::new("/a", "unsupported").kind(),
+            ParserErrorKind::Other
+        );
+    }
+}
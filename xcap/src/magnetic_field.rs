@@ -0,0 +1,101 @@
+use crate::extractor::Extractor;
+use mcap::Message;
+use rerun::RecordingStream;
+use ros2_sensor_msgs::msg::MagneticField;
+use std::{
+    fs,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::{atomic::AtomicBool, Arc},
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Payload error. {0}")]
+    Payload(#[from] crate::extractor::DecodeError),
+    #[error("CDR error. {0}")]
+    Cdr(#[from] cdr::Error),
+}
+
+/// Parser of magnetometer readings. The three axes and the magnitude of the field are plotted over
+/// time, a steady magnitude while turning is what a calibrated magnetometer looks like.
+pub struct Parser {
+    // Visualizer with rerun
+    rec_stream: Option<RecordingStream>,
+
+    // CSV file for all the readings, only available when dumping data
+    csv: Option<BufWriter<fs::File>>,
+
+    // Scratch buffer for decoded message payloads, reused across messages
+    buffer: Vec<u8>,
+}
+
+impl Parser {
+    pub fn new(output_path: &Path, rerun_stream: Option<RecordingStream>, dump_data: bool) -> Self {
+        // Create output file
+        let csv = if dump_data {
+            fs::create_dir_all(output_path).unwrap();
+            let mut file =
+                BufWriter::new(fs::File::create(output_path.join("magnetic_field.csv")).unwrap());
+            writeln!(file, "sec,nanosec,x,y,z,magnitude").unwrap();
+            Some(file)
+        } else {
+            None
+        };
+
+        Parser {
+            rec_stream: rerun_stream,
+            csv,
+            buffer: Vec::new(),
+        }
+    }
+}
+
+impl Extractor for Parser {
+    type ExtractorError = Box<dyn std::error::Error + Send + Sync>;
+
+    fn buffer(&mut self) -> &mut Vec<u8> {
+        &mut self.buffer
+    }
+
+    fn step(&mut self, message: &Message) -> Result<(), Self::ExtractorError> {
+        let payload = self.decode_into(message).map_err(Error::Payload)?;
+        let field = cdr::deserialize_from::<_, MagneticField, _>(payload, cdr::size::Infinite)
+            .map_err(Error::Cdr)?;
+        let b = &field.magnetic_field;
+        let magnitude = (b.x * b.x + b.y * b.y + b.z * b.z).sqrt();
+
+        if let Some(rec) = &self.rec_stream {
+            rec.set_time_seconds(
+                "main",
+                field.header.stamp.sec as f64 + field.header.stamp.nanosec as f64 * 1e-9,
+            );
+            // Axes not reported are NaN, and left out of the plots
+            for (name, value) in [("x", b.x), ("y", b.y), ("z", b.z), ("magnitude", magnitude)] {
+                if !value.is_nan() {
+                    rec.log(
+                        format!("magnetic_field/{}/{}", message.channel.topic, name),
+                        &rerun::Scalar::new(value),
+                    )?;
+                }
+            }
+        }
+
+        // Dump data?
+        if let Some(csv) = self.csv.as_mut() {
+            writeln!(
+                csv,
+                "{},{},{},{},{},{}",
+                field.header.stamp.sec, field.header.stamp.nanosec, b.x, b.y, b.z, magnitude
+            )?;
+        }
+        Ok(())
+    }
+
+    fn post_process(&mut self, _sigint: Arc<AtomicBool>) -> Result<(), Self::ExtractorError> {
+        if let Some(csv) = self.csv.as_mut() {
+            csv.flush()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,111 @@
+//! Manifest of an extraction run, describing what went in and what came out, for
+//! reproducibility and data lineage.
+use crate::meta::{self, Parameters, Processed};
+use crate::Topic;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+#[derive(Serialize)]
+struct InputFile {
+    path: PathBuf,
+    size: u64,
+    sha256: String,
+}
+
+#[derive(Serialize)]
+struct TopicOutput<'a> {
+    topic: &'a str,
+    schema: &'a str,
+    message_count: u64,
+    // Messages skipped on errors
+    skipped: u64,
+    start_time: Option<u64>,
+    end_time: Option<u64>,
+    // Files in the topic directory and their total size in bytes
+    files: usize,
+    bytes: u64,
+}
+
+#[derive(Serialize)]
+struct Manifest<'a> {
+    tool: &'static str,
+    version: &'static str,
+    created_at: String,
+    inputs: Vec<InputFile>,
+    topics: Vec<TopicOutput<'a>>,
+    parameters: &'a Parameters,
+}
+
+/// Write `manifest.json` of the run into the output directory.
+pub(crate) fn write(
+    output_dir: &Path,
+    files: &[PathBuf],
+    topics: &[&Topic],
+    processed: &BTreeMap<String, Processed>,
+    skipped: &BTreeMap<String, u64>,
+    parameters: &Parameters,
+) -> io::Result<()> {
+    let inputs = files
+        .iter()
+        .map(|file| {
+            Ok(InputFile {
+                path: file.clone(),
+                size: fs::metadata(file)?.len(),
+                sha256: sha256(file)?,
+            })
+        })
+        .collect::<io::Result<_>>()?;
+
+    let mut outputs = Vec::with_capacity(topics.len());
+    for topic in topics {
+        let dir = output_dir.join(topic.name.trim_start_matches('/'));
+        let files = match dir.is_dir() {
+            true => meta::output_files(&dir)?,
+            false => Vec::new(),
+        };
+        let bytes = files
+            .iter()
+            .map(|f| fs::metadata(f).map(|m| m.len()))
+            .sum::<io::Result<u64>>()?;
+        let processed = processed.get(&topic.name);
+        outputs.push(TopicOutput {
+            topic: &topic.name,
+            schema: &topic.format,
+            message_count: processed.map_or(0, |p| p.count),
+            skipped: skipped.get(&topic.name).copied().unwrap_or(0),
+            start_time: processed.and_then(|p| p.start_time),
+            end_time: processed.and_then(|p| p.end_time),
+            files: files.len(),
+            bytes,
+        });
+    }
+
+    let manifest = Manifest {
+        tool: env!("CARGO_PKG_NAME"),
+        version: env!("CARGO_PKG_VERSION"),
+        created_at: chrono::Utc::now().to_rfc3339(),
+        inputs,
+        topics: outputs,
+        parameters,
+    };
+    fs::create_dir_all(output_dir)?;
+    let file = fs::File::create(output_dir.join("manifest.json"))?;
+    serde_json::to_writer_pretty(file, &manifest)?;
+    Ok(())
+}
+
+/// SHA-256 of the file in hex.
+fn sha256(file: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut fs::File::open(file)?, &mut hasher)?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
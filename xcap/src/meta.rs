@@ -0,0 +1,115 @@
+//! Sidecar `meta.json` of each extracted topic, so that consumers could validate what they got.
+use crate::Topic;
+use serde::Serialize;
+use std::{
+    collections::BTreeSet,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+// Files written by xcap for every topic, not part of the output format
+const SIDECARS: [&str; 2] = ["index.csv", "meta.json"];
+
+/// Messages of a topic processed successfully.
+#[derive(Debug, Default)]
+pub(crate) struct Processed {
+    pub count: u64,
+    // Log times of the first and the last message in nanoseconds
+    pub start_time: Option<u64>,
+    pub end_time: Option<u64>,
+}
+
+impl Processed {
+    pub fn add(&mut self, log_time: u64) {
+        self.count += 1;
+        self.start_time = Some(self.start_time.map_or(log_time, |t| t.min(log_time)));
+        self.end_time = Some(self.end_time.map_or(log_time, |t| t.max(log_time)));
+    }
+}
+
+/// Extraction parameters affecting the output.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct Parameters {
+    pub image_format: String,
+    pub jpeg_quality: u8,
+    pub frame_stride: Option<u32>,
+    pub frame_rate: Option<f64>,
+    pub name_template: Option<String>,
+    pub seq_names: bool,
+    pub transcode_images: bool,
+    pub image_scale: Option<f32>,
+    pub digest: Option<String>,
+    pub composite: Vec<String>,
+    pub burn_timestamp: bool,
+    pub video_decoder: String,
+    pub pcd_format: Option<String>,
+    pub parquet: bool,
+    pub extrinsics_in_output: bool,
+    pub depth_cloud: bool,
+    pub deskew: Option<String>,
+    pub accumulate: Option<String>,
+    pub map_format: Option<String>,
+    pub map_voxel: Option<f32>,
+    // Messages published out of this range are dropped, in nanoseconds
+    pub trim_start: i64,
+    pub trim_end: i64,
+}
+
+#[derive(Serialize)]
+struct Meta<'a> {
+    topic: &'a str,
+    schema: &'a str,
+    encoding: &'a str,
+    message_encoding: &'a str,
+    message_count: u64,
+    start_time: Option<u64>,
+    end_time: Option<u64>,
+    // Extensions of the files in the topic directory
+    output_format: BTreeSet<String>,
+    parameters: &'a Parameters,
+}
+
+/// Write `meta.json` of the topic into its output directory.
+pub(crate) fn write(
+    dir: &Path,
+    topic: &Topic,
+    processed: &Processed,
+    parameters: &Parameters,
+) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let output_format = output_files(dir)?
+        .iter()
+        .filter_map(|f| f.extension())
+        .map(|e| e.to_string_lossy().into_owned())
+        .collect();
+    let meta = Meta {
+        topic: &topic.name,
+        schema: &topic.format,
+        encoding: &topic.encoding,
+        message_encoding: &topic.message_encoding,
+        message_count: processed.count,
+        start_time: processed.start_time,
+        end_time: processed.end_time,
+        output_format,
+        parameters,
+    };
+    let file = fs::File::create(dir.join("meta.json"))?;
+    serde_json::to_writer_pretty(file, &meta)?;
+    Ok(())
+}
+
+/// Output files in the directory of a topic. Sub directories are skipped, as they could be
+/// directories of other topics.
+pub(crate) fn output_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_sidecar = path
+            .file_name()
+            .is_some_and(|name| SIDECARS.iter().any(|s| name == *s));
+        if !path.is_dir() && !is_sidecar {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
@@ -0,0 +1,176 @@
+use crate::extractor::Extractor;
+use mcap::Message;
+use rerun::RecordingStream;
+use ros2_sensor_msgs::msg::NavSatFix;
+use std::{
+    fs,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::{atomic::AtomicBool, Arc},
+};
+
+// WGS 84 ellipsoid
+const WGS84_A: f64 = 6378137.0;
+const WGS84_E2: f64 = 6.694379990141316e-3;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Payload error. {0}")]
+    Payload(#[from] crate::extractor::DecodeError),
+    #[error("CDR error. {0}")]
+    Cdr(#[from] cdr::Error),
+}
+
+pub struct Parser {
+    // Visualizer with rerun
+    rec_stream: Option<RecordingStream>,
+
+    // CSV file for all the fixes, only available when dumping data
+    csv: Option<BufWriter<fs::File>>,
+
+    // Scratch buffer for decoded message payloads, reused across messages
+    buffer: Vec<u8>,
+
+    // Origin of the local ENU frame, the first valid fix
+    origin: Option<Geodetic>,
+
+    // Track in the local ENU frame
+    track: Vec<[f32; 3]>,
+
+    // Topic name, used as the entity path of the track
+    topic: String,
+}
+
+impl Parser {
+    pub fn new(output_path: &Path, rerun_stream: Option<RecordingStream>, dump_data: bool) -> Self {
+        // Create output file
+        let csv = if dump_data {
+            fs::create_dir_all(output_path).unwrap();
+            let mut file =
+                BufWriter::new(fs::File::create(output_path.join("navsat.csv")).unwrap());
+            writeln!(
+                file,
+                "sec,nanosec,status,service,latitude,longitude,altitude,position_covariance_type"
+            )
+            .unwrap();
+            Some(file)
+        } else {
+            None
+        };
+
+        Parser {
+            rec_stream: rerun_stream,
+            csv,
+            buffer: Vec::new(),
+            origin: None,
+            track: Vec::new(),
+            topic: String::new(),
+        }
+    }
+}
+
+impl Extractor for Parser {
+    type ExtractorError = Box<dyn std::error::Error + Send + Sync>;
+
+    fn buffer(&mut self) -> &mut Vec<u8> {
+        &mut self.buffer
+    }
+
+    fn step(&mut self, message: &Message) -> Result<(), Self::ExtractorError> {
+        let payload = self.decode_into(message).map_err(Error::Payload)?;
+        let fix = cdr::deserialize_from::<_, NavSatFix, _>(payload, cdr::size::Infinite)
+            .map_err(Error::Cdr)?;
+        self.topic.clone_from(&message.channel.topic);
+
+        if let Some(rec) = self.rec_stream.as_ref().filter(|_| fix.status.has_fix()) {
+            let altitude = if fix.altitude.is_nan() {
+                0.0
+            } else {
+                fix.altitude
+            };
+            let position = Geodetic {
+                latitude: fix.latitude,
+                longitude: fix.longitude,
+                altitude,
+            };
+            let origin = self.origin.get_or_insert(position);
+            let enu = position.to_enu(origin);
+            self.track.push(enu);
+
+            rec.set_time_seconds(
+                "main",
+                fix.header.stamp.sec as f64 + fix.header.stamp.nanosec as f64 * 1e-9,
+            );
+            rec.log(
+                format!("gps/{}", self.topic),
+                &rerun::Points3D::new([enu]).with_radii([0.5]),
+            )?;
+        }
+
+        // Dump data?
+        if let Some(csv) = self.csv.as_mut() {
+            writeln!(
+                csv,
+                "{},{},{},{},{},{},{},{}",
+                fix.header.stamp.sec,
+                fix.header.stamp.nanosec,
+                fix.status.status,
+                fix.status.service,
+                fix.latitude,
+                fix.longitude,
+                fix.altitude,
+                fix.position_covariance_type
+            )?;
+        }
+        Ok(())
+    }
+
+    fn post_process(&mut self, _sigint: Arc<AtomicBool>) -> Result<(), Self::ExtractorError> {
+        // The whole track is logged once so it stays visible while scrubbing the timeline.
+        if let Some(rec) = self.rec_stream.as_ref().filter(|_| !self.track.is_empty()) {
+            rec.log_static(
+                format!("gps/{}/track", self.topic),
+                &rerun::LineStrips3D::new([self.track.clone()]),
+            )?;
+        }
+        if let Some(csv) = self.csv.as_mut() {
+            csv.flush()?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Geodetic {
+    // Degrees
+    latitude: f64,
+    longitude: f64,
+
+    // Meters
+    altitude: f64,
+}
+
+impl Geodetic {
+    /// Convert to Earth-Centered, Earth-Fixed coordinates.
+    fn to_ecef(self) -> [f64; 3] {
+        let (lat, lon) = (self.latitude.to_radians(), self.longitude.to_radians());
+        let n = WGS84_A / (1.0 - WGS84_E2 * lat.sin().powi(2)).sqrt();
+        [
+            (n + self.altitude) * lat.cos() * lon.cos(),
+            (n + self.altitude) * lat.cos() * lon.sin(),
+            (n * (1.0 - WGS84_E2) + self.altitude) * lat.sin(),
+        ]
+    }
+
+    /// Convert to the local East-North-Up frame centered at `origin`.
+    fn to_enu(self, origin: &Geodetic) -> [f32; 3] {
+        let [x, y, z] = self.to_ecef();
+        let [x0, y0, z0] = origin.to_ecef();
+        let (dx, dy, dz) = (x - x0, y - y0, z - z0);
+        let (lat, lon) = (origin.latitude.to_radians(), origin.longitude.to_radians());
+        let east = -lon.sin() * dx + lon.cos() * dy;
+        let north = -lat.sin() * lon.cos() * dx - lat.sin() * lon.sin() * dy + lat.cos() * dz;
+        let up = lat.cos() * lon.cos() * dx + lat.cos() * lon.sin() * dy + lat.sin() * dz;
+        [east as f32, north as f32, up as f32]
+    }
+}
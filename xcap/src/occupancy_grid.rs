@@ -0,0 +1,202 @@
+use crate::extractor::Extractor;
+use crate::tf::FrameTree;
+use mcap::Message;
+use rerun::RecordingStream;
+use ros2_nav_msgs::msg::OccupancyGrid;
+use std::{
+    fs,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{atomic::AtomicBool, Arc, Mutex},
+};
+
+// Thresholds of occupancy in percent, as the map_saver of nav2 uses in the trinary mode
+const FREE_THRESH: i8 = 25;
+const OCCUPIED_THRESH: i8 = 65;
+
+// Gray levels of the cells in PGM files
+const FREE: u8 = 254;
+const OCCUPIED: u8 = 0;
+const UNKNOWN: u8 = 205;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Payload error. {0}")]
+    Payload(#[from] crate::extractor::DecodeError),
+    #[error("CDR error. {0}")]
+    Cdr(#[from] cdr::Error),
+    #[error("Grid data has {0} cells, expected {1}")]
+    Size(usize, usize),
+}
+
+/// Parser of occupancy grids. Grids are shown as textured planes at their origins, and saved as
+/// map files the map_server of nav2 could load, `{publish_time}.pgm` and `{publish_time}.yaml`.
+pub struct Parser {
+    // Output directory
+    output_dir: PathBuf,
+
+    // Visualizer with rerun
+    rec_stream: Option<RecordingStream>,
+
+    // Should dump data to disk
+    dump_data: bool,
+
+    // Scratch buffer for decoded message payloads, reused across messages
+    buffer: Vec<u8>,
+
+    // Transform tree, grids are logged in their frames once the frames are known
+    frame_tree: Arc<Mutex<FrameTree>>,
+}
+
+impl Parser {
+    pub fn new(
+        output_path: &Path,
+        rerun_stream: Option<RecordingStream>,
+        dump_data: bool,
+        frame_tree: Arc<Mutex<FrameTree>>,
+    ) -> Self {
+        // Create output dir
+        if dump_data {
+            fs::create_dir_all(output_path).unwrap();
+        }
+
+        Parser {
+            output_dir: output_path.into(),
+            rec_stream: rerun_stream,
+            dump_data,
+            buffer: Vec::new(),
+            frame_tree,
+        }
+    }
+
+    /// Log the grid as a plane textured with the cells, placed at the origin of the grid.
+    fn log(
+        &self,
+        rec: &RecordingStream,
+        grid: &OccupancyGrid,
+        topic: &str,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let entity_path = match self
+            .frame_tree
+            .lock()
+            .unwrap()
+            .entity_path(&grid.header.frame_id)
+        {
+            Some(frame_path) => format!("{}/map/{}", frame_path, topic),
+            None => format!("map/{}", topic),
+        };
+        let (p, q) = (&grid.info.origin.position, &grid.info.origin.orientation);
+        rec.log(
+            entity_path.as_str(),
+            &rerun::Transform3D::from_translation_rotation(
+                [p.x as f32, p.y as f32, p.z as f32],
+                rerun::Quaternion::from_xyzw([q.x as f32, q.y as f32, q.z as f32, q.w as f32]),
+            ),
+        )?;
+
+        // Cell (0, 0) is at the origin, and the rows of the texture go along Y
+        let (width, height) = (grid.info.width, grid.info.height);
+        let (x, y) = (
+            width as f32 * grid.info.resolution,
+            height as f32 * grid.info.resolution,
+        );
+        let texture: Vec<u8> = grid
+            .data
+            .iter()
+            .flat_map(|&cell| [shade(cell); 3])
+            .collect();
+        rec.log(
+            entity_path.as_str(),
+            &rerun::Mesh3D::new([[0.0, 0.0, 0.0], [x, 0.0, 0.0], [x, y, 0.0], [0.0, y, 0.0]])
+                .with_triangle_indices([[0, 1, 2], [0, 2, 3]])
+                .with_vertex_texcoords([[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]])
+                .with_albedo_texture(rerun::ImageFormat::rgb8([width, height]), texture),
+        )?;
+        Ok(())
+    }
+
+    /// Save the grid as a PGM image and its YAML metadata, like the map_saver of nav2.
+    fn save(&self, grid: &OccupancyGrid, stem: &str) -> std::io::Result<()> {
+        let (width, height) = (grid.info.width as usize, grid.info.height as usize);
+        let mut pgm = BufWriter::new(fs::File::create(
+            self.output_dir.join(format!("{}.pgm", stem)),
+        )?);
+        write!(pgm, "P5\n{} {}\n255\n", width, height)?;
+        // The first row of the image is the top of the map, the last row of the grid
+        for row in grid.data.chunks_exact(width.max(1)).take(height).rev() {
+            let pixels: Vec<u8> = row.iter().map(|&cell| trinary(cell)).collect();
+            pgm.write_all(&pixels)?;
+        }
+        pgm.flush()?;
+
+        let (p, q) = (&grid.info.origin.position, &grid.info.origin.orientation);
+        let yaw = (2.0 * (q.w * q.z + q.x * q.y)).atan2(1.0 - 2.0 * (q.y * q.y + q.z * q.z));
+        fs::write(
+            self.output_dir.join(format!("{}.yaml", stem)),
+            format!(
+                "image: {}.pgm\nmode: trinary\nresolution: {}\norigin: [{}, {}, {}]\n\
+                negate: 0\noccupied_thresh: {}\nfree_thresh: {}\n",
+                stem,
+                grid.info.resolution,
+                p.x,
+                p.y,
+                yaw,
+                OCCUPIED_THRESH as f64 / 100.0,
+                FREE_THRESH as f64 / 100.0
+            ),
+        )
+    }
+}
+
+/// Gray level of the cell in the viewer: white if free, black if occupied, shades in between.
+fn shade(cell: i8) -> u8 {
+    match cell {
+        0..=100 => 255 - (cell as u16 * 255 / 100) as u8,
+        _ => UNKNOWN,
+    }
+}
+
+/// Gray level of the cell in PGM files, as the map_server of nav2 reads in the trinary mode.
+fn trinary(cell: i8) -> u8 {
+    match cell {
+        0..=FREE_THRESH => FREE,
+        OCCUPIED_THRESH..=100 => OCCUPIED,
+        _ => UNKNOWN,
+    }
+}
+
+impl Extractor for Parser {
+    type ExtractorError = Box<dyn std::error::Error + Send + Sync>;
+
+    fn buffer(&mut self) -> &mut Vec<u8> {
+        &mut self.buffer
+    }
+
+    fn step(&mut self, message: &Message) -> Result<(), Self::ExtractorError> {
+        let payload = self.decode_into(message).map_err(Error::Payload)?;
+        let grid = cdr::deserialize_from::<_, OccupancyGrid, _>(payload, cdr::size::Infinite)
+            .map_err(Error::Cdr)?;
+        let cells = grid.info.width as usize * grid.info.height as usize;
+        if grid.data.len() != cells {
+            return Err(Error::Size(grid.data.len(), cells).into());
+        }
+
+        if let Some(rec) = &self.rec_stream {
+            rec.set_time_seconds(
+                "main",
+                grid.header.stamp.sec as f64 + grid.header.stamp.nanosec as f64 * 1e-9,
+            );
+            self.log(rec, &grid, &message.channel.topic)?;
+        }
+
+        // Dump data?
+        if self.dump_data {
+            self.save(&grid, &message.publish_time.to_string())?;
+        }
+        Ok(())
+    }
+
+    fn post_process(&mut self, _sigint: Arc<AtomicBool>) -> Result<(), Self::ExtractorError> {
+        Ok(())
+    }
+}
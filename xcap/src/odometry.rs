@@ -0,0 +1,154 @@
+use crate::extractor::Extractor;
+use crate::tf::FrameTree;
+use mcap::Message;
+use rerun::RecordingStream;
+use ros2_nav_msgs::msg::Odometry;
+use std::{
+    fs,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::{atomic::AtomicBool, Arc, Mutex},
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Payload error. {0}")]
+    Payload(#[from] crate::extractor::DecodeError),
+    #[error("CDR error. {0}")]
+    Cdr(#[from] cdr::Error),
+}
+
+pub struct Parser {
+    // Visualizer with rerun
+    rec_stream: Option<RecordingStream>,
+
+    // Trajectory in TUM format, only available when dumping data
+    tum: Option<BufWriter<fs::File>>,
+
+    // Scratch buffer for decoded message payloads, reused across messages
+    buffer: Vec<u8>,
+
+    // Transform tree, poses are logged in their parent frames once the frames are known
+    frame_tree: Arc<Mutex<FrameTree>>,
+
+    // All positions received so far
+    trajectory: Vec<[f32; 3]>,
+
+    // Entity path of the trajectory
+    entity_path: String,
+}
+
+impl Parser {
+    pub fn new(
+        output_path: &Path,
+        rerun_stream: Option<RecordingStream>,
+        dump_data: bool,
+        frame_tree: Arc<Mutex<FrameTree>>,
+    ) -> Self {
+        // Create output file
+        let tum = if dump_data {
+            fs::create_dir_all(output_path).unwrap();
+            let mut file =
+                BufWriter::new(fs::File::create(output_path.join("trajectory.txt")).unwrap());
+            writeln!(file, "# timestamp tx ty tz qx qy qz qw").unwrap();
+            Some(file)
+        } else {
+            None
+        };
+
+        Parser {
+            rec_stream: rerun_stream,
+            tum,
+            buffer: Vec::new(),
+            frame_tree,
+            trajectory: Vec::new(),
+            entity_path: String::new(),
+        }
+    }
+}
+
+impl Extractor for Parser {
+    type ExtractorError = Box<dyn std::error::Error + Send + Sync>;
+
+    fn buffer(&mut self) -> &mut Vec<u8> {
+        &mut self.buffer
+    }
+
+    fn step(&mut self, message: &Message) -> Result<(), Self::ExtractorError> {
+        let payload = self.decode_into(message).map_err(Error::Payload)?;
+        let odom = cdr::deserialize_from::<_, Odometry, _>(payload, cdr::size::Infinite)
+            .map_err(Error::Cdr)?;
+        let stamp = odom.header.stamp.sec as f64 + odom.header.stamp.nanosec as f64 * 1e-9;
+        let (p, q) = (&odom.pose.pose.position, &odom.pose.pose.orientation);
+
+        if let Some(rec) = &self.rec_stream {
+            self.entity_path = match self
+                .frame_tree
+                .lock()
+                .unwrap()
+                .entity_path(&odom.header.frame_id)
+            {
+                Some(frame_path) => format!("{}/odom/{}", frame_path, message.channel.topic),
+                None => format!("odom/{}", message.channel.topic),
+            };
+            let position = [p.x as f32, p.y as f32, p.z as f32];
+            self.trajectory.push(position);
+
+            rec.set_time_seconds("main", stamp);
+            rec.log(
+                format!("{}/pose", self.entity_path),
+                &rerun::Transform3D::from_translation_rotation(
+                    position,
+                    rerun::Quaternion::from_xyzw([q.x as f32, q.y as f32, q.z as f32, q.w as f32]),
+                ),
+            )?;
+            rec.log(
+                format!("{}/pose", self.entity_path),
+                &rerun::Points3D::new([[0.0, 0.0, 0.0]]).with_radii([0.1]),
+            )?;
+
+            let (v, w) = (&odom.twist.twist.linear, &odom.twist.twist.angular);
+            for (name, value) in [
+                ("linear/x", v.x),
+                ("linear/y", v.y),
+                ("linear/z", v.z),
+                ("angular/x", w.x),
+                ("angular/y", w.y),
+                ("angular/z", w.z),
+            ] {
+                rec.log(
+                    format!("velocity/{}/{}", message.channel.topic, name),
+                    &rerun::Scalar::new(value),
+                )?;
+            }
+        }
+
+        // Dump data?
+        if let Some(tum) = self.tum.as_mut() {
+            writeln!(
+                tum,
+                "{:.9} {} {} {} {} {} {} {}",
+                stamp, p.x, p.y, p.z, q.x, q.y, q.z, q.w
+            )?;
+        }
+        Ok(())
+    }
+
+    fn post_process(&mut self, _sigint: Arc<AtomicBool>) -> Result<(), Self::ExtractorError> {
+        // The whole trajectory is logged once so it stays visible while scrubbing the timeline.
+        if let Some(rec) = self
+            .rec_stream
+            .as_ref()
+            .filter(|_| !self.trajectory.is_empty())
+        {
+            rec.log_static(
+                format!("{}/trajectory", self.entity_path),
+                &rerun::LineStrips3D::new([self.trajectory.clone()]),
+            )?;
+        }
+        if let Some(tum) = self.tum.as_mut() {
+            tum.flush()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,76 @@
+//! Stamps burned into the saved frames, so that reviewers could refer to the exact time of a frame.
+use crate::image_file::Frame;
+use ab_glyph::{point, Font, FontRef, PxScale, ScaleFont};
+use image::{DynamicImage, GenericImage, GenericImageView, Rgba};
+use std::sync::OnceLock;
+
+// Height of the text relative to the image, and the smallest one in pixels
+const TEXT_RATIO: f32 = 1.0 / 30.0;
+const MIN_TEXT_HEIGHT: f32 = 12.0;
+
+// Opacity of the dark box behind the text
+const BOX_ALPHA: f32 = 0.6;
+
+fn font() -> &'static FontRef<'static> {
+    static FONT: OnceLock<FontRef<'static>> = OnceLock::new();
+    FONT.get_or_init(|| {
+        FontRef::try_from_slice(epaint_default_fonts::HACK_REGULAR).expect("bundled font")
+    })
+}
+
+/// Text burned into the frame: topic, stamp in seconds, and the sequence number.
+fn label(frame: &Frame) -> String {
+    format!(
+        "{}  {}.{:09}  #{}",
+        frame.topic,
+        frame.stamp / 1_000_000_000,
+        frame.stamp % 1_000_000_000,
+        frame.seq
+    )
+}
+
+/// Draw the label of the frame at the top left corner of the image, white on a dark box.
+pub fn burn(image: &mut DynamicImage, frame: &Frame) {
+    let font = font().as_scaled(PxScale::from(
+        (image.height() as f32 * TEXT_RATIO).max(MIN_TEXT_HEIGHT),
+    ));
+    let text = label(frame);
+    let margin = (font.height() / 4.0).ceil();
+    let text_width: f32 = text.chars().map(|c| font.h_advance(font.glyph_id(c))).sum();
+    let box_width = ((text_width + 2.0 * margin).ceil() as u32).min(image.width());
+    let box_height = ((font.height() + 2.0 * margin).ceil() as u32).min(image.height());
+    for y in 0..box_height {
+        for x in 0..box_width {
+            let pixel = blend(image.get_pixel(x, y), 0, BOX_ALPHA);
+            image.put_pixel(x, y, pixel);
+        }
+    }
+
+    let mut caret = margin;
+    for c in text.chars() {
+        let mut glyph = font.scaled_glyph(c);
+        glyph.position = point(caret, margin + font.ascent());
+        caret += font.h_advance(glyph.id);
+        let Some(outlined) = font.outline_glyph(glyph) else {
+            continue;
+        };
+        let bounds = outlined.px_bounds();
+        outlined.draw(|x, y, coverage| {
+            let (x, y) = (
+                bounds.min.x as i64 + x as i64,
+                bounds.min.y as i64 + y as i64,
+            );
+            if x >= 0 && y >= 0 && x < image.width() as i64 && y < image.height() as i64 {
+                let (x, y) = (x as u32, y as u32);
+                let pixel = blend(image.get_pixel(x, y), 255, coverage);
+                image.put_pixel(x, y, pixel);
+            }
+        });
+    }
+}
+
+/// Blend the pixel toward the gray level by alpha, keeping its own alpha.
+fn blend(pixel: Rgba<u8>, level: u8, alpha: f32) -> Rgba<u8> {
+    let mix = |v: u8| (v as f32 * (1.0 - alpha) + level as f32 * alpha).round() as u8;
+    Rgba([mix(pixel[0]), mix(pixel[1]), mix(pixel[2]), pixel[3]])
+}
@@ -0,0 +1,136 @@
+use crate::extractor::Extractor;
+use crate::tf::FrameTree;
+use mcap::Message;
+use rerun::RecordingStream;
+use ros2_nav_msgs::msg::Path as NavPath;
+use std::{
+    fs,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::{atomic::AtomicBool, Arc, Mutex},
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Payload error. {0}")]
+    Payload(#[from] crate::extractor::DecodeError),
+    #[error("CDR error. {0}")]
+    Cdr(#[from] cdr::Error),
+}
+
+/// Parser of paths, like the plans of planners. Each path is shown as a line strip replacing the
+/// previous one, so plans could be reviewed against the odometry by scrubbing the timeline.
+pub struct Parser {
+    // Visualizer with rerun
+    rec_stream: Option<RecordingStream>,
+
+    // CSV file for the poses of all the paths, only available when dumping data
+    csv: Option<BufWriter<fs::File>>,
+
+    // Scratch buffer for decoded message payloads, reused across messages
+    buffer: Vec<u8>,
+
+    // Transform tree, paths are logged in their frames once the frames are known
+    frame_tree: Arc<Mutex<FrameTree>>,
+}
+
+impl Parser {
+    pub fn new(
+        output_path: &Path,
+        rerun_stream: Option<RecordingStream>,
+        dump_data: bool,
+        frame_tree: Arc<Mutex<FrameTree>>,
+    ) -> Self {
+        // Create output file
+        let csv = if dump_data {
+            fs::create_dir_all(output_path).unwrap();
+            let mut file = BufWriter::new(fs::File::create(output_path.join("path.csv")).unwrap());
+            writeln!(
+                file,
+                "sec,nanosec,index,pose_sec,pose_nanosec,x,y,z,\
+                orientation_x,orientation_y,orientation_z,orientation_w"
+            )
+            .unwrap();
+            Some(file)
+        } else {
+            None
+        };
+
+        Parser {
+            rec_stream: rerun_stream,
+            csv,
+            buffer: Vec::new(),
+            frame_tree,
+        }
+    }
+}
+
+impl Extractor for Parser {
+    type ExtractorError = Box<dyn std::error::Error + Send + Sync>;
+
+    fn buffer(&mut self) -> &mut Vec<u8> {
+        &mut self.buffer
+    }
+
+    fn step(&mut self, message: &Message) -> Result<(), Self::ExtractorError> {
+        let payload = self.decode_into(message).map_err(Error::Payload)?;
+        let path = cdr::deserialize_from::<_, NavPath, _>(payload, cdr::size::Infinite)
+            .map_err(Error::Cdr)?;
+
+        if let Some(rec) = &self.rec_stream {
+            let entity_path = match self
+                .frame_tree
+                .lock()
+                .unwrap()
+                .entity_path(&path.header.frame_id)
+            {
+                Some(frame_path) => format!("{}/path/{}", frame_path, message.channel.topic),
+                None => format!("path/{}", message.channel.topic),
+            };
+            let points: Vec<[f32; 3]> = path
+                .poses
+                .iter()
+                .map(|p| {
+                    let position = &p.pose.position;
+                    [position.x as f32, position.y as f32, position.z as f32]
+                })
+                .collect();
+            rec.set_time_seconds(
+                "main",
+                path.header.stamp.sec as f64 + path.header.stamp.nanosec as f64 * 1e-9,
+            );
+            rec.log(entity_path, &rerun::LineStrips3D::new([points]))?;
+        }
+
+        // Dump data?
+        if let Some(csv) = self.csv.as_mut() {
+            for (i, pose) in path.poses.iter().enumerate() {
+                let (p, q) = (&pose.pose.position, &pose.pose.orientation);
+                writeln!(
+                    csv,
+                    "{},{},{},{},{},{},{},{},{},{},{},{}",
+                    path.header.stamp.sec,
+                    path.header.stamp.nanosec,
+                    i,
+                    pose.header.stamp.sec,
+                    pose.header.stamp.nanosec,
+                    p.x,
+                    p.y,
+                    p.z,
+                    q.x,
+                    q.y,
+                    q.z,
+                    q.w
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    fn post_process(&mut self, _sigint: Arc<AtomicBool>) -> Result<(), Self::ExtractorError> {
+        if let Some(csv) = self.csv.as_mut() {
+            csv.flush()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,364 @@
+//! Writer for the PCD file format of the Point Cloud Library.
+//! Reference: https://pointclouds.org/documentation/tutorials/pcd_file_format.html
+use ros2_sensor_msgs::msg::{PointCloud2, PointField};
+use std::{
+    fs,
+    io::{BufWriter, Write},
+    path::Path,
+    str::FromStr,
+};
+
+// LZF limits, same as liblzf
+const LZF_HASH_LOG: usize = 14;
+const LZF_MAX_LITERAL: usize = 1 << 5;
+const LZF_MAX_OFFSET: usize = 1 << 13;
+const LZF_MAX_REF: usize = (1 << 8) + (1 << 3);
+
+/// Data storage of the PCD file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Ascii,
+    Binary,
+    BinaryCompressed,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ascii" => Ok(Format::Ascii),
+            "binary" => Ok(Format::Binary),
+            "binary_compressed" => Ok(Format::BinaryCompressed),
+            _ => Err(format!(
+                "Invalid PCD format: {}, expected one of ascii, binary, binary_compressed",
+                s
+            )),
+        }
+    }
+}
+
+impl Format {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            Format::Ascii => "ascii",
+            Format::Binary => "binary",
+            Format::BinaryCompressed => "binary_compressed",
+        }
+    }
+}
+
+/// Write the point cloud into a PCD file. Padding between fields is dropped.
+pub fn write(path: &Path, cloud: &PointCloud2, format: Format) -> std::io::Result<()> {
+    let mut file = BufWriter::new(fs::File::create(path)?);
+    let fields = &cloud.fields;
+
+    // Header
+    writeln!(file, "# .PCD v0.7 - Point Cloud Data file format")?;
+    writeln!(file, "VERSION 0.7")?;
+    writeln!(file, "FIELDS {}", join(fields, |f| f.name.clone()))?;
+    writeln!(file, "SIZE {}", join(fields, |f| f.size().to_string()))?;
+    writeln!(file, "TYPE {}", join(fields, |f| type_char(f).to_string()))?;
+    writeln!(file, "COUNT {}", join(fields, |f| f.count.to_string()))?;
+    writeln!(file, "WIDTH {}", cloud.width)?;
+    writeln!(file, "HEIGHT {}", cloud.height)?;
+    writeln!(file, "VIEWPOINT 0 0 0 1 0 0 0")?;
+    writeln!(file, "POINTS {}", cloud.len())?;
+    writeln!(file, "DATA {}", format.as_str())?;
+
+    // Data
+    match format {
+        Format::Ascii => {
+            for point in points(cloud) {
+                let values: Vec<String> = fields
+                    .iter()
+                    .flat_map(|f| elements(f, point).map(|bytes| ascii_value(f, bytes)))
+                    .collect();
+                writeln!(file, "{}", values.join(" "))?;
+            }
+        }
+        Format::Binary => {
+            for point in points(cloud) {
+                for field in fields {
+                    elements(field, point).try_for_each(|bytes| file.write_all(bytes))?;
+                }
+            }
+        }
+        Format::BinaryCompressed => {
+            // Fields are stored one after another, so that similar values get together.
+            let mut data = Vec::new();
+            for field in fields {
+                for point in points(cloud) {
+                    elements(field, point).for_each(|bytes| data.extend_from_slice(bytes));
+                }
+            }
+            let compressed = lzf_compress(&data);
+            file.write_all(&(compressed.len() as u32).to_le_bytes())?;
+            file.write_all(&(data.len() as u32).to_le_bytes())?;
+            file.write_all(&compressed)?;
+        }
+    }
+    file.flush()
+}
+
+fn join(fields: &[PointField], f: impl Fn(&PointField) -> String) -> String {
+    fields.iter().map(f).collect::<Vec<_>>().join(" ")
+}
+
+fn type_char(field: &PointField) -> char {
+    match field.datatype {
+        1 | 3 | 5 => 'I',
+        2 | 4 | 6 => 'U',
+        _ => 'F',
+    }
+}
+
+/// Raw bytes of all the points.
+fn points(cloud: &PointCloud2) -> impl Iterator<Item = &[u8]> {
+    cloud
+        .data
+        .chunks_exact(cloud.point_step as usize)
+        .take(cloud.len())
+}
+
+/// Raw bytes of each element of the field in the point.
+fn elements<'a>(field: &PointField, point: &'a [u8]) -> impl Iterator<Item = &'a [u8]> {
+    let start = field.offset as usize;
+    let end = start + field.count as usize * field.size();
+    point[start..end].chunks_exact(field.size())
+}
+
+fn ascii_value(field: &PointField, bytes: &[u8]) -> String {
+    let bytes = bytes.to_vec();
+    match field.datatype {
+        // Packed colors are written as integers, as PCL does
+        7 if field.name == "rgb" || field.name == "rgba" => {
+            u32::from_ne_bytes(bytes.try_into().unwrap()).to_string()
+        }
+        1 => i8::from_ne_bytes(bytes.try_into().unwrap()).to_string(),
+        2 => u8::from_ne_bytes(bytes.try_into().unwrap()).to_string(),
+        3 => i16::from_ne_bytes(bytes.try_into().unwrap()).to_string(),
+        4 => u16::from_ne_bytes(bytes.try_into().unwrap()).to_string(),
+        5 => i32::from_ne_bytes(bytes.try_into().unwrap()).to_string(),
+        6 => u32::from_ne_bytes(bytes.try_into().unwrap()).to_string(),
+        7 => f32::from_ne_bytes(bytes.try_into().unwrap()).to_string(),
+        _ => f64::from_ne_bytes(bytes.try_into().unwrap()).to_string(),
+    }
+}
+
+/// Compress with LZF, the algorithm used by PCL for binary compressed PCD files.
+fn lzf_compress(input: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(input.len() / 2);
+    let mut literals: Vec<u8> = Vec::with_capacity(LZF_MAX_LITERAL);
+    let mut table = vec![0usize; 1 << LZF_HASH_LOG];
+
+    let flush = |output: &mut Vec<u8>, literals: &mut Vec<u8>| {
+        if !literals.is_empty() {
+            output.push(literals.len() as u8 - 1);
+            output.append(literals);
+        }
+    };
+
+    let mut i = 0;
+    while i + 2 < input.len() {
+        let hash =
+            ((input[i] as usize) << 16 | (input[i + 1] as usize) << 8 | input[i + 2] as usize)
+                .wrapping_mul(2654435761)
+                >> 8
+                & ((1 << LZF_HASH_LOG) - 1);
+
+        // Table entries are positions plus one, zero means empty
+        let candidate = table[hash];
+        table[hash] = i + 1;
+        if candidate > 0 {
+            let reference = candidate - 1;
+            let offset = i - reference - 1;
+            if offset < LZF_MAX_OFFSET && input[reference..reference + 3] == input[i..i + 3] {
+                let max_len = LZF_MAX_REF.min(input.len() - i);
+                let mut len = 3;
+                while len < max_len && input[reference + len] == input[i + len] {
+                    len += 1;
+                }
+                flush(&mut output, &mut literals);
+
+                // Back reference, lengths are stored minus two
+                let encoded_len = len - 2;
+                if encoded_len < 7 {
+                    output.push((encoded_len << 5 | offset >> 8) as u8);
+                } else {
+                    output.push((7 << 5 | offset >> 8) as u8);
+                    output.push((encoded_len - 7) as u8);
+                }
+                output.push((offset & 0xff) as u8);
+                i += len;
+                continue;
+            }
+        }
+
+        literals.push(input[i]);
+        if literals.len() == LZF_MAX_LITERAL {
+            flush(&mut output, &mut literals);
+        }
+        i += 1;
+    }
+
+    for &byte in &input[i..] {
+        literals.push(byte);
+        if literals.len() == LZF_MAX_LITERAL {
+            flush(&mut output, &mut literals);
+        }
+    }
+    flush(&mut output, &mut literals);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+
+    /// Decompress LZF data, as PCL does for binary compressed PCD files.
+    fn lzf_decompress(input: &[u8]) -> Vec<u8> {
+        let mut output = Vec::new();
+        let mut i = 0;
+        while i < input.len() {
+            let control = input[i] as usize;
+            i += 1;
+            if control < LZF_MAX_LITERAL {
+                output.extend_from_slice(&input[i..i + control + 1]);
+                i += control + 1;
+                continue;
+            }
+            let mut len = control >> 5;
+            if len == 7 {
+                len += input[i] as usize;
+                i += 1;
+            }
+            let start = output.len() - ((control & 0x1f) << 8 | input[i] as usize) - 1;
+            i += 1;
+            for k in 0..len + 2 {
+                output.push(output[start + k]);
+            }
+        }
+        output
+    }
+
+    /// Three points of `x`, `y`, `z` and `ring`, with two bytes of padding.
+    fn cloud() -> PointCloud2 {
+        let mut data = vec![];
+        for (i, xyz) in [[1.0f32, 2.0, 3.0], [-4.5, 0.25, 6.0], [7.0, 8.0, -9.75]]
+            .iter()
+            .enumerate()
+        {
+            xyz.iter()
+                .for_each(|v| data.extend_from_slice(&v.to_ne_bytes()));
+            data.extend_from_slice(&(i as u16 * 100).to_ne_bytes());
+            data.extend_from_slice(&[0xaa, 0xaa]);
+        }
+        test_util::cloud(
+            vec![
+                test_util::field("x", 0, 7, 1),
+                test_util::field("y", 4, 7, 1),
+                test_util::field("z", 8, 7, 1),
+                test_util::field("ring", 12, 4, 1),
+            ],
+            16,
+            data,
+        )
+    }
+
+    /// Header lines and the data of the PCD file.
+    fn read(path: &Path) -> (Vec<String>, Vec<u8>) {
+        let content = fs::read(path).unwrap();
+        let mut header = vec![];
+        let mut pos = 0;
+        while !header
+            .last()
+            .is_some_and(|l: &String| l.starts_with("DATA"))
+        {
+            let end = pos + content[pos..].iter().position(|&b| b == b'\n').unwrap();
+            header.push(String::from_utf8(content[pos..end].to_vec()).unwrap());
+            pos = end + 1;
+        }
+        (header, content[pos..].to_vec())
+    }
+
+    #[test]
+    fn write_ascii() {
+        let path = test_util::dir("pcd_ascii").join("cloud.pcd");
+        write(&path, &cloud(), Format::Ascii).unwrap();
+        let (header, data) = read(&path);
+        assert!(header.contains(&"FIELDS x y z ring".to_owned()));
+        assert!(header.contains(&"SIZE 4 4 4 2".to_owned()));
+        assert!(header.contains(&"TYPE F F F U".to_owned()));
+        assert!(header.contains(&"POINTS 3".to_owned()));
+        assert_eq!(header.last().unwrap(), "DATA ascii");
+        assert_eq!(
+            String::from_utf8(data).unwrap(),
+            "1 2 3 0\n-4.5 0.25 6 100\n7 8 -9.75 200\n"
+        );
+    }
+
+    #[test]
+    fn write_binary_without_padding() {
+        let path = test_util::dir("pcd_binary").join("cloud.pcd");
+        let cloud = cloud();
+        write(&path, &cloud, Format::Binary).unwrap();
+        let (header, data) = read(&path);
+        assert_eq!(header.last().unwrap(), "DATA binary");
+        let expected: Vec<u8> = cloud
+            .data
+            .chunks_exact(16)
+            .flat_map(|point| point[..14].to_vec())
+            .collect();
+        assert_eq!(data, expected);
+    }
+
+    #[test]
+    fn write_binary_compressed_by_field() {
+        let path = test_util::dir("pcd_compressed").join("cloud.pcd");
+        let cloud = cloud();
+        write(&path, &cloud, Format::BinaryCompressed).unwrap();
+        let (header, data) = read(&path);
+        assert_eq!(header.last().unwrap(), "DATA binary_compressed");
+        let compressed_len = u32::from_le_bytes(data[..4].try_into().unwrap()) as usize;
+        let len = u32::from_le_bytes(data[4..8].try_into().unwrap()) as usize;
+        assert_eq!(data.len(), 8 + compressed_len);
+        let decompressed = lzf_decompress(&data[8..]);
+        assert_eq!(decompressed.len(), len);
+
+        // All the x, then all the y and so on
+        let mut expected = vec![];
+        for (offset, size) in [(0, 4), (4, 4), (8, 4), (12, 2)] {
+            for point in cloud.data.chunks_exact(16) {
+                expected.extend_from_slice(&point[offset..offset + size]);
+            }
+        }
+        assert_eq!(decompressed, expected);
+    }
+
+    #[test]
+    fn lzf_round_trip() {
+        // Pseudo random bytes, without many matches
+        let mut seed = 1u32;
+        let random: Vec<u8> = (0..10000)
+            .map(|_| {
+                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                (seed >> 16) as u8
+            })
+            .collect();
+        let inputs: Vec<Vec<u8>> = vec![
+            vec![],
+            vec![1, 2],
+            vec![0; 1000],
+            (0..4000).map(|i| (i % 37) as u8).collect(),
+            // Matches further than the largest offset are literals
+            [random.clone(), random[..100].to_vec(), random.clone()].concat(),
+            random,
+        ];
+        for input in inputs {
+            assert_eq!(lzf_decompress(&lzf_compress(&input)), input);
+        }
+        assert!(lzf_compress(&[0; 1000]).len() < 50);
+    }
+}
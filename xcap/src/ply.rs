@@ -0,0 +1,50 @@
+//! Writer for the binary PLY file format of points with intensities.
+//! Reference: https://paulbourke.net/dataformats/ply/
+use std::{
+    fs,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+/// Write the points of `x`, `y`, `z` and `intensity` into a little endian binary PLY file.
+pub fn write(path: &Path, points: &[[f32; 4]]) -> std::io::Result<()> {
+    let mut file = BufWriter::new(fs::File::create(path)?);
+
+    // Header
+    writeln!(file, "ply")?;
+    writeln!(file, "format binary_little_endian 1.0")?;
+    writeln!(file, "element vertex {}", points.len())?;
+    for name in ["x", "y", "z", "intensity"] {
+        writeln!(file, "property float {}", name)?;
+    }
+    writeln!(file, "end_header")?;
+
+    // Data
+    for value in points.iter().flatten() {
+        file.write_all(&value.to_le_bytes())?;
+    }
+    file.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+
+    #[test]
+    fn write_points() {
+        let path = test_util::dir("ply").join("map.ply");
+        let points = [[1.0, 2.0, 3.0, 10.0], [-4.0, 5.5, -6.0, 0.5]];
+        write(&path, &points).unwrap();
+        let content = fs::read(&path).unwrap();
+        let header = "ply\nformat binary_little_endian 1.0\nelement vertex 2\n\
+            property float x\nproperty float y\nproperty float z\nproperty float intensity\n\
+            end_header\n";
+        assert_eq!(&content[..header.len()], header.as_bytes());
+        let values: Vec<f32> = content[header.len()..]
+            .chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+            .collect();
+        assert_eq!(values, points.concat());
+    }
+}
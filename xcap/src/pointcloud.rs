@@ -1,23 +1,373 @@
+use crate::accumulate::{Map, Source};
+use crate::colormap;
 use crate::extractor::Extractor;
-use colorgrad::Gradient;
+use crate::frame_index::{Entry, FrameIndex};
+use crate::pcd;
+use crate::pointcloud_parquet;
+use crate::tf::FrameTree;
+use crate::trajectory::Trajectory;
+use log::warn;
 use mcap::Message;
 use rerun::{external::glam, RecordingStream};
-use ros2_sensor_msgs::msg::{PointCloud2, PointCloud2Iterator};
+use ros2_sensor_msgs::msg::{Datatype, PointCloud2, PointCloud2Iterator};
 use std::{
     fs,
     io::Write,
     path::{Path, PathBuf},
-    sync::{atomic::AtomicBool, Arc},
+    str::FromStr,
+    sync::{atomic::AtomicBool, Arc, Mutex},
 };
 
-const ZSTD_MAGIC_NUMBER: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
-
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
-    #[error("ZSTD error. {0}")]
-    Zstd(#[from] std::io::Error),
+    #[error("Payload error. {0}")]
+    Payload(#[from] crate::extractor::DecodeError),
     #[error("CDR error. {0}")]
-    CDR(#[from] cdr::Error),
+    Cdr(#[from] cdr::Error),
+    #[error("Point field not found: {0}, available fields: {1}")]
+    MissingField(String, String),
+    #[error("Only float coordinates could be transformed, field {0} is not")]
+    NotFloat(String),
+}
+
+// Fields holding the measuring time of each point, as named by common lidar drivers
+const TIME_FIELDS: [&str; 4] = ["time", "t", "timestamp", "offset_time"];
+
+// Point times above this are seconds since the epoch, offsets from the stamp of the cloud otherwise
+const ABSOLUTE_TIME: f64 = 1e6;
+
+/// Names of the point fields used as coordinates, intensity, ring and time. Mapped coordinates,
+/// intensity and ring are renamed to `x`, `y`, `z`, `intensity` and `ring` in the output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldMap {
+    pub x: String,
+    pub y: String,
+    pub z: String,
+    pub intensity: Option<String>,
+    pub ring: String,
+    // Detected from the common names if not set
+    pub time: Option<String>,
+}
+
+impl Default for FieldMap {
+    fn default() -> Self {
+        FieldMap {
+            x: "x".to_owned(),
+            y: "y".to_owned(),
+            z: "z".to_owned(),
+            intensity: Some("intensity".to_owned()),
+            ring: "ring".to_owned(),
+            time: None,
+        }
+    }
+}
+
+impl FromStr for FieldMap {
+    type Err = String;
+
+    /// Parse mappings like `x=px,intensity=reflectivity`. Fields not mentioned keep their default
+    /// names, and `intensity=none` drops the intensity.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut map = FieldMap::default();
+        for pair in s.split(',').map(|p| p.trim()).filter(|p| !p.is_empty()) {
+            let (key, name) = pair.split_once('=').ok_or(format!(
+                "Invalid point field mapping: {}, expected key=field",
+                pair
+            ))?;
+            let name = name.trim().to_owned();
+            match key.trim() {
+                "x" => map.x = name,
+                "y" => map.y = name,
+                "z" => map.z = name,
+                "intensity" if name.is_empty() || name == "none" => map.intensity = None,
+                "intensity" => map.intensity = Some(name),
+                "ring" => map.ring = name,
+                "time" => map.time = Some(name),
+                _ => {
+                    return Err(format!(
+                        "Invalid point field: {}, expected one of x, y, z, intensity, ring, time",
+                        key
+                    ))
+                }
+            }
+        }
+        Ok(map)
+    }
+}
+
+impl FieldMap {
+    /// Rename the mapped fields of the cloud to the standard names. Fields already holding a
+    /// standard name get a `_raw` suffix to avoid duplicates.
+    fn apply(&self, cloud: &mut PointCloud2) {
+        let mut pairs = vec![
+            ("x", &self.x),
+            ("y", &self.y),
+            ("z", &self.z),
+            ("ring", &self.ring),
+        ];
+        if let Some(intensity) = &self.intensity {
+            pairs.push(("intensity", intensity));
+        }
+        for (standard, name) in pairs {
+            if standard == name.as_str() || !cloud.fields.iter().any(|f| f.name == *name) {
+                continue;
+            }
+            for field in cloud.fields.iter_mut() {
+                if field.name == standard {
+                    field.name = format!("{}_raw", standard);
+                } else if field.name == *name {
+                    field.name = standard.to_owned();
+                }
+            }
+        }
+        if self.intensity.is_none() {
+            for field in cloud.fields.iter_mut().filter(|f| f.name == "intensity") {
+                field.name = "intensity_raw".to_owned();
+            }
+        }
+    }
+
+    /// Measuring time of each point in seconds since the epoch, None if the cloud has no time
+    /// field. Integer times are taken as nanoseconds and float times as seconds, either since the
+    /// epoch or since the stamp of the cloud.
+    pub(crate) fn point_times(&self, cloud: &PointCloud2) -> Option<Vec<f64>> {
+        let field = match &self.time {
+            Some(name) => cloud.fields.iter().find(|f| f.name == *name),
+            None => TIME_FIELDS
+                .iter()
+                .find_map(|name| cloud.fields.iter().find(|f| f.name == *name)),
+        }?;
+        let i = cloud.fields.iter().position(|f| f == field)?;
+        let scale = if field.datatype == 7 || field.datatype == 8 {
+            1.0
+        } else {
+            1e-9
+        };
+        let stamp = cloud.header.stamp.sec as f64 + cloud.header.stamp.nanosec as f64 * 1e-9;
+        Some(
+            PointCloud2Iterator::new(cloud)
+                .map(|p| {
+                    let time = f64::from(p[i][0]) * scale;
+                    if time > ABSOLUTE_TIME {
+                        time
+                    } else {
+                        stamp + time
+                    }
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Points out of the box are dropped. The box is in the frame of the cloud unless `frame` is set.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CropBox {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+    pub frame: Option<String>,
+}
+
+impl FromStr for CropBox {
+    type Err = String;
+
+    /// Parse boxes like `xmin,xmax,ymin,ymax,zmin,zmax`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s
+            .split(',')
+            .map(|v| v.trim().parse::<f32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| format!("Invalid crop box: {}", s))?;
+        let [xmin, xmax, ymin, ymax, zmin, zmax] = values[..] else {
+            return Err(format!(
+                "Invalid crop box: {}, expected xmin,xmax,ymin,ymax,zmin,zmax",
+                s
+            ));
+        };
+        if xmin > xmax || ymin > ymax || zmin > zmax {
+            return Err(format!("Crop box min should not exceed max: {}", s));
+        }
+        Ok(CropBox {
+            min: [xmin, ymin, zmin],
+            max: [xmax, ymax, zmax],
+            frame: None,
+        })
+    }
+}
+
+impl CropBox {
+    fn contains(&self, point: glam::Vec3) -> bool {
+        point.cmpge(glam::Vec3::from(self.min)).all()
+            && point.cmple(glam::Vec3::from(self.max)).all()
+    }
+
+    /// Keep the points in the box only, the cloud becomes unorganized.
+    fn apply(&self, cloud: &mut PointCloud2, transform: glam::Affine3A) -> Result<(), Error> {
+        let (x, y, z) = (
+            field_index(cloud, "x")?,
+            field_index(cloud, "y")?,
+            field_index(cloud, "z")?,
+        );
+        let step = cloud.point_step as usize;
+        let mut data = Vec::with_capacity(cloud.data.len());
+        for (i, p) in PointCloud2Iterator::new(cloud).enumerate() {
+            let point = glam::vec3(p[x][0].into(), p[y][0].into(), p[z][0].into());
+            if self.contains(transform.transform_point3(point)) {
+                data.extend_from_slice(&cloud.data[i * step..(i + 1) * step]);
+            }
+        }
+        cloud.height = 1;
+        cloud.width = (data.len() / step) as u32;
+        cloud.row_step = data.len() as u32;
+        cloud.data = data;
+        Ok(())
+    }
+}
+
+/// Transform coordinates of all the points in place.
+fn transform_cloud(cloud: &mut PointCloud2, transform: glam::Affine3A) -> Result<(), Error> {
+    map_points(cloud, |_, point| transform.transform_point3(point))
+}
+
+/// Move every point to where it was at the stamp of the cloud, with the poses of the vehicle when
+/// the points were measured. `sensor` is the pose of the sensor in the moving frame of the
+/// trajectory. Return false if the trajectory does not cover the cloud, which is left as it was.
+fn deskew(
+    cloud: &mut PointCloud2,
+    times: &[f64],
+    trajectory: &Trajectory,
+    sensor: glam::Affine3A,
+) -> Result<bool, Error> {
+    let nanos = |seconds: f64| (seconds * 1e9).round() as u64;
+    let stamp = cloud.header.stamp.sec as u64 * 1_000_000_000 + cloud.header.stamp.nanosec as u64;
+    let (Some(first), Some(last)) = (
+        times.iter().copied().reduce(f64::min),
+        times.iter().copied().reduce(f64::max),
+    ) else {
+        return Ok(true);
+    };
+    let Some(reference) = trajectory.pose_at(stamp) else {
+        return Ok(false);
+    };
+    if trajectory.pose_at(nanos(first)).is_none() || trajectory.pose_at(nanos(last)).is_none() {
+        return Ok(false);
+    }
+    let to_reference = (reference * sensor).inverse();
+
+    // Points of a column or a firing share their times, so the last transform is reused
+    let mut last: Option<(u64, glam::Affine3A)> = None;
+    map_points(cloud, |i, point| {
+        let time = nanos(times[i]);
+        let transform = match last {
+            Some((t, transform)) if t == time => transform,
+            _ => {
+                let pose = trajectory.pose_at(time).unwrap_or(reference);
+                let transform = to_reference * pose * sensor;
+                last = Some((time, transform));
+                transform
+            }
+        };
+        transform.transform_point3(point)
+    })?;
+    Ok(true)
+}
+
+/// Replace the coordinates of each point by the function of its index and coordinates.
+fn map_points(
+    cloud: &mut PointCloud2,
+    mut f: impl FnMut(usize, glam::Vec3) -> glam::Vec3,
+) -> Result<(), Error> {
+    let fields = [
+        field_index(cloud, "x")?,
+        field_index(cloud, "y")?,
+        field_index(cloud, "z")?,
+    ]
+    .map(|i| cloud.fields[i].clone());
+    if let Some(field) = fields.iter().find(|f| f.datatype != 7 && f.datatype != 8) {
+        return Err(Error::NotFloat(field.name.clone()));
+    }
+    let len = cloud.len();
+    for (i, point) in cloud
+        .data
+        .chunks_exact_mut(cloud.point_step as usize)
+        .take(len)
+        .enumerate()
+    {
+        let [x, y, z] = fields.each_ref().map(|f| {
+            let start = f.offset as usize;
+            f32::from(f.decode_bytes(&point[start..start + f.size()])[0])
+        });
+        let transformed = f(i, glam::vec3(x, y, z));
+        for (field, value) in fields.iter().zip(transformed.to_array()) {
+            let start = field.offset as usize;
+            match field.datatype {
+                7 => point[start..start + 4].copy_from_slice(&value.to_ne_bytes()),
+                _ => point[start..start + 8].copy_from_slice(&(value as f64).to_ne_bytes()),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Swap the bytes of every field element of clouds recorded on hosts of the other endianness.
+/// The CDR encapsulation is handled by the deserializer, while the point data is left as it was
+/// published.
+fn to_native_endian(cloud: &mut PointCloud2) {
+    if (cloud.is_bigendian != 0) == cfg!(target_endian = "big") || cloud.point_step == 0 {
+        return;
+    }
+    let fields: Vec<_> = cloud
+        .fields
+        .iter()
+        .filter(|f| (1..=8).contains(&f.datatype))
+        .cloned()
+        .collect();
+    let len = cloud.len();
+    for point in cloud
+        .data
+        .chunks_exact_mut(cloud.point_step as usize)
+        .take(len)
+    {
+        for field in fields.iter() {
+            let size = field.size();
+            for i in 0..field.count as usize {
+                let start = field.offset as usize + i * size;
+                if let Some(element) = point.get_mut(start..start + size) {
+                    element.reverse();
+                }
+            }
+        }
+    }
+    cloud.is_bigendian = cfg!(target_endian = "big") as u8;
+}
+
+/// Field of packed colors in the cloud, and whether it has alpha.
+pub(crate) fn color_field(cloud: &PointCloud2) -> Option<(usize, bool)> {
+    let find = |name: &str| cloud.fields.iter().position(|f| f.name == name);
+    find("rgba")
+        .map(|i| (i, true))
+        .or_else(|| find("rgb").map(|i| (i, false)))
+}
+
+/// Unpack the color packed into 32 bits as in PCL, `rgb` fields are usually float typed. Colors
+/// without alpha are opaque.
+pub(crate) fn unpack_rgba(value: Datatype, has_alpha: bool) -> [u8; 4] {
+    let bits = match value {
+        Datatype::FLOAT32(v) => v.to_bits(),
+        Datatype::UINT32(v) => v,
+        Datatype::INT32(v) => v as u32,
+        other => f64::from(other) as u32,
+    };
+    let [b, g, r, a] = bits.to_le_bytes();
+    [r, g, b, if has_alpha { a } else { u8::MAX }]
+}
+
+/// Index of the field in the cloud.
+fn field_index(cloud: &PointCloud2, name: &str) -> Result<usize, Error> {
+    cloud
+        .fields
+        .iter()
+        .position(|f| f.name == name)
+        .ok_or_else(|| Error::MissingField(name.to_owned(), cloud.field_names().join(",")))
 }
 
 pub struct Parser {
@@ -30,6 +380,18 @@ pub struct Parser {
     // Should dump data to disk
     dump_data: bool,
 
+    // Dump clouds as PCD files in this format, raw point data otherwise
+    pcd_format: Option<pcd::Format>,
+
+    // Also export all points into a Parquet file?
+    parquet: bool,
+
+    // Parquet file, created on the first cloud and named after it
+    parquet_writer: Option<pointcloud_parquet::Writer>,
+
+    // Scratch buffer for decoded message payloads, reused across messages
+    buffer: Vec<u8>,
+
     // Scale the points in spatial domain? This could be usefull if users want to visualize the pointcloud in a
     // different spatial scale.
     spatial_scale: f32,
@@ -38,16 +400,68 @@ pub struct Parser {
     intensity_scale: f32,
 
     // Color map. Map point cloud intensity to a color.
-    color_map: colorgrad::LinearGradient,
+    color_map: colormap::Gradient,
+
+    // Fields used as coordinates and intensity
+    field_map: FieldMap,
+
+    // Drop points out of this box
+    crop: Option<CropBox>,
+
+    // Transform of the sensor in the rig frame, from the calibration file
+    extrinsic: Option<glam::Affine3A>,
+
+    // Also transform the dumped clouds into the rig frame?
+    extrinsic_in_output: bool,
+
+    // Transform tree, clouds are logged in their own frames once the frames are known
+    frame_tree: Arc<Mutex<FrameTree>>,
+
+    // Trajectory to remove the motion distortion of the clouds with
+    deskew: Option<Arc<Trajectory>>,
+
+    // Clouds left distorted as the trajectory does not cover them
+    distorted: u64,
+
+    // Map the clouds are accumulated into, shared with parsers of other clouds
+    map: Option<Arc<Mutex<Map>>>,
+
+    // Where the poses of the clouds in the map come from
+    accumulate: Option<Source>,
+
+    // Clouds added to the map, and left out as their poses are unknown
+    mapped: u64,
+    unplaced: u64,
+
+    // Index of the dumped clouds, shared with parsers of other files
+    frame_index: Arc<Mutex<FrameIndex>>,
+
+    // Clouds dumped so far
+    count: u64,
+
+    // Input file of the messages
+    source: String,
 }
 
 impl Parser {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         output_path: &Path,
         rerun_stream: Option<RecordingStream>,
         dump_data: bool,
         spatial_scale: Option<f32>,
         intensity_scale: Option<f32>,
+        color_map: colormap::Gradient,
+        field_map: FieldMap,
+        crop: Option<CropBox>,
+        extrinsic: Option<glam::Affine3A>,
+        extrinsic_in_output: bool,
+        pcd_format: Option<pcd::Format>,
+        parquet: bool,
+        frame_tree: Arc<Mutex<FrameTree>>,
+        deskew: Option<Arc<Trajectory>>,
+        map: Option<Arc<Mutex<Map>>>,
+        frame_index: Arc<Mutex<FrameIndex>>,
     ) -> Self {
         // Create output dir
         if dump_data {
@@ -58,70 +472,371 @@ impl Parser {
             output_dir: output_path.into(),
             rec_stream: rerun_stream,
             dump_data,
+            pcd_format,
+            parquet,
+            parquet_writer: None,
+            buffer: Vec::new(),
             spatial_scale: spatial_scale.unwrap_or(1.0),
             intensity_scale: intensity_scale.unwrap_or(1.0),
-            color_map: colorgrad::GradientBuilder::new()
-                .html_colors(&["#00F", "#FFF", "gold"])
-                .domain(&[0.0, 0.3, 0.6])
-                .mode(colorgrad::BlendMode::LinearRgb)
-                .build::<colorgrad::LinearGradient>()
-                .expect("Color map should be created"),
+            color_map,
+            field_map,
+            crop,
+            extrinsic,
+            extrinsic_in_output,
+            frame_tree,
+            deskew,
+            distorted: 0,
+            accumulate: map.as_ref().map(|m| m.lock().unwrap().source().clone()),
+            map,
+            mapped: 0,
+            unplaced: 0,
+            frame_index,
+            count: 0,
+            source: String::new(),
         }
     }
 }
 
+impl Parser {
+    /// Pose of the sensor in the moving frame of the trajectory, from TF or the extrinsics. The
+    /// sensor is taken at the origin of the frame if neither knows it.
+    fn sensor_pose(&self, frame: &str, trajectory: &Trajectory) -> glam::Affine3A {
+        if frame == trajectory.child_frame {
+            return glam::Affine3A::IDENTITY;
+        }
+        self.frame_tree
+            .lock()
+            .unwrap()
+            .transform(frame, &trajectory.child_frame)
+            .or(self.extrinsic)
+            .unwrap_or(glam::Affine3A::IDENTITY)
+    }
+
+    /// Pose of the cloud in the world frame of the map. Return None if it is unknown.
+    fn world_pose(&self, cloud: &PointCloud2, source: &Source) -> Option<glam::Affine3A> {
+        let frame = &cloud.header.frame_id;
+        match source {
+            Source::Odometry(trajectory) => {
+                let stamp = cloud.header.stamp.sec as u64 * 1_000_000_000
+                    + cloud.header.stamp.nanosec as u64;
+                let pose = trajectory.pose_at(stamp)?;
+                Some(pose * self.sensor_pose(frame, trajectory))
+            }
+            Source::Frame(world) => self.frame_tree.lock().unwrap().transform(frame, world),
+        }
+    }
+
+    /// Add the cloud to the map with its pose in the world frame, and show the new part of the
+    /// map. Every part is logged at its own entity so that the map builds up over time.
+    fn add_to_map(
+        &mut self,
+        cloud: &PointCloud2,
+        pose: glam::Affine3A,
+        topic: &str,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let Some(map) = &self.map else {
+            return Ok(());
+        };
+        let (x, y, z) = (
+            field_index(cloud, "x")?,
+            field_index(cloud, "y")?,
+            field_index(cloud, "z")?,
+        );
+        let intensity = field_index(cloud, "intensity").ok();
+        let new = map.lock().unwrap().add(
+            &cloud.header,
+            PointCloud2Iterator::new(cloud).map(|p| {
+                let v = glam::vec3(p[x][0].into(), p[y][0].into(), p[z][0].into());
+                let value = intensity.map_or(0.0, |i| f32::from(p[i][0]));
+                (pose.transform_point3(v), value)
+            }),
+        );
+        self.mapped += 1;
+
+        let Some(rec) = &self.rec_stream else {
+            return Ok(());
+        };
+        if new.is_empty() {
+            return Ok(());
+        }
+        rec.set_time_seconds(
+            "main",
+            cloud.header.stamp.sec as f64 + cloud.header.stamp.nanosec as f64 * 1e-9,
+        );
+        let world = self.accumulate.as_ref().map(Source::world_frame);
+        let entity_path = match world.and_then(|w| self.frame_tree.lock().unwrap().entity_path(w)) {
+            Some(frame_path) => format!("{}/map/{}/{}", frame_path, topic, self.mapped),
+            None => format!("map/{}/{}", topic, self.mapped),
+        };
+        let mut part = rerun::Points3D::new(new.iter().map(|(p, _)| *p * self.spatial_scale))
+            .with_radii([0.01]);
+        if intensity.is_some() {
+            part = part.with_colors(new.iter().map(|(_, value)| {
+                let [r, g, b, a] = self.color_map.at(value * self.intensity_scale);
+                rerun::Color::from_unmultiplied_rgba(r, g, b, a)
+            }));
+        }
+        rec.log(entity_path, &part)?;
+        Ok(())
+    }
+}
+
 impl Extractor for Parser {
-    type ExtractorError = Box<dyn std::error::Error>;
+    type ExtractorError = Box<dyn std::error::Error + Send + Sync>;
+
+    fn buffer(&mut self) -> &mut Vec<u8> {
+        &mut self.buffer
+    }
 
     fn step(&mut self, message: &Message) -> Result<(), Self::ExtractorError> {
-        let buf = message.data.as_ref();
-        let serialized = if &message.data[..4] == ZSTD_MAGIC_NUMBER {
-            zstd::stream::decode_all(buf).map_err(|e| Error::Zstd(e))?
-        } else {
-            message.data.to_vec()
+        let payload = self.decode_into(message).map_err(Error::Payload)?;
+        let mut points = cdr::deserialize_from::<_, PointCloud2, _>(payload, cdr::size::Infinite)
+            .map_err(Error::Cdr)?;
+        to_native_endian(&mut points);
+
+        self.field_map.apply(&mut points);
+
+        if let Some(crop) = &self.crop {
+            let transform = match &crop.frame {
+                Some(frame) => self
+                    .frame_tree
+                    .lock()
+                    .unwrap()
+                    .transform(&points.header.frame_id, frame),
+                None => Some(glam::Affine3A::IDENTITY),
+            };
+            match transform {
+                Some(transform) => crop.apply(&mut points, transform)?,
+                None => warn!(
+                    "No transform from {} to {}, the cloud is not cropped.",
+                    points.header.frame_id,
+                    crop.frame.as_deref().unwrap_or_default()
+                ),
+            }
+        }
+
+        // Times of the points, for the motion compensation and the Parquet export
+        let times = match (&self.deskew, self.dump_data && self.parquet) {
+            (None, false) => None,
+            _ => self.field_map.point_times(&points),
         };
-        let points =
-            cdr::deserialize_from::<_, PointCloud2, _>(serialized.as_slice(), cdr::size::Infinite)
-                .map_err(|e| Error::CDR(e))?;
+        if let Some(trajectory) = &self.deskew {
+            let deskewed = match &times {
+                Some(times) => {
+                    let sensor = self.sensor_pose(&points.header.frame_id, trajectory);
+                    deskew(&mut points, times, trajectory, sensor)?
+                }
+                None => false,
+            };
+            if !deskewed {
+                if self.distorted == 0 {
+                    warn!(
+                        "Clouds of {} are left distorted if they have no point times or the \
+                        odometry does not cover them.",
+                        message.channel.topic
+                    );
+                }
+                self.distorted += 1;
+            }
+        }
+
+        // Clouds are accumulated in the sensor frame, before any transform for the output
+        if let Some(source) = self.accumulate.clone() {
+            match self.world_pose(&points, &source) {
+                Some(pose) => self.add_to_map(&points, pose, &message.channel.topic)?,
+                None => {
+                    if self.unplaced == 0 {
+                        warn!(
+                            "Clouds of {} are left out of the map if their poses in {} are \
+                            unknown.",
+                            message.channel.topic,
+                            source.world_frame()
+                        );
+                    }
+                    self.unplaced += 1;
+                }
+            }
+        }
+
+        // Clouds with an extrinsic are shown in the rig frame, transformed in the output too if
+        // required.
+        let mut vis_transform = glam::Affine3A::IDENTITY;
+        if let Some(extrinsic) = self.extrinsic {
+            if self.extrinsic_in_output {
+                transform_cloud(&mut points, extrinsic)?;
+            } else {
+                vis_transform = extrinsic;
+            }
+        }
 
         if let Some(rec) = &self.rec_stream {
-            let points_for_vis = PointCloud2Iterator::new(&points).into_iter().map(|p| {
-                let v = glam::vec3((p[0][0]).into(), p[1][0].into(), p[2][0].into());
-                v * self.spatial_scale
-            });
-            let intensity = PointCloud2Iterator::new(&points).into_iter().map(|p| {
-                f32::from(p.last().unwrap().last().unwrap().clone()) * self.intensity_scale
-            });
-            let colors = intensity.map(|i| {
-                let [r, g, b, a] = self.color_map.at(i).to_rgba8();
-                rerun::Color::from_unmultiplied_rgba(r, g, b, a)
+            let (x, y, z) = (
+                field_index(&points, "x")?,
+                field_index(&points, "y")?,
+                field_index(&points, "z")?,
+            );
+            let points_for_vis = PointCloud2Iterator::new(&points).map(|p| {
+                let v = glam::vec3(p[x][0].into(), p[y][0].into(), p[z][0].into());
+                vis_transform.transform_point3(v) * self.spatial_scale
             });
+            // Points are colored by their own colors, or by intensity. Points without either are
+            // left in the default color.
+            let colors: Vec<rerun::Color> = if let Some((i, has_alpha)) = color_field(&points) {
+                PointCloud2Iterator::new(&points)
+                    .map(|p| {
+                        let [r, g, b, a] = unpack_rgba(p[i][0], has_alpha);
+                        rerun::Color::from_unmultiplied_rgba(r, g, b, a)
+                    })
+                    .collect()
+            } else if let Ok(i) = field_index(&points, "intensity") {
+                PointCloud2Iterator::new(&points)
+                    .map(|p| {
+                        let [r, g, b, a] =
+                            self.color_map.at(f32::from(p[i][0]) * self.intensity_scale);
+                        rerun::Color::from_unmultiplied_rgba(r, g, b, a)
+                    })
+                    .collect()
+            } else {
+                vec![]
+            };
 
             rec.set_time_seconds(
                 "main",
                 points.header.stamp.sec as f64 + points.header.stamp.nanosec as f64 * 1e-9,
             );
-            rec.log(
-                format!("cloud/{}", message.channel.topic.clone()),
-                &rerun::Points3D::new(points_for_vis)
-                    .with_colors(colors)
-                    .with_radii([0.01]),
-            )?;
+            let frame_path = match self.extrinsic {
+                Some(_) => None,
+                None => self
+                    .frame_tree
+                    .lock()
+                    .unwrap()
+                    .entity_path(&points.header.frame_id),
+            };
+            let entity_path = match frame_path {
+                Some(frame_path) => format!("{}/cloud/{}", frame_path, message.channel.topic),
+                None => format!("cloud/{}", message.channel.topic),
+            };
+            let mut cloud = rerun::Points3D::new(points_for_vis).with_radii([0.01]);
+            if !colors.is_empty() {
+                cloud = cloud.with_colors(colors);
+            }
+            rec.log(entity_path, &cloud)?;
         }
 
         // Create output file
         if self.dump_data {
-            let mut file = fs::File::create(
-                &self
+            let path = if let Some(format) = self.pcd_format {
+                let path = self
                     .output_dir
-                    .join(format!("{}.bin", message.publish_time)),
-            )?;
-            file.write_all(&points.data)?;
+                    .join(format!("{}.pcd", message.publish_time));
+                pcd::write(&path, &points, format)?;
+                path
+            } else {
+                let path = self
+                    .output_dir
+                    .join(format!("{}.bin", message.publish_time));
+                fs::File::create(&path)?.write_all(&points.data)?;
+                path
+            };
+            self.frame_index.lock().unwrap().add(
+                &message.channel.topic,
+                &self.output_dir,
+                Entry {
+                    file: path,
+                    stamp: points.header.stamp.sec as u64 * 1_000_000_000
+                        + points.header.stamp.nanosec as u64,
+                    publish_time: message.publish_time,
+                    seq: self.count,
+                    source: self.source.clone(),
+                },
+            );
+            self.count += 1;
+        }
+        if self.dump_data && self.parquet {
+            if self.parquet_writer.is_none() {
+                self.parquet_writer = Some(pointcloud_parquet::Writer::new(
+                    &self
+                        .output_dir
+                        .join(format!("{}.parquet", message.publish_time)),
+                )?);
+            }
+            self.parquet_writer
+                .as_mut()
+                .unwrap()
+                .write(&points, times.as_deref())?;
         }
         Ok(())
     }
 
+    fn set_source(&mut self, source: &Path) {
+        self.source = source.display().to_string();
+    }
+
     fn post_process(&mut self, _sigint: Arc<AtomicBool>) -> Result<(), Self::ExtractorError> {
+        if self.distorted > 0 {
+            warn!("Clouds left distorted: {}", self.distorted);
+        }
+        if self.unplaced > 0 {
+            warn!("Clouds left out of the map: {}", self.unplaced);
+        }
+        if let Some(writer) = self.parquet_writer.take() {
+            writer.close()?;
+        }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+
+    /// Points of `x` as f32, `ring` as u16, `flags` as two i8 and `time` as f64.
+    fn cloud(points: &[(f32, u16, [i8; 2], f64)], swap: bool) -> PointCloud2 {
+        let mut data = vec![];
+        for (x, ring, flags, time) in points {
+            let mut put = |mut bytes: Vec<u8>| {
+                if swap {
+                    bytes.reverse();
+                }
+                data.extend_from_slice(&bytes);
+            };
+            put(x.to_ne_bytes().to_vec());
+            put(ring.to_ne_bytes().to_vec());
+            flags.iter().for_each(|f| put(f.to_ne_bytes().to_vec()));
+            put(time.to_ne_bytes().to_vec());
+        }
+        let mut cloud = test_util::cloud(
+            vec![
+                test_util::field("x", 0, 7, 1),
+                test_util::field("ring", 4, 4, 1),
+                test_util::field("flags", 6, 1, 2),
+                test_util::field("time", 8, 8, 1),
+            ],
+            16,
+            data,
+        );
+        if swap {
+            cloud.is_bigendian = 1 - cloud.is_bigendian;
+        }
+        cloud
+    }
+
+    #[test]
+    fn swap_clouds_of_the_other_endianness() {
+        let points = [(1.5, 3, [-1, 2], 0.25), (-2.0, 65000, [5, -6], 1e9)];
+        let mut swapped = cloud(&points, true);
+        let native = cloud(&points, false);
+        assert_ne!(swapped.data, native.data);
+        to_native_endian(&mut swapped);
+        assert_eq!(swapped, native);
+    }
+
+    #[test]
+    fn keep_native_clouds() {
+        let points = [(1.5, 3, [-1, 2], 0.25)];
+        let mut native = cloud(&points, false);
+        to_native_endian(&mut native);
+        assert_eq!(native, cloud(&points, false));
+    }
+}
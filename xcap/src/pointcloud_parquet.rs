@@ -0,0 +1,113 @@
+//! Point clouds in Apache Parquet, one row per point.
+use crate::pointcloud::{color_field, unpack_rgba};
+use arrow::{
+    array::{
+        new_null_array, ArrayRef, Float32Array, Float64Array, RecordBatch, UInt16Array, UInt8Array,
+    },
+    datatypes::{DataType, Field, Schema, SchemaRef},
+};
+use parquet::{
+    arrow::ArrowWriter,
+    basic::{Compression, ZstdLevel},
+    errors::Result,
+    file::properties::WriterProperties,
+};
+use ros2_sensor_msgs::msg::{Datatype, PointCloud2, PointField};
+use std::{fs, path::Path, sync::Arc};
+
+pub struct Writer {
+    inner: ArrowWriter<fs::File>,
+    schema: SchemaRef,
+}
+
+impl Writer {
+    pub fn new(path: &Path) -> Result<Self> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("x", DataType::Float32, false),
+            Field::new("y", DataType::Float32, false),
+            Field::new("z", DataType::Float32, false),
+            Field::new("intensity", DataType::Float32, true),
+            Field::new("ring", DataType::UInt16, true),
+            Field::new("timestamp", DataType::Float64, false),
+            Field::new("r", DataType::UInt8, true),
+            Field::new("g", DataType::UInt8, true),
+            Field::new("b", DataType::UInt8, true),
+        ]));
+        let props = WriterProperties::builder()
+            .set_compression(Compression::ZSTD(ZstdLevel::default()))
+            .build();
+        let inner = ArrowWriter::try_new(fs::File::create(path)?, schema.clone(), Some(props))?;
+        Ok(Writer { inner, schema })
+    }
+
+    /// Append all points of the cloud. Columns missing in the cloud are null, except the
+    /// timestamp which is the measuring time of each point in seconds, or the stamp of the cloud
+    /// without point times. Colors are unpacked from the `rgb` or `rgba` field.
+    pub fn write(&mut self, cloud: &PointCloud2, times: Option<&[f64]>) -> Result<()> {
+        let stamp = cloud.header.stamp.sec as f64 + cloud.header.stamp.nanosec as f64 * 1e-9;
+        let f32_column = |name: &str| -> ArrayRef {
+            match find(cloud, name) {
+                Some(field) => Arc::new(Float32Array::from(values(cloud, field, f32::from))),
+                None => new_null_array(&DataType::Float32, cloud.len()),
+            }
+        };
+        let ring: ArrayRef = match find(cloud, "ring") {
+            Some(field) => Arc::new(UInt16Array::from(values(cloud, field, |v| {
+                f32::from(v) as u16
+            }))),
+            None => new_null_array(&DataType::UInt16, cloud.len()),
+        };
+        let timestamp = match times {
+            Some(times) => times.to_vec(),
+            None => vec![stamp; cloud.len()],
+        };
+
+        let color_column = |channel: usize| -> ArrayRef {
+            match color_field(cloud) {
+                Some((i, has_alpha)) => {
+                    Arc::new(UInt8Array::from(values(cloud, &cloud.fields[i], |v| {
+                        unpack_rgba(v, has_alpha)[channel]
+                    })))
+                }
+                None => new_null_array(&DataType::UInt8, cloud.len()),
+            }
+        };
+
+        let batch = RecordBatch::try_new(
+            self.schema.clone(),
+            vec![
+                f32_column("x"),
+                f32_column("y"),
+                f32_column("z"),
+                f32_column("intensity"),
+                ring,
+                Arc::new(Float64Array::from(timestamp)),
+                color_column(0),
+                color_column(1),
+                color_column(2),
+            ],
+        )?;
+        self.inner.write(&batch)
+    }
+
+    pub fn close(self) -> Result<()> {
+        self.inner.close()?;
+        Ok(())
+    }
+}
+
+fn find<'a>(cloud: &'a PointCloud2, name: &str) -> Option<&'a PointField> {
+    cloud.fields.iter().find(|f| f.name == name)
+}
+
+/// Values of the field in all points, only the first element is taken.
+fn values<T>(cloud: &PointCloud2, field: &PointField, f: impl Fn(Datatype) -> T) -> Vec<T> {
+    let start = field.offset as usize;
+    let end = start + field.size();
+    cloud
+        .data
+        .chunks_exact(cloud.point_step as usize)
+        .take(cloud.len())
+        .map(|point| f(field.decode_bytes(&point[start..end])[0]))
+        .collect()
+}
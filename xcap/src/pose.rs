@@ -0,0 +1,192 @@
+use crate::extractor::Extractor;
+use crate::tf::FrameTree;
+use mcap::Message;
+use rerun::RecordingStream;
+use ros2_geometry_msgs::msg::{Pose, PoseArray, PoseStamped};
+use ros2_std_msgs::msg::Header;
+use std::{
+    fs,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::{atomic::AtomicBool, Arc, Mutex},
+};
+
+// Length of the arrows showing the headings of the poses, in meters
+const ARROW_LENGTH: f32 = 0.5;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Payload error. {0}")]
+    Payload(#[from] crate::extractor::DecodeError),
+    #[error("CDR error. {0}")]
+    Cdr(#[from] cdr::Error),
+}
+
+/// Parser of PoseStamped and PoseArray topics, like ego poses and poses of detections. Poses are
+/// shown as points with arrows along their X axes, and the trajectory of a PoseStamped topic is
+/// drawn as well.
+pub struct Parser {
+    // Messages are PoseArray rather than PoseStamped
+    array: bool,
+
+    // Visualizer with rerun
+    rec_stream: Option<RecordingStream>,
+
+    // CSV file for all the poses, only available when dumping data
+    csv: Option<BufWriter<fs::File>>,
+
+    // Scratch buffer for decoded message payloads, reused across messages
+    buffer: Vec<u8>,
+
+    // Transform tree, poses are logged in their frames once the frames are known
+    frame_tree: Arc<Mutex<FrameTree>>,
+
+    // All positions of a PoseStamped topic received so far
+    trajectory: Vec<[f32; 3]>,
+
+    // Entity path of the poses
+    entity_path: String,
+}
+
+impl Parser {
+    pub fn new(
+        output_path: &Path,
+        rerun_stream: Option<RecordingStream>,
+        dump_data: bool,
+        frame_tree: Arc<Mutex<FrameTree>>,
+        array: bool,
+    ) -> Self {
+        // Create output file
+        let csv = if dump_data {
+            fs::create_dir_all(output_path).unwrap();
+            let mut file = BufWriter::new(fs::File::create(output_path.join("poses.csv")).unwrap());
+            writeln!(
+                file,
+                "sec,nanosec,index,x,y,z,orientation_x,orientation_y,orientation_z,orientation_w"
+            )
+            .unwrap();
+            Some(file)
+        } else {
+            None
+        };
+
+        Parser {
+            array,
+            rec_stream: rerun_stream,
+            csv,
+            buffer: Vec::new(),
+            frame_tree,
+            trajectory: Vec::new(),
+            entity_path: String::new(),
+        }
+    }
+
+    /// Header and poses of the message.
+    fn decode(&self) -> Result<(Header, Vec<Pose>), cdr::Error> {
+        if self.array {
+            let msg = cdr::deserialize_from::<_, PoseArray, _>(
+                self.buffer.as_slice(),
+                cdr::size::Infinite,
+            )?;
+            Ok((msg.header, msg.poses))
+        } else {
+            let msg = cdr::deserialize_from::<_, PoseStamped, _>(
+                self.buffer.as_slice(),
+                cdr::size::Infinite,
+            )?;
+            Ok((msg.header, vec![msg.pose]))
+        }
+    }
+}
+
+/// X axis of the pose rotated by its orientation, scaled to the arrow length.
+fn heading(pose: &Pose) -> [f32; 3] {
+    let q = &pose.orientation;
+    [
+        (1.0 - 2.0 * (q.y * q.y + q.z * q.z)) as f32 * ARROW_LENGTH,
+        (2.0 * (q.x * q.y + q.w * q.z)) as f32 * ARROW_LENGTH,
+        (2.0 * (q.x * q.z - q.w * q.y)) as f32 * ARROW_LENGTH,
+    ]
+}
+
+impl Extractor for Parser {
+    type ExtractorError = Box<dyn std::error::Error + Send + Sync>;
+
+    fn buffer(&mut self) -> &mut Vec<u8> {
+        &mut self.buffer
+    }
+
+    fn step(&mut self, message: &Message) -> Result<(), Self::ExtractorError> {
+        self.decode_into(message).map_err(Error::Payload)?;
+        let (header, poses) = self.decode().map_err(Error::Cdr)?;
+
+        if let Some(rec) = &self.rec_stream {
+            self.entity_path = match self
+                .frame_tree
+                .lock()
+                .unwrap()
+                .entity_path(&header.frame_id)
+            {
+                Some(frame_path) => format!("{}/pose/{}", frame_path, message.channel.topic),
+                None => format!("pose/{}", message.channel.topic),
+            };
+            let positions: Vec<[f32; 3]> = poses
+                .iter()
+                .map(|p| {
+                    [
+                        p.position.x as f32,
+                        p.position.y as f32,
+                        p.position.z as f32,
+                    ]
+                })
+                .collect();
+            if !self.array {
+                self.trajectory.extend_from_slice(&positions);
+            }
+
+            rec.set_time_seconds(
+                "main",
+                header.stamp.sec as f64 + header.stamp.nanosec as f64 * 1e-9,
+            );
+            rec.log(
+                self.entity_path.as_str(),
+                &rerun::Points3D::new(positions.iter().copied()).with_radii([0.1]),
+            )?;
+            rec.log(
+                self.entity_path.as_str(),
+                &rerun::Arrows3D::from_vectors(poses.iter().map(heading)).with_origins(positions),
+            )?;
+        }
+
+        // Dump data?
+        if let Some(csv) = self.csv.as_mut() {
+            for (i, pose) in poses.iter().enumerate() {
+                let (p, q) = (&pose.position, &pose.orientation);
+                writeln!(
+                    csv,
+                    "{},{},{},{},{},{},{},{},{},{}",
+                    header.stamp.sec, header.stamp.nanosec, i, p.x, p.y, p.z, q.x, q.y, q.z, q.w
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    fn post_process(&mut self, _sigint: Arc<AtomicBool>) -> Result<(), Self::ExtractorError> {
+        // The whole trajectory is logged once so it stays visible while scrubbing the timeline.
+        if let Some(rec) = self
+            .rec_stream
+            .as_ref()
+            .filter(|_| !self.trajectory.is_empty())
+        {
+            rec.log_static(
+                format!("{}/trajectory", self.entity_path),
+                &rerun::LineStrips3D::new([self.trajectory.clone()]),
+            )?;
+        }
+        if let Some(csv) = self.csv.as_mut() {
+            csv.flush()?;
+        }
+        Ok(())
+    }
+}
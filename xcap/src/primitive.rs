@@ -0,0 +1,130 @@
+use crate::extractor::Extractor;
+use crate::frame_index::csv_field;
+use mcap::Message;
+use rerun::RecordingStream;
+use std::{
+    fs,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::{atomic::AtomicBool, Arc},
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Payload error. {0}")]
+    Payload(#[from] crate::extractor::DecodeError),
+    #[error("CDR error. {0}")]
+    Cdr(#[from] cdr::Error),
+}
+
+/// Messages of std_msgs holding a single primitive field, `data`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Float32,
+    Float64,
+    Int32,
+    Bool,
+    String,
+}
+
+// Decoded value of a message
+enum Data {
+    Number(f64),
+    Text(String),
+}
+
+/// Parser of std_msgs primitives, like the debug values many robots publish. Numbers and booleans
+/// are plotted over time, and strings are shown as text logs. Messages have no header, so the
+/// publish time is used.
+pub struct Parser {
+    // Kind of the messages
+    kind: Kind,
+
+    // Visualizer with rerun
+    rec_stream: Option<RecordingStream>,
+
+    // CSV file for all the values, only available when dumping data
+    csv: Option<BufWriter<fs::File>>,
+
+    // Scratch buffer for decoded message payloads, reused across messages
+    buffer: Vec<u8>,
+}
+
+impl Parser {
+    pub fn new(
+        output_path: &Path,
+        rerun_stream: Option<RecordingStream>,
+        dump_data: bool,
+        kind: Kind,
+    ) -> Self {
+        // Create output file
+        let csv = if dump_data {
+            fs::create_dir_all(output_path).unwrap();
+            let mut file = BufWriter::new(fs::File::create(output_path.join("data.csv")).unwrap());
+            writeln!(file, "publish_time,data").unwrap();
+            Some(file)
+        } else {
+            None
+        };
+
+        Parser {
+            kind,
+            rec_stream: rerun_stream,
+            csv,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Value of the `data` field. A message of a single field is encoded the same as the field.
+    fn decode(&self) -> Result<Data, cdr::Error> {
+        let buf = self.buffer.as_slice();
+        let size = cdr::size::Infinite;
+        Ok(match self.kind {
+            Kind::Float32 => Data::Number(cdr::deserialize_from::<_, f32, _>(buf, size)? as f64),
+            Kind::Float64 => Data::Number(cdr::deserialize_from::<_, f64, _>(buf, size)?),
+            Kind::Int32 => Data::Number(cdr::deserialize_from::<_, i32, _>(buf, size)? as f64),
+            Kind::Bool => {
+                Data::Number(cdr::deserialize_from::<_, bool, _>(buf, size)? as u8 as f64)
+            }
+            Kind::String => Data::Text(cdr::deserialize_from::<_, String, _>(buf, size)?),
+        })
+    }
+}
+
+impl Extractor for Parser {
+    type ExtractorError = Box<dyn std::error::Error + Send + Sync>;
+
+    fn buffer(&mut self) -> &mut Vec<u8> {
+        &mut self.buffer
+    }
+
+    fn step(&mut self, message: &Message) -> Result<(), Self::ExtractorError> {
+        self.decode_into(message).map_err(Error::Payload)?;
+        let data = self.decode().map_err(Error::Cdr)?;
+
+        if let Some(rec) = &self.rec_stream {
+            rec.set_time_seconds("main", message.publish_time as f64 * 1e-9);
+            let topic = message.channel.topic.as_str();
+            match &data {
+                Data::Number(number) => rec.log(topic, &rerun::Scalar::new(*number))?,
+                Data::Text(text) => rec.log(topic, &rerun::TextLog::new(text.as_str()))?,
+            }
+        }
+
+        // Dump data?
+        if let Some(csv) = self.csv.as_mut() {
+            match &data {
+                Data::Number(number) => writeln!(csv, "{},{}", message.publish_time, number)?,
+                Data::Text(text) => writeln!(csv, "{},{}", message.publish_time, csv_field(text))?,
+            }
+        }
+        Ok(())
+    }
+
+    fn post_process(&mut self, _sigint: Arc<AtomicBool>) -> Result<(), Self::ExtractorError> {
+        if let Some(csv) = self.csv.as_mut() {
+            csv.flush()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,33 @@
+//! Progress of processing and downloading, reported to the caller so that xcap could be embedded
+//! without a terminal.
+use std::path::Path;
+
+/// Receiver of the progress of `process`. Messages of order independent topics are processed by
+/// multiple threads, so the sink is shared between them.
+pub trait ProgressSink: Send + Sync {
+    /// A selected topic, with its message count if the file summary has one.
+    fn on_topic_start(&self, _topic: &str, _message_count: Option<u64>) {}
+
+    /// A file is opened.
+    fn on_file_start(&self, _file: &Path) {}
+
+    /// A message of the topic is processed.
+    fn on_message(&self, _topic: &str) {}
+
+    /// All messages of the topic are processed.
+    fn on_topic_done(&self, _topic: &str) {}
+
+    /// A remote object starts downloading, with its size in bytes if known.
+    fn on_download_start(&self, _object: &str, _size: Option<u64>) {}
+
+    /// Bytes of the object are downloaded.
+    fn on_download(&self, _object: &str, _bytes: u64) {}
+
+    /// The object is downloaded, or failed.
+    fn on_download_done(&self, _object: &str) {}
+}
+
+/// Ignore the progress.
+pub struct NoProgress;
+
+impl ProgressSink for NoProgress {}
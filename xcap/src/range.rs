@@ -0,0 +1,159 @@
+use crate::extractor::Extractor;
+use crate::tf::FrameTree;
+use mcap::Message;
+use rerun::RecordingStream;
+use ros2_sensor_msgs::msg::Range;
+use std::{
+    f32::consts::TAU,
+    fs,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::{atomic::AtomicBool, Arc, Mutex},
+};
+
+// Segments of the rim of the detection cone
+const RIM_SEGMENTS: usize = 24;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Payload error. {0}")]
+    Payload(#[from] crate::extractor::DecodeError),
+    #[error("CDR error. {0}")]
+    Cdr(#[from] cdr::Error),
+}
+
+/// Parser of single range readings, like the ones of ultrasonic and infrared rangers. The range is
+/// plotted over time, and the detection cone is shown in the 3D view once the frame of the ranger
+/// is known.
+pub struct Parser {
+    // Visualizer with rerun
+    rec_stream: Option<RecordingStream>,
+
+    // CSV file for all the readings, only available when dumping data
+    csv: Option<BufWriter<fs::File>>,
+
+    // Scratch buffer for decoded message payloads, reused across messages
+    buffer: Vec<u8>,
+
+    // Transform tree, cones are shown in the frames of the rangers
+    frame_tree: Arc<Mutex<FrameTree>>,
+}
+
+impl Parser {
+    pub fn new(
+        output_path: &Path,
+        rerun_stream: Option<RecordingStream>,
+        dump_data: bool,
+        frame_tree: Arc<Mutex<FrameTree>>,
+    ) -> Self {
+        // Create output file
+        let csv = if dump_data {
+            fs::create_dir_all(output_path).unwrap();
+            let mut file = BufWriter::new(fs::File::create(output_path.join("range.csv")).unwrap());
+            writeln!(
+                file,
+                "sec,nanosec,radiation_type,field_of_view,min_range,max_range,range"
+            )
+            .unwrap();
+            Some(file)
+        } else {
+            None
+        };
+
+        Parser {
+            rec_stream: rerun_stream,
+            csv,
+            buffer: Vec::new(),
+            frame_tree,
+        }
+    }
+}
+
+/// Outline of the detection cone along the X axis: the rim at the range, and four edges from the
+/// apex to the rim.
+fn cone(range: f32, field_of_view: f32) -> Vec<Vec<[f32; 3]>> {
+    let (x, radius) = (
+        range * (field_of_view / 2.0).cos(),
+        range * (field_of_view / 2.0).sin(),
+    );
+    let rim: Vec<[f32; 3]> = (0..=RIM_SEGMENTS)
+        .map(|i| {
+            let theta = i as f32 * TAU / RIM_SEGMENTS as f32;
+            [x, radius * theta.cos(), radius * theta.sin()]
+        })
+        .collect();
+    let mut strips: Vec<Vec<[f32; 3]>> = (0..4)
+        .map(|i| vec![[0.0; 3], rim[i * RIM_SEGMENTS / 4]])
+        .collect();
+    strips.push(rim);
+    strips
+}
+
+impl Extractor for Parser {
+    type ExtractorError = Box<dyn std::error::Error + Send + Sync>;
+
+    fn buffer(&mut self) -> &mut Vec<u8> {
+        &mut self.buffer
+    }
+
+    fn step(&mut self, message: &Message) -> Result<(), Self::ExtractorError> {
+        let payload = self.decode_into(message).map_err(Error::Payload)?;
+        let range = cdr::deserialize_from::<_, Range, _>(payload, cdr::size::Infinite)
+            .map_err(Error::Cdr)?;
+
+        if let Some(rec) = &self.rec_stream {
+            rec.set_time_seconds(
+                "main",
+                range.header.stamp.sec as f64 + range.header.stamp.nanosec as f64 * 1e-9,
+            );
+            // Readings out of the limits are discarded
+            if range.is_valid() {
+                rec.log(
+                    format!("range/{}", message.channel.topic),
+                    &rerun::Scalar::new(range.range as f64),
+                )?;
+            }
+
+            // The cone is left out if the ranger could not be placed in the 3D view
+            let frame_path = self
+                .frame_tree
+                .lock()
+                .unwrap()
+                .entity_path(&range.header.frame_id);
+            if let Some(frame_path) = frame_path {
+                let entity_path = format!("{}/range/{}", frame_path, message.channel.topic);
+                if range.is_valid() && range.field_of_view > 0.0 {
+                    rec.log(
+                        entity_path,
+                        &rerun::LineStrips3D::new(cone(range.range, range.field_of_view)),
+                    )?;
+                } else {
+                    rec.log(entity_path, &rerun::Clear::flat())?;
+                }
+            }
+        }
+
+        // Dump data?
+        if let Some(csv) = self.csv.as_mut() {
+            writeln!(
+                csv,
+                "{},{},{},{},{},{},{}",
+                range.header.stamp.sec,
+                range.header.stamp.nanosec,
+                range.radiation_type,
+                range.field_of_view,
+                range.min_range,
+                range.max_range,
+                range.range
+            )?;
+        }
+        Ok(())
+    }
+
+    fn post_process(&mut self, _sigint: Arc<AtomicBool>) -> Result<(), Self::ExtractorError> {
+        if let Some(csv) = self.csv.as_mut() {
+            csv.flush()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,57 @@
+use crate::extractor::Extractor;
+use mcap::Message;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{atomic::AtomicBool, Arc},
+};
+
+/// Parser for topics without schemas, which could not be decoded. Payloads of the messages are
+/// dumped as they are, one file per message named by the publish time.
+pub struct Parser {
+    // Output directory
+    output_dir: PathBuf,
+
+    // Should dump data to disk
+    dump_data: bool,
+
+    // Scratch buffer for decoded message payloads, unused as payloads are dumped as they are
+    buffer: Vec<u8>,
+}
+
+impl Parser {
+    pub fn new(output_path: &Path, dump_data: bool) -> Self {
+        // Create output dir
+        if dump_data {
+            fs::create_dir_all(output_path).unwrap();
+        }
+
+        Parser {
+            output_dir: output_path.into(),
+            dump_data,
+            buffer: Vec::new(),
+        }
+    }
+}
+
+impl Extractor for Parser {
+    type ExtractorError = Box<dyn std::error::Error + Send + Sync>;
+
+    fn buffer(&mut self) -> &mut Vec<u8> {
+        &mut self.buffer
+    }
+
+    fn step(&mut self, message: &Message) -> Result<(), Self::ExtractorError> {
+        if self.dump_data {
+            let path = self
+                .output_dir
+                .join(format!("{}.bin", message.publish_time));
+            fs::write(path, &message.data)?;
+        }
+        Ok(())
+    }
+
+    fn post_process(&mut self, _sigint: Arc<AtomicBool>) -> Result<(), Self::ExtractorError> {
+        Ok(())
+    }
+}
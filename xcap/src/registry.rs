@@ -0,0 +1,35 @@
+//! Parsers of custom message types, registered by downstream crates.
+use crate::BoxedExtractor;
+use rerun::RecordingStream;
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{Arc, LazyLock, RwLock},
+};
+
+/// Create a parser of a topic, given its output directory, the viewer stream if visualizing and
+/// whether data should be dumped.
+pub type ExtractorFactory =
+    dyn Fn(&Path, Option<RecordingStream>, bool) -> BoxedExtractor + Send + Sync;
+
+// Schema name to its factory
+static REGISTRY: LazyLock<RwLock<HashMap<String, Arc<ExtractorFactory>>>> =
+    LazyLock::new(|| RwLock::new(HashMap::new()));
+
+/// Parse topics of the schema, like `my_msgs/msg/Radar`, with parsers created by the factory.
+/// Registered parsers take precedence over the built-in ones, and replace the previous factory of
+/// the same schema.
+pub fn register_extractor(
+    schema_name: &str,
+    factory: impl Fn(&Path, Option<RecordingStream>, bool) -> BoxedExtractor + Send + Sync + 'static,
+) {
+    REGISTRY
+        .write()
+        .unwrap()
+        .insert(schema_name.to_owned(), Arc::new(factory));
+}
+
+/// Factory registered for the schema.
+pub(crate) fn get(schema_name: &str) -> Option<Arc<ExtractorFactory>> {
+    REGISTRY.read().unwrap().get(schema_name).cloned()
+}
@@ -0,0 +1,151 @@
+//! Slices of MCAP files with random access only, like objects read with ranged requests. The
+//! summary is read first, then only the chunks holding the selected topics in the time range.
+use crate::Error;
+use mcap::{
+    read::{parse_record, ChunkReader, LinearReader},
+    records::{self, op, Record},
+    Channel, Message, Schema, MAGIC,
+};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fs,
+    io::{self, BufWriter, Read, Seek, SeekFrom},
+    ops::RangeBounds,
+    path::Path,
+    sync::Arc,
+};
+
+// Footer record, with its opcode and length, followed by the magic
+const FOOTER_LEN: u64 = 1 + 8 + 8 + 8 + 4 + 8;
+
+/// Copy messages of the topics with log times in the range, in nanoseconds, from the MCAP file
+/// into a new local file. Returns the number of messages copied.
+///
+/// Files without a summary have no chunk index to seek with, and should be read in whole.
+pub fn fetch_slice<R: Read + Seek>(
+    reader: &mut R,
+    topics: &[&str],
+    time_range: impl RangeBounds<u64>,
+    output: &Path,
+) -> Result<u64, Error> {
+    let size = reader.seek(SeekFrom::End(0))?;
+    if size < FOOTER_LEN {
+        return Err(Error::NoSummary("File too small".to_string()));
+    }
+    let tail = read_at(reader, size - FOOTER_LEN, FOOTER_LEN)?;
+    if !tail.ends_with(MAGIC) {
+        return Err(mcap::McapError::BadMagic.into());
+    }
+    let foot = match LinearReader::sans_magic(&tail[..tail.len() - MAGIC.len()]).next() {
+        Some(Ok(Record::Footer(foot))) => foot,
+        _ => return Err(mcap::McapError::BadFooter.into()),
+    };
+    if foot.summary_start == 0 {
+        return Err(Error::NoSummary("No summary section".to_string()));
+    }
+    let summary_end = match foot.summary_offset_start {
+        0 => size - FOOTER_LEN,
+        offset => offset,
+    };
+    let summary = read_at(reader, foot.summary_start, summary_end - foot.summary_start)?;
+
+    // Channels of the selected topics, and the chunks to fetch
+    let mut schemas: HashMap<u16, Arc<Schema>> = HashMap::new();
+    let mut channels: HashMap<u16, records::Channel> = HashMap::new();
+    let mut chunk_indexes = Vec::new();
+    for record in LinearReader::sans_magic(&summary) {
+        match record? {
+            Record::Schema { header, data } => {
+                let schema = Schema {
+                    name: header.name,
+                    encoding: header.encoding,
+                    data: data.into_owned().into(),
+                };
+                schemas.insert(header.id, Arc::new(schema));
+            }
+            Record::Channel(channel) if topics.contains(&channel.topic.as_str()) => {
+                channels.insert(channel.id, channel);
+            }
+            Record::ChunkIndex(index) => chunk_indexes.push(index),
+            _ => {}
+        }
+    }
+    let channels: HashMap<u16, Arc<Channel>> = channels
+        .into_iter()
+        .map(|(id, c)| {
+            let channel = Channel {
+                topic: c.topic,
+                schema: schemas.get(&c.schema_id).cloned(),
+                message_encoding: c.message_encoding,
+                metadata: c.metadata,
+            };
+            (id, Arc::new(channel))
+        })
+        .collect();
+
+    let mut writer = mcap::Writer::new(BufWriter::new(fs::File::create(output)?))?;
+    let mut count = 0;
+    chunk_indexes.sort_by_key(|index| index.chunk_start_offset);
+    for index in chunk_indexes.iter().filter(|index| {
+        overlaps(
+            &time_range,
+            index.message_start_time,
+            index.message_end_time,
+        ) && index
+            .message_index_offsets
+            .keys()
+            .any(|id| channels.contains_key(id))
+    }) {
+        // The chunk record without its opcode and length
+        let chunk = read_at(reader, index.chunk_start_offset + 9, index.chunk_length - 9)?;
+        let (header, data) = match parse_record(op::CHUNK, &chunk)? {
+            Record::Chunk { header, data } => (header, data),
+            _ => return Err(mcap::McapError::BadIndex.into()),
+        };
+        for record in ChunkReader::new(header, &data)? {
+            if let Record::Message { header, data } = record? {
+                let Some(channel) = channels.get(&header.channel_id) else {
+                    continue;
+                };
+                if !time_range.contains(&header.log_time) {
+                    continue;
+                }
+                writer.write(&Message {
+                    channel: channel.clone(),
+                    sequence: header.sequence,
+                    log_time: header.log_time,
+                    publish_time: header.publish_time,
+                    data: Cow::Owned(data.into_owned()),
+                })?;
+                count += 1;
+            }
+        }
+    }
+    writer.finish()?;
+    Ok(count)
+}
+
+/// Read `length` bytes at the offset.
+fn read_at<R: Read + Seek>(reader: &mut R, offset: u64, length: u64) -> io::Result<Vec<u8>> {
+    reader.seek(SeekFrom::Start(offset))?;
+    let mut buf = vec![0; length as usize];
+    reader.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+/// Whether the range overlaps `[start, end]`.
+fn overlaps(range: &impl RangeBounds<u64>, start: u64, end: u64) -> bool {
+    use std::ops::Bound::*;
+    let after_start = match range.end_bound() {
+        Included(&e) => e >= start,
+        Excluded(&e) => e > start,
+        Unbounded => true,
+    };
+    let before_end = match range.start_bound() {
+        Included(&s) => s <= end,
+        Excluded(&s) => s < end,
+        Unbounded => true,
+    };
+    after_start && before_end
+}
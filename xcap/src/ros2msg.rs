@@ -0,0 +1,918 @@
+//! Decode CDR messages with the `ros2msg` schema stored in MCAP files, without compile-time
+//! message structs.
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+// Lines of `=` separate the definitions of dependent messages
+const SEPARATOR: char = '=';
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Invalid schema. {0}")]
+    Schema(String),
+    #[error("Unknown message type: {0}")]
+    UnknownType(String),
+    #[error("Unsupported type: {0}")]
+    Unsupported(String),
+    #[error("Unsupported CDR encapsulation: {0:?}")]
+    Encapsulation([u8; 2]),
+    #[error("Unexpected end of message")]
+    Eof,
+    #[error("Invalid UTF-8 string. {0}")]
+    Utf8(#[from] std::string::FromUtf8Error),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Primitive {
+    Bool,
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Int64,
+    UInt64,
+    Float32,
+    Float64,
+}
+
+impl Primitive {
+    /// Size of the primitive on the wire, in bytes.
+    fn size(&self) -> usize {
+        match self {
+            Primitive::Bool | Primitive::Int8 | Primitive::UInt8 => 1,
+            Primitive::Int16 | Primitive::UInt16 => 2,
+            Primitive::Int32 | Primitive::UInt32 | Primitive::Float32 => 4,
+            Primitive::Int64 | Primitive::UInt64 | Primitive::Float64 => 8,
+        }
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "bool" => Some(Primitive::Bool),
+            "int8" => Some(Primitive::Int8),
+            "uint8" | "byte" | "char" => Some(Primitive::UInt8),
+            "int16" => Some(Primitive::Int16),
+            "uint16" => Some(Primitive::UInt16),
+            "int32" => Some(Primitive::Int32),
+            "uint32" => Some(Primitive::UInt32),
+            "int64" => Some(Primitive::Int64),
+            "uint64" => Some(Primitive::UInt64),
+            "float32" => Some(Primitive::Float32),
+            "float64" => Some(Primitive::Float64),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum FieldType {
+    Primitive(Primitive),
+    String,
+    Message(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Array {
+    None,
+    Fixed(usize),
+    Sequence,
+}
+
+#[derive(Debug, Clone)]
+struct Field {
+    name: String,
+    ty: FieldType,
+    array: Array,
+}
+
+/// Decoded value of a message or any of its fields.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    String(String),
+    Array(Vec<Value>),
+    Struct(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// Flatten into `(column, cell)` pairs. Nested fields are joined by `.`, arrays of primitives
+    /// are written into one cell separated by spaces, and arrays of messages are skipped.
+    pub fn flatten(&self) -> Vec<(String, String)> {
+        let mut cells = Vec::new();
+        self.flatten_into("", &mut cells);
+        cells
+    }
+
+    /// Convert into JSON, nothing is dropped.
+    pub fn to_json(&self) -> serde_json::Value {
+        match self {
+            Value::Bool(v) => serde_json::Value::from(*v),
+            Value::Int(v) => serde_json::Value::from(*v),
+            Value::UInt(v) => serde_json::Value::from(*v),
+            Value::Float(v) => serde_json::Value::from(*v),
+            Value::String(v) => serde_json::Value::from(v.as_str()),
+            Value::Array(items) => items.iter().map(|v| v.to_json()).collect(),
+            Value::Struct(fields) => fields
+                .iter()
+                .map(|(name, value)| (name.clone(), value.to_json()))
+                .collect::<serde_json::Map<_, _>>()
+                .into(),
+        }
+    }
+
+    /// Convert from JSON. Nulls become empty strings.
+    pub fn from_json(json: &serde_json::Value) -> Self {
+        match json {
+            serde_json::Value::Null => Value::String(String::new()),
+            serde_json::Value::Bool(v) => Value::Bool(*v),
+            serde_json::Value::Number(v) => match (v.as_u64(), v.as_i64()) {
+                (Some(v), _) => Value::UInt(v),
+                (_, Some(v)) => Value::Int(v),
+                _ => Value::Float(v.as_f64().unwrap_or(f64::NAN)),
+            },
+            serde_json::Value::String(v) => Value::String(v.clone()),
+            serde_json::Value::Array(items) => {
+                Value::Array(items.iter().map(Value::from_json).collect())
+            }
+            serde_json::Value::Object(fields) => Value::Struct(
+                fields
+                    .iter()
+                    .map(|(name, value)| (name.clone(), Value::from_json(value)))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Field by its path, like `header.stamp.sec`.
+    pub fn get(&self, path: &str) -> Option<&Value> {
+        path.split('.').try_fold(self, |value, name| match value {
+            Value::Struct(fields) => fields.iter().find(|(n, _)| n == name).map(|(_, v)| v),
+            _ => None,
+        })
+    }
+
+    /// Numeric value as f64, None for strings and containers.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Bool(v) => Some(*v as u8 as f64),
+            Value::Int(v) => Some(*v as f64),
+            Value::UInt(v) => Some(*v as f64),
+            Value::Float(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    fn flatten_into(&self, prefix: &str, cells: &mut Vec<(String, String)>) {
+        match self {
+            Value::Struct(fields) => {
+                for (name, value) in fields {
+                    let column = if prefix.is_empty() {
+                        name.clone()
+                    } else {
+                        format!("{}.{}", prefix, name)
+                    };
+                    value.flatten_into(&column, cells);
+                }
+            }
+            Value::Array(items) => {
+                if items.iter().any(|v| matches!(v, Value::Struct(_))) {
+                    return;
+                }
+                let cell: Vec<String> = items.iter().map(|v| v.to_string()).collect();
+                cells.push((prefix.to_owned(), cell.join(" ")));
+            }
+            _ => cells.push((prefix.to_owned(), self.to_string())),
+        }
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Bool(v) => write!(f, "{}", v),
+            Value::Int(v) => write!(f, "{}", v),
+            Value::UInt(v) => write!(f, "{}", v),
+            Value::Float(v) => write!(f, "{}", v),
+            Value::String(v) => write!(f, "{}", v),
+            Value::Array(_) | Value::Struct(_) => write!(f, "{:?}", self),
+        }
+    }
+}
+
+/// Message definitions parsed from the `ros2msg` schema text.
+#[derive(Debug, Clone)]
+pub struct Schema {
+    // Name of the top level message
+    root: String,
+
+    // All the message definitions by their full names, like `std_msgs/Header`
+    definitions: HashMap<String, Vec<Field>>,
+}
+
+impl Schema {
+    /// Parse the schema. `name` is the schema name of the channel, like `sensor_msgs/msg/Imu`.
+    pub fn parse(name: &str, text: &str) -> Result<Self, Error> {
+        let root = full_name(name);
+        let mut definitions = HashMap::new();
+        let mut current = root.clone();
+        let mut fields = Vec::new();
+
+        for line in text.lines() {
+            let line = line.trim();
+            if !line.is_empty() && line.chars().all(|c| c == SEPARATOR) {
+                definitions.insert(current.clone(), std::mem::take(&mut fields));
+                continue;
+            }
+            if let Some(name) = line.strip_prefix("MSG:") {
+                current = full_name(name.trim());
+                continue;
+            }
+            if let Some(field) = parse_field(line, package(&current))? {
+                fields.push(field);
+            }
+        }
+        definitions.insert(current, fields);
+
+        let schema = Schema { root, definitions };
+        schema.check(&schema.root, 0)?;
+        Ok(schema)
+    }
+
+    /// Decode the CDR serialized message, encapsulation header included. Both XCDR1 and the plain
+    /// or delimited XCDR2 encapsulations are supported.
+    pub fn decode(&self, data: &[u8]) -> Result<Value, Error> {
+        self.decode_message(&self.root, &mut Reader::new(data)?)
+    }
+
+    /// Transcode the CDR serialized message into little endian XCDR1 in `out`, field by field
+    /// without decoding it into values. Arrays of primitives are copied in bulk.
+    pub fn transcode(&self, data: &[u8], out: &mut Vec<u8>) -> Result<(), Error> {
+        let mut reader = Reader::new(data)?;
+        out.clear();
+        out.extend_from_slice(&[0x00, 0x01, 0x00, 0x00]);
+        let mut writer = Writer {
+            data: std::mem::take(out),
+        };
+        let result = self.transcode_message(&self.root, &mut reader, &mut writer);
+        *out = writer.data;
+        result
+    }
+
+    /// Make sure all the referenced types are defined.
+    fn check(&self, name: &str, depth: usize) -> Result<(), Error> {
+        if depth > 32 {
+            return Err(Error::Schema(format!("Recursive message {}", name)));
+        }
+        let fields = self
+            .definitions
+            .get(name)
+            .ok_or(Error::UnknownType(name.to_owned()))?;
+        for field in fields {
+            if let FieldType::Message(ty) = &field.ty {
+                self.check(ty, depth + 1)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn decode_message(&self, name: &str, reader: &mut Reader) -> Result<Value, Error> {
+        let fields = &self.definitions[name];
+
+        // Delimited structs are led by their sizes, fields appended by newer versions are skipped
+        let end = match reader.delimited {
+            true => {
+                let len = reader.u32()? as usize;
+                Some(reader.pos + len)
+            }
+            false => None,
+        };
+
+        // Empty messages still take one byte on the wire
+        if fields.is_empty() {
+            reader.take(1, 1)?;
+        }
+        let mut values = Vec::with_capacity(fields.len());
+        for field in fields {
+            // Arrays of non-primitive elements are led by their sizes in XCDR2
+            if reader.xcdr2
+                && field.array != Array::None
+                && !matches!(field.ty, FieldType::Primitive(_))
+            {
+                reader.u32()?;
+            }
+            let value = match field.array {
+                Array::None => self.decode_single(&field.ty, reader)?,
+                Array::Fixed(len) => Value::Array(
+                    (0..len)
+                        .map(|_| self.decode_single(&field.ty, reader))
+                        .collect::<Result<_, _>>()?,
+                ),
+                Array::Sequence => {
+                    // Every element takes at least one byte, guard against broken lengths
+                    let len = reader.u32()? as usize;
+                    if len > reader.remaining() {
+                        return Err(Error::Eof);
+                    }
+                    Value::Array(
+                        (0..len)
+                            .map(|_| self.decode_single(&field.ty, reader))
+                            .collect::<Result<_, _>>()?,
+                    )
+                }
+            };
+            values.push((field.name.clone(), value));
+        }
+        if let Some(end) = end {
+            if end > reader.data.len() {
+                return Err(Error::Eof);
+            }
+            reader.pos = end;
+        }
+        Ok(Value::Struct(values))
+    }
+
+    fn transcode_message(
+        &self,
+        name: &str,
+        reader: &mut Reader,
+        writer: &mut Writer,
+    ) -> Result<(), Error> {
+        let fields = &self.definitions[name];
+        let end = match reader.delimited {
+            true => {
+                let len = reader.u32()? as usize;
+                Some(reader.pos + len)
+            }
+            false => None,
+        };
+        if fields.is_empty() {
+            reader.take(1, 1)?;
+            writer.put(&[0], 1);
+        }
+        for field in fields {
+            if reader.xcdr2
+                && field.array != Array::None
+                && !matches!(field.ty, FieldType::Primitive(_))
+            {
+                reader.u32()?;
+            }
+            match field.array {
+                Array::None => self.transcode_single(&field.ty, reader, writer)?,
+                Array::Fixed(len) => self.transcode_array(&field.ty, len, reader, writer)?,
+                Array::Sequence => {
+                    let len = reader.u32()? as usize;
+                    if len > reader.remaining() {
+                        return Err(Error::Eof);
+                    }
+                    writer.put(&(len as u32).to_le_bytes(), 4);
+                    self.transcode_array(&field.ty, len, reader, writer)?;
+                }
+            }
+        }
+        if let Some(end) = end {
+            if end > reader.data.len() {
+                return Err(Error::Eof);
+            }
+            reader.pos = end;
+        }
+        Ok(())
+    }
+
+    fn transcode_array(
+        &self,
+        ty: &FieldType,
+        len: usize,
+        reader: &mut Reader,
+        writer: &mut Writer,
+    ) -> Result<(), Error> {
+        match ty {
+            // Elements of primitives are packed, so the whole array is aligned as its first one
+            FieldType::Primitive(p) if len > 0 => {
+                let size = p.size();
+                let len = len.checked_mul(size).ok_or(Error::Eof)?;
+                let bytes = reader.take(len, reader.align(size))?;
+                writer.put_array(bytes, size, reader.little_endian);
+                Ok(())
+            }
+            _ => (0..len).try_for_each(|_| self.transcode_single(ty, reader, writer)),
+        }
+    }
+
+    fn transcode_single(
+        &self,
+        ty: &FieldType,
+        reader: &mut Reader,
+        writer: &mut Writer,
+    ) -> Result<(), Error> {
+        match ty {
+            FieldType::Primitive(p) => {
+                let size = p.size();
+                let bytes = reader.take(size, reader.align(size))?;
+                writer.put_array(bytes, size, reader.little_endian);
+            }
+            FieldType::String => {
+                // Length includes the null terminator
+                let len = reader.u32()?;
+                let bytes = reader.take(len as usize, 1)?;
+                writer.put(&len.to_le_bytes(), 4);
+                writer.put(bytes, 1);
+            }
+            FieldType::Message(name) => self.transcode_message(name, reader, writer)?,
+        }
+        Ok(())
+    }
+
+    fn decode_single(&self, ty: &FieldType, reader: &mut Reader) -> Result<Value, Error> {
+        match ty {
+            FieldType::Primitive(p) => reader.primitive(*p),
+            FieldType::String => reader.string().map(Value::String),
+            FieldType::Message(name) => self.decode_message(name, reader),
+        }
+    }
+}
+
+// Schema parsed for transcoding, with the text it was parsed from
+type CachedSchema = (Vec<u8>, Rc<Schema>);
+
+thread_local! {
+    // Schemas parsed for transcoding, by their names
+    static SCHEMAS: RefCell<HashMap<String, CachedSchema>> = RefCell::new(HashMap::new());
+}
+
+/// Whether the CDR payload is in one of the XCDR2 encapsulations.
+pub(crate) fn is_xcdr2(data: &[u8]) -> bool {
+    matches!(data, [0x00, 0x06..=0x09, ..])
+}
+
+/// Transcode the XCDR2 payload into little endian XCDR1 in `out` with the `ros2msg` schema.
+pub(crate) fn to_xcdr1(schema: &mcap::Schema, data: &[u8], out: &mut Vec<u8>) -> Result<(), Error> {
+    let parsed = SCHEMAS.with(|schemas| -> Result<Rc<Schema>, Error> {
+        let mut schemas = schemas.borrow_mut();
+        if let Some((text, parsed)) = schemas.get(&schema.name) {
+            if *text == schema.data.as_ref() {
+                return Ok(parsed.clone());
+            }
+        }
+        let text = String::from_utf8_lossy(&schema.data);
+        let parsed = Rc::new(Schema::parse(&schema.name, &text)?);
+        schemas.insert(schema.name.clone(), (schema.data.to_vec(), parsed.clone()));
+        Ok(parsed)
+    })?;
+    parsed.transcode(data, out)
+}
+
+/// `pkg/msg/Type` and `pkg/Type` are the same type.
+fn full_name(name: &str) -> String {
+    name.replace("/msg/", "/")
+}
+
+fn package(name: &str) -> &str {
+    name.split('/').next().unwrap_or_default()
+}
+
+/// Parse one line of a message definition. Return None for empty lines, comments and constants.
+fn parse_field(line: &str, package: &str) -> Result<Option<Field>, Error> {
+    let line = line.split('#').next().unwrap_or_default();
+    let mut tokens = line.split_whitespace();
+    let (Some(ty), Some(name)) = (tokens.next(), tokens.next()) else {
+        return Ok(None);
+    };
+    if name.contains('=') || tokens.next().is_some_and(|t| t.starts_with('=')) {
+        return Ok(None);
+    }
+
+    // Arrays, like `type[]`, `type[<=N]` or `type[N]`
+    let (ty, array) = match ty.strip_suffix(']').and_then(|t| t.rsplit_once('[')) {
+        Some((ty, "")) => (ty, Array::Sequence),
+        Some((ty, bound)) if bound.starts_with("<=") => (ty, Array::Sequence),
+        Some((ty, len)) => (
+            ty,
+            Array::Fixed(
+                len.parse()
+                    .map_err(|_| Error::Schema(format!("Invalid array length: {}", line)))?,
+            ),
+        ),
+        None => (ty, Array::None),
+    };
+
+    // Bounded strings, like `string<=N`
+    let ty = ty.split("<=").next().unwrap_or_default();
+    let ty = if let Some(p) = Primitive::parse(ty) {
+        FieldType::Primitive(p)
+    } else if ty == "string" {
+        FieldType::String
+    } else if ty == "wstring" {
+        return Err(Error::Unsupported(ty.to_owned()));
+    } else if ty.contains('/') {
+        FieldType::Message(full_name(ty))
+    } else if ty == "Header" {
+        FieldType::Message("std_msgs/Header".to_owned())
+    } else {
+        FieldType::Message(format!("{}/{}", package, ty))
+    };
+    Ok(Some(Field {
+        name: name.to_owned(),
+        ty,
+        array,
+    }))
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    little_endian: bool,
+    // XCDR2 aligns to 4 bytes at most, and leads arrays of non-primitives by their sizes
+    xcdr2: bool,
+    // Every struct is led by its size, as appendable types in XCDR2
+    delimited: bool,
+}
+
+impl<'a> Reader<'a> {
+    /// Reader of the message after its encapsulation header.
+    fn new(data: &'a [u8]) -> Result<Self, Error> {
+        let header: [u8; 2] = data.get(..2).ok_or(Error::Eof)?.try_into().unwrap();
+        let (little_endian, xcdr2, delimited) = match header {
+            [0x00, 0x00] => (false, false, false),
+            [0x00, 0x01] => (true, false, false),
+            [0x00, 0x06] => (false, true, false),
+            [0x00, 0x07] => (true, true, false),
+            [0x00, 0x08] => (false, true, true),
+            [0x00, 0x09] => (true, true, true),
+            _ => return Err(Error::Encapsulation(header)),
+        };
+        Ok(Reader {
+            data,
+            pos: 4,
+            little_endian,
+            xcdr2,
+            delimited,
+        })
+    }
+
+    /// Alignment of primitives of the size.
+    fn align(&self, size: usize) -> usize {
+        if self.xcdr2 {
+            size.min(4)
+        } else {
+            size
+        }
+    }
+
+    /// Take `len` bytes aligned to `align`, relative to the end of the encapsulation header.
+    fn take(&mut self, len: usize, align: usize) -> Result<&'a [u8], Error> {
+        let offset = self.pos - 4;
+        self.pos += (align - offset % align) % align;
+        let bytes = self
+            .data
+            .get(self.pos..)
+            .and_then(|rest| rest.get(..len))
+            .ok_or(Error::Eof)?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn remaining(&self) -> usize {
+        self.data.len().saturating_sub(self.pos)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let mut bytes: [u8; N] = self.take(N, self.align(N))?.try_into().unwrap();
+        if !self.little_endian {
+            bytes.reverse();
+        }
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn string(&mut self) -> Result<String, Error> {
+        // Length includes the null terminator
+        let len = self.u32()? as usize;
+        let bytes = self.take(len, 1)?;
+        let bytes = bytes.strip_suffix(&[0]).unwrap_or(bytes);
+        Ok(String::from_utf8(bytes.to_vec())?)
+    }
+
+    fn primitive(&mut self, p: Primitive) -> Result<Value, Error> {
+        Ok(match p {
+            Primitive::Bool => Value::Bool(self.array::<1>()?[0] != 0),
+            Primitive::Int8 => Value::Int(i8::from_le_bytes(self.array()?) as i64),
+            Primitive::UInt8 => Value::UInt(u8::from_le_bytes(self.array()?) as u64),
+            Primitive::Int16 => Value::Int(i16::from_le_bytes(self.array()?) as i64),
+            Primitive::UInt16 => Value::UInt(u16::from_le_bytes(self.array()?) as u64),
+            Primitive::Int32 => Value::Int(i32::from_le_bytes(self.array()?) as i64),
+            Primitive::UInt32 => Value::UInt(self.u32()? as u64),
+            Primitive::Int64 => Value::Int(i64::from_le_bytes(self.array()?)),
+            Primitive::UInt64 => Value::UInt(u64::from_le_bytes(self.array()?)),
+            Primitive::Float32 => Value::Float(f32::from_le_bytes(self.array()?) as f64),
+            Primitive::Float64 => Value::Float(f64::from_le_bytes(self.array()?)),
+        })
+    }
+}
+
+/// Writer of little endian XCDR1 messages.
+struct Writer {
+    data: Vec<u8>,
+}
+
+impl Writer {
+    /// Put the bytes aligned to `align`, relative to the end of the encapsulation header.
+    fn put(&mut self, bytes: &[u8], align: usize) {
+        let offset = self.data.len() - 4;
+        let padding = (align - offset % align) % align;
+        self.data.resize(self.data.len() + padding, 0);
+        self.data.extend_from_slice(bytes);
+    }
+
+    /// Put the packed elements of `size` bytes, swapping the bytes of big endian elements.
+    fn put_array(&mut self, bytes: &[u8], size: usize, little_endian: bool) {
+        self.put(&[], size);
+        let start = self.data.len();
+        self.data.extend_from_slice(bytes);
+        if !little_endian && size > 1 {
+            self.data[start..]
+                .chunks_exact_mut(size)
+                .for_each(|element| element.reverse());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    const DRIVE: &str = "# Constants are not fields
+uint8 IDLE=0
+uint8 DRIVE = 1
+string<=8 NAME=\"drive\"
+uint8 mode
+string<=8 label  # Shown in the viewer
+float32[<=4] gains
+";
+
+    const WHEELS: &str = "std_msgs/Header header
+geometry_msgs/Vector3[] wheels
+bool[2] flags
+int16[] samples
+================================================================================
+MSG: std_msgs/Header
+builtin_interfaces/Time stamp
+string frame_id
+================================================================================
+MSG: builtin_interfaces/Time
+int32 sec
+uint32 nanosec
+================================================================================
+MSG: geometry_msgs/Vector3
+float64 x
+float64 y
+float64 z
+";
+
+    #[test]
+    fn decode_constants_and_bounded_fields() {
+        let schema = Schema::parse("test_msgs/msg/Drive", DRIVE).unwrap();
+        let data = [
+            0x00, 0x01, 0x00, 0x00, // Little endian CDR
+            0x01, 0x00, 0x00, 0x00, // mode, padded
+            0x06, 0x00, 0x00, 0x00, b'w', b'h', b'e', b'e', b'l', 0x00, // label
+            0x00, 0x00, 0x02, 0x00, 0x00, 0x00, // Padding, length of gains
+            0x00, 0x00, 0x00, 0x3f, 0x00, 0x00, 0x00, 0xc0, // gains
+        ];
+        let value = schema.decode(&data).unwrap();
+        assert_eq!(
+            value.to_json(),
+            serde_json::json!({"mode": 1, "label": "wheel", "gains": [0.5, -2.0]})
+        );
+        assert!(schema.decode(&data[..data.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn decode_nested_messages_and_arrays() {
+        let schema = Schema::parse("test_msgs/msg/Wheels", WHEELS).unwrap();
+        let data = cdr::serialize::<_, _, cdr::CdrLe>(
+            &(
+                (3i32, 500u32),
+                "base_link",
+                vec![(1.0f64, 2.0f64, 3.0f64), (-1.0, 0.0, 0.5)],
+                [true, false],
+                vec![-7i16, 8],
+            ),
+            cdr::Infinite,
+        )
+        .unwrap();
+        let value = schema.decode(&data).unwrap();
+        assert_eq!(
+            value.to_json(),
+            serde_json::json!({
+                "header": {"stamp": {"sec": 3, "nanosec": 500}, "frame_id": "base_link"},
+                "wheels": [{"x": 1.0, "y": 2.0, "z": 3.0}, {"x": -1.0, "y": 0.0, "z": 0.5}],
+                "flags": [true, false],
+                "samples": [-7, 8],
+            })
+        );
+        assert_eq!(
+            value.get("header.stamp.nanosec").and_then(|v| v.as_f64()),
+            Some(500.0)
+        );
+
+        // Arrays of messages are left out of the CSV columns
+        let columns: Vec<String> = value.flatten().into_iter().map(|(c, _)| c).collect();
+        assert_eq!(
+            columns,
+            [
+                "header.stamp.sec",
+                "header.stamp.nanosec",
+                "header.frame_id",
+                "flags",
+                "samples"
+            ]
+        );
+    }
+
+    const SCHEMA: &str = "uint8 flag
+float64 value
+string name
+int16[] samples
+Point[2] points
+Point[] path
+================================================================================
+MSG: test_msgs/Point
+float32 x
+float64 y
+";
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Point {
+        x: f32,
+        y: f64,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Sample {
+        flag: u8,
+        value: f64,
+        name: String,
+        samples: Vec<i16>,
+        points: [Point; 2],
+        path: Vec<Point>,
+    }
+
+    fn sample() -> Sample {
+        Sample {
+            flag: 7,
+            value: -2.5,
+            name: "lidar".to_owned(),
+            samples: vec![1, -2, 300],
+            points: [Point { x: 1.5, y: 2.0 }, Point { x: -3.0, y: 4.25 }],
+            path: vec![Point { x: 0.5, y: -0.5 }],
+        }
+    }
+
+    /// Encoder of XCDR2 messages, led by DHEADERs in the delimited encapsulation.
+    struct Encoder {
+        data: Vec<u8>,
+        little_endian: bool,
+        delimited: bool,
+    }
+
+    impl Encoder {
+        fn new(little_endian: bool, delimited: bool) -> Self {
+            let kind = 0x06 + little_endian as u8 + 2 * delimited as u8;
+            Encoder {
+                data: vec![0x00, kind, 0x00, 0x00],
+                little_endian,
+                delimited,
+            }
+        }
+
+        fn put<const N: usize>(&mut self, mut bytes: [u8; N]) {
+            while !(self.data.len() - 4).is_multiple_of(N.min(4)) {
+                self.data.push(0);
+            }
+            if !self.little_endian {
+                bytes.reverse();
+            }
+            self.data.extend_from_slice(&bytes);
+        }
+
+        fn string(&mut self, s: &str) {
+            self.put((s.len() as u32 + 1).to_le_bytes());
+            self.data.extend_from_slice(s.as_bytes());
+            self.data.push(0);
+        }
+
+        /// Reserve the size of what follows, filled by `end`.
+        fn begin(&mut self) -> usize {
+            self.put([0; 4]);
+            self.data.len() - 4
+        }
+
+        fn end(&mut self, pos: usize) {
+            let size = (self.data.len() - pos - 4) as u32;
+            let bytes = match self.little_endian {
+                true => size.to_le_bytes(),
+                false => size.to_be_bytes(),
+            };
+            self.data[pos..pos + 4].copy_from_slice(&bytes);
+        }
+
+        fn point(&mut self, point: &Point) {
+            let header = self.delimited.then(|| self.begin());
+            self.put(point.x.to_le_bytes());
+            self.put(point.y.to_le_bytes());
+            if let Some(header) = header {
+                self.end(header);
+            }
+        }
+
+        fn sample(mut self, sample: &Sample) -> Vec<u8> {
+            let header = self.delimited.then(|| self.begin());
+            self.put([sample.flag]);
+            self.put(sample.value.to_le_bytes());
+            self.string(&sample.name);
+            self.put((sample.samples.len() as u32).to_le_bytes());
+            for value in &sample.samples {
+                self.put(value.to_le_bytes());
+            }
+            // Arrays of structs are led by their sizes
+            let size = self.begin();
+            sample.points.iter().for_each(|p| self.point(p));
+            self.end(size);
+            let size = self.begin();
+            self.put((sample.path.len() as u32).to_le_bytes());
+            sample.path.iter().for_each(|p| self.point(p));
+            self.end(size);
+            if let Some(header) = header {
+                self.end(header);
+            }
+            self.data
+        }
+    }
+
+    #[test]
+    fn transcode_xcdr2_into_xcdr1() {
+        let schema = Schema::parse("test_msgs/msg/Sample", SCHEMA).unwrap();
+        for (little_endian, delimited) in
+            [(true, false), (true, true), (false, false), (false, true)]
+        {
+            let data = Encoder::new(little_endian, delimited).sample(&sample());
+            assert!(is_xcdr2(&data));
+            let mut out = vec![];
+            schema.transcode(&data, &mut out).unwrap();
+            assert_eq!(out[..4], [0x00, 0x01, 0x00, 0x00]);
+            let decoded: Sample = cdr::deserialize(&out).unwrap();
+            assert_eq!(decoded, sample(), "{:?}", data[..2].to_vec());
+
+            // Values decoded from both encapsulations are the same
+            let value = schema.decode(&data).unwrap();
+            assert_eq!(value, schema.decode(&out).unwrap());
+            assert_eq!(value.get("name"), Some(&Value::String("lidar".to_owned())));
+        }
+    }
+
+    #[test]
+    fn transcode_reuses_the_buffer() {
+        let schema = Schema::parse("test_msgs/msg/Sample", SCHEMA).unwrap();
+        let data = Encoder::new(true, false).sample(&sample());
+        let mut out = vec![0xff; 1024];
+        schema.transcode(&data, &mut out).unwrap();
+        let first = out.clone();
+        schema.transcode(&data, &mut out).unwrap();
+        assert_eq!(out, first);
+    }
+
+    #[test]
+    fn reject_truncated_messages() {
+        let schema = Schema::parse("test_msgs/msg/Sample", SCHEMA).unwrap();
+        let data = Encoder::new(true, true).sample(&sample());
+        let mut out = vec![];
+        for len in [2, 4, 12, data.len() - 1] {
+            assert!(schema.transcode(&data[..len], &mut out).is_err(), "{}", len);
+            assert!(schema.decode(&data[..len]).is_err(), "{}", len);
+        }
+
+        // Sequences longer than the message
+        let mut data = Encoder::new(true, false).sample(&sample());
+        let len = data.iter().position(|&b| b == 3).unwrap();
+        data[len..len + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(schema.transcode(&data, &mut out), Err(Error::Eof)));
+    }
+
+    #[test]
+    fn reject_unknown_encapsulations() {
+        let schema = Schema::parse("test_msgs/msg/Sample", SCHEMA).unwrap();
+        assert!(matches!(
+            schema.decode(&[0x00, 0x0a, 0x00, 0x00]),
+            Err(Error::Encapsulation([0x00, 0x0a]))
+        ));
+        assert!(!is_xcdr2(&[0x00, 0x01, 0x00, 0x00]));
+    }
+}
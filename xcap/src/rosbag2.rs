@@ -0,0 +1,216 @@
+//! Read rosbag2 SQLite storage files (`.db3`) as MCAP messages, so that all the parsers work on
+//! them unchanged.
+use mcap::{Channel, McapResult, Message, Schema};
+use rusqlite::{Connection, OpenFlags};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap, VecDeque},
+    path::Path,
+    sync::Arc,
+};
+
+// Messages are loaded from the database in batches of this size
+const BATCH_SIZE: usize = 1000;
+
+/// A topic recorded in the bag.
+pub struct Topic {
+    pub id: i64,
+    pub channel: Arc<Channel<'static>>,
+    pub msg_count: u64,
+}
+
+pub struct Reader {
+    connection: Connection,
+    topics: HashMap<i64, Topic>,
+}
+
+impl Reader {
+    pub fn open(path: &Path) -> rusqlite::Result<Self> {
+        let connection = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+
+        // Message definitions are only recorded by newer versions of rosbag2
+        let mut definitions: HashMap<String, (String, String)> = HashMap::new();
+        let has_definitions: bool = connection.query_row(
+            "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = 'message_definitions'",
+            [],
+            |row| row.get(0),
+        )?;
+        if has_definitions {
+            let mut stmt = connection.prepare(
+                "SELECT topic_type, encoding, encoded_message_definition FROM message_definitions",
+            )?;
+            let rows = stmt.query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, (row.get(1)?, row.get(2)?)))
+            })?;
+            for row in rows {
+                let (name, definition) = row?;
+                definitions.insert(name, definition);
+            }
+        }
+
+        let mut counts: HashMap<i64, u64> = HashMap::new();
+        {
+            let mut stmt =
+                connection.prepare("SELECT topic_id, COUNT(*) FROM messages GROUP BY topic_id")?;
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            for row in rows {
+                let (id, count) = row?;
+                counts.insert(id, count);
+            }
+        }
+
+        let mut topics = HashMap::new();
+        {
+            let mut stmt =
+                connection.prepare("SELECT id, name, type, serialization_format FROM topics")?;
+            let rows = stmt.query_map([], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                ))
+            })?;
+            for row in rows {
+                let (id, name, format, serialization) = row?;
+                // Keep the type name even without a definition, parsers are chosen by it
+                let (encoding, text) = definitions
+                    .get(&format)
+                    .filter(|(encoding, text)| !encoding.is_empty() && !text.is_empty())
+                    .cloned()
+                    .unwrap_or_default();
+                let schema = Some(Arc::new(Schema {
+                    name: format,
+                    encoding,
+                    data: Cow::Owned(text.into_bytes()),
+                }));
+                let channel = Arc::new(Channel {
+                    topic: name,
+                    schema,
+                    message_encoding: serialization,
+                    metadata: BTreeMap::new(),
+                });
+                topics.insert(
+                    id,
+                    Topic {
+                        id,
+                        channel,
+                        msg_count: counts.get(&id).copied().unwrap_or(0),
+                    },
+                );
+            }
+        }
+
+        Ok(Reader { connection, topics })
+    }
+
+    pub fn topics(&self) -> impl Iterator<Item = &Topic> {
+        self.topics.values()
+    }
+
+    /// Time stamp of the first message in the bag, None if the bag is empty.
+    pub fn start_time(&self) -> rusqlite::Result<Option<u64>> {
+        self.connection
+            .query_row("SELECT MIN(timestamp) FROM messages", [], |row| {
+                row.get::<_, Option<i64>>(0)
+            })
+            .map(|t| t.map(|t| t as u64))
+    }
+
+    /// Problems found by the integrity check of SQLite, empty if the database is fine.
+    pub fn integrity_check(&self) -> rusqlite::Result<Vec<String>> {
+        let mut stmt = self.connection.prepare("PRAGMA integrity_check")?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+        let mut problems = Vec::new();
+        for row in rows {
+            let row = row?;
+            if row != "ok" {
+                problems.push(row);
+            }
+        }
+        Ok(problems)
+    }
+
+    /// Messages of the selected topics ordered by their time stamps. All the topics are selected
+    /// if `topics` is None.
+    pub fn messages(&self, topics: Option<&[&str]>) -> Messages<'_> {
+        let topic_ids = self
+            .topics
+            .values()
+            .filter(|t| topics.is_none_or(|names| names.contains(&t.channel.topic.as_str())))
+            .map(|t| t.id.to_string())
+            .collect::<Vec<_>>();
+        Messages {
+            reader: self,
+            topic_ids: topic_ids.join(","),
+            cursor: (i64::MIN, i64::MIN),
+            batch: VecDeque::new(),
+            done: topic_ids.is_empty(),
+        }
+    }
+}
+
+/// Iterator over messages in the bag, loaded batch by batch.
+pub struct Messages<'a> {
+    reader: &'a Reader,
+    topic_ids: String,
+
+    // Time stamp and ID of the last message loaded
+    cursor: (i64, i64),
+
+    batch: VecDeque<Message<'static>>,
+    done: bool,
+}
+
+impl Messages<'_> {
+    fn load(&mut self) -> rusqlite::Result<()> {
+        let mut stmt = self.reader.connection.prepare_cached(&format!(
+            "SELECT id, topic_id, timestamp, data FROM messages \
+             WHERE topic_id IN ({}) AND (timestamp > ?1 OR (timestamp = ?1 AND id > ?2)) \
+             ORDER BY timestamp, id LIMIT {}",
+            self.topic_ids, BATCH_SIZE
+        ))?;
+        let rows = stmt.query_map([self.cursor.0, self.cursor.1], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, i64>(2)?,
+                row.get::<_, Vec<u8>>(3)?,
+            ))
+        })?;
+        for row in rows {
+            let (id, topic_id, timestamp, data) = row?;
+            self.cursor = (timestamp, id);
+            self.batch.push_back(Message {
+                channel: self.reader.topics[&topic_id].channel.clone(),
+                sequence: 0,
+                log_time: timestamp as u64,
+                publish_time: timestamp as u64,
+                data: Cow::Owned(data),
+            });
+        }
+        if self.batch.len() < BATCH_SIZE {
+            self.done = true;
+        }
+        Ok(())
+    }
+}
+
+impl Iterator for Messages<'_> {
+    type Item = McapResult<Message<'static>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.batch.is_empty() && !self.done {
+            if let Err(e) = self.load() {
+                self.done = true;
+                return Some(Err(std::io::Error::other(e).into()));
+            }
+        }
+        self.batch.pop_front().map(Ok)
+    }
+}
+
+/// Is this a rosbag2 SQLite storage file?
+pub fn is_db3(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == "db3")
+}
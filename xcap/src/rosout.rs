@@ -0,0 +1,139 @@
+use crate::extractor::Extractor;
+use mcap::Message;
+use rerun::{RecordingStream, TextLogLevel};
+use ros2_rcl_interfaces::msg::Log;
+use std::{
+    fs,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{atomic::AtomicBool, Arc},
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Payload error. {0}")]
+    Payload(#[from] crate::extractor::DecodeError),
+    #[error("CDR error. {0}")]
+    Cdr(#[from] cdr::Error),
+    #[error("JSON error. {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// Parser of the ROS log, `/rosout`. Logs are saved as `rosout.log` in the console format of ROS
+/// and as `rosout.jsonl`, ordered by stamp. Warnings and errors are shown as text logs.
+pub struct Parser {
+    // Output directory
+    output_dir: PathBuf,
+
+    // Visualizer with rerun
+    rec_stream: Option<RecordingStream>,
+
+    // Should dump data to disk
+    dump_data: bool,
+
+    // Scratch buffer for decoded message payloads, reused across messages
+    buffer: Vec<u8>,
+
+    // All the logs, sorted and written in post processing as nodes publish out of order
+    logs: Vec<Log>,
+}
+
+impl Parser {
+    pub fn new(output_path: &Path, rerun_stream: Option<RecordingStream>, dump_data: bool) -> Self {
+        // Create output dir
+        if dump_data {
+            fs::create_dir_all(output_path).unwrap();
+        }
+
+        Parser {
+            output_dir: output_path.into(),
+            rec_stream: rerun_stream,
+            dump_data,
+            buffer: Vec::new(),
+            logs: Vec::new(),
+        }
+    }
+}
+
+/// Name of the logging level.
+fn level_name(level: u8) -> &'static str {
+    match level {
+        Log::DEBUG => TextLogLevel::DEBUG,
+        Log::INFO => TextLogLevel::INFO,
+        Log::WARN => TextLogLevel::WARN,
+        Log::ERROR => TextLogLevel::ERROR,
+        Log::FATAL => "FATAL",
+        _ => "UNKNOWN",
+    }
+}
+
+impl Extractor for Parser {
+    type ExtractorError = Box<dyn std::error::Error + Send + Sync>;
+
+    fn buffer(&mut self) -> &mut Vec<u8> {
+        &mut self.buffer
+    }
+
+    fn step(&mut self, message: &Message) -> Result<(), Self::ExtractorError> {
+        let payload = self.decode_into(message).map_err(Error::Payload)?;
+        let log =
+            cdr::deserialize_from::<_, Log, _>(payload, cdr::size::Infinite).map_err(Error::Cdr)?;
+
+        if let Some(rec) = self.rec_stream.as_ref().filter(|_| log.level >= Log::WARN) {
+            rec.set_time_seconds(
+                "main",
+                log.stamp.sec as f64 + log.stamp.nanosec as f64 * 1e-9,
+            );
+            rec.log(
+                message.channel.topic.as_str(),
+                &rerun::TextLog::new(format!("[{}]: {}", log.name, log.msg))
+                    .with_level(level_name(log.level)),
+            )?;
+        }
+
+        // Dump data?
+        if self.dump_data {
+            self.logs.push(log);
+        }
+        Ok(())
+    }
+
+    fn post_process(&mut self, _sigint: Arc<AtomicBool>) -> Result<(), Self::ExtractorError> {
+        if !self.dump_data {
+            return Ok(());
+        }
+        self.logs.sort_by_key(|log| log.stamp);
+
+        let mut text = BufWriter::new(fs::File::create(self.output_dir.join("rosout.log"))?);
+        let mut jsonl = BufWriter::new(fs::File::create(self.output_dir.join("rosout.jsonl"))?);
+        for log in &self.logs {
+            writeln!(
+                text,
+                "[{}] [{}.{:09}] [{}]: {}",
+                level_name(log.level),
+                log.stamp.sec,
+                log.stamp.nanosec,
+                log.name,
+                log.msg
+            )?;
+            serde_json::to_writer(
+                &mut jsonl,
+                &serde_json::json!({
+                    "sec": log.stamp.sec,
+                    "nanosec": log.stamp.nanosec,
+                    "level": level_name(log.level),
+                    "name": log.name,
+                    "msg": log.msg,
+                    "file": log.file,
+                    "function": log.function,
+                    "line": log.line,
+                }),
+            )
+            .map_err(Error::Json)?;
+            writeln!(jsonl)?;
+        }
+        text.flush()?;
+        jsonl.flush()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,156 @@
+//! Decoded messages of recordings, for pipelines of library users that do not dump to disk.
+use crate::{extractor, ros2msg, summary, Error, Input, ParserError, Topic};
+use serde::de::DeserializeOwned;
+use std::{
+    collections::HashMap,
+    ops::RangeBounds,
+    path::{Path, PathBuf},
+};
+
+type MessageIter<'a> =
+    Box<dyn Iterator<Item = Result<(&'a PathBuf, mcap::Message<'a>), Error>> + 'a>;
+
+/// Message decoded with the schema in the file, or from JSON for JSON encoded channels.
+pub type DecodedMessage = ros2msg::Value;
+
+/// MCAP or rosbag2 files opened for reading decoded messages.
+pub struct McapSession {
+    files: Vec<PathBuf>,
+    inputs: Vec<Input>,
+    // Summary sections of the inputs, used to skip chunks without the selected topics
+    summaries: Vec<Option<mcap::read::Summary<'static>>>,
+    topics: Vec<Topic>,
+}
+
+impl McapSession {
+    pub fn open(files: &[PathBuf]) -> Result<Self, Error> {
+        let inputs: Vec<Input> = files
+            .iter()
+            .map(|file| Input::open(file))
+            .collect::<Result<_, _>>()?;
+        Ok(McapSession {
+            files: files.to_vec(),
+            summaries: inputs.iter().map(Input::owned_summary).collect(),
+            inputs,
+            topics: summary(files)?,
+        })
+    }
+
+    /// Topics of all the files.
+    pub fn topics(&self) -> &[Topic] {
+        &self.topics
+    }
+
+    /// Messages of the topics with log times in the range, in nanoseconds, as
+    /// `(topic, log time, message)`. Files are read one by one in the order they are opened.
+    pub fn messages<'a>(
+        &'a self,
+        topics: &[&str],
+        time_range: impl RangeBounds<u64> + 'a,
+    ) -> impl Iterator<Item = Result<(&'a Topic, u64, DecodedMessage), Error>> + 'a {
+        let topics: Vec<&'a Topic> = self
+            .topics
+            .iter()
+            .filter(|t| topics.contains(&t.name.as_str()))
+            .collect();
+        let names: Vec<&str> = topics.iter().map(|t| t.name.as_str()).collect();
+        let messages = self.raw_messages(&names);
+
+        // Schemas are parsed once per topic, and the payload buffer is reused
+        let mut schemas: HashMap<&'a str, ros2msg::Schema> = HashMap::new();
+        let mut buffer = Vec::new();
+        messages.filter_map(move |message| {
+            let (file, message) = match message {
+                Ok(message) => message,
+                Err(e) => return Some(Err(e)),
+            };
+            if !time_range.contains(&message.log_time) {
+                return None;
+            }
+            let topic = *topics.iter().find(|t| t.name == message.channel.topic)?;
+            let decoded = decode(&message, topic, &mut schemas, &mut buffer)
+                .map_err(|e| ParserError::new(&topic.name, e).at(message.log_time, file));
+            Some(
+                decoded
+                    .map(|decoded| (topic, message.log_time, decoded))
+                    .map_err(Error::from),
+            )
+        })
+    }
+
+    /// Messages of the topic deserialized from CDR into `T`, like `ros2_sensor_msgs::msg::Image`,
+    /// as `(log time, message)`. Fail if the topic is not in the files.
+    pub fn read_topic<'a, T: DeserializeOwned>(
+        &'a self,
+        topic: &'a str,
+    ) -> Result<impl Iterator<Item = Result<(u64, T), Error>> + 'a, Error> {
+        if !self.topics.iter().any(|t| t.name == topic) {
+            return Err(Error::InvalidTopic(format!("Topic not found: {}", topic)));
+        }
+        let mut buffer = Vec::new();
+        Ok(self.raw_messages(&[topic]).map(move |message| {
+            let (file, message) = message?;
+            extractor::decode_payload(&message, &mut buffer)
+                .map_err(|e| ParserError::new(topic, e).at(message.log_time, file))?;
+            let decoded = cdr::deserialize_from::<_, T, _>(buffer.as_slice(), cdr::size::Infinite)
+                .map_err(|e| ParserError::new(topic, e).at(message.log_time, file))?;
+            Ok((message.log_time, decoded))
+        }))
+    }
+
+    /// Undecoded messages of the topics, with the files they are read from.
+    fn raw_messages(&self, topics: &[&str]) -> MessageIter<'_> {
+        let names: Vec<String> = topics.iter().map(|t| t.to_string()).collect();
+        let inputs = self.inputs.iter().zip(&self.summaries);
+        Box::new(self.files.iter().zip(inputs).flat_map(
+            move |(file, (input, summary))| -> MessageIter<'_> {
+                let selected: Vec<&str> = names.iter().map(String::as_str).collect();
+                let names = names.clone();
+                match input.messages(summary.as_ref(), &selected) {
+                    Ok(messages) => Box::new(
+                        messages
+                            .filter(move |message| {
+                                message
+                                    .as_ref()
+                                    .map_or(true, |message| names.contains(&message.channel.topic))
+                            })
+                            .map(move |message| {
+                                message.map(|message| (file, message)).map_err(Error::from)
+                            }),
+                    ),
+                    Err(e) => Box::new(std::iter::once(Err(e))),
+                }
+            },
+        ))
+    }
+
+    /// Files of the session.
+    pub fn files(&self) -> impl Iterator<Item = &Path> {
+        self.files.iter().map(PathBuf::as_path)
+    }
+}
+
+/// Decode the message of the topic, parsing the schema of the topic if it is new.
+fn decode<'a>(
+    message: &mcap::Message,
+    topic: &'a Topic,
+    schemas: &mut HashMap<&'a str, ros2msg::Schema>,
+    buffer: &mut Vec<u8>,
+) -> Result<DecodedMessage, Box<dyn std::error::Error + Send + Sync>> {
+    extractor::decode_payload(message, buffer)?;
+    if message.channel.message_encoding == "json" {
+        let json: serde_json::Value = serde_json::from_slice(buffer)?;
+        return Ok(DecodedMessage::from_json(&json));
+    }
+    if !schemas.contains_key(topic.name.as_str()) {
+        let schema = message
+            .channel
+            .schema
+            .as_ref()
+            .filter(|s| s.encoding == "ros2msg")
+            .ok_or(format!("No ros2msg schema of topic {}", topic.name))?;
+        let text = String::from_utf8_lossy(&schema.data);
+        schemas.insert(&topic.name, ros2msg::Schema::parse(&schema.name, &text)?);
+    }
+    Ok(schemas[topic.name.as_str()].decode(buffer)?)
+}
@@ -1,16 +1,41 @@
-use log::{debug, error, info};
+use crate::credentials::Credentials;
+use crate::progress::ProgressSink;
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use log::{debug, error, info, warn};
 use minio::s3::{
-    args::{BucketExistsArgs, ListObjectsV2Args, ObjectConditionalReadArgs},
+    args::{
+        BucketExistsArgs, ListObjectsV2Args, ObjectConditionalReadArgs, StatObjectArgs,
+        UploadObjectArgs,
+    },
     client::{Client, ClientBuilder},
     creds::StaticProvider,
     http::BaseUrl,
 };
-use std::sync::{atomic::AtomicBool, Arc};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
 use std::{
+    collections::{BTreeMap, HashMap},
     fs,
+    io::{self, Read, Seek, SeekFrom, Write},
     path::{Path, PathBuf},
+    process::{Command, Stdio},
 };
 use thiserror::Error;
+use url::Url;
+
+// Bytes of an upload sent in one request, for backends uploading in parts
+const UPLOAD_PART: usize = 16 << 20;
+
+// Bytes of a download read in one ranged request, and the requests sent at a time
+const DOWNLOAD_PART: u64 = 16 << 20;
+const DOWNLOAD_CONCURRENCY: usize = 4;
 
 #[derive(Error, Debug)]
 pub enum Error {
@@ -20,101 +45,1236 @@ pub enum Error {
     RequestError(#[from] reqwest::Error),
     #[error("Disk IO error: {0}")]
     DiskError(#[from] std::io::Error),
+    #[error("JSON error: {0}")]
+    JsonError(#[from] serde_json::Error),
+    #[error("Invalid URL: {0}")]
+    UrlError(#[from] url::ParseError),
+    #[error("Invalid pattern: {0}")]
+    PatternError(#[from] glob::PatternError),
+    #[error("Command error: {0}")]
+    CommandError(String),
+    #[error("Invalid data: {0}")]
+    InvalidData(String),
+    #[error("Not supported: {0}")]
+    Unsupported(String),
     #[error("Not existed: {0}")]
     NotExisted(String),
     #[error("unknown error")]
     Unknown,
 }
 
+/// Object listed in a backend. Metadata the backend does not provide is None.
+#[derive(Debug, Clone, Default)]
+pub struct Object {
+    pub name: String,
+    pub size: Option<u64>,
+    pub last_modified: Option<DateTime<Utc>>,
+    pub etag: Option<String>,
+}
+
+impl Object {
+    fn named(name: &str) -> Self {
+        Object {
+            name: name.to_owned(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Remote storage of recordings, like a S3 bucket.
+#[async_trait]
+pub trait Backend: Send + Sync {
+    /// URL of the bucket, container or host, telling objects of different backends apart.
+    fn location(&self) -> String;
+
+    /// Objects with names starting with the prefix.
+    async fn list(&self, prefix: &str) -> Result<Vec<Object>, Error>;
+
+    /// Objects in the directory, or in all the directories matching it as a glob pattern like
+    /// `drive-2024-*/camera`. Wildcards do not match across `/`.
+    async fn list_dir(&self, dir: &str) -> Result<Vec<Object>, Error> {
+        if !is_pattern(dir) {
+            return self.list(dir).await;
+        }
+        let pattern = glob::Pattern::new(dir)?;
+        let options = glob::MatchOptions {
+            require_literal_separator: true,
+            ..Default::default()
+        };
+        let depth = dir.split('/').count();
+        let literal = &dir[..dir.find(['*', '?', '[']).unwrap()];
+        let mut objects = self.list(literal).await?;
+        objects.retain(|object| {
+            let segments: Vec<&str> = object.name.splitn(depth + 1, '/').collect();
+            segments.len() > depth && pattern.matches_with(&segments[..depth].join("/"), options)
+        });
+        Ok(objects)
+    }
+
+    /// Download the object into the local file, until done or interrupted. Backends supporting
+    /// ranged reads continue an existing local file instead of starting over. Downloaded bytes,
+    /// those of the existing file included, are reported to the progress.
+    async fn download_object(
+        &self,
+        object: &str,
+        local_path: &Path,
+        progress: &dyn ProgressSink,
+        sigint: &Arc<AtomicBool>,
+    ) -> Result<(), Error>;
+
+    /// Size of the object in bytes, for backends supporting ranged reads.
+    async fn object_size(&self, _object: &str) -> Result<u64, Error> {
+        Err(Error::Unsupported("ranged reads".to_string()))
+    }
+
+    /// Read `length` bytes of the object from the offset.
+    async fn read_range(
+        &self,
+        _object: &str,
+        _offset: u64,
+        _length: u64,
+    ) -> Result<Vec<u8>, Error> {
+        Err(Error::Unsupported("ranged reads".to_string()))
+    }
+
+    /// Upload the local file as the object.
+    async fn upload(&self, _local_path: &Path, _object: &str) -> Result<(), Error> {
+        Err(Error::Unsupported("uploads".to_string()))
+    }
+
+    /// Download all the objects in the directory, or the directories matching the pattern, into
+    /// the local directory. Objects are written to `.part` files first, so that interrupted
+    /// downloads could be resumed by a later run, and objects already downloaded are skipped.
+    /// Parts of objects changed since are downloaded again, where the backend tells.
+    ///
+    /// With a cache, objects with ETags are downloaded into the cache and linked from the local
+    /// directory.
+    async fn download_dir(
+        &self,
+        dir: &str,
+        local_path: &Path,
+        cache: Option<&Cache>,
+        progress: &dyn ProgressSink,
+        sigint: &Arc<AtomicBool>,
+    ) -> Result<(), Error> {
+        let objects = self.list_dir(dir).await?;
+        for object in objects {
+            if sigint.load(Ordering::Relaxed) {
+                break;
+            }
+            let obj_file = &local_name(dir, &object.name);
+            if obj_file.is_empty() {
+                continue;
+            }
+            let obj_file_path = local_path.join(obj_file);
+            if obj_file_path.exists() {
+                info!("Already downloaded: {}", obj_file);
+                continue;
+            }
+            let entry = cache.and_then(|c| c.entry(&self.location(), &object));
+            let target = match &entry {
+                Some(entry) => {
+                    fs::create_dir_all(entry)?;
+                    entry.join(obj_file)
+                }
+                None => obj_file_path.clone(),
+            };
+            if target.exists() {
+                info!("Found in cache: {}", obj_file);
+            } else {
+                let partial = target.with_file_name(format!("{}.part", obj_file));
+                info!("Downloading: {}", obj_file);
+                progress.on_download_start(&object.name, object.size);
+                let downloaded = self
+                    .download_object(&object.name, &partial, progress, sigint)
+                    .await;
+                progress.on_download_done(&object.name);
+                downloaded?;
+                if sigint.load(Ordering::Relaxed) {
+                    break;
+                }
+                fs::rename(&partial, &target)?;
+            }
+            if entry.is_some() {
+                std::os::unix::fs::symlink(&target, &obj_file_path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Read the next part of the file to upload, up to `UPLOAD_PART` bytes. Empty at the end.
+fn read_part(file: &mut fs::File) -> io::Result<Vec<u8>> {
+    let mut part = Vec::with_capacity(UPLOAD_PART);
+    file.take(UPLOAD_PART as u64).read_to_end(&mut part)?;
+    Ok(part)
+}
+
+/// Whether the directory is a glob pattern.
+pub fn is_pattern(dir: &str) -> bool {
+    dir.contains(['*', '?', '['])
+}
+
+/// Name of the local file of the object listed in the directory. Objects matching a pattern are
+/// named after their whole paths, as files of different directories could have the same names.
+pub fn local_name(dir: &str, object: &str) -> String {
+    if is_pattern(dir) && !object.ends_with('/') {
+        object.replace('/', "_")
+    } else {
+        object.split('/').next_back().unwrap().to_string()
+    }
+}
+
+/// File next to the partial download keeping the version of the object it is a part of.
+fn version_path(partial: &Path) -> PathBuf {
+    let mut path = partial.as_os_str().to_owned();
+    path.push(".etag");
+    PathBuf::from(path)
+}
+
+/// Write the body of the response into the file, until done or interrupted.
+async fn write_body(
+    response: &mut reqwest::Response,
+    file: &mut fs::File,
+    object: &str,
+    progress: &dyn ProgressSink,
+    sigint: &Arc<AtomicBool>,
+) -> Result<(), Error> {
+    while let Some(chunk) = response.chunk().await? {
+        file.write_all(&chunk)?;
+        progress.on_download(object, chunk.len() as u64);
+        if sigint.load(Ordering::Relaxed) {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Directory of the user for files kept across runs, `$XDG_CACHE_HOME/xcap` or `~/.cache/xcap`.
+/// None if the home directory is unknown.
+pub fn cache_home() -> Option<PathBuf> {
+    let base = match std::env::var_os("XDG_CACHE_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(std::env::var_os("HOME")?).join(".cache"),
+    };
+    Some(base.join("xcap"))
+}
+
+/// Objects downloaded before, kept across runs in `$XDG_CACHE_HOME/xcap` or `~/.cache/xcap`.
+/// Entries are found by the ETags of the objects, so that changed objects are downloaded again.
+/// Nothing is evicted, remove the directory to free the space.
+#[derive(Debug)]
+pub struct Cache {
+    root: PathBuf,
+}
+
+impl Cache {
+    /// The cache of the user, None if the home directory is unknown.
+    pub fn open() -> Option<Self> {
+        Some(Self {
+            root: cache_home()?,
+        })
+    }
+
+    /// Directory of the object in the cache, None if the object has no ETag.
+    fn entry(&self, location: &str, object: &Object) -> Option<PathBuf> {
+        let etag = object.etag.as_ref()?;
+        let digest = Sha256::new()
+            .chain_update(location)
+            .chain_update("/")
+            .chain_update(&object.name)
+            .chain_update(etag)
+            .finalize();
+        let key: String = digest[..16].iter().map(|b| format!("{:02x}", b)).collect();
+        Some(self.root.join(key))
+    }
+}
+
+/// MinIO or S3 compatible bucket.
 #[derive(Debug)]
 pub struct Agent {
     client: Client,
+    base_url: String,
+    bucket: String,
 }
 
 impl Agent {
+    #[allow(clippy::result_large_err)]
     pub fn new(
         base_url: &str,
         region: &str,
-        access_key: &str,
-        secret_key: &str,
+        credentials: &Credentials,
+        bucket: &str,
     ) -> Result<Self, Error> {
-        let mut base_url = base_url.parse::<BaseUrl>()?;
-        base_url.region = region.to_string();
-        let static_provider = StaticProvider::new(access_key, secret_key, None);
-        let client = ClientBuilder::new(base_url)
+        let mut url = base_url.parse::<BaseUrl>()?;
+        url.region = region.to_string();
+        let static_provider = StaticProvider::new(
+            &credentials.access_key,
+            &credentials.secret_key,
+            credentials.session_token.as_deref(),
+        );
+        let client = ClientBuilder::new(url)
             .provider(Some(Box::new(static_provider)))
             .build()?;
-        Ok(Self { client })
+        Ok(Self {
+            client,
+            base_url: base_url.to_owned(),
+            bucket: bucket.to_owned(),
+        })
     }
 
-    pub async fn download_dir(
+    async fn check_bucket(&self) -> Result<(), Error> {
+        let exists: bool = self
+            .client
+            .bucket_exists(&BucketExistsArgs::new(&self.bucket)?)
+            .await?;
+        if !exists {
+            error!("Bucket {} does not exist.", self.bucket);
+            return Err(Error::NotExisted(self.bucket.clone()));
+        }
+        Ok(())
+    }
+
+    // Read a range of the object, failing if it is no longer the version of the ETag
+    async fn read_version_range(
         &self,
-        bucket: &str,
-        dir: &str,
+        object: &str,
+        etag: Option<&str>,
+        offset: u64,
+        length: u64,
+    ) -> Result<Vec<u8>, Error> {
+        let mut obj_dscp = ObjectConditionalReadArgs::new(&self.bucket, object)?;
+        obj_dscp.offset = Some(offset as usize);
+        obj_dscp.length = Some(length as usize);
+        obj_dscp.match_etag = etag;
+        let response = self.client.get_object(&obj_dscp).await?;
+        Ok(response.bytes().await?.to_vec())
+    }
+}
+
+#[async_trait]
+impl Backend for Agent {
+    fn location(&self) -> String {
+        format!("{}/{}", self.base_url, self.bucket)
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<Object>, Error> {
+        self.check_bucket().await?;
+        let mut objects = vec![];
+
+        // Up to 1000 objects are listed per request, the rest follow the continuation token
+        let mut continuation_token = None;
+        loop {
+            let mut list_obj_args = ListObjectsV2Args::new(&self.bucket)?;
+            list_obj_args.prefix = Some(prefix).filter(|p| !p.is_empty());
+            list_obj_args.continuation_token = continuation_token;
+            let result = self.client.list_objects_v2(&list_obj_args).await?;
+            for item in result.contents.into_iter() {
+                debug!("Found {}", item.name);
+                objects.push(Object {
+                    name: item.name,
+                    size: item.size.map(|s| s as u64),
+                    last_modified: item.last_modified,
+                    etag: item.etag,
+                });
+            }
+            continuation_token = result.next_continuation_token;
+            if !result.is_truncated || continuation_token.is_none() {
+                break;
+            }
+        }
+        Ok(objects)
+    }
+
+    #[allow(clippy::result_large_err)]
+    async fn upload(&self, local_path: &Path, object: &str) -> Result<(), Error> {
+        self.check_bucket().await?;
+        let filename = local_path
+            .to_str()
+            .ok_or(Error::InvalidData(local_path.display().to_string()))?;
+        // Large files are uploaded in multiple parts. The future of the client is not `Send`,
+        // so it blocks the current thread instead.
+        let args = UploadObjectArgs::new(&self.bucket, object, filename)?;
+        tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current().block_on(self.client.upload_object(&args))
+        })?;
+        Ok(())
+    }
+
+    async fn object_size(&self, object: &str) -> Result<u64, Error> {
+        let stat = self
+            .client
+            .stat_object(&StatObjectArgs::new(&self.bucket, object)?)
+            .await?;
+        Ok(stat.size as u64)
+    }
+
+    async fn read_range(&self, object: &str, offset: u64, length: u64) -> Result<Vec<u8>, Error> {
+        self.read_version_range(object, None, offset, length).await
+    }
+
+    async fn download_object(
+        &self,
+        object: &str,
         local_path: &Path,
+        progress: &dyn ProgressSink,
         sigint: &Arc<AtomicBool>,
     ) -> Result<(), Error> {
-        // Check bucket exist or not.
-        let exists: bool = self
+        self.check_bucket().await?;
+
+        // Continue the local file if it is a part of the same version of the object, whose ETag
+        // is kept next to the file
+        let stat = self
             .client
-            .bucket_exists(&BucketExistsArgs::new(bucket)?)
+            .stat_object(&StatObjectArgs::new(&self.bucket, object)?)
             .await?;
-        if !exists {
-            error!("Bucket {} does not exist.", bucket);
-            return Err(Error::NotExisted(bucket.to_string()));
+        let size = stat.size as u64;
+        let version_path = version_path(local_path);
+        let mut offset = match fs::read_to_string(&version_path) {
+            Ok(etag) if etag == stat.etag => fs::metadata(local_path).map_or(0, |m| m.len()),
+            _ => 0,
+        };
+        if offset > size {
+            offset = 0;
+        }
+        if offset == 0 {
+            fs::write(&version_path, &stat.etag)?;
+        }
+
+        let mut file = if offset > 0 {
+            info!("Resuming from byte {}", offset);
+            progress.on_download(object, offset);
+            fs::OpenOptions::new().append(true).open(local_path)?
+        } else {
+            fs::File::create(local_path)?
+        };
+
+        // Parts are read with concurrent ranged requests, and written in order so that the local
+        // file is always the head of the object to resume from.
+        while offset < size && !sigint.load(Ordering::Relaxed) {
+            let ranges = (0..DOWNLOAD_CONCURRENCY as u64)
+                .map(|i| offset + i * DOWNLOAD_PART)
+                .take_while(|start| *start < size)
+                .map(|start| (start, DOWNLOAD_PART.min(size - start)))
+                .collect::<Vec<_>>();
+            let parts = futures::future::try_join_all(ranges.iter().map(|&(start, length)| {
+                self.read_version_range(object, Some(&stat.etag), start, length)
+            }))
+            .await?;
+            // A short part would shift the rest of the file
+            for (part, (start, length)) in parts.into_iter().zip(ranges) {
+                if part.len() as u64 != length {
+                    return Err(Error::InvalidData(format!(
+                        "{} bytes of {} from byte {}, expected {}",
+                        part.len(),
+                        object,
+                        start,
+                        length
+                    )));
+                }
+                file.write_all(&part)?;
+                progress.on_download(object, part.len() as u64);
+                offset += part.len() as u64;
+            }
+        }
+        if offset == size {
+            fs::remove_file(&version_path)?;
+        }
+        Ok(())
+    }
+}
+
+/// Google Cloud Storage bucket, read with the JSON API.
+///
+/// Requests are authorized with the OAuth token in `GCS_ACCESS_TOKEN` if set, like the output of
+/// `gcloud auth print-access-token`, otherwise the bucket should be public. `STORAGE_EMULATOR_HOST`
+/// redirects requests to an emulator.
+#[derive(Debug)]
+pub struct Gcs {
+    client: reqwest::Client,
+    endpoint: Url,
+    bucket: String,
+    token: Option<String>,
+}
+
+// A page of objects listed
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GcsObjects {
+    #[serde(default)]
+    items: Vec<GcsObject>,
+    next_page_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct GcsObject {
+    name: String,
+    // Sizes are strings in the JSON API, and times are in RFC 3339
+    size: Option<String>,
+    updated: Option<String>,
+    etag: Option<String>,
+}
+
+impl Gcs {
+    #[allow(clippy::result_large_err)]
+    pub fn new(bucket: &str) -> Result<Self, Error> {
+        let endpoint = std::env::var("STORAGE_EMULATOR_HOST")
+            .unwrap_or("https://storage.googleapis.com".to_string());
+        Ok(Self {
+            client: reqwest::Client::new(),
+            endpoint: Url::parse(&endpoint)?,
+            bucket: bucket.to_owned(),
+            token: std::env::var("GCS_ACCESS_TOKEN").ok(),
+        })
+    }
+
+    /// URL of the objects in the bucket, or of the object if named.
+    fn objects_url(&self, object: Option<&str>) -> Url {
+        let mut url = self.endpoint.clone();
+        {
+            let mut segments = url.path_segments_mut().unwrap();
+            segments
+                .pop_if_empty()
+                .extend(["storage", "v1", "b", &self.bucket, "o"]);
+            // Slashes in object names are escaped
+            if let Some(object) = object {
+                segments.push(object);
+            }
         }
+        url
+    }
 
-        // List objects
-        let mut objects: Vec<String> = vec![];
-        let list_obj_args = ListObjectsV2Args::new(bucket)?;
-        let result = self.client.list_objects_v2(&list_obj_args).await?;
-        for item in result.contents.iter() {
-            objects.push(item.name.clone());
-            debug!("Found {}", item.name);
+    fn authorize(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
         }
+    }
 
-        // Filter objects
-        let targets: Vec<&String> = objects.iter().filter(|&o| o.starts_with(dir)).collect();
+    async fn get(&self, url: Url) -> Result<reqwest::Response, Error> {
+        let response = self.authorize(self.client.get(url.clone())).send().await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            error!("Not found: {}", url);
+            return Err(Error::NotExisted(url.to_string()));
+        }
+        Ok(response.error_for_status()?)
+    }
+}
+
+#[async_trait]
+impl Backend for Gcs {
+    fn location(&self) -> String {
+        format!("gs://{}", self.bucket)
+    }
 
-        // Download objects
-        for object in targets {
-            if sigint.load(std::sync::atomic::Ordering::Relaxed) {
+    async fn list(&self, prefix: &str) -> Result<Vec<Object>, Error> {
+        let mut objects = vec![];
+        let mut page_token: Option<String> = None;
+        loop {
+            let mut url = self.objects_url(None);
+            url.query_pairs_mut().append_pair("prefix", prefix);
+            if let Some(token) = &page_token {
+                url.query_pairs_mut().append_pair("pageToken", token);
+            }
+            let page: GcsObjects = serde_json::from_slice(&self.get(url).await?.bytes().await?)?;
+            for item in page.items {
+                debug!("Found {}", item.name);
+                objects.push(Object {
+                    size: item.size.and_then(|s| s.parse().ok()),
+                    last_modified: item
+                        .updated
+                        .and_then(|t| DateTime::parse_from_rfc3339(&t).ok())
+                        .map(|t| t.with_timezone(&Utc)),
+                    etag: item.etag,
+                    name: item.name,
+                });
+            }
+            page_token = page.next_page_token;
+            if page_token.is_none() {
                 break;
             }
-            let obj_file = object.split('/').last().unwrap();
-            let obj_file_path = local_path.join(obj_file);
-            info!("Downloading: {}", obj_file);
-            self.download_object(bucket, object, &obj_file_path).await?;
         }
+        Ok(objects)
+    }
+
+    async fn download_object(
+        &self,
+        object: &str,
+        local_path: &Path,
+        progress: &dyn ProgressSink,
+        sigint: &Arc<AtomicBool>,
+    ) -> Result<(), Error> {
+        let mut url = self.objects_url(Some(object));
+        url.query_pairs_mut().append_pair("alt", "media");
+        let mut response = self.get(url).await?;
+        write_body(
+            &mut response,
+            &mut fs::File::create(local_path)?,
+            object,
+            progress,
+            sigint,
+        )
+        .await
+    }
+
+    /// Upload in parts with a resumable upload session.
+    async fn upload(&self, local_path: &Path, object: &str) -> Result<(), Error> {
+        let mut url = self.endpoint.clone();
+        url.path_segments_mut().unwrap().pop_if_empty().extend([
+            "upload",
+            "storage",
+            "v1",
+            "b",
+            &self.bucket,
+            "o",
+        ]);
+        url.query_pairs_mut()
+            .append_pair("uploadType", "resumable")
+            .append_pair("name", object);
+        let response = self
+            .authorize(self.client.post(url))
+            .header(reqwest::header::CONTENT_LENGTH, 0)
+            .send()
+            .await?
+            .error_for_status()?;
+        let session = response
+            .headers()
+            .get(reqwest::header::LOCATION)
+            .and_then(|l| l.to_str().ok())
+            .ok_or(Error::InvalidData("No upload session URL".to_string()))?
+            .to_string();
 
+        let mut file = fs::File::open(local_path)?;
+        let size = file.metadata()?.len();
+        let mut offset = 0;
+        loop {
+            let part = read_part(&mut file)?;
+            let end = offset + part.len() as u64;
+            let range = match part.is_empty() {
+                true => format!("bytes */{}", size),
+                false => format!("bytes {}-{}/{}", offset, end - 1, size),
+            };
+            let response = self
+                .authorize(self.client.put(&session))
+                .header(reqwest::header::CONTENT_RANGE, range)
+                .body(part)
+                .send()
+                .await?;
+            // Parts before the last are acknowledged with 308
+            if response.status().as_u16() != 308 {
+                response.error_for_status()?;
+            }
+            offset = end;
+            if offset >= size {
+                break;
+            }
+        }
         Ok(())
     }
+}
 
-    pub async fn download_object(
+/// Azure Blob Storage container.
+///
+/// The account and the credentials are read from `AZURE_STORAGE_CONNECTION_STRING`, or from
+/// `AZURE_STORAGE_ACCOUNT` with an optional SAS token in `AZURE_STORAGE_SAS_TOKEN`. Without any
+/// credential the container should allow public read access.
+#[derive(Debug)]
+pub struct Azure {
+    client: reqwest::Client,
+    account: String,
+    // Container URL, like `https://account.blob.core.windows.net/container`
+    container_url: Url,
+    auth: AzureAuth,
+}
+
+#[derive(Debug)]
+enum AzureAuth {
+    // Decoded account key, requests are signed with it
+    SharedKey(Vec<u8>),
+    // Query string of the shared access signature
+    Sas(String),
+    Anonymous,
+}
+
+impl Azure {
+    const VERSION: &'static str = "2021-08-06";
+
+    #[allow(clippy::result_large_err)]
+    pub fn new(container: &str) -> Result<Self, Error> {
+        let (account, endpoint, auth) = match std::env::var("AZURE_STORAGE_CONNECTION_STRING") {
+            Ok(connection) => Self::parse_connection_string(&connection)?,
+            Err(_) => {
+                let account = std::env::var("AZURE_STORAGE_ACCOUNT").map_err(|_| {
+                    Error::NotExisted("Environment variable `AZURE_STORAGE_ACCOUNT`".to_string())
+                })?;
+                let endpoint = format!("https://{}.blob.core.windows.net", account);
+                let auth = match std::env::var("AZURE_STORAGE_SAS_TOKEN") {
+                    Ok(sas) => AzureAuth::Sas(sas.trim_start_matches('?').to_string()),
+                    Err(_) => AzureAuth::Anonymous,
+                };
+                (account, endpoint, auth)
+            }
+        };
+        let mut container_url = Url::parse(&endpoint)?;
+        container_url
+            .path_segments_mut()
+            .unwrap()
+            .pop_if_empty()
+            .push(container);
+        Ok(Self {
+            client: reqwest::Client::new(),
+            account,
+            container_url,
+            auth,
+        })
+    }
+
+    /// Account name, blob endpoint and credential of the connection string.
+    #[allow(clippy::result_large_err)]
+    fn parse_connection_string(connection: &str) -> Result<(String, String, AzureAuth), Error> {
+        let fields: HashMap<&str, &str> = connection
+            .split(';')
+            .filter_map(|field| field.split_once('='))
+            .collect();
+        let account = fields
+            .get("AccountName")
+            .ok_or(Error::NotExisted(
+                "AccountName in connection string".to_string(),
+            ))?
+            .to_string();
+        let endpoint = match fields.get("BlobEndpoint") {
+            Some(endpoint) => endpoint.to_string(),
+            None => format!(
+                "{}://{}.blob.{}",
+                fields.get("DefaultEndpointsProtocol").unwrap_or(&"https"),
+                account,
+                fields.get("EndpointSuffix").unwrap_or(&"core.windows.net")
+            ),
+        };
+        let auth = if let Some(key) = fields.get("AccountKey") {
+            let key = BASE64
+                .decode(key)
+                .map_err(|e| Error::InvalidData(e.to_string()))?;
+            AzureAuth::SharedKey(key)
+        } else if let Some(sas) = fields.get("SharedAccessSignature") {
+            AzureAuth::Sas(sas.to_string())
+        } else {
+            AzureAuth::Anonymous
+        };
+        Ok((account, endpoint, auth))
+    }
+
+    /// URL of the container, or of the blob if named.
+    fn blob_url(&self, blob: Option<&str>) -> Url {
+        let mut url = self.container_url.clone();
+        if let Some(blob) = blob {
+            url.path_segments_mut().unwrap().extend(blob.split('/'));
+        }
+        url
+    }
+
+    async fn get(&self, url: Url) -> Result<reqwest::Response, Error> {
+        self.send(reqwest::Method::GET, url, &[], Vec::new()).await
+    }
+
+    /// Send the request with the `x-ms-*` headers and the body, signed or with the SAS token.
+    async fn send(
+        &self,
+        method: reqwest::Method,
+        mut url: Url,
+        headers: &[(&str, &str)],
+        body: Vec<u8>,
+    ) -> Result<reqwest::Response, Error> {
+        if let AzureAuth::Sas(sas) = &self.auth {
+            let query = match url.query() {
+                Some(query) => format!("{}&{}", query, sas),
+                None => sas.clone(),
+            };
+            url.set_query(Some(&query));
+        }
+        let date = chrono::Utc::now()
+            .format("%a, %d %b %Y %H:%M:%S GMT")
+            .to_string();
+        let mut headers = headers.to_vec();
+        headers.extend([
+            ("x-ms-date", date.as_str()),
+            ("x-ms-version", Self::VERSION),
+        ]);
+        headers.sort();
+        let mut request = self.client.request(method.clone(), url.clone());
+        for (name, value) in headers.iter() {
+            request = request.header(*name, *value);
+        }
+        if let AzureAuth::SharedKey(key) = &self.auth {
+            let signature = self.sign(key, &method, &url, &headers, body.len());
+            request = request.header(
+                "Authorization",
+                format!("SharedKey {}:{}", self.account, signature),
+            );
+        }
+        if !body.is_empty() || method != reqwest::Method::GET {
+            request = request
+                .header(reqwest::header::CONTENT_LENGTH, body.len())
+                .body(body);
+        }
+        let response = request.send().await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            error!("Not found: {}", self.blob_url(None));
+            return Err(Error::NotExisted(self.blob_url(None).to_string()));
+        }
+        Ok(response.error_for_status()?)
+    }
+
+    /// Shared key signature of a request, with the sorted `x-ms-*` headers and the length of the
+    /// body. Other standard headers are not sent.
+    fn sign(
+        &self,
+        key: &[u8],
+        method: &reqwest::Method,
+        url: &Url,
+        headers: &[(&str, &str)],
+        length: usize,
+    ) -> String {
+        let mut query: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for (name, value) in url.query_pairs() {
+            query
+                .entry(name.to_lowercase())
+                .or_default()
+                .push(value.into_owned());
+        }
+        let mut resource = format!("/{}{}", self.account, url.path());
+        for (name, values) in query {
+            resource.push_str(&format!("\n{}:{}", name, values.join(",")));
+        }
+        // Content-Length is left empty for requests without body
+        let length = match length {
+            0 => String::new(),
+            length => length.to_string(),
+        };
+        let headers: String = headers
+            .iter()
+            .map(|(name, value)| format!("{}:{}\n", name, value))
+            .collect();
+        let string_to_sign = format!(
+            "{}\n\n\n{}\n\n\n\n\n\n\n\n\n{}{}",
+            method, length, headers, resource
+        );
+        let mut mac = Hmac::<Sha256>::new_from_slice(key).unwrap();
+        mac.update(string_to_sign.as_bytes());
+        BASE64.encode(mac.finalize().into_bytes())
+    }
+}
+
+#[async_trait]
+impl Backend for Azure {
+    fn location(&self) -> String {
+        self.container_url.to_string()
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<Object>, Error> {
+        let mut objects = vec![];
+        let mut marker = String::new();
+        loop {
+            let mut url = self.blob_url(None);
+            url.query_pairs_mut()
+                .append_pair("restype", "container")
+                .append_pair("comp", "list")
+                .append_pair("prefix", prefix);
+            if !marker.is_empty() {
+                url.query_pairs_mut().append_pair("marker", &marker);
+            }
+            let body = self.get(url).await?.bytes().await?;
+            let root = xmltree::Element::parse(body.as_ref())
+                .map_err(|e| Error::InvalidData(e.to_string()))?;
+            let blobs = root.get_child("Blobs").map(|b| b.children.as_slice());
+            for blob in blobs
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|b| b.as_element())
+            {
+                let Some(name) = blob.get_child("Name").and_then(|n| n.get_text()) else {
+                    continue;
+                };
+                debug!("Found {}", name);
+                let property = |key: &str| {
+                    blob.get_child("Properties")
+                        .and_then(|p| p.get_child(key))
+                        .and_then(|v| v.get_text())
+                };
+                objects.push(Object {
+                    name: name.into_owned(),
+                    size: property("Content-Length").and_then(|s| s.parse().ok()),
+                    last_modified: property("Last-Modified")
+                        .and_then(|t| DateTime::parse_from_rfc2822(&t).ok())
+                        .map(|t| t.with_timezone(&Utc)),
+                    etag: property("Etag").map(|e| e.into_owned()),
+                });
+            }
+            marker = root
+                .get_child("NextMarker")
+                .and_then(|m| m.get_text())
+                .map(|m| m.into_owned())
+                .unwrap_or_default();
+            if marker.is_empty() {
+                break;
+            }
+        }
+        Ok(objects)
+    }
+
+    async fn download_object(
         &self,
-        bucket: &str,
         object: &str,
-        local_path: &PathBuf,
+        local_path: &Path,
+        progress: &dyn ProgressSink,
+        sigint: &Arc<AtomicBool>,
     ) -> Result<(), Error> {
-        // Check bucket exist or not.
-        let exists: bool = self
-            .client
-            .bucket_exists(&BucketExistsArgs::new(bucket)?)
+        let mut response = self.get(self.blob_url(Some(object))).await?;
+        write_body(
+            &mut response,
+            &mut fs::File::create(local_path)?,
+            object,
+            progress,
+            sigint,
+        )
+        .await
+    }
+
+    /// Upload in blocks, committed with a block list at the end.
+    async fn upload(&self, local_path: &Path, object: &str) -> Result<(), Error> {
+        let mut file = fs::File::open(local_path)?;
+        let mut block_ids = vec![];
+        loop {
+            let part = read_part(&mut file)?;
+            if part.is_empty() {
+                break;
+            }
+            // IDs of the blocks should have the same length
+            let block_id = BASE64.encode(format!("{:08}", block_ids.len()));
+            let mut url = self.blob_url(Some(object));
+            url.query_pairs_mut()
+                .append_pair("comp", "block")
+                .append_pair("blockid", &block_id);
+            self.send(reqwest::Method::PUT, url, &[], part).await?;
+            block_ids.push(block_id);
+        }
+        let block_list: String = block_ids
+            .iter()
+            .map(|id| format!("<Latest>{}</Latest>", id))
+            .collect();
+        let body = format!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?><BlockList>{}</BlockList>",
+            block_list
+        );
+        let mut url = self.blob_url(Some(object));
+        url.query_pairs_mut().append_pair("comp", "blocklist");
+        self.send(reqwest::Method::PUT, url, &[], body.into_bytes())
             .await?;
-        if !exists {
-            error!("Bucket {} does not exist.", bucket);
-            return Err(Error::NotExisted(bucket.to_string()));
+        Ok(())
+    }
+}
+
+/// File served over plain HTTP(S), like a web share. Dropped connections are resumed with ranged
+/// requests from where they stopped, and a complete local file is not downloaded again.
+#[derive(Debug)]
+pub struct Http {
+    client: reqwest::Client,
+    url: Url,
+}
+
+impl Http {
+    // Attempts to continue a dropped download
+    const RETRIES: u32 = 5;
+
+    #[allow(clippy::result_large_err)]
+    pub fn new(url: &str) -> Result<Self, Error> {
+        Ok(Self {
+            client: reqwest::Client::new(),
+            url: Url::parse(url)?,
+        })
+    }
+}
+
+#[async_trait]
+impl Backend for Http {
+    fn location(&self) -> String {
+        self.url.to_string()
+    }
+
+    /// The file itself, there is nothing to list. Metadata is taken from the headers if the
+    /// server answers HEAD requests.
+    async fn list(&self, _prefix: &str) -> Result<Vec<Object>, Error> {
+        let mut object = Object::named(self.url.path());
+        if let Ok(response) = self.client.head(self.url.clone()).send().await {
+            let headers = response.headers();
+            let header = |name| headers.get(name).and_then(|v| v.to_str().ok());
+            if response.status().is_success() {
+                object.size = header(reqwest::header::CONTENT_LENGTH).and_then(|s| s.parse().ok());
+                object.last_modified = header(reqwest::header::LAST_MODIFIED)
+                    .and_then(|t| DateTime::parse_from_rfc2822(t).ok())
+                    .map(|t| t.with_timezone(&Utc));
+                object.etag = header(reqwest::header::ETAG).map(str::to_string);
+            }
         }
+        Ok(vec![object])
+    }
 
-        // Download object
-        let obj_dscp = ObjectConditionalReadArgs::new(bucket, object)?;
-        let response = self.client.get_object(&obj_dscp).await?;
-        if response.status().is_success() {
-            fs::write(
-                local_path,
-                response.bytes().await.map_err(|e| Error::RequestError(e))?,
-            )?;
+    async fn download_object(
+        &self,
+        object: &str,
+        local_path: &Path,
+        progress: &dyn ProgressSink,
+        sigint: &Arc<AtomicBool>,
+    ) -> Result<(), Error> {
+        // Ranges are only served of the version listed, otherwise the whole file is sent. Weak
+        // ETags could not tell versions apart byte by byte.
+        let listed = self.list("").await?.remove(0);
+        let validator = listed
+            .etag
+            .filter(|etag| !etag.starts_with("W/"))
+            .or_else(|| {
+                listed
+                    .last_modified
+                    .map(|t| t.format("%a, %d %b %Y %H:%M:%S GMT").to_string())
+            });
+        let mut retries = 0;
+        loop {
+            let offset = fs::metadata(local_path).map_or(0, |m| m.len());
+            let mut request = self.client.get(self.url.clone());
+            if offset > 0 {
+                request = request.header(reqwest::header::RANGE, format!("bytes={}-", offset));
+                if let Some(validator) = &validator {
+                    request = request.header(reqwest::header::IF_RANGE, validator);
+                }
+            }
+            let mut response = request.send().await?;
+            let mut file = match response.status() {
+                // Nothing left beyond the local file
+                reqwest::StatusCode::RANGE_NOT_SATISFIABLE => return Ok(()),
+                reqwest::StatusCode::PARTIAL_CONTENT => {
+                    info!("Resuming from byte {}", offset);
+                    // Bytes of earlier attempts are reported already
+                    if retries == 0 {
+                        progress.on_download(object, offset);
+                    }
+                    fs::OpenOptions::new().append(true).open(local_path)?
+                }
+                reqwest::StatusCode::NOT_FOUND => {
+                    error!("Not found: {}", self.url);
+                    return Err(Error::NotExisted(self.url.to_string()));
+                }
+                // Ranges not supported, or the file changed since, start over
+                _ => {
+                    response = response.error_for_status()?;
+                    fs::File::create(local_path)?
+                }
+            };
+            match write_body(&mut response, &mut file, object, progress, sigint).await {
+                Err(Error::RequestError(e)) if retries < Self::RETRIES => {
+                    retries += 1;
+                    warn!(
+                        "Download interrupted, retrying ({}/{}). {}",
+                        retries,
+                        Self::RETRIES,
+                        e
+                    );
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Files on a host reachable over SSH, read with the `sftp` command in batch mode. Passwords could
+/// not be prompted, the host should accept a key of `~/.ssh` or the SSH agent.
+#[derive(Debug)]
+pub struct Sftp {
+    // Destination of sftp, like `user@host`
+    destination: String,
+    port: Option<u16>,
+}
+
+impl Sftp {
+    pub fn new(destination: &str, port: Option<u16>) -> Self {
+        Self {
+            destination: destination.to_owned(),
+            port,
+        }
+    }
+
+    /// Run the batch of sftp commands, returning the output.
+    #[allow(clippy::result_large_err)]
+    fn run(&self, batch: &str) -> Result<String, Error> {
+        let mut command = Command::new("sftp");
+        command.args(["-q", "-o", "BatchMode=yes", "-b", "-"]);
+        if let Some(port) = self.port {
+            command.args(["-P", &port.to_string()]);
+        }
+        let mut child = command
+            .arg(&self.destination)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| {
+                Error::CommandError(format!("Failed to run sftp, is it installed? {}", e))
+            })?;
+        child.stdin.take().unwrap().write_all(batch.as_bytes())?;
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            return Err(Error::CommandError(format!(
+                "sftp exited with {}. {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+/// Path quoted for sftp batch commands.
+fn sftp_quote(path: &str) -> String {
+    format!("\"{}\"", path.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Name in a line of `ls -l`, after the mode, links, owner, group, size and date fields.
+fn long_listing_name(line: &str) -> Option<&str> {
+    let mut rest = line;
+    for _ in 0..8 {
+        rest = rest.trim_start();
+        rest = &rest[rest.find(char::is_whitespace)?..];
+    }
+    Some(rest.trim_start())
+}
+
+#[async_trait]
+impl Backend for Sftp {
+    fn location(&self) -> String {
+        match self.port {
+            Some(port) => format!("sftp://{}:{}", self.destination, port),
+            None => format!("sftp://{}", self.destination),
+        }
+    }
+
+    /// Regular files in the directory, or the file itself.
+    async fn list(&self, prefix: &str) -> Result<Vec<Object>, Error> {
+        let output = self.run(&format!("ls -l {}\n", sftp_quote(prefix)))?;
+        let mut objects = vec![];
+        for line in output.lines().filter(|l| l.starts_with('-')) {
+            if let Some(name) = long_listing_name(line) {
+                debug!("Found {}", name);
+                objects.push(Object {
+                    size: line.split_whitespace().nth(4).and_then(|s| s.parse().ok()),
+                    ..Object::named(name)
+                });
+            }
         }
+        Ok(objects)
+    }
+
+    async fn download_object(
+        &self,
+        object: &str,
+        local_path: &Path,
+        progress: &dyn ProgressSink,
+        _sigint: &Arc<AtomicBool>,
+    ) -> Result<(), Error> {
+        let local_file = local_path
+            .to_str()
+            .ok_or(Error::InvalidData(local_path.display().to_string()))?;
+        self.run(&format!(
+            "get {} {}\n",
+            sftp_quote(object),
+            sftp_quote(local_file)
+        ))?;
+        // Progress is not known until sftp is done
+        progress.on_download(object, fs::metadata(local_path)?.len());
+        Ok(())
+    }
+
+    async fn upload(&self, local_path: &Path, object: &str) -> Result<(), Error> {
+        let local_path = local_path
+            .to_str()
+            .ok_or(Error::InvalidData(local_path.display().to_string()))?;
+        self.run(&format!(
+            "put {} {}\n",
+            sftp_quote(local_path),
+            sftp_quote(object)
+        ))?;
         Ok(())
     }
 }
+
+/// Object of a backend read with ranged requests, so that parts of a large recording could be read
+/// without downloading all of it. Reads block the current thread of the async runtime.
+pub struct RangeReader<'a> {
+    backend: &'a dyn Backend,
+    object: String,
+    size: u64,
+    position: u64,
+    runtime: tokio::runtime::Handle,
+
+    // The latest block read and its offset in the object
+    block: (u64, Vec<u8>),
+}
+
+impl<'a> RangeReader<'a> {
+    // Reads smaller than this are read ahead, as requests are expensive
+    const READ_AHEAD: u64 = 1 << 20;
+
+    /// Open the object. Should be called inside a multi-threaded tokio runtime.
+    pub async fn new(backend: &'a dyn Backend, object: &str) -> Result<Self, Error> {
+        Ok(Self {
+            backend,
+            object: object.to_owned(),
+            size: backend.object_size(object).await?,
+            position: 0,
+            runtime: tokio::runtime::Handle::current(),
+            block: (0, Vec::new()),
+        })
+    }
+}
+
+impl Read for RangeReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position >= self.size || buf.is_empty() {
+            return Ok(0);
+        }
+        let (start, data) = &self.block;
+        if self.position < *start || self.position >= start + data.len() as u64 {
+            let length = (buf.len() as u64)
+                .max(Self::READ_AHEAD)
+                .min(self.size - self.position);
+            let data = tokio::task::block_in_place(|| {
+                self.runtime
+                    .block_on(self.backend.read_range(&self.object, self.position, length))
+                    .map_err(io::Error::other)
+            })?;
+            if data.is_empty() {
+                return Ok(0);
+            }
+            self.block = (self.position, data);
+        }
+        let (start, data) = &self.block;
+        let available = &data[(self.position - start) as usize..];
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.position += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for RangeReader<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.size.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+        self.position = position.ok_or(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Seek before the start of the object",
+        ))?;
+        Ok(self.position)
+    }
+}
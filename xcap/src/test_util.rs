@@ -0,0 +1,47 @@
+//! Helpers shared by the unit tests.
+use ros2_sensor_msgs::msg::{PointCloud2, PointField};
+use ros2_std_msgs::msg::Header;
+use std::{fs, path::PathBuf};
+
+/// Empty directory for the files of the test, cleared if left by an earlier run.
+pub fn dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("xcap-test-{}-{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Header stamped at 1 second in the `lidar` frame.
+pub fn header() -> Header {
+    let mut data = vec![0x00, 0x01, 0x00, 0x00];
+    data.extend_from_slice(&1i32.to_le_bytes());
+    data.extend_from_slice(&0u32.to_le_bytes());
+    data.extend_from_slice(&6u32.to_le_bytes());
+    data.extend_from_slice(b"lidar\0");
+    cdr::deserialize(&data).unwrap()
+}
+
+pub fn field(name: &str, offset: u32, datatype: u8, count: u32) -> PointField {
+    PointField {
+        name: name.to_owned(),
+        offset,
+        datatype,
+        count,
+    }
+}
+
+/// Unordered cloud of the points in native endianness.
+pub fn cloud(fields: Vec<PointField>, point_step: u32, data: Vec<u8>) -> PointCloud2 {
+    let width = data.len() as u32 / point_step;
+    PointCloud2 {
+        header: header(),
+        height: 1,
+        width,
+        fields,
+        is_bigendian: cfg!(target_endian = "big") as u8,
+        point_step,
+        row_step: width * point_step,
+        data,
+        is_dense: 1,
+    }
+}
@@ -0,0 +1,229 @@
+use crate::depth::Intrinsics;
+use crate::extractor::Extractor;
+use mcap::Message;
+use rerun::{external::glam, RecordingStream};
+use ros2_tf2_msgs::msg::TFMessage;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::{atomic::AtomicBool, Arc, Mutex},
+};
+
+// Guard against cycles in broken transform trees.
+const MAX_TREE_DEPTH: usize = 64;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Payload error. {0}")]
+    Payload(#[from] crate::extractor::DecodeError),
+    #[error("CDR error. {0}")]
+    Cdr(#[from] cdr::Error),
+}
+
+/// The transform tree, shared by all parsers so that data could be logged in the frame it
+/// belongs to.
+#[derive(Debug, Default)]
+pub struct FrameTree {
+    // Child frame to parent frame
+    parents: HashMap<String, String>,
+
+    // Frames with a pinhole camera model logged
+    cameras: HashSet<String>,
+
+    // Topic to the entity path its images were logged at last
+    images: BTreeMap<String, String>,
+
+    // Child frame to its latest transform in the parent frame
+    transforms: HashMap<String, glam::Affine3A>,
+
+    // Camera frame to its latest intrinsics
+    intrinsics: HashMap<String, Intrinsics>,
+}
+
+impl FrameTree {
+    pub fn shared() -> Arc<Mutex<Self>> {
+        Arc::new(Mutex::new(Self::default()))
+    }
+
+    pub fn insert(&mut self, parent: &str, child: &str) {
+        self.parents
+            .insert(frame_name(child).to_owned(), frame_name(parent).to_owned());
+    }
+
+    /// Set the latest transform of the child frame in its parent frame.
+    pub fn set_transform(&mut self, child: &str, transform: glam::Affine3A) {
+        self.transforms
+            .insert(frame_name(child).to_owned(), transform);
+    }
+
+    /// Root of the frame and the pose of the frame in the root. Return None if any transform on
+    /// the way is unknown.
+    fn pose<'a>(&'a self, frame: &'a str) -> Option<(&'a str, glam::Affine3A)> {
+        let mut frame = frame_name(frame);
+        let mut pose = glam::Affine3A::IDENTITY;
+        for _ in 0..MAX_TREE_DEPTH {
+            let Some(parent) = self.parents.get(frame) else {
+                return Some((frame, pose));
+            };
+            pose = *self.transforms.get(frame)? * pose;
+            frame = parent;
+        }
+        None
+    }
+
+    /// Transform from the `from` frame to the `to` frame with the latest transforms. Return None
+    /// if the frames are not connected.
+    pub fn transform(&self, from: &str, to: &str) -> Option<glam::Affine3A> {
+        let (from_root, from_pose) = self.pose(from)?;
+        let (to_root, to_pose) = self.pose(to)?;
+        (from_root == to_root).then(|| to_pose.inverse() * from_pose)
+    }
+
+    /// Rerun entity path of the frame, following the tree from the root. Return None if the
+    /// frame is unknown.
+    pub fn entity_path(&self, frame: &str) -> Option<String> {
+        let mut frame = frame_name(frame);
+        if !self.parents.contains_key(frame) && !self.parents.values().any(|p| p == frame) {
+            return None;
+        }
+        let mut chain = vec![frame.replace('/', "_")];
+        while let Some(parent) = self.parents.get(frame) {
+            if chain.len() >= MAX_TREE_DEPTH {
+                break;
+            }
+            chain.push(parent.replace('/', "_"));
+            frame = parent;
+        }
+        chain.reverse();
+        Some(format!("tf/{}", chain.join("/")))
+    }
+
+    /// Register a pinhole camera in the frame and return the entity path of the camera.
+    pub fn add_camera(&mut self, frame: &str) -> String {
+        self.cameras.insert(frame_name(frame).to_owned());
+        self.camera_entity_path(frame)
+    }
+
+    /// Set the latest intrinsics of the camera in the frame.
+    pub fn set_intrinsics(&mut self, frame: &str, intrinsics: Intrinsics) {
+        self.intrinsics
+            .insert(frame_name(frame).to_owned(), intrinsics);
+    }
+
+    /// Latest intrinsics of the camera in the frame, if any camera info is seen.
+    pub fn intrinsics(&self, frame: &str) -> Option<Intrinsics> {
+        self.intrinsics.get(frame_name(frame)).copied()
+    }
+
+    /// Rerun entity path for images of the topic. Images are logged under the camera of their
+    /// frame if there is one, so that they are projected into the 3D view.
+    pub fn image_entity_path(&mut self, frame: &str, topic: &str) -> String {
+        let path = if self.cameras.contains(frame_name(frame)) {
+            format!("{}/image/{}", self.camera_entity_path(frame), topic)
+        } else {
+            format!("image/{}", topic)
+        };
+        self.images.insert(topic.to_owned(), path.clone());
+        path
+    }
+
+    /// Entity paths of images logged so far, by topic.
+    pub fn images(&self) -> &BTreeMap<String, String> {
+        &self.images
+    }
+
+    fn camera_entity_path(&self, frame: &str) -> String {
+        match self.entity_path(frame) {
+            Some(frame_path) => format!("{}/camera", frame_path),
+            None => format!("camera/{}", frame_name(frame).replace('/', "_")),
+        }
+    }
+}
+
+/// Frame names with or without the leading slash are the same frame.
+fn frame_name(frame: &str) -> &str {
+    frame.trim_start_matches('/')
+}
+
+pub struct Parser {
+    // Visualizer with rerun
+    rec_stream: Option<RecordingStream>,
+
+    // Transform tree shared with other parsers
+    frame_tree: Arc<Mutex<FrameTree>>,
+
+    // Scratch buffer for decoded message payloads, reused across messages
+    buffer: Vec<u8>,
+}
+
+impl Parser {
+    pub fn new(rerun_stream: Option<RecordingStream>, frame_tree: Arc<Mutex<FrameTree>>) -> Self {
+        Parser {
+            rec_stream: rerun_stream,
+            frame_tree,
+            buffer: Vec::new(),
+        }
+    }
+}
+
+impl Extractor for Parser {
+    type ExtractorError = Box<dyn std::error::Error + Send + Sync>;
+
+    fn buffer(&mut self) -> &mut Vec<u8> {
+        &mut self.buffer
+    }
+
+    fn step(&mut self, message: &Message) -> Result<(), Self::ExtractorError> {
+        let payload = self.decode_into(message).map_err(Error::Payload)?;
+        let tf = cdr::deserialize_from::<_, TFMessage, _>(payload, cdr::size::Infinite)
+            .map_err(Error::Cdr)?;
+        let is_static = message.channel.topic.ends_with("tf_static");
+
+        let mut tree = self.frame_tree.lock().unwrap();
+        for transform in tf.transforms.iter() {
+            tree.insert(&transform.header.frame_id, &transform.child_frame_id);
+            let (t, q) = (
+                &transform.transform.translation,
+                &transform.transform.rotation,
+            );
+            tree.set_transform(
+                &transform.child_frame_id,
+                glam::Affine3A::from_rotation_translation(
+                    glam::Quat::from_xyzw(q.x as f32, q.y as f32, q.z as f32, q.w as f32),
+                    glam::vec3(t.x as f32, t.y as f32, t.z as f32),
+                ),
+            );
+        }
+
+        let Some(rec) = &self.rec_stream else {
+            return Ok(());
+        };
+        for transform in tf.transforms.iter() {
+            let Some(path) = tree.entity_path(&transform.child_frame_id) else {
+                continue;
+            };
+            let (t, q) = (
+                &transform.transform.translation,
+                &transform.transform.rotation,
+            );
+            let transform3d = rerun::Transform3D::from_translation_rotation(
+                [t.x as f32, t.y as f32, t.z as f32],
+                rerun::Quaternion::from_xyzw([q.x as f32, q.y as f32, q.z as f32, q.w as f32]),
+            );
+            if is_static {
+                rec.log_static(path, &transform3d)?;
+            } else {
+                rec.set_time_seconds(
+                    "main",
+                    transform.header.stamp.sec as f64
+                        + transform.header.stamp.nanosec as f64 * 1e-9,
+                );
+                rec.log(path, &transform3d)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn post_process(&mut self, _sigint: Arc<AtomicBool>) -> Result<(), Self::ExtractorError> {
+        Ok(())
+    }
+}
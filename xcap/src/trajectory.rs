@@ -0,0 +1,91 @@
+//! Poses of the vehicle over time, read from an odometry topic ahead of the extraction. Used to
+//! move points measured at different times into a common frame.
+use crate::session::McapSession;
+use rerun::external::glam;
+use ros2_nav_msgs::msg::Odometry;
+use std::path::PathBuf;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Failed to read odometry. {0}")]
+    Read(Box<crate::Error>),
+    #[error("No odometry message on topic {0}")]
+    Empty(String),
+}
+
+/// Poses of the child frame of an odometry topic in its parent frame, sorted by stamp.
+#[derive(Debug, Clone)]
+pub struct Trajectory {
+    // Fixed frame of the poses, like `odom`
+    pub frame: String,
+    // Frame moving along the trajectory, like `base_link`
+    pub child_frame: String,
+    // Stamps in nanoseconds, and the poses at them
+    stamps: Vec<u64>,
+    poses: Vec<glam::Affine3A>,
+}
+
+impl Trajectory {
+    /// Read all messages of the odometry topic in the files.
+    pub fn load(files: &[PathBuf], topic: &str) -> Result<Self, Error> {
+        let session = McapSession::open(files).map_err(|e| Error::Read(Box::new(e)))?;
+        let mut samples: Vec<(u64, glam::Affine3A)> = vec![];
+        let (mut frame, mut child_frame) = (String::new(), String::new());
+        let messages = session
+            .read_topic::<Odometry>(topic)
+            .map_err(|e| Error::Read(Box::new(e)))?;
+        for message in messages {
+            let (_, odom) = message.map_err(|e| Error::Read(Box::new(e)))?;
+            let (p, q) = (&odom.pose.pose.position, &odom.pose.pose.orientation);
+            let stamp =
+                odom.header.stamp.sec as u64 * 1_000_000_000 + odom.header.stamp.nanosec as u64;
+            samples.push((
+                stamp,
+                glam::Affine3A::from_rotation_translation(
+                    glam::Quat::from_xyzw(q.x as f32, q.y as f32, q.z as f32, q.w as f32)
+                        .normalize(),
+                    glam::vec3(p.x as f32, p.y as f32, p.z as f32),
+                ),
+            ));
+            frame = odom.header.frame_id;
+            child_frame = odom.child_frame_id;
+        }
+        if samples.is_empty() {
+            return Err(Error::Empty(topic.to_owned()));
+        }
+        samples.sort_by_key(|(stamp, _)| *stamp);
+        let (stamps, poses) = samples.into_iter().unzip();
+        Ok(Trajectory {
+            frame,
+            child_frame,
+            stamps,
+            poses,
+        })
+    }
+
+    /// Pose at the stamp in nanoseconds, interpolated between the nearest poses. Return None out
+    /// of the time range of the trajectory.
+    pub fn pose_at(&self, stamp: u64) -> Option<glam::Affine3A> {
+        let after = self.stamps.partition_point(|&s| s < stamp);
+        if after == self.stamps.len() {
+            return None;
+        }
+        if self.stamps[after] == stamp {
+            return Some(self.poses[after]);
+        }
+        let before = after.checked_sub(1)?;
+        let ratio = (stamp - self.stamps[before]) as f32
+            / (self.stamps[after] - self.stamps[before]) as f32;
+        let (r0, t0) = to_rotation_translation(&self.poses[before]);
+        let (r1, t1) = to_rotation_translation(&self.poses[after]);
+        Some(glam::Affine3A::from_rotation_translation(
+            r0.slerp(r1, ratio),
+            t0.lerp(t1, ratio),
+        ))
+    }
+}
+
+fn to_rotation_translation(pose: &glam::Affine3A) -> (glam::Quat, glam::Vec3) {
+    let (_, rotation, translation) = pose.to_scale_rotation_translation();
+    (rotation, translation)
+}
@@ -0,0 +1,255 @@
+//! Integrity checks of the recordings: every record is walked through, CRCs are checked, and the
+//! chunk indexes of the summary are validated against the chunks actually found.
+use crate::{rosbag2, Error};
+use mcap::{
+    read::{parse_record, ChunkReader},
+    records::Record,
+    MAGIC,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, fs,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+// Opcode and length of each record
+const RECORD_HEADER_LEN: usize = 1 + 8;
+
+/// A problem found in a file.
+#[derive(Debug)]
+pub struct Issue {
+    // Offset of the record in bytes, None if not bound to a record
+    pub offset: Option<u64>,
+    pub description: String,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.offset {
+            Some(offset) => write!(f, "at {}: {}", offset, self.description),
+            None => write!(f, "{}", self.description),
+        }
+    }
+}
+
+/// Result of the verification of a file.
+#[derive(Debug, Default)]
+pub struct Report {
+    pub file: PathBuf,
+    pub records: u64,
+    pub chunks: u64,
+    pub messages: u64,
+    pub issues: Vec<Issue>,
+}
+
+impl Report {
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    fn issue(&mut self, offset: Option<usize>, description: String) {
+        self.issues.push(Issue {
+            offset: offset.map(|o| o as u64),
+            description,
+        });
+    }
+}
+
+// Chunk found in the data section
+struct ChunkFound {
+    length: u64,
+    message_start_time: u64,
+    message_end_time: u64,
+}
+
+/// Verify the MCAP or rosbag2 file. Corruptions are reported in the returned report, while
+/// errors are returned only if the file could not be read at all.
+pub fn verify(file: &Path, sigint: &AtomicBool) -> Result<Report, Error> {
+    let mut report = Report {
+        file: file.to_path_buf(),
+        ..Default::default()
+    };
+    if rosbag2::is_db3(file) {
+        verify_db3(file, &mut report);
+        return Ok(report);
+    }
+    let fd = fs::File::open(file)?;
+    let mmap = unsafe { memmap2::Mmap::map(&fd)? };
+    verify_mcap(&mmap, &mut report, sigint)?;
+    Ok(report)
+}
+
+fn verify_db3(file: &Path, report: &mut Report) {
+    match rosbag2::Reader::open(file) {
+        Ok(reader) => {
+            report.messages = reader.topics().map(|t| t.msg_count).sum();
+            match reader.integrity_check() {
+                Ok(problems) => problems
+                    .into_iter()
+                    .for_each(|p| report.issue(None, format!("SQLite integrity: {}", p))),
+                Err(e) => report.issue(None, format!("SQLite integrity check failed. {}", e)),
+            }
+        }
+        Err(e) => report.issue(None, format!("Failed to open the database. {}", e)),
+    }
+}
+
+fn verify_mcap(buf: &[u8], report: &mut Report, sigint: &AtomicBool) -> Result<(), Error> {
+    if !buf.starts_with(MAGIC) {
+        report.issue(Some(0), "Bad magic, not a MCAP file".to_string());
+        return Ok(());
+    }
+
+    let mut chunks: HashMap<usize, ChunkFound> = HashMap::new();
+    let mut message_indexes: HashSet<usize> = HashSet::new();
+    let mut footer_found = false;
+    let mut truncated = false;
+    let mut pos = MAGIC.len();
+    while pos < buf.len() {
+        if sigint.load(Ordering::Relaxed) {
+            return Err(Error::Interrupted);
+        }
+        if footer_found {
+            break;
+        }
+        if buf.len() - pos < RECORD_HEADER_LEN {
+            report.issue(Some(pos), "Truncated record header".to_string());
+            truncated = true;
+            break;
+        }
+        let opcode = buf[pos];
+        let length = u64::from_le_bytes(buf[pos + 1..pos + RECORD_HEADER_LEN].try_into().unwrap());
+        let available = (buf.len() - pos - RECORD_HEADER_LEN) as u64;
+        if length > available {
+            report.issue(
+                Some(pos),
+                format!(
+                    "Truncated record, opcode {:#04x}, {} of {} bytes present",
+                    opcode, available, length
+                ),
+            );
+            truncated = true;
+            break;
+        }
+        let body = &buf[pos + RECORD_HEADER_LEN..pos + RECORD_HEADER_LEN + length as usize];
+        report.records += 1;
+        match parse_record(opcode, body) {
+            Ok(Record::Chunk { header, data }) => {
+                report.chunks += 1;
+                chunks.insert(
+                    pos,
+                    ChunkFound {
+                        length: RECORD_HEADER_LEN as u64 + length,
+                        message_start_time: header.message_start_time,
+                        message_end_time: header.message_end_time,
+                    },
+                );
+                // Records are decompressed and the CRC of the uncompressed data is checked
+                match ChunkReader::new(header, &data) {
+                    Ok(reader) => {
+                        for record in reader {
+                            match record {
+                                Ok(Record::Message { .. }) => report.messages += 1,
+                                Ok(_) => {}
+                                Err(e) => {
+                                    report.issue(Some(pos), format!("Corrupted chunk. {}", e));
+                                    break;
+                                }
+                            }
+                        }
+                    }
+                    Err(e) => report.issue(Some(pos), format!("Unreadable chunk. {}", e)),
+                }
+            }
+            Ok(Record::Message { .. }) => report.messages += 1,
+            Ok(Record::MessageIndex(_)) => {
+                message_indexes.insert(pos);
+            }
+            Ok(Record::DataEnd(end)) if end.data_section_crc != 0 => {
+                let crc = crc32fast::hash(&buf[..pos]);
+                if crc != end.data_section_crc {
+                    report.issue(
+                        Some(pos),
+                        format!(
+                            "Data section CRC mismatch, saved {:#010x}, calculated {:#010x}",
+                            end.data_section_crc, crc
+                        ),
+                    );
+                }
+            }
+            Ok(Record::Footer(_)) => footer_found = true,
+            Ok(_) => {}
+            Err(e) => report.issue(
+                Some(pos),
+                format!("Corrupted record, opcode {:#04x}. {}", opcode, e),
+            ),
+        }
+        pos += RECORD_HEADER_LEN + length as usize;
+    }
+    if truncated {
+        return Ok(());
+    }
+    if !footer_found {
+        report.issue(Some(pos), "No footer, the file is truncated".to_string());
+        return Ok(());
+    }
+    if &buf[pos..] != MAGIC {
+        report.issue(Some(pos), "Bad magic at the end of the file".to_string());
+        return Ok(());
+    }
+
+    let summary = match mcap::read::Summary::read(buf) {
+        Ok(Some(summary)) => summary,
+        Ok(None) => return Ok(()),
+        Err(e) => {
+            report.issue(None, format!("Corrupted summary. {}", e));
+            return Ok(());
+        }
+    };
+    let mut indexed = HashSet::new();
+    for index in summary.chunk_indexes.iter() {
+        let offset = index.chunk_start_offset as usize;
+        indexed.insert(offset);
+        let Some(chunk) = chunks.get(&offset) else {
+            report.issue(Some(offset), "Chunk index points to no chunk".to_string());
+            continue;
+        };
+        if chunk.length != index.chunk_length
+            || chunk.message_start_time != index.message_start_time
+            || chunk.message_end_time != index.message_end_time
+        {
+            report.issue(
+                Some(offset),
+                "Chunk index does not match the chunk".to_string(),
+            );
+        }
+        if index
+            .message_index_offsets
+            .values()
+            .any(|o| !message_indexes.contains(&(*o as usize)))
+        {
+            report.issue(
+                Some(offset),
+                "Message index offsets of the chunk point to no message index".to_string(),
+            );
+        }
+    }
+    let unindexed = chunks.keys().filter(|o| !indexed.contains(o)).count();
+    if !summary.chunk_indexes.is_empty() && unindexed > 0 {
+        report.issue(None, format!("Chunks not indexed: {}", unindexed));
+    }
+    // Messages of corrupted chunks are not counted
+    if let Some(stats) = summary.stats.filter(|_| report.is_ok()) {
+        if stats.message_count != report.messages {
+            report.issue(
+                None,
+                format!(
+                    "Message count of the statistics is {}, found {}",
+                    stats.message_count, report.messages
+                ),
+            );
+        }
+    }
+    Ok(())
+}
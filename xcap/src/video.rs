@@ -0,0 +1,237 @@
+use bytes::Bytes;
+use mp4::{AvcConfig, MediaConfig, Mp4Config, Mp4Sample, Mp4Writer, TrackConfig, TrackType};
+use openh264::decoder::Decoder;
+use openh264::formats::YUVSource;
+use std::{
+    fs,
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+
+// Timescale of the video track, the de facto standard for H.264 in MP4.
+const TRACK_TIMESCALE: u32 = 90_000;
+
+// Duration of the last sample if it could not be inferred. Assuming 30 fps.
+const DEFAULT_SAMPLE_DURATION: u32 = TRACK_TIMESCALE / 30;
+
+// NAL unit types that matter for muxing.
+const NAL_IDR: u8 = 5;
+const NAL_SPS: u8 = 7;
+const NAL_PPS: u8 = 8;
+const NAL_AUD: u8 = 9;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("MP4 error. {0}")]
+    Mp4(#[from] mp4::Error),
+    #[error("IO error. {0}")]
+    IO(#[from] std::io::Error),
+    #[error("H.264 error. {0}")]
+    H264(#[from] openh264::Error),
+}
+
+struct Sample {
+    // Header stamp in nanoseconds
+    stamp: u64,
+
+    // Contains an IDR frame
+    is_sync: bool,
+
+    // NAL units in AVCC format
+    data: Vec<u8>,
+}
+
+/// Mux an H.264 Annex-B stream into a MP4 file, one access unit per message. Sample times are
+/// taken from the message headers so the video plays back at the recorded pace.
+pub struct H264Muxer {
+    // Output file path
+    path: PathBuf,
+
+    // Parameter sets, required for the track config
+    sps: Option<Vec<u8>>,
+    pps: Option<Vec<u8>>,
+
+    // Decoder used to probe the frame dimensions. Dropped once the writer is ready.
+    probe: Option<Decoder>,
+
+    // Samples received before the writer is ready
+    pending: Vec<Sample>,
+
+    // The last sample, waiting for the next one to get its duration
+    last: Option<Sample>,
+
+    // Stamp of the first sample in nanoseconds
+    first_stamp: Option<u64>,
+
+    // Stamps of all the samples written, in nanoseconds
+    stamps: Vec<u64>,
+
+    writer: Option<Mp4Writer<BufWriter<fs::File>>>,
+}
+
+impl H264Muxer {
+    pub fn new(path: &Path) -> Self {
+        H264Muxer {
+            path: path.into(),
+            sps: None,
+            pps: None,
+            probe: None,
+            pending: Vec::new(),
+            last: None,
+            first_stamp: None,
+            stamps: Vec::new(),
+            writer: None,
+        }
+    }
+
+    /// Append one access unit in Annex-B format, stamped in nanoseconds.
+    pub fn push(&mut self, stamp: u64, packet: &[u8]) -> Result<(), Error> {
+        let mut sample = Sample {
+            stamp,
+            is_sync: false,
+            data: Vec::with_capacity(packet.len()),
+        };
+        for nal in openh264::nal_units(packet) {
+            let nal = strip_start_code(nal);
+            let Some(header) = nal.first() else {
+                continue;
+            };
+            match header & 0x1f {
+                NAL_SPS => self.sps = Some(nal.to_vec()),
+                NAL_PPS => self.pps = Some(nal.to_vec()),
+                NAL_AUD => {}
+                nal_type => {
+                    sample.is_sync |= nal_type == NAL_IDR;
+                    sample
+                        .data
+                        .extend_from_slice(&(nal.len() as u32).to_be_bytes());
+                    sample.data.extend_from_slice(nal);
+                }
+            }
+        }
+
+        if self.writer.is_some() {
+            return match sample.data.is_empty() {
+                true => Ok(()),
+                false => self.write(sample),
+            };
+        }
+
+        // Probe the dimensions by decoding the stream until the first frame shows up. Frames
+        // before the first parameter sets could not be decoded by anyone, drop them.
+        if self.probe.is_none() {
+            self.probe = Some(Decoder::new()?);
+        }
+        let decoder = self.probe.as_mut().unwrap();
+        let dimensions = decoder
+            .decode(packet)
+            .ok()
+            .flatten()
+            .map(|yuv| yuv.dimensions());
+        if self.sps.is_some() && self.pps.is_some() && !sample.data.is_empty() {
+            self.pending.push(sample);
+        }
+        if let Some((width, height)) = dimensions {
+            self.probe = None;
+            self.start(width as u16, height as u16)?;
+            for sample in std::mem::take(&mut self.pending) {
+                self.write(sample)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Path of the video file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Stamps of the samples in the video, in nanoseconds. Each sample is presented at its stamp
+    /// minus the first one.
+    pub fn stamps(&self) -> &[u64] {
+        &self.stamps
+    }
+
+    /// Flush the last sample and finalize the file.
+    pub fn finish(&mut self) -> Result<(), Error> {
+        let Some(mut writer) = self.writer.take() else {
+            return Ok(());
+        };
+        if let Some(last) = self.last.take() {
+            let sample = self.to_mp4_sample(last, DEFAULT_SAMPLE_DURATION);
+            writer.write_sample(1, &sample)?;
+        }
+        writer.write_end()?;
+        Ok(())
+    }
+
+    fn start(&mut self, width: u16, height: u16) -> Result<(), Error> {
+        let config = Mp4Config {
+            major_brand: str::parse("isom").unwrap(),
+            minor_version: 512,
+            compatible_brands: vec![
+                str::parse("isom").unwrap(),
+                str::parse("iso2").unwrap(),
+                str::parse("avc1").unwrap(),
+                str::parse("mp41").unwrap(),
+            ],
+            timescale: 1000,
+        };
+        let file = BufWriter::new(fs::File::create(&self.path)?);
+        let mut writer = Mp4Writer::write_start(file, &config)?;
+        writer.add_track(&TrackConfig {
+            track_type: TrackType::Video,
+            timescale: TRACK_TIMESCALE,
+            language: String::from("und"),
+            media_conf: MediaConfig::AvcConfig(AvcConfig {
+                width,
+                height,
+                seq_param_set: self.sps.clone().unwrap_or_default(),
+                pic_param_set: self.pps.clone().unwrap_or_default(),
+            }),
+        })?;
+        self.writer = Some(writer);
+        Ok(())
+    }
+
+    fn write(&mut self, sample: Sample) -> Result<(), Error> {
+        self.first_stamp.get_or_insert(sample.stamp);
+        self.stamps.push(sample.stamp);
+        if let Some(last) = self.last.replace(sample) {
+            let next_stamp = self.last.as_ref().unwrap().stamp;
+            let duration = to_track_time(next_stamp.saturating_sub(last.stamp)).max(1) as u32;
+            let sample = self.to_mp4_sample(last, duration);
+            if let Some(writer) = self.writer.as_mut() {
+                writer.write_sample(1, &sample)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn to_mp4_sample(&self, sample: Sample, duration: u32) -> Mp4Sample {
+        let first_stamp = self.first_stamp.unwrap_or(sample.stamp);
+        Mp4Sample {
+            start_time: to_track_time(sample.stamp.saturating_sub(first_stamp)),
+            duration,
+            rendering_offset: 0,
+            is_sync: sample.is_sync,
+            bytes: Bytes::from(sample.data),
+        }
+    }
+}
+
+/// Convert nanoseconds to track time units.
+fn to_track_time(nanos: u64) -> u64 {
+    (nanos as u128 * TRACK_TIMESCALE as u128 / 1_000_000_000) as u64
+}
+
+/// Remove the leading Annex-B start code of a NAL unit.
+fn strip_start_code(nal: &[u8]) -> &[u8] {
+    if nal.starts_with(&[0, 0, 0, 1]) {
+        &nal[4..]
+    } else if nal.starts_with(&[0, 0, 1]) {
+        &nal[3..]
+    } else {
+        nal
+    }
+}
@@ -0,0 +1,56 @@
+//! Conversion of packed YUV 4:2:2 images to RGB.
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Not a packed YUV image: {0}")]
+    Encoding(String),
+    #[error("Image data is shorter than {0} rows of {1} bytes")]
+    Truncated(u32, u32),
+}
+
+/// Byte offsets of Y0, U, Y1 and V in each 4 byte macropixel of two pixels.
+fn layout(encoding: &str) -> Option<[usize; 4]> {
+    match encoding {
+        "yuyv" | "yuv422_yuy2" => Some([0, 1, 2, 3]),
+        "uyvy" | "yuv422" => Some([1, 0, 3, 2]),
+        _ => None,
+    }
+}
+
+/// Is this encoding packed YUV 4:2:2?
+pub fn is_packed(encoding: &str) -> bool {
+    layout(encoding).is_some()
+}
+
+/// Convert the image into 8 bit RGB, with the BT.601 full range coefficients used for HEVC
+/// frames as well.
+pub fn to_rgb(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    step: u32,
+    encoding: &str,
+) -> Result<Vec<u8>, Error> {
+    let [y0, u, y1, v] = layout(encoding).ok_or(Error::Encoding(encoding.to_owned()))?;
+    let (w, h, step) = (width as usize, height as usize, step as usize);
+    if step < w.div_ceil(2) * 4 || data.len() < step * h {
+        return Err(Error::Truncated(height, step as u32));
+    }
+
+    let mut rgb = Vec::with_capacity(w * h * 3);
+    for row in data.chunks_exact(step).take(h) {
+        for (x, macropixel) in row.chunks_exact(4).take(w.div_ceil(2)).enumerate() {
+            let (cb, cr) = (macropixel[u] as f32 - 128.0, macropixel[v] as f32 - 128.0);
+            let lumas = [macropixel[y0], macropixel[y1]];
+            // The last macropixel of odd widths covers one pixel only
+            for &luma in &lumas[..(w - x * 2).min(2)] {
+                let luma = luma as f32;
+                let r = luma + 1.402 * cr;
+                let g = luma - 0.344136 * cb - 0.714136 * cr;
+                let b = luma + 1.772 * cb;
+                rgb.extend([r, g, b].map(|c| c.clamp(0.0, 255.0) as u8));
+            }
+        }
+    }
+    Ok(rgb)
+}